@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiEntityName } from "../../types/ApiEntityName";
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * What an alert rule watches, a place rule applies to every sensor of the place
+ */
+export type AlertTarget = { "Place": ApiEntityName } | { "Sensor": DeviceId };
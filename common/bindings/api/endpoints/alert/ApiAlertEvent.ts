@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+import type { Metric } from "../sensor_data/Metric";
+import type { AlertComparison } from "./AlertComparison";
+
+export type ApiAlertEvent = { id: number, rule_id: number, device_id: DeviceId, metric: Metric, comparison: AlertComparison, threshold: number, 
+/**
+ * Value of the reading that triggered the alert
+ */
+trigger_value: number, triggered_at: number, 
+/**
+ * None while the alert is open
+ */
+resolved_at: number | null, acknowledged_at: number | null, };
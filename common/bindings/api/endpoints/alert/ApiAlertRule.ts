@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Metric } from "../sensor_data/Metric";
+import type { AlertComparison } from "./AlertComparison";
+import type { AlertTarget } from "./AlertTarget";
+
+export type ApiAlertRule = { id: number, target: AlertTarget, metric: Metric, comparison: AlertComparison, threshold: number, 
+/**
+ * How far back past the threshold the metric must go for a triggered alert to be resolved
+ */
+hysteresis: number, 
+/**
+ * Seconds the threshold must be crossed before triggering
+ */
+for_secs: number, created_at: number, updated_at: number, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * Latest alerts of the sensors of the user first
+ */
+export type GetAlerts = { device_id: DeviceId | null, 
+/**
+ * Only the ones not resolved yet
+ */
+open_only: boolean | null, unacknowledged_only: boolean | null, 
+/**
+ * Defaults to GetAlerts::MAX_LIMIT
+ */
+limit: number | null, };
@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Metric } from "../sensor_data/Metric";
+import type { AlertComparison } from "./AlertComparison";
+import type { AlertTarget } from "./AlertTarget";
+
+/**
+ * I.e.: CO2 above 1200 ppm for 10 minutes, resolved once below 1100 ppm
+ * (threshold 1200, hysteresis 100, for_secs 600)
+ */
+export type PostAlertRule = { target: AlertTarget, metric: Metric, comparison: AlertComparison, threshold: number, 
+/**
+ * Defaults to 0
+ */
+hysteresis: number | null, 
+/**
+ * Defaults to 0, at most a week
+ */
+for_secs: number | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AlertComparison } from "./AlertComparison";
+
+/**
+ * Replaces the condition of the rule, an alert already triggered stays so until resolved
+ */
+export type PutAlertRule = { id: number, comparison: AlertComparison, threshold: number, hysteresis: number | null, for_secs: number | null, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiEntityName } from "../../types/ApiEntityName";
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * What an annotation is about, without one it is about everything of the user
+ */
+export type AnnotationTarget = { "Place": ApiEntityName } | { "Sensor": DeviceId };
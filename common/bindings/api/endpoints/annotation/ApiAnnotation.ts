@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiAnnotationText } from "../../types/ApiAnnotationText";
+import type { AnnotationTarget } from "./AnnotationTarget";
+
+export type ApiAnnotation = { id: number, target: AnnotationTarget | null, text: ApiAnnotationText, starts_at: number, 
+/**
+ * None for a point in time
+ */
+ends_at: number | null, created_at: number, updated_at: number, };
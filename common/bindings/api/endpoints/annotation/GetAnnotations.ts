@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiEntityName } from "../../types/ApiEntityName";
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * Annotations overlapping the range, sorted by starts_at
+ * With a device_id: the ones of the sensor, of its place and the ones without a target
+ * With a place_name: the ones of the place, of its sensors and the ones without a target
+ * With neither: every one of the user
+ */
+export type GetAnnotations = { device_id: DeviceId | null, place_name: ApiEntityName | null, lowest_at: number | null, upper_at: number | null, 
+/**
+ * Defaults to GetAnnotations::MAX_LIMIT
+ */
+limit: number | null, };
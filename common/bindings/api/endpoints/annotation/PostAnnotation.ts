@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiAnnotationText } from "../../types/ApiAnnotationText";
+import type { AnnotationTarget } from "./AnnotationTarget";
+
+export type PostAnnotation = { target: AnnotationTarget | null, text: ApiAnnotationText, starts_at: number, 
+/**
+ * None for a point in time, else not before starts_at
+ */
+ends_at: number | null, };
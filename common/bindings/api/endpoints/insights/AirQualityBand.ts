@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Indoor air quality by CO2 concentration, following the EN 13779 IDA classes with 400 ppm
+ * outdoors
+ */
+export type AirQualityBand = "excellent" | "good" | "moderate" | "poor";
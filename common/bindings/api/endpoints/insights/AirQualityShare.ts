@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { AirQualityBand } from "./AirQualityBand";
+
+export type AirQualityShare = { band: AirQualityBand, 
+/**
+ * CO2 readings in the band
+ */
+readings: number, };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiSensorInsights } from "./ApiSensorInsights";
+import type { OccupancyBucket } from "./OccupancyBucket";
+
+export type ApiInsights = { 
+/**
+ * Sensors of the place with CO2 readings in range
+ */
+sensors: Array<ApiSensorInsights>, 
+/**
+ * Only buckets with CO2 readings
+ */
+occupancy: Array<OccupancyBucket>, };
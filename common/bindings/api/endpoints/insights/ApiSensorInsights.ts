@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+import type { AirQualityBand } from "./AirQualityBand";
+import type { AirQualityShare } from "./AirQualityShare";
+import type { VentilationEstimate } from "./VentilationEstimate";
+
+export type ApiSensorInsights = { device_id: DeviceId, 
+/**
+ * Of the last CO2 reading in range, None without CO2 readings
+ */
+current_band: AirQualityBand | null, 
+/**
+ * One per AirQualityBand, in order
+ */
+bands: Array<AirQualityShare>, ventilation: Array<VentilationEstimate>, 
+/**
+ * Median of the ventilation estimates
+ */
+air_changes_per_hour: number | null, };
@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiEntityName } from "../../types/ApiEntityName";
+import type { BucketSize } from "../sensor_data/BucketSize";
+
+export type GetInsights = { place_name: ApiEntityName, 
+/**
+ * The range can be at most GetInsights::MAX_RANGE_DAYS long
+ */
+lowest_added_at: number | null, upper_added_at: number | null, 
+/**
+ * Size of the occupancy buckets, defaults to BucketSize::Hour
+ */
+bucket: BucketSize | null, };
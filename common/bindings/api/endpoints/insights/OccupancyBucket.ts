@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Likelihood, in [0, 1], of the place being occupied during [bucket_start, bucket_start + bucket)
+ */
+export type OccupancyBucket = { bucket_start: number, likelihood: number, };
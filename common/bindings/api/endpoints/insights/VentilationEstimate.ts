@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Ventilation estimated from how CO2 decayed towards the outdoor level after a peak
+ */
+export type VentilationEstimate = { peak_at: number, peak_co2: number, 
+/**
+ * Last reading of the decay
+ */
+end_at: number, air_changes_per_hour: number, };
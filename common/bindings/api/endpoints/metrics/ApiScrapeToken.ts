@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Bearer token Prometheus scrapes the latest readings of the sensors of the user with
+ */
+export type ApiScrapeToken = { 
+/**
+ * Only returned when created, the server keeps just its hash
+ */
+token: string | null, created_at: number, };
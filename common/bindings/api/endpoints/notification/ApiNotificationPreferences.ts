@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Which emails the user gets, all of them until changed
+ */
+export type ApiNotificationPreferences = { 
+/**
+ * Alerts of the sensors of the user triggered or resolved
+ */
+alert_emails: boolean, 
+/**
+ * Password, email or username of the user changed
+ */
+account_emails: boolean, 
+/**
+ * New sensor registered to a place of the user, or one going offline and back
+ */
+sensor_emails: boolean, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Changes the given preferences
+ */
+export type PutNotificationPreferences = { alert_emails: boolean | null, account_emails: boolean | null, sensor_emails: boolean | null, };
@@ -0,0 +1,17 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiAnnotation } from "../annotation/ApiAnnotation";
+import type { ApiPlaceStatsBucket } from "./ApiPlaceStatsBucket";
+
+export type ApiPlaceStats = { 
+/**
+ * Over the whole range, its bucket_start is the start of the range
+ */
+total: ApiPlaceStatsBucket, 
+/**
+ * Empty unless GetPlaceStats::bucket was set
+ */
+buckets: Array<ApiPlaceStatsBucket>, 
+/**
+ * Annotations of the place, of its sensors and without a target overlapping the range
+ */
+annotations: Array<ApiAnnotation>, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { PlaceMetricStats } from "./PlaceMetricStats";
+
+/**
+ * Stats of every metric for the readings added in [bucket_start, bucket_start + bucket)
+ * Metrics without any reading in the bucket are None
+ */
+export type ApiPlaceStatsBucket = { bucket_start: number, co2: PlaceMetricStats | null, temperature: PlaceMetricStats | null, humidity: PlaceMetricStats | null, dew_point: PlaceMetricStats | null, absolute_humidity: PlaceMetricStats | null, humidex: PlaceMetricStats | null, };
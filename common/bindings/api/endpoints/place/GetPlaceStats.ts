@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiEntityName } from "../../types/ApiEntityName";
+import type { BucketSize } from "../sensor_data/BucketSize";
+
+export type GetPlaceStats = { place_name: ApiEntityName, 
+/**
+ * The range can be at most GetPlaceStats::MAX_RANGE_DAYS long
+ */
+lowest_added_at: number | null, upper_added_at: number | null, 
+/**
+ * If set, the stats are also returned per bucket of this size, as long as the range is split
+ * in at most GetPlaceStats::MAX_BUCKETS
+ */
+bucket: BucketSize | null, 
+/**
+ * Leaves the suspect metrics of each reading out, defaults to false
+ */
+exclude_suspect: boolean | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * A reading that hit the min or max of a metric
+ */
+export type MetricExtreme = { value: number, device_id: DeviceId, added_at: number, };
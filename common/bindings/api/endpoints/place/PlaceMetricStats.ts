@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MetricExtreme } from "./MetricExtreme";
+
+/**
+ * Aggregate of a metric over the readings of every sensor of the place, avg weights each reading
+ * equally so sensors that report more often weigh more
+ */
+export type PlaceMetricStats = { min: MetricExtreme, max: MetricExtreme, avg: number, count: number, };
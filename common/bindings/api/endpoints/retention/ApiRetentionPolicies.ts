@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiSensorRetention } from "./ApiSensorRetention";
+
+export type ApiRetentionPolicies = { 
+/**
+ * Set by the server, no user or sensor policy can keep data longer than this
+ */
+server_keep_days: number | null, 
+/**
+ * Applies to every sensor of the user without its own policy
+ */
+user_keep_days: number | null, sensors: Array<ApiSensorRetention>, };
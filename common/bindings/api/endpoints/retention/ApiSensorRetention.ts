@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+
+export type ApiSensorRetention = { device_id: DeviceId, 
+/**
+ * Policy set on this sensor
+ */
+keep_days: number | null, 
+/**
+ * Days this sensor data is actually kept, taking every level into account, None if forever
+ */
+effective_keep_days: number | null, };
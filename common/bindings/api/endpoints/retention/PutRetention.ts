@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { RetentionTarget } from "./RetentionTarget";
+
+export type PutRetention = { target: RetentionTarget, 
+/**
+ * None removes the policy so the less specific one applies
+ */
+keep_days: number | null, };
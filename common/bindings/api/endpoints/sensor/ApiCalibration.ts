@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Metric } from "../sensor_data/Metric";
+
+/**
+ * Linear correction of a measured metric of a sensor, applied when its readings are read so the
+ * stored ones stay raw. I.e.: an AHT10 reading 1.5 °C high is calibrated with offset -1.5
+ */
+export type ApiCalibration = { metric: Metric, offset: number, scale: number, 
+/**
+ * Applies to the readings added from then until the next calibration of the metric
+ */
+effective_from: number, };
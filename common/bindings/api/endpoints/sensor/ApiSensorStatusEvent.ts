@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * Sent when a sensor goes offline and when it reports again
+ */
+export type ApiSensorStatusEvent = { device_id: DeviceId, last_seen_at: number, expected_interval_secs: number, };
@@ -4,5 +4,23 @@ import type { ApiDescription } from "../../types/ApiDescription";
 import type { ApiEntityName } from "../../types/ApiEntityName";
 import type { ApiPubKey } from "../../types/ApiPubKey";
 import type { DeviceId } from "../../types/DeviceId";
+import type { ApiCalibration } from "./ApiCalibration";
 
-export type ApiUserSensor = { device_id: DeviceId, pub_key: ApiPubKey, name: ApiEntityName, description: ApiDescription | null, color: ApiColor, created_at: number, updated_at: number, place_name: ApiEntityName, };
+export type ApiUserSensor = { device_id: DeviceId, pub_key: ApiPubKey, name: ApiEntityName, description: ApiDescription | null, color: ApiColor, created_at: number, updated_at: number, place_name: ApiEntityName, 
+/**
+ * Seconds the clock of the sensor is ahead of the server (negative if behind), measured on
+ * its last reading that carried a created_at
+ */
+clock_skew_secs: number | null, 
+/**
+ * When the server last got a reading from the sensor
+ */
+last_seen_at: number | null, 
+/**
+ * How often the sensor is expected to report
+ */
+expected_interval_secs: number, 
+/**
+ * Every calibration of the sensor, sorted by metric and effective_from
+ */
+calibrations: Array<ApiCalibration>, };
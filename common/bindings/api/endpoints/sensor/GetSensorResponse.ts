@@ -1,5 +1,6 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { ApiSensorData } from "../sensor_data/ApiSensorData";
 import type { ApiUserSensor } from "./ApiUserSensor";
+import type { SensorStatus } from "./SensorStatus";
 
-export type GetSensorResponse = { sensor: ApiUserSensor, last_data: ApiSensorData | null, };
+export type GetSensorResponse = { sensor: ApiUserSensor, last_data: ApiSensorData | null, status: SensorStatus, };
@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { Metric } from "../sensor_data/Metric";
+
+/**
+ * Offset 0 and scale 1 from some point on stops calibrating the metric
+ */
+export type PutCalibration = { 
+/**
+ * Only measured metrics, derived ones follow from them
+ */
+metric: Metric, offset: number, 
+/**
+ * Defaults to 1
+ */
+scale: number | null, 
+/**
+ * Defaults to now, earlier to recalibrate already stored readings
+ */
+effective_from: number | null, };
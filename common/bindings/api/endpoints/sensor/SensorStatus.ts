@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Whether the sensor is reporting, it is offline once it misses
+ * SensorStatus::MISSED_REPORTS expected reports in a row
+ */
+export type SensorStatus = "Online" | "Offline" | "NeverSeen";
@@ -1,3 +1,19 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DerivedMetrics } from "./DerivedMetrics";
+import type { Metric } from "./Metric";
+import type { SensorReading } from "./SensorReading";
 
-export type ApiSensorData = { data: string, added_at: number, };
+export type ApiSensorData = { 
+/**
+ * Of the stored reading, what DeleteSensorData::Ids takes
+ */
+id: number, data: SensorReading, 
+/**
+ * Computed from data, ignored if sent
+ */
+derived: DerivedMetrics, 
+/**
+ * Metrics that looked like a glitch when the reading was stored, empty for good readings
+ * Derived metrics are suspect if what they are computed from is
+ */
+suspect_metrics: Array<Metric>, added_at: number, };
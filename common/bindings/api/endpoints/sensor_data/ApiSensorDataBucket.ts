@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MetricAggregate } from "./MetricAggregate";
+
+/**
+ * Aggregates of every metric for the readings added in [bucket_start, bucket_start + bucket)
+ * Metrics without any reading in the bucket are None
+ */
+export type ApiSensorDataBucket = { bucket_start: number, co2: MetricAggregate | null, temperature: MetricAggregate | null, humidity: MetricAggregate | null, dew_point: MetricAggregate | null, absolute_humidity: MetricAggregate | null, humidex: MetricAggregate | null, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiAnnotation } from "../annotation/ApiAnnotation";
+import type { ApiSensorDataBucket } from "./ApiSensorDataBucket";
+
+export type ApiSensorDataBuckets = { buckets: Array<ApiSensorDataBucket>, 
+/**
+ * Annotations of the sensor, of its place and without a target overlapping the range
+ */
+annotations: Array<ApiAnnotation>, };
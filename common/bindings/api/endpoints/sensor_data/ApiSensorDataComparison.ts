@@ -0,0 +1,18 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+import type { ApiAnnotation } from "../annotation/ApiAnnotation";
+import type { BucketSize } from "./BucketSize";
+import type { ComparisonRow } from "./ComparisonRow";
+import type { Metric } from "./Metric";
+
+export type ApiSensorDataComparison = { device_ids: Array<DeviceId>, metric: Metric, bucket: BucketSize, 
+/**
+ * Every bucket from the first to the last one with data of any of the sensors, the ones
+ * without data of any are included with every value None
+ */
+rows: Array<ComparisonRow>, 
+/**
+ * Annotations of any of the sensors, of their places and without a target overlapping the
+ * range, sorted by starts_at
+ */
+annotations: Array<ApiAnnotation>, };
@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+import type { ApiSensorData } from "./ApiSensorData";
+
+/**
+ * Data of every "sensor_data" event of the stream
+ */
+export type ApiSensorDataEvent = { device_id: DeviceId, api_data: ApiSensorData, };
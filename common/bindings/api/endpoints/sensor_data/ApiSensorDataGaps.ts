@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DataGap } from "./DataGap";
+
+export type ApiSensorDataGaps = { 
+/**
+ * Range the report covers
+ */
+lowest_added_at: number, upper_added_at: number, 
+/**
+ * Silences longer than this are gaps, GetSensorDataGaps::GAP_INTERVALS expected intervals
+ */
+threshold_secs: number, 
+/**
+ * Percentage, in [0, 100], of the range not in any gap
+ */
+uptime: number, total_gaps: number, 
+/**
+ * Oldest first, at most the limit
+ */
+gaps: Array<DataGap>, };
@@ -0,0 +1,14 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiAnnotation } from "../annotation/ApiAnnotation";
+import type { ApiSensorData } from "./ApiSensorData";
+
+export type ApiSensorDataPage = { data: Array<ApiSensorData>, 
+/**
+ * Some if there is more data, pass it as GetSensorData::cursor to get the next page
+ */
+next_cursor: string | null, 
+/**
+ * Annotations of the sensor, of its place and without a target overlapping the range, so
+ * that charts can overlay them. Only on the first page, the rest have none
+ */
+annotations: Array<ApiAnnotation>, };
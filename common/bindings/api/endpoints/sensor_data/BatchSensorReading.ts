@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SensorReading } from "./SensorReading";
+
+/**
+ * A buffered reading, the device must provide when it was measured
+ */
+export type BatchSensorReading = { data: SensorReading, created_at: number, };
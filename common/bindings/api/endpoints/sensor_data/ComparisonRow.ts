@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { MetricAggregate } from "./MetricAggregate";
+
+/**
+ * A bucket of the comparison, values has one aggregate per sensor in the order of device_ids,
+ * None where the sensor has no data in the bucket
+ */
+export type ComparisonRow = { bucket_start: number, values: Array<MetricAggregate | null>, };
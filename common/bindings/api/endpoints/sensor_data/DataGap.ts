@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Time between two consecutive readings, or between a reading and an end of the range
+ */
+export type DataGap = { start: number, end: number, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+import type { SensorDataSelection } from "./SensorDataSelection";
+
+export type DeleteSensorData = { device_id: DeviceId, selection: SensorDataSelection, 
+/**
+ * Only reports what would be deleted, defaults to false
+ */
+dry_run: boolean | null, };
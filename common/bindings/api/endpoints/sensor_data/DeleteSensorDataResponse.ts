@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type DeleteSensorDataResponse = { dry_run: boolean, 
+/**
+ * Readings deleted, or that would be if dry_run
+ */
+deleted: number, 
+/**
+ * added_at of the oldest and newest of them, None if there are none
+ */
+oldest_added_at: number | null, newest_added_at: number | null, };
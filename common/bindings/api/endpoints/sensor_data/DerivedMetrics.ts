@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Metrics computed from the measured temperature and humidity of a reading, see
+ * crate::psychrometrics
+ */
+export type DerivedMetrics = { 
+/**
+ * Celsius degrees
+ */
+dew_point: number | null, 
+/**
+ * g/m³
+ */
+absolute_humidity: number | null, humidex: number | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Format of exported and imported sensor data, one datum per line, CSV has a header with
+ * added_at and the Metric keys
+ */
+export type ExportFormat = "Csv" | "Ndjson";
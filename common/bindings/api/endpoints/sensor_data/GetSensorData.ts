@@ -1,4 +1,30 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
 import type { DeviceId } from "../../types/DeviceId";
+import type { BucketSize } from "./BucketSize";
+import type { SortOrder } from "./SortOrder";
 
-export type GetSensorData = { device_id: DeviceId, lowest_added_at: number | null, upper_added_at: number | null, };
+export type GetSensorData = { device_id: DeviceId, lowest_added_at: number | null, upper_added_at: number | null, 
+/**
+ * If set, data will be aggregated into buckets of this size instead of returned raw, the
+ * range can be split in at most GetSensorData::MAX_BUCKETS of them. Without
+ * lowest_added_at, the range goes back that many buckets from upper_added_at
+ * limit, order and cursor only apply to raw data
+ */
+bucket: BucketSize | null, 
+/**
+ * Leaves the suspect metrics of each reading out of the aggregates, defaults to false
+ */
+exclude_suspect: boolean | null, 
+/**
+ * Max datums returned, defaults to and is capped at GetSensorData::MAX_LIMIT
+ */
+limit: number | null, 
+/**
+ * Defaults to SortOrder::Asc
+ */
+order: SortOrder | null, 
+/**
+ * next_cursor of the previous page, the rest of the params must be the same as in that
+ * request
+ */
+cursor: string | null, };
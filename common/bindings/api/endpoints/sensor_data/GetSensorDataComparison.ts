@@ -0,0 +1,21 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { BucketSize } from "./BucketSize";
+import type { Metric } from "./Metric";
+
+/**
+ * One metric of several sensors aggregated into the same buckets
+ */
+export type GetSensorDataComparison = { 
+/**
+ * Comma separated device ids, at most GetSensorDataComparison::MAX_SENSORS, each one is a
+ * column of the table in that order. I.e.: "<bedroom device_id>,<living room device_id>"
+ */
+device_ids: string, metric: Metric, bucket: BucketSize, 
+/**
+ * Defaults to GetSensorDataComparison::MAX_ROWS buckets before upper_added_at
+ */
+lowest_added_at: number | null, upper_added_at: number | null, 
+/**
+ * Leaves the suspect readings out of the aggregates, defaults to false
+ */
+exclude_suspect: boolean | null, };
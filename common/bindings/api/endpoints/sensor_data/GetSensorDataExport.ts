@@ -0,0 +1,19 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+import type { ExportFormat } from "./ExportFormat";
+import type { ExportTimestampFormat } from "./ExportTimestampFormat";
+
+export type GetSensorDataExport = { device_id: DeviceId, lowest_added_at: number | null, upper_added_at: number | null, 
+/**
+ * Defaults to ExportFormat::Csv
+ */
+format: ExportFormat | null, 
+/**
+ * Comma separated Metric keys exported, in that order, defaults to Metric::MEASURED
+ * I.e.: "co2,temperature"
+ */
+columns: string | null, 
+/**
+ * Defaults to ExportTimestampFormat::Unix
+ */
+timestamp_format: ExportTimestampFormat | null, };
@@ -0,0 +1,15 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * When a sensor was silent, by the gaps between its readings
+ */
+export type GetSensorDataGaps = { device_id: DeviceId, 
+/**
+ * The range can be at most GetSensorDataGaps::MAX_RANGE_DAYS long, it ends now at most
+ */
+lowest_added_at: number | null, upper_added_at: number | null, 
+/**
+ * Max gaps returned, defaults to and is capped at GetSensorDataGaps::MAX_LIMIT
+ */
+limit: number | null, };
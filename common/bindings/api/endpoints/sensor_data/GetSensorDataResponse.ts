@@ -0,0 +1,5 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiSensorDataBuckets } from "./ApiSensorDataBuckets";
+import type { ApiSensorDataPage } from "./ApiSensorDataPage";
+
+export type GetSensorDataResponse = ApiSensorDataPage | ApiSensorDataBuckets;
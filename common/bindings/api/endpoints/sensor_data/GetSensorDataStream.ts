@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiEntityName } from "../../types/ApiEntityName";
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * Subscription to the live readings of the sensors of the user, of every sensor if no filter is
+ * set
+ */
+export type GetSensorDataStream = { device_id: DeviceId | null, place_name: ApiEntityName | null, };
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+
+/**
+ * Readings with suspect metrics, newest first
+ */
+export type GetSuspectSensorData = { device_id: DeviceId, lowest_added_at: number | null, upper_added_at: number | null, 
+/**
+ * Defaults to and is capped at GetSensorData::MAX_LIMIT
+ */
+limit: number | null, };
@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+export type ImportRowError = { 
+/**
+ * 1 based, counting the CSV header
+ */
+line: number, reason: string, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Each of the values a SensorReading can hold, serializes to the same key used in SensorReading
+ */
+export type Metric = "co2" | "temperature" | "humidity" | "dew_point" | "absolute_humidity" | "humidex";
@@ -1,3 +1,9 @@
 // This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { SensorReading } from "./SensorReading";
 
-export type PostSensorData = { serialized_data: string, created_at: number | null, };
+export type PostSensorData = { data: SensorReading, created_at: number | null, 
+/**
+ * Unique per reading of a sensor, a retried upload with the same key is stored only once and
+ * answered with the originally stored data
+ */
+idempotency_key: string | null, };
@@ -0,0 +1,9 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiSession } from "../session/ApiSession";
+import type { BatchItemResult } from "./BatchItemResult";
+
+export type PostSensorDataBatchResponse = { 
+/**
+ * One result per reading, in the same order they were sent
+ */
+results: Array<BatchItemResult>, new_session: ApiSession, };
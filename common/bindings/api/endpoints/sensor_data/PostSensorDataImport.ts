@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { DeviceId } from "../../types/DeviceId";
+import type { ExportFormat } from "./ExportFormat";
+
+/**
+ * Query of the import, the body is the CSV or NDJSON text, timestamps can be in any
+ * ExportTimestampFormat
+ */
+export type PostSensorDataImport = { device_id: DeviceId, 
+/**
+ * Defaults to ExportFormat::Csv
+ */
+format: ExportFormat | null, };
@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ImportRowError } from "./ImportRowError";
+
+export type PostSensorDataImportResponse = { imported: number, 
+/**
+ * Rows already stored for the same sensor and added_at, or repeated in the input
+ */
+skipped: number, failed: number, 
+/**
+ * Reasons of the first failed rows, at most PostSensorDataImportResponse::MAX_ERRORS
+ */
+errors: Array<ImportRowError>, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Readings of a sensor to delete
+ */
+export type SensorDataSelection = { "Range": { lowest_added_at: number, upper_added_at: number, } } | { "Ids": Array<number> };
@@ -0,0 +1,20 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * A single measurement taken by a sensor, mirrors `sensor-client`'s `sensors::SensorsData`
+ * Every metric is optional as a device may have only some of its sensors working, new metrics
+ * must be added as Option too so that already stored readings keep deserializing
+ */
+export type SensorReading = { 
+/**
+ * CO2 concentration in ppm (SCD41 range)
+ */
+co2: number | null, 
+/**
+ * Temperature in Celsius degrees (AHT10 range)
+ */
+temperature: number | null, 
+/**
+ * Relative humidity in %
+ */
+humidity: number | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Order in which raw sensor data is returned, by added_at
+ */
+export type SortOrder = "Asc" | "Desc";
@@ -0,0 +1,11 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiWebhook } from "./ApiWebhook";
+
+/**
+ * Response of POST /webhook, the only time the secret is sent
+ */
+export type ApiNewWebhook = { webhook: ApiWebhook, 
+/**
+ * Hex key of the signatures
+ */
+secret: string, };
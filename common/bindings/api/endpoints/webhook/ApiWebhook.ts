@@ -0,0 +1,4 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WebhookEventKind } from "./WebhookEventKind";
+
+export type ApiWebhook = { id: number, url: string, events: Array<WebhookEventKind>, enabled: boolean, created_at: number, updated_at: number, };
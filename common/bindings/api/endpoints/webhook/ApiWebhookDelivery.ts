@@ -0,0 +1,13 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WebhookDeliveryStatus } from "./WebhookDeliveryStatus";
+import type { WebhookEventKind } from "./WebhookEventKind";
+
+export type ApiWebhookDelivery = { id: number, webhook_id: number, kind: WebhookEventKind, status: WebhookDeliveryStatus, attempts: number, 
+/**
+ * HTTP status of the last attempt, None if it didn't get a response
+ */
+last_status_code: number | null, last_error: string | null, created_at: number, 
+/**
+ * None once delivered or failed
+ */
+next_attempt_at: number | null, delivered_at: number | null, };
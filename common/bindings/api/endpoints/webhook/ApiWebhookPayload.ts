@@ -0,0 +1,12 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WebhookEvent } from "./WebhookEvent";
+
+/**
+ * Body POSTed to the url of a webhook. It's signed with the secret of the webhook, see
+ * WebhookSignature
+ */
+export type ApiWebhookPayload = { 
+/**
+ * Same on every retry of the delivery, can be used to discard duplicates
+ */
+delivery_id: number, created_at: number, event: WebhookEvent, };
@@ -0,0 +1,10 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Latest deliveries of the webhook first
+ */
+export type GetWebhookDeliveries = { id: number, 
+/**
+ * Defaults to GetWebhookDeliveries::MAX_LIMIT
+ */
+limit: number | null, };
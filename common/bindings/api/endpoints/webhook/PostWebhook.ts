@@ -0,0 +1,8 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WebhookEventKind } from "./WebhookEventKind";
+
+export type PostWebhook = { 
+/**
+ * http or https url
+ */
+url: string, events: Array<WebhookEventKind>, };
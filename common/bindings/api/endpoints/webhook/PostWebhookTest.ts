@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Queues a Test event to the webhook, attempted right away by the dispatcher
+ */
+export type PostWebhookTest = { id: number, };
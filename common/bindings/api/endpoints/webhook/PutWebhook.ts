@@ -0,0 +1,7 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { WebhookEventKind } from "./WebhookEventKind";
+
+/**
+ * Changes the given fields of the webhook
+ */
+export type PutWebhook = { id: number, url: string | null, events: Array<WebhookEventKind> | null, enabled: boolean | null, };
@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+import type { ApiAlertEvent } from "../alert/ApiAlertEvent";
+import type { ApiSensorStatusEvent } from "../sensor/ApiSensorStatusEvent";
+import type { ApiSensorDataEvent } from "../sensor_data/ApiSensorDataEvent";
+
+export type WebhookEvent = { "kind": "SensorData", "data": ApiSensorDataEvent } | { "kind": "AlertTriggered", "data": ApiAlertEvent } | { "kind": "AlertResolved", "data": ApiAlertEvent } | { "kind": "SensorOffline", "data": ApiSensorStatusEvent } | { "kind": "SensorOnline", "data": ApiSensorStatusEvent } | { "kind": "Test" };
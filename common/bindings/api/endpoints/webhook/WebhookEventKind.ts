@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Kinds of events a webhook can subscribe to
+ */
+export type WebhookEventKind = "SensorData" | "AlertTriggered" | "AlertResolved" | "SensorOffline" | "SensorOnline" | "Test";
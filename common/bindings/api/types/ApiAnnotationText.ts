@@ -0,0 +1,6 @@
+// This file was generated by [ts-rs](https://github.com/Aleph-Alpha/ts-rs). Do not edit this file manually.
+
+/**
+ * Free text of an annotation, i.e.: "Window opened, 2nd floor"
+ */
+export type ApiAnnotationText = string;
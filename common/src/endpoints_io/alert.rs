@@ -0,0 +1,149 @@
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use ts_rs::TS;
+
+use crate::{
+    endpoints_io::sensor_data::Metric,
+    types::{
+        ApiTimestamp,
+        validate::{api_entity_name::ApiEntityName, device_id::DeviceId},
+    },
+};
+
+#[derive(TS, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+pub enum AlertComparison {
+    Above,
+    Below,
+}
+
+/// What an alert rule watches, a place rule applies to every sensor of the place
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+pub enum AlertTarget {
+    Place(#[validate] ApiEntityName),
+    Sensor(DeviceId),
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiAlertRule {
+    pub id: i32,
+    pub target: AlertTarget,
+    pub metric: Metric,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    /// How far back past the threshold the metric must go for a triggered alert to be resolved
+    pub hysteresis: f64,
+    /// Seconds the threshold must be crossed before triggering
+    pub for_secs: u32,
+    pub created_at: ApiTimestamp,
+    pub updated_at: ApiTimestamp,
+}
+
+/// I.e.: CO2 above 1200 ppm for 10 minutes, resolved once below 1100 ppm
+/// (threshold 1200, hysteresis 100, for_secs 600)
+#[derive(TS, Debug, Clone, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+pub struct PostAlertRule {
+    #[validate]
+    pub target: AlertTarget,
+    pub metric: Metric,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    /// Defaults to 0
+    #[validate(minimum = 0.0)]
+    pub hysteresis: Option<f64>,
+    /// Defaults to 0, at most a week
+    #[validate(maximum = 604_800)]
+    pub for_secs: Option<u32>,
+}
+
+/// Replaces the condition of the rule, an alert already triggered stays so until resolved
+#[derive(TS, Debug, Clone, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+pub struct PutAlertRule {
+    pub id: i32,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    #[validate(minimum = 0.0)]
+    pub hysteresis: Option<f64>,
+    #[validate(maximum = 604_800)]
+    pub for_secs: Option<u32>,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+pub struct DeleteAlertRule {
+    pub id: i32,
+}
+
+#[derive(TS, Clone, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiAlertEvent {
+    pub id: i32,
+    pub rule_id: i32,
+    pub device_id: DeviceId,
+    pub metric: Metric,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    /// Value of the reading that triggered the alert
+    pub trigger_value: f64,
+    pub triggered_at: ApiTimestamp,
+    /// None while the alert is open
+    pub resolved_at: Option<ApiTimestamp>,
+    pub acknowledged_at: Option<ApiTimestamp>,
+}
+
+/// Latest alerts of the sensors of the user first
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+pub struct GetAlerts {
+    pub device_id: Option<DeviceId>,
+    /// Only the ones not resolved yet
+    pub open_only: Option<bool>,
+    pub unacknowledged_only: Option<bool>,
+    /// Defaults to GetAlerts::MAX_LIMIT
+    #[validate(minimum = 1)]
+    pub limit: Option<u32>,
+}
+
+impl GetAlerts {
+    pub const MAX_LIMIT: u32 = 500;
+}
+
+/// Acknowledges the alert
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/alert/")]
+pub struct PutAlert {
+    pub id: i32,
+}
+
+#[cfg(test)]
+mod test {
+    use serde_valid::Validate;
+
+    use crate::endpoints_io::{
+        alert::{AlertComparison, AlertTarget, PostAlertRule},
+        sensor_data::Metric,
+    };
+
+    #[test]
+    fn test_post_alert_rule() {
+        let rule = |hysteresis, for_secs| PostAlertRule {
+            target: AlertTarget::Place("Living room".to_string().into()),
+            metric: Metric::Co2,
+            comparison: AlertComparison::Above,
+            threshold: 1200.0,
+            hysteresis,
+            for_secs,
+        };
+
+        assert!(rule(None, None).validate().is_ok());
+        assert!(rule(Some(100.0), Some(600)).validate().is_ok());
+        assert!(rule(Some(-1.0), None).validate().is_err());
+        assert!(rule(None, Some(604_801)).validate().is_err());
+    }
+}
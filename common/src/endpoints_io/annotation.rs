@@ -0,0 +1,145 @@
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use ts_rs::TS;
+
+use crate::types::{
+    ApiTimestamp,
+    validate::{
+        api_annotation_text::ApiAnnotationText, api_entity_name::ApiEntityName,
+        device_id::DeviceId,
+    },
+};
+
+/// What an annotation is about, without one it is about everything of the user
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/annotation/")]
+pub enum AnnotationTarget {
+    Place(#[validate] ApiEntityName),
+    Sensor(DeviceId),
+}
+
+#[derive(TS, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/annotation/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiAnnotation {
+    pub id: i32,
+    pub target: Option<AnnotationTarget>,
+    pub text: ApiAnnotationText,
+    pub starts_at: ApiTimestamp,
+    /// None for a point in time
+    pub ends_at: Option<ApiTimestamp>,
+    pub created_at: ApiTimestamp,
+    pub updated_at: ApiTimestamp,
+}
+
+/// Annotations overlapping the range, sorted by starts_at
+/// With a device_id: the ones of the sensor, of its place and the ones without a target
+/// With a place_name: the ones of the place, of its sensors and the ones without a target
+/// With neither: every one of the user
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/annotation/")]
+#[validate(custom = GetAnnotations::single_filter)]
+pub struct GetAnnotations {
+    pub device_id: Option<DeviceId>,
+    #[validate]
+    pub place_name: Option<ApiEntityName>,
+    pub lowest_at: Option<ApiTimestamp>,
+    pub upper_at: Option<ApiTimestamp>,
+    /// Defaults to GetAnnotations::MAX_LIMIT
+    #[validate(minimum = 1)]
+    pub limit: Option<u32>,
+}
+
+impl GetAnnotations {
+    pub const MAX_LIMIT: u32 = 500;
+
+    fn single_filter(&self) -> Result<(), serde_valid::validation::Error> {
+        if self.device_id.is_some() && self.place_name.is_some() {
+            Err(serde_valid::validation::Error::Custom(
+                "Either device_id or place_name, not both".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/annotation/")]
+#[validate(custom = PostAnnotation::ends_after_start)]
+pub struct PostAnnotation {
+    #[validate]
+    pub target: Option<AnnotationTarget>,
+    #[validate]
+    pub text: ApiAnnotationText,
+    pub starts_at: ApiTimestamp,
+    /// None for a point in time, else not before starts_at
+    pub ends_at: Option<ApiTimestamp>,
+}
+
+impl PostAnnotation {
+    fn ends_after_start(&self) -> Result<(), serde_valid::validation::Error> {
+        if self.ends_at.is_some_and(|ends_at| ends_at < self.starts_at) {
+            Err(serde_valid::validation::Error::Custom(
+                "ends_at is before starts_at".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Replaces every field of the annotation
+#[derive(TS, Debug, Clone, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/annotation/")]
+pub struct PutAnnotation {
+    pub id: i32,
+    #[serde(flatten)]
+    #[validate]
+    pub annotation: PostAnnotation,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/annotation/")]
+pub struct DeleteAnnotation {
+    pub id: i32,
+}
+
+#[cfg(test)]
+mod test {
+    use serde_valid::Validate;
+
+    use crate::{
+        endpoints_io::annotation::{GetAnnotations, PostAnnotation},
+        types::validate::device_id::DeviceId,
+    };
+
+    #[test]
+    fn test_post_annotation() {
+        let annotation = |ends_at| PostAnnotation {
+            target: None,
+            text: "Window opened".to_string().into(),
+            starts_at: 1_000,
+            ends_at,
+        };
+
+        assert!(annotation(None).validate().is_ok());
+        assert!(annotation(Some(1_000)).validate().is_ok());
+        assert!(annotation(Some(999)).validate().is_err());
+    }
+
+    #[test]
+    fn test_get_annotations() {
+        let mut get = GetAnnotations {
+            device_id: Some(DeviceId::random()),
+            place_name: None,
+            lowest_at: None,
+            upper_at: None,
+            limit: None,
+        };
+        assert!(get.validate().is_ok());
+
+        get.place_name = Some("Living room".to_string().into());
+        assert!(get.validate().is_err());
+    }
+}
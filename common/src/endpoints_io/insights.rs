@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use ts_rs::TS;
+
+use crate::{
+    endpoints_io::sensor_data::BucketSize,
+    types::{
+        ApiTimestamp,
+        validate::{api_entity_name::ApiEntityName, device_id::DeviceId},
+    },
+};
+
+/// Indoor air quality by CO2 concentration, following the EN 13779 IDA classes with 400 ppm
+/// outdoors
+#[derive(TS, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/insights/")]
+#[serde(rename_all = "snake_case")]
+pub enum AirQualityBand {
+    /// Below 800 ppm
+    Excellent,
+    /// Below 1000 ppm
+    Good,
+    /// Below 1400 ppm
+    Moderate,
+    Poor,
+}
+
+impl AirQualityBand {
+    pub const ALL: [AirQualityBand; 4] = [
+        AirQualityBand::Excellent,
+        AirQualityBand::Good,
+        AirQualityBand::Moderate,
+        AirQualityBand::Poor,
+    ];
+
+    /// Lowest CO2 ppm of the next band, None for the last one
+    pub fn upper_co2(&self) -> Option<f64> {
+        match self {
+            AirQualityBand::Excellent => Some(800.0),
+            AirQualityBand::Good => Some(1000.0),
+            AirQualityBand::Moderate => Some(1400.0),
+            AirQualityBand::Poor => None,
+        }
+    }
+
+    pub fn from_co2(co2: f64) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|band| band.upper_co2().is_none_or(|upper| co2 < upper))
+            .unwrap_or(AirQualityBand::Poor)
+    }
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/insights/")]
+pub struct GetInsights {
+    #[validate]
+    pub place_name: ApiEntityName,
+    /// The range can be at most GetInsights::MAX_RANGE_DAYS long
+    pub lowest_added_at: Option<ApiTimestamp>,
+    pub upper_added_at: Option<ApiTimestamp>,
+    /// Size of the occupancy buckets, defaults to BucketSize::Hour
+    pub bucket: Option<BucketSize>,
+}
+
+impl GetInsights {
+    pub const MAX_RANGE_DAYS: i64 = 31;
+}
+
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/insights/")]
+pub struct AirQualityShare {
+    pub band: AirQualityBand,
+    /// CO2 readings in the band
+    pub readings: usize,
+}
+
+/// Ventilation estimated from how CO2 decayed towards the outdoor level after a peak
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/insights/")]
+pub struct VentilationEstimate {
+    pub peak_at: ApiTimestamp,
+    pub peak_co2: f64,
+    /// Last reading of the decay
+    pub end_at: ApiTimestamp,
+    pub air_changes_per_hour: f64,
+}
+
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/insights/")]
+pub struct ApiSensorInsights {
+    pub device_id: DeviceId,
+    /// Of the last CO2 reading in range, None without CO2 readings
+    pub current_band: Option<AirQualityBand>,
+    /// One per AirQualityBand, in order
+    pub bands: Vec<AirQualityShare>,
+    pub ventilation: Vec<VentilationEstimate>,
+    /// Median of the ventilation estimates
+    pub air_changes_per_hour: Option<f64>,
+}
+
+/// Likelihood, in [0, 1], of the place being occupied during [bucket_start, bucket_start + bucket)
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/insights/")]
+pub struct OccupancyBucket {
+    pub bucket_start: ApiTimestamp,
+    pub likelihood: f64,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/insights/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiInsights {
+    /// Sensors of the place with CO2 readings in range
+    pub sensors: Vec<ApiSensorInsights>,
+    /// Only buckets with CO2 readings
+    pub occupancy: Vec<OccupancyBucket>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_air_quality_band() {
+        assert_eq!(AirQualityBand::from_co2(420.0), AirQualityBand::Excellent);
+        assert_eq!(AirQualityBand::from_co2(800.0), AirQualityBand::Good);
+        assert_eq!(AirQualityBand::from_co2(1399.9), AirQualityBand::Moderate);
+        assert_eq!(AirQualityBand::from_co2(5000.0), AirQualityBand::Poor);
+    }
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use ts_rs::TS;
+
+use crate::types::ApiTimestamp;
+
+/// Bearer token Prometheus scrapes the latest readings of the sensors of the user with
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/metrics/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiScrapeToken {
+    /// Only returned when created, the server keeps just its hash
+    pub token: Option<String>,
+    pub created_at: ApiTimestamp,
+}
@@ -1,6 +1,13 @@
+pub mod alert;
+pub mod annotation;
 pub mod health;
+pub mod insights;
+pub mod metrics;
+pub mod notification;
 pub mod place;
+pub mod retention;
 pub mod sensor;
 pub mod sensor_data;
 pub mod session;
 pub mod user;
+pub mod webhook;
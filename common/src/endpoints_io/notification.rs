@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use ts_rs::TS;
+
+/// Which emails the user gets, all of them until changed
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/notification/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiNotificationPreferences {
+    /// Alerts of the sensors of the user triggered or resolved
+    pub alert_emails: bool,
+    /// Password, email or username of the user changed
+    pub account_emails: bool,
+    /// New sensor registered to a place of the user, or one going offline and back
+    pub sensor_emails: bool,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/notification/")]
+pub struct GetNotificationPreferences {}
+
+/// Changes the given preferences
+#[derive(TS, Debug, Clone, Default, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/notification/")]
+pub struct PutNotificationPreferences {
+    pub alert_emails: Option<bool>,
+    pub account_emails: Option<bool>,
+    pub sensor_emails: Option<bool>,
+}
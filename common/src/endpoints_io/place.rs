@@ -2,10 +2,17 @@ use serde::{Deserialize, Serialize};
 use serde_valid::Validate;
 use ts_rs::TS;
 
-use crate::types::{
-    ApiTimestamp,
-    validate::{
-        api_color::ApiColor, api_description::ApiDescription, api_entity_name::ApiEntityName,
+use crate::{
+    endpoints_io::{
+        annotation::ApiAnnotation,
+        sensor_data::{BucketSize, Metric},
+    },
+    types::{
+        ApiTimestamp,
+        validate::{
+            api_color::ApiColor, api_description::ApiDescription, api_entity_name::ApiEntityName,
+            device_id::DeviceId,
+        },
     },
 };
 
@@ -85,3 +92,82 @@ pub struct PostPlace {
     #[validate]
     pub color: ApiColor,
 }
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/place/")]
+pub struct GetPlaceStats {
+    #[validate]
+    pub place_name: ApiEntityName,
+    /// The range can be at most GetPlaceStats::MAX_RANGE_DAYS long
+    pub lowest_added_at: Option<ApiTimestamp>,
+    pub upper_added_at: Option<ApiTimestamp>,
+    /// If set, the stats are also returned per bucket of this size, as long as the range is split
+    /// in at most GetPlaceStats::MAX_BUCKETS
+    pub bucket: Option<BucketSize>,
+    /// Leaves the suspect metrics of each reading out, defaults to false
+    pub exclude_suspect: Option<bool>,
+}
+
+impl GetPlaceStats {
+    pub const MAX_RANGE_DAYS: i64 = 31;
+    pub const MAX_BUCKETS: usize = 5000;
+}
+
+/// A reading that hit the min or max of a metric
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/place/")]
+pub struct MetricExtreme {
+    pub value: f64,
+    pub device_id: DeviceId,
+    pub added_at: ApiTimestamp,
+}
+
+/// Aggregate of a metric over the readings of every sensor of the place, avg weights each reading
+/// equally so sensors that report more often weigh more
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/place/")]
+pub struct PlaceMetricStats {
+    pub min: MetricExtreme,
+    pub max: MetricExtreme,
+    pub avg: f64,
+    pub count: usize,
+}
+
+/// Stats of every metric for the readings added in [bucket_start, bucket_start + bucket)
+/// Metrics without any reading in the bucket are None
+#[derive(TS, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/place/")]
+pub struct ApiPlaceStatsBucket {
+    pub bucket_start: ApiTimestamp,
+    pub co2: Option<PlaceMetricStats>,
+    pub temperature: Option<PlaceMetricStats>,
+    pub humidity: Option<PlaceMetricStats>,
+    pub dew_point: Option<PlaceMetricStats>,
+    pub absolute_humidity: Option<PlaceMetricStats>,
+    pub humidex: Option<PlaceMetricStats>,
+}
+
+impl ApiPlaceStatsBucket {
+    pub fn metric_mut(&mut self, metric: Metric) -> &mut Option<PlaceMetricStats> {
+        match metric {
+            Metric::Co2 => &mut self.co2,
+            Metric::Temperature => &mut self.temperature,
+            Metric::Humidity => &mut self.humidity,
+            Metric::DewPoint => &mut self.dew_point,
+            Metric::AbsoluteHumidity => &mut self.absolute_humidity,
+            Metric::Humidex => &mut self.humidex,
+        }
+    }
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/place/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiPlaceStats {
+    /// Over the whole range, its bucket_start is the start of the range
+    pub total: ApiPlaceStatsBucket,
+    /// Empty unless GetPlaceStats::bucket was set
+    pub buckets: Vec<ApiPlaceStatsBucket>,
+    /// Annotations of the place, of its sensors and without a target overlapping the range
+    pub annotations: Vec<ApiAnnotation>,
+}
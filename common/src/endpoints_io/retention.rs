@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use serde_valid::Validate;
+use ts_rs::TS;
+
+use crate::types::validate::device_id::DeviceId;
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/retention/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiSensorRetention {
+    pub device_id: DeviceId,
+    /// Policy set on this sensor
+    pub keep_days: Option<u32>,
+    /// Days this sensor data is actually kept, taking every level into account, None if forever
+    pub effective_keep_days: Option<u32>,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/retention/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiRetentionPolicies {
+    /// Set by the server, no user or sensor policy can keep data longer than this
+    pub server_keep_days: Option<u32>,
+    /// Applies to every sensor of the user without its own policy
+    pub user_keep_days: Option<u32>,
+    pub sensors: Vec<ApiSensorRetention>,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/retention/")]
+pub enum RetentionTarget {
+    User,
+    Sensor(DeviceId),
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/retention/")]
+pub struct PutRetention {
+    pub target: RetentionTarget,
+    /// None removes the policy so the less specific one applies
+    #[validate(minimum = 1)]
+    #[validate(maximum = 36_500)]
+    pub keep_days: Option<u32>,
+}
+
+#[cfg(test)]
+mod test {
+    use serde_valid::Validate;
+
+    use crate::endpoints_io::retention::{PutRetention, RetentionTarget};
+
+    #[test]
+    fn test_put_retention_keep_days() {
+        for (keep_days, valid) in [(None, true), (Some(90), true), (Some(0), false)] {
+            let put = PutRetention {
+                target: RetentionTarget::User,
+                keep_days,
+            };
+            assert_eq!(put.validate().is_ok(), valid, "{keep_days:?}");
+        }
+    }
+}
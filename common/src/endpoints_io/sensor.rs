@@ -3,7 +3,7 @@ use serde_valid::Validate;
 use ts_rs::TS;
 
 use crate::{
-    endpoints_io::sensor_data::ApiSensorData,
+    endpoints_io::sensor_data::{ApiSensorData, Metric},
     types::{
         ApiTimestamp,
         validate::{
@@ -29,6 +29,76 @@ pub struct ApiUserSensor {
     pub created_at: ApiTimestamp,
     pub updated_at: ApiTimestamp,
     pub place_name: ApiEntityName,
+    /// Seconds the clock of the sensor is ahead of the server (negative if behind), measured on
+    /// its last reading that carried a created_at
+    pub clock_skew_secs: Option<i32>,
+    /// When the server last got a reading from the sensor
+    pub last_seen_at: Option<ApiTimestamp>,
+    /// How often the sensor is expected to report
+    pub expected_interval_secs: u32,
+    /// Every calibration of the sensor, sorted by metric and effective_from
+    pub calibrations: Vec<ApiCalibration>,
+}
+
+/// Linear correction of a measured metric of a sensor, applied when its readings are read so the
+/// stored ones stay raw. I.e.: an AHT10 reading 1.5 °C high is calibrated with offset -1.5
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor/")]
+pub struct ApiCalibration {
+    pub metric: Metric,
+    pub offset: f64,
+    pub scale: f64,
+    /// Applies to the readings added from then until the next calibration of the metric
+    pub effective_from: ApiTimestamp,
+}
+
+impl ApiCalibration {
+    /// raw * scale + offset
+    pub fn apply(&self, raw: f64) -> f64 {
+        raw * self.scale + self.offset
+    }
+}
+
+/// Whether the sensor is reporting, it is offline once it misses
+/// SensorStatus::MISSED_REPORTS expected reports in a row
+#[derive(TS, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor/")]
+pub enum SensorStatus {
+    Online,
+    Offline,
+    /// Never sent a reading
+    NeverSeen,
+}
+
+impl SensorStatus {
+    pub const MISSED_REPORTS: u32 = 5;
+
+    pub fn from_last_seen(
+        last_seen_at: Option<ApiTimestamp>,
+        expected_interval_secs: u32,
+        now: ApiTimestamp,
+    ) -> Self {
+        let Some(last_seen_at) = last_seen_at else {
+            return SensorStatus::NeverSeen;
+        };
+
+        let timeout = expected_interval_secs as ApiTimestamp * Self::MISSED_REPORTS as ApiTimestamp;
+        if now.saturating_sub(last_seen_at) > timeout {
+            SensorStatus::Offline
+        } else {
+            SensorStatus::Online
+        }
+    }
+}
+
+/// Sent when a sensor goes offline and when it reports again
+#[derive(TS, Clone, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiSensorStatusEvent {
+    pub device_id: DeviceId,
+    pub last_seen_at: ApiTimestamp,
+    pub expected_interval_secs: u32,
 }
 
 // impl ApiUserSensor {
@@ -68,6 +138,7 @@ pub struct GetSensor {
 pub struct GetSensorResponse {
     pub sensor: ApiUserSensor,
     pub last_data: Option<ApiSensorData>,
+    pub status: SensorStatus,
 }
 
 #[derive(TS, Debug, serde::Serialize, serde::Deserialize, Clone, Validate)]
@@ -76,6 +147,44 @@ pub enum SensorChange {
     Name(#[validate] ApiEntityName),
     Description(#[validate] Option<ApiDescription>),
     Color(#[validate] ApiColor),
+    /// Seconds between the reports of the sensor, from 10 seconds to a day
+    ExpectedInterval(
+        #[validate(minimum = 10)]
+        #[validate(maximum = 86_400)]
+        u32,
+    ),
+    /// Replaces the calibration of the metric with the same effective_from, if any
+    Calibration(#[validate] PutCalibration),
+}
+
+/// Offset 0 and scale 1 from some point on stops calibrating the metric
+#[derive(TS, Debug, serde::Serialize, serde::Deserialize, Clone, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor/")]
+#[validate(custom = PutCalibration::is_measured)]
+pub struct PutCalibration {
+    /// Only measured metrics, derived ones follow from them
+    pub metric: Metric,
+    #[validate(minimum = -1000.0)]
+    #[validate(maximum = 1000.0)]
+    pub offset: f64,
+    /// Defaults to 1
+    #[validate(exclusive_minimum = 0.0)]
+    #[validate(maximum = 10.0)]
+    pub scale: Option<f64>,
+    /// Defaults to now, earlier to recalibrate already stored readings
+    pub effective_from: Option<ApiTimestamp>,
+}
+
+impl PutCalibration {
+    fn is_measured(&self) -> Result<(), serde_valid::validation::Error> {
+        if self.metric.is_derived() {
+            Err(serde_valid::validation::Error::Custom(
+                "Derived metrics can't be calibrated".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
 }
 
 #[derive(TS, Debug, serde::Serialize, serde::Deserialize, Validate)]
@@ -109,3 +218,59 @@ pub enum DeleteSensor {
     FromSensorDeviceId(DeviceId),
     FromPlaceName(#[validate] ApiEntityName),
 }
+
+#[cfg(test)]
+mod test {
+    use serde_valid::Validate;
+
+    use crate::endpoints_io::{
+        sensor::{PutCalibration, SensorChange, SensorStatus},
+        sensor_data::Metric,
+    };
+
+    #[test]
+    fn test_sensor_status() {
+        let now = 10_000;
+        assert_eq!(
+            SensorStatus::from_last_seen(None, 60, now),
+            SensorStatus::NeverSeen
+        );
+        assert_eq!(
+            SensorStatus::from_last_seen(Some(now - 300), 60, now),
+            SensorStatus::Online
+        );
+        assert_eq!(
+            SensorStatus::from_last_seen(Some(now - 301), 60, now),
+            SensorStatus::Offline
+        );
+        // Clock of the server went back
+        assert_eq!(
+            SensorStatus::from_last_seen(Some(now + 60), 60, now),
+            SensorStatus::Online
+        );
+    }
+
+    #[test]
+    fn test_expected_interval() {
+        assert!(SensorChange::ExpectedInterval(60).validate().is_ok());
+        assert!(SensorChange::ExpectedInterval(5).validate().is_err());
+        assert!(SensorChange::ExpectedInterval(86_401).validate().is_err());
+    }
+
+    #[test]
+    fn test_calibration() {
+        let calibration = |metric, scale| {
+            SensorChange::Calibration(PutCalibration {
+                metric,
+                offset: -1.5,
+                scale,
+                effective_from: None,
+            })
+        };
+
+        assert!(calibration(Metric::Temperature, None).validate().is_ok());
+        assert!(calibration(Metric::Co2, Some(1.1)).validate().is_ok());
+        assert!(calibration(Metric::Humidity, Some(0.0)).validate().is_err());
+        assert!(calibration(Metric::DewPoint, None).validate().is_err());
+    }
+}
@@ -3,15 +3,190 @@ use serde_valid::Validate;
 use ts_rs::TS;
 
 use crate::{
-    endpoints_io::session::ApiSession,
-    types::{ApiTimestamp, validate::device_id::DeviceId},
+    endpoints_io::{annotation::ApiAnnotation, session::ApiSession},
+    psychrometrics,
+    types::{
+        ApiTimestamp,
+        validate::{api_entity_name::ApiEntityName, device_id::DeviceId},
+    },
 };
 
-#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+/// A single measurement taken by a sensor, mirrors `sensor-client`'s `sensors::SensorsData`
+/// Every metric is optional as a device may have only some of its sensors working, new metrics
+/// must be added as Option too so that already stored readings keep deserializing
+#[derive(TS, Clone, Debug, Default, PartialEq, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+#[validate(custom = SensorReading::has_some_metric)]
+pub struct SensorReading {
+    /// CO2 concentration in ppm (SCD41 range)
+    #[validate(maximum = 40_000)]
+    pub co2: Option<u16>,
+    /// Temperature in Celsius degrees (AHT10 range)
+    #[validate(minimum = -40.0)]
+    #[validate(maximum = 85.0)]
+    pub temperature: Option<f32>,
+    /// Relative humidity in %
+    #[validate(minimum = 0.0)]
+    #[validate(maximum = 100.0)]
+    pub humidity: Option<f32>,
+}
+
+impl SensorReading {
+    fn has_some_metric(&self) -> Result<(), serde_valid::validation::Error> {
+        if self.co2.is_none() && self.temperature.is_none() && self.humidity.is_none() {
+            Err(serde_valid::validation::Error::Custom(
+                "Reading contains no metrics".into(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The value of metric, None if not measured, derived metrics need both temperature and
+    /// humidity
+    pub fn metric(&self, metric: Metric) -> Option<f64> {
+        let derive = |f: fn(f64, f64) -> Option<f64>| {
+            f(f64::from(self.temperature?), f64::from(self.humidity?))
+        };
+
+        match metric {
+            Metric::Co2 => self.co2.map(f64::from),
+            Metric::Temperature => self.temperature.map(f64::from),
+            Metric::Humidity => self.humidity.map(f64::from),
+            Metric::DewPoint => derive(psychrometrics::dew_point),
+            Metric::AbsoluteHumidity => derive(psychrometrics::absolute_humidity),
+            Metric::Humidex => derive(psychrometrics::humidex),
+        }
+    }
+
+    /// A copy without the given metrics, i.e.: to leave the suspect ones out
+    pub fn without(&self, metrics: &[Metric]) -> SensorReading {
+        SensorReading {
+            co2: self.co2.filter(|_| !metrics.contains(&Metric::Co2)),
+            temperature: self
+                .temperature
+                .filter(|_| !metrics.contains(&Metric::Temperature)),
+            humidity: self
+                .humidity
+                .filter(|_| !metrics.contains(&Metric::Humidity)),
+        }
+    }
+
+    /// Replaces the value of a measured metric with f of it, CO2 is kept in whole, non negative
+    /// ppm rounding half to even like PostgreSQL does. Derived metrics are left as they are
+    pub fn map_metric(&mut self, metric: Metric, f: impl FnOnce(f64) -> f64) {
+        match metric {
+            Metric::Co2 => {
+                self.co2 = self.co2.map(|v| {
+                    f(f64::from(v))
+                        .round_ties_even()
+                        .clamp(0.0, u16::MAX as f64) as u16
+                })
+            }
+            Metric::Temperature => {
+                self.temperature = self.temperature.map(|v| f(f64::from(v)) as f32)
+            }
+            Metric::Humidity => self.humidity = self.humidity.map(|v| f(f64::from(v)) as f32),
+            Metric::DewPoint | Metric::AbsoluteHumidity | Metric::Humidex => {}
+        }
+    }
+
+    /// The value of metric formatted as a plain number, None if not measured
+    /// Derived metrics are rounded to 2 decimals
+    pub fn display_metric(&self, metric: Metric) -> Option<String> {
+        match metric {
+            Metric::Co2 => self.co2.map(|v| v.to_string()),
+            Metric::Temperature => self.temperature.map(|v| v.to_string()),
+            Metric::Humidity => self.humidity.map(|v| v.to_string()),
+            Metric::DewPoint | Metric::AbsoluteHumidity | Metric::Humidex => {
+                self.metric(metric).map(|v| format!("{v:.2}"))
+            }
+        }
+    }
+}
+
+/// Metrics computed from the measured temperature and humidity of a reading, see
+/// crate::psychrometrics
+#[derive(TS, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct DerivedMetrics {
+    /// Celsius degrees
+    pub dew_point: Option<f64>,
+    /// g/m³
+    pub absolute_humidity: Option<f64>,
+    pub humidex: Option<f64>,
+}
+
+impl DerivedMetrics {
+    pub fn from_reading(reading: &SensorReading) -> Self {
+        Self {
+            dew_point: reading.metric(Metric::DewPoint),
+            absolute_humidity: reading.metric(Metric::AbsoluteHumidity),
+            humidex: reading.metric(Metric::Humidex),
+        }
+    }
+}
+
+/// Each of the values a SensorReading can hold, serializes to the same key used in SensorReading
+#[derive(TS, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    Co2,
+    Temperature,
+    Humidity,
+    /// Derived, see DerivedMetrics
+    DewPoint,
+    AbsoluteHumidity,
+    Humidex,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 6] = [
+        Metric::Co2,
+        Metric::Temperature,
+        Metric::Humidity,
+        Metric::DewPoint,
+        Metric::AbsoluteHumidity,
+        Metric::Humidex,
+    ];
+    /// The ones stored in SensorReading
+    pub const MEASURED: [Metric; 3] = [Metric::Co2, Metric::Temperature, Metric::Humidity];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Metric::Co2 => "co2",
+            Metric::Temperature => "temperature",
+            Metric::Humidity => "humidity",
+            Metric::DewPoint => "dew_point",
+            Metric::AbsoluteHumidity => "absolute_humidity",
+            Metric::Humidex => "humidex",
+        }
+    }
+
+    pub fn is_derived(&self) -> bool {
+        !Self::MEASURED.contains(self)
+    }
+
+    pub fn from_key(key: &str) -> Option<Metric> {
+        Self::ALL.into_iter().find(|m| m.key() == key)
+    }
+}
+
+#[derive(TS, Clone, Debug, Serialize, Deserialize, Validate)]
 #[ts(export, export_to = "./api/endpoints/sensor_data/")]
 pub struct ApiSensorData {
-    #[validate(max_length = 500)]
-    pub data: String,
+    /// Of the stored reading, what DeleteSensorData::Ids takes
+    pub id: usize,
+    #[validate]
+    pub data: SensorReading,
+    /// Computed from data, ignored if sent
+    #[serde(default)]
+    pub derived: DerivedMetrics,
+    /// Metrics that looked like a glitch when the reading was stored, empty for good readings
+    /// Derived metrics are suspect if what they are computed from is
+    #[serde(default)]
+    pub suspect_metrics: Vec<Metric>,
     pub added_at: ApiTimestamp,
 }
 
@@ -23,6 +198,25 @@ pub struct PostSensorDataResponse {
     pub new_session: ApiSession,
 }
 
+/// Size of the time buckets sensor data is aggregated into
+#[derive(TS, Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub enum BucketSize {
+    Minute,
+    Hour,
+    Day,
+    Week,
+}
+
+/// Order in which raw sensor data is returned, by added_at
+#[derive(TS, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
 #[derive(TS, Debug, Serialize, Deserialize, Validate)]
 #[ts(export, export_to = "./api/endpoints/sensor_data/")]
 pub struct GetSensorData {
@@ -30,12 +224,586 @@ pub struct GetSensorData {
     // Not included if added_at == [upper | lowest]_added_at
     pub lowest_added_at: Option<ApiTimestamp>,
     pub upper_added_at: Option<ApiTimestamp>,
+    /// If set, data will be aggregated into buckets of this size instead of returned raw, the
+    /// range can be split in at most GetSensorData::MAX_BUCKETS of them. Without
+    /// lowest_added_at, the range goes back that many buckets from upper_added_at
+    /// limit, order and cursor only apply to raw data
+    pub bucket: Option<BucketSize>,
+    /// Leaves the suspect metrics of each reading out of the aggregates, defaults to false
+    pub exclude_suspect: Option<bool>,
+    /// Max datums returned, defaults to and is capped at GetSensorData::MAX_LIMIT
+    #[validate(minimum = 1)]
+    pub limit: Option<u32>,
+    /// Defaults to SortOrder::Asc
+    pub order: Option<SortOrder>,
+    /// next_cursor of the previous page, the rest of the params must be the same as in that
+    /// request
+    pub cursor: Option<String>,
+}
+
+impl GetSensorData {
+    pub const MAX_LIMIT: u32 = 1000;
+    pub const MAX_BUCKETS: usize = 5000;
+}
+
+/// Readings with suspect metrics, newest first
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct GetSuspectSensorData {
+    pub device_id: DeviceId,
+    pub lowest_added_at: Option<ApiTimestamp>,
+    pub upper_added_at: Option<ApiTimestamp>,
+    /// Defaults to and is capped at GetSensorData::MAX_LIMIT
+    #[validate(minimum = 1)]
+    pub limit: Option<u32>,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiSensorDataPage {
+    pub data: Vec<ApiSensorData>,
+    /// Some if there is more data, pass it as GetSensorData::cursor to get the next page
+    pub next_cursor: Option<String>,
+    /// Annotations of the sensor, of its place and without a target overlapping the range, so
+    /// that charts can overlay them. Only on the first page, the rest have none
+    pub annotations: Vec<ApiAnnotation>,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiSensorDataBuckets {
+    pub buckets: Vec<ApiSensorDataBucket>,
+    /// Annotations of the sensor, of its place and without a target overlapping the range
+    pub annotations: Vec<ApiAnnotation>,
+}
+
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct MetricAggregate {
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: usize,
+}
+
+/// Aggregates of every metric for the readings added in [bucket_start, bucket_start + bucket)
+/// Metrics without any reading in the bucket are None
+#[derive(TS, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct ApiSensorDataBucket {
+    pub bucket_start: ApiTimestamp,
+    pub co2: Option<MetricAggregate>,
+    pub temperature: Option<MetricAggregate>,
+    pub humidity: Option<MetricAggregate>,
+    pub dew_point: Option<MetricAggregate>,
+    pub absolute_humidity: Option<MetricAggregate>,
+    pub humidex: Option<MetricAggregate>,
+}
+
+impl ApiSensorDataBucket {
+    pub fn metric_mut(&mut self, metric: Metric) -> &mut Option<MetricAggregate> {
+        match metric {
+            Metric::Co2 => &mut self.co2,
+            Metric::Temperature => &mut self.temperature,
+            Metric::Humidity => &mut self.humidity,
+            Metric::DewPoint => &mut self.dew_point,
+            Metric::AbsoluteHumidity => &mut self.absolute_humidity,
+            Metric::Humidex => &mut self.humidex,
+        }
+    }
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+#[serde(untagged)]
+// WARN: Dont accept this in any endpoint
+pub enum GetSensorDataResponse {
+    Raw(ApiSensorDataPage),
+    Aggregated(ApiSensorDataBuckets),
+}
+
+/// One metric of several sensors aggregated into the same buckets
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct GetSensorDataComparison {
+    /// Comma separated device ids, at most GetSensorDataComparison::MAX_SENSORS, each one is a
+    /// column of the table in that order. I.e.: "<bedroom device_id>,<living room device_id>"
+    pub device_ids: String,
+    pub metric: Metric,
+    pub bucket: BucketSize,
+    /// Defaults to GetSensorDataComparison::MAX_ROWS buckets before upper_added_at
+    pub lowest_added_at: Option<ApiTimestamp>,
+    pub upper_added_at: Option<ApiTimestamp>,
+    /// Leaves the suspect readings out of the aggregates, defaults to false
+    pub exclude_suspect: Option<bool>,
+}
+
+impl GetSensorDataComparison {
+    pub const MAX_SENSORS: usize = 10;
+    /// Buckets the requested range can be split in, longer ranges are rejected
+    pub const MAX_ROWS: usize = 5000;
+
+    /// Parses device_ids, returns the first invalid or repeated one on error
+    pub fn device_ids(&self) -> Result<Vec<DeviceId>, String> {
+        let mut device_ids: Vec<DeviceId> = vec![];
+        for id in self.device_ids.split(',') {
+            let device_id = DeviceId::from_string(id.trim()).map_err(|_| id.to_string())?;
+            if device_ids.contains(&device_id) {
+                return Err(id.to_string());
+            }
+            device_ids.push(device_id);
+        }
+
+        Ok(device_ids)
+    }
+}
+
+/// A bucket of the comparison, values has one aggregate per sensor in the order of device_ids,
+/// None where the sensor has no data in the bucket
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct ComparisonRow {
+    pub bucket_start: ApiTimestamp,
+    pub values: Vec<Option<MetricAggregate>>,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiSensorDataComparison {
+    pub device_ids: Vec<DeviceId>,
+    pub metric: Metric,
+    pub bucket: BucketSize,
+    /// Every bucket from the first to the last one with data of any of the sensors, the ones
+    /// without data of any are included with every value None
+    pub rows: Vec<ComparisonRow>,
+    /// Annotations of any of the sensors, of their places and without a target overlapping the
+    /// range, sorted by starts_at
+    pub annotations: Vec<ApiAnnotation>,
+}
+
+/// Format of exported and imported sensor data, one datum per line, CSV has a header with
+/// added_at and the Metric keys
+#[derive(TS, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+#[derive(TS, Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub enum ExportTimestampFormat {
+    /// Seconds since the UNIX epoch
+    #[default]
+    Unix,
+    /// I.e.: 2025-01-01T10:00:00Z
+    Rfc3339,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct GetSensorDataExport {
+    pub device_id: DeviceId,
+    pub lowest_added_at: Option<ApiTimestamp>,
+    pub upper_added_at: Option<ApiTimestamp>,
+    /// Defaults to ExportFormat::Csv
+    pub format: Option<ExportFormat>,
+    /// Comma separated Metric keys exported, in that order, defaults to Metric::MEASURED
+    /// I.e.: "co2,temperature"
+    pub columns: Option<String>,
+    /// Defaults to ExportTimestampFormat::Unix
+    pub timestamp_format: Option<ExportTimestampFormat>,
+}
+
+impl GetSensorDataExport {
+    /// Parses columns, returns the first unknown key on error
+    pub fn metrics(&self) -> Result<Vec<Metric>, String> {
+        let Some(columns) = &self.columns else {
+            return Ok(Metric::MEASURED.to_vec());
+        };
+
+        columns
+            .split(',')
+            .map(|key| Metric::from_key(key.trim()).ok_or_else(|| key.to_string()))
+            .collect()
+    }
+}
+
+/// Query of the import, the body is the CSV or NDJSON text, timestamps can be in any
+/// ExportTimestampFormat
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct PostSensorDataImport {
+    pub device_id: DeviceId,
+    /// Defaults to ExportFormat::Csv
+    pub format: Option<ExportFormat>,
+}
+
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct ImportRowError {
+    /// 1 based, counting the CSV header
+    pub line: usize,
+    pub reason: String,
+}
+
+#[derive(TS, Clone, Debug, Default, PartialEq, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct PostSensorDataImportResponse {
+    pub imported: usize,
+    /// Rows already stored for the same sensor and added_at, or repeated in the input
+    pub skipped: usize,
+    pub failed: usize,
+    /// Reasons of the first failed rows, at most PostSensorDataImportResponse::MAX_ERRORS
+    pub errors: Vec<ImportRowError>,
+}
+
+impl PostSensorDataImportResponse {
+    pub const MAX_ERRORS: usize = 100;
 }
 
 #[derive(TS, Clone, Debug, serde::Serialize, serde::Deserialize, Validate)]
 #[ts(export, export_to = "./api/endpoints/sensor_data/")]
 pub struct PostSensorData {
-    #[validate(max_length = 500)]
-    pub serialized_data: String,
+    #[validate]
+    pub data: SensorReading,
     pub created_at: Option<ApiTimestamp>,
+    /// Unique per reading of a sensor, a retried upload with the same key is stored only once and
+    /// answered with the originally stored data
+    #[validate(min_length = 1)]
+    #[validate(max_length = 64)]
+    #[validate(pattern = r"^[0-9A-Za-z_-]+$")]
+    pub idempotency_key: Option<String>,
+}
+
+/// A buffered reading, the device must provide when it was measured
+#[derive(TS, Clone, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct BatchSensorReading {
+    // Validated one by one so that a bad reading doesn't reject the whole batch
+    pub data: SensorReading,
+    pub created_at: ApiTimestamp,
+}
+
+#[derive(TS, Clone, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct PostSensorDataBatch {
+    #[validate(min_items = 1)]
+    #[validate(max_items = 500)]
+    pub readings: Vec<BatchSensorReading>,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub enum BatchItemResult {
+    Accepted(ApiSensorData),
+    Rejected { reason: String },
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct PostSensorDataBatchResponse {
+    /// One result per reading, in the same order they were sent
+    pub results: Vec<BatchItemResult>,
+    pub new_session: ApiSession,
+}
+
+/// When a sensor was silent, by the gaps between its readings
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct GetSensorDataGaps {
+    pub device_id: DeviceId,
+    /// The range can be at most GetSensorDataGaps::MAX_RANGE_DAYS long, it ends now at most
+    pub lowest_added_at: Option<ApiTimestamp>,
+    pub upper_added_at: Option<ApiTimestamp>,
+    /// Max gaps returned, defaults to and is capped at GetSensorDataGaps::MAX_LIMIT
+    #[validate(minimum = 1)]
+    pub limit: Option<u32>,
+}
+
+impl GetSensorDataGaps {
+    pub const MAX_RANGE_DAYS: i64 = 31;
+    pub const MAX_LIMIT: u32 = 1000;
+    /// A gap is longer than this many expected intervals of the sensor
+    pub const GAP_INTERVALS: u32 = 2;
+}
+
+/// Time between two consecutive readings, or between a reading and an end of the range
+#[derive(TS, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct DataGap {
+    pub start: ApiTimestamp,
+    pub end: ApiTimestamp,
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiSensorDataGaps {
+    /// Range the report covers
+    pub lowest_added_at: ApiTimestamp,
+    pub upper_added_at: ApiTimestamp,
+    /// Silences longer than this are gaps, GetSensorDataGaps::GAP_INTERVALS expected intervals
+    pub threshold_secs: u32,
+    /// Percentage, in [0, 100], of the range not in any gap
+    pub uptime: f64,
+    pub total_gaps: usize,
+    /// Oldest first, at most the limit
+    pub gaps: Vec<DataGap>,
+}
+
+/// Readings of a sensor to delete
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub enum SensorDataSelection {
+    /// Every reading added in [lowest_added_at, upper_added_at]
+    Range {
+        lowest_added_at: ApiTimestamp,
+        upper_added_at: ApiTimestamp,
+    },
+    /// ApiSensorData::id of the readings, at most DeleteSensorData::MAX_IDS, the ones of other
+    /// sensors are ignored
+    Ids(Vec<usize>),
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+#[validate(custom = DeleteSensorData::valid_selection)]
+pub struct DeleteSensorData {
+    pub device_id: DeviceId,
+    pub selection: SensorDataSelection,
+    /// Only reports what would be deleted, defaults to false
+    pub dry_run: Option<bool>,
+}
+
+impl DeleteSensorData {
+    pub const MAX_IDS: usize = 1000;
+
+    fn valid_selection(&self) -> Result<(), serde_valid::validation::Error> {
+        let error = match &self.selection {
+            SensorDataSelection::Range {
+                lowest_added_at,
+                upper_added_at,
+            } if upper_added_at < lowest_added_at => "upper_added_at is before lowest_added_at",
+            SensorDataSelection::Ids(ids) if ids.is_empty() => "No ids",
+            SensorDataSelection::Ids(ids) if ids.len() > Self::MAX_IDS => "Too many ids",
+            _ => return Ok(()),
+        };
+
+        Err(serde_valid::validation::Error::Custom(error.into()))
+    }
+}
+
+#[derive(TS, Debug, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct DeleteSensorDataResponse {
+    pub dry_run: bool,
+    /// Readings deleted, or that would be if dry_run
+    pub deleted: usize,
+    /// added_at of the oldest and newest of them, None if there are none
+    pub oldest_added_at: Option<ApiTimestamp>,
+    pub newest_added_at: Option<ApiTimestamp>,
+}
+
+/// Subscription to the live readings of the sensors of the user, of every sensor if no filter is
+/// set
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+pub struct GetSensorDataStream {
+    pub device_id: Option<DeviceId>,
+    #[validate]
+    pub place_name: Option<ApiEntityName>,
+}
+
+/// Data of every "sensor_data" event of the stream
+#[derive(TS, Clone, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/sensor_data/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiSensorDataEvent {
+    pub device_id: DeviceId,
+    #[validate]
+    pub api_data: ApiSensorData,
+}
+
+#[cfg(test)]
+mod test {
+    use serde_valid::Validate;
+
+    use crate::{
+        endpoints_io::sensor_data::{
+            BucketSize, DeleteSensorData, GetSensorDataComparison, GetSensorDataExport, Metric,
+            SensorDataSelection, SensorReading,
+        },
+        psychrometrics,
+        types::validate::device_id::DeviceId,
+    };
+
+    #[test]
+    fn test_sensor_reading_success() {
+        SensorReading {
+            co2: Some(412),
+            temperature: Some(21.5),
+            humidity: Some(45.0),
+        }
+        .validate()
+        .expect("Should be fine");
+        SensorReading {
+            co2: None,
+            temperature: Some(-12.0),
+            humidity: None,
+        }
+        .validate()
+        .expect("Should be fine");
+    }
+
+    #[test]
+    fn test_metric_key() {
+        for metric in Metric::ALL {
+            assert_eq!(Metric::from_key(metric.key()), Some(metric));
+            assert_eq!(
+                serde_valid::json::json!(metric),
+                serde_valid::json::json!(metric.key())
+            );
+        }
+        assert_eq!(Metric::from_key("pressure"), None);
+    }
+
+    #[test]
+    fn test_derived_metric() {
+        let mut reading = SensorReading {
+            co2: Some(412),
+            temperature: Some(20.0),
+            humidity: None,
+        };
+        assert_eq!(reading.metric(Metric::DewPoint), None);
+
+        reading.humidity = Some(50.0);
+        assert_eq!(
+            reading.metric(Metric::DewPoint),
+            psychrometrics::dew_point(20.0, 50.0)
+        );
+        assert_eq!(
+            reading.display_metric(Metric::DewPoint),
+            Some("9.26".into())
+        );
+        assert!(Metric::DewPoint.is_derived());
+        assert!(!Metric::Humidity.is_derived());
+
+        let without = reading.without(&[Metric::Humidity]);
+        assert_eq!(without.metric(Metric::DewPoint), None);
+        assert_eq!(without.co2, Some(412));
+
+        let mut calibrated = reading.clone();
+        calibrated.map_metric(Metric::Temperature, |t| t - 1.5);
+        calibrated.map_metric(Metric::Co2, |co2| co2 * 1.1 - 500.0);
+        calibrated.map_metric(Metric::DewPoint, |_| 0.0);
+        assert_eq!(calibrated.temperature, Some(18.5));
+        assert_eq!(calibrated.co2, Some(0));
+        assert_eq!(calibrated.humidity, reading.humidity);
+    }
+
+    #[test]
+    fn test_export_metrics() {
+        let mut export = GetSensorDataExport {
+            device_id: DeviceId::random(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            format: None,
+            columns: None,
+            timestamp_format: None,
+        };
+        assert_eq!(export.metrics(), Ok(Metric::MEASURED.to_vec()));
+
+        export.columns = Some("humidity, co2".into());
+        assert_eq!(export.metrics(), Ok(vec![Metric::Humidity, Metric::Co2]));
+
+        export.columns = Some("co2,pressure".into());
+        assert_eq!(export.metrics(), Err("pressure".into()));
+    }
+
+    #[test]
+    fn test_comparison_device_ids() {
+        let (bedroom, living_room) = (DeviceId::random(), DeviceId::random());
+        let mut comparison = GetSensorDataComparison {
+            device_ids: format!("{}, {}", bedroom.as_str(), living_room.as_str()),
+            metric: Metric::Co2,
+            bucket: BucketSize::Hour,
+            lowest_added_at: None,
+            upper_added_at: None,
+            exclude_suspect: None,
+        };
+        assert_eq!(
+            comparison.device_ids(),
+            Ok(vec![bedroom.clone(), living_room])
+        );
+
+        comparison.device_ids = format!("{},{}", bedroom.as_str(), bedroom.as_str());
+        assert_eq!(comparison.device_ids(), Err(bedroom.as_str().to_string()));
+
+        comparison.device_ids = "bedroom".into();
+        assert_eq!(comparison.device_ids(), Err("bedroom".into()));
+    }
+
+    #[test]
+    fn test_sensor_reading_fail() {
+        SensorReading::default()
+            .validate()
+            .expect_err("Should error, no metrics");
+        SensorReading {
+            co2: Some(40_001),
+            ..Default::default()
+        }
+        .validate()
+        .expect_err("Should error");
+        SensorReading {
+            temperature: Some(80000.0),
+            ..Default::default()
+        }
+        .validate()
+        .expect_err("Should error");
+        SensorReading {
+            humidity: Some(-1.0),
+            ..Default::default()
+        }
+        .validate()
+        .expect_err("Should error");
+    }
+
+    #[test]
+    fn test_delete_sensor_data_selection() {
+        let delete = |selection| DeleteSensorData {
+            device_id: DeviceId::random(),
+            selection,
+            dry_run: None,
+        };
+
+        delete(SensorDataSelection::Ids(vec![1, 2]))
+            .validate()
+            .expect("Should not error");
+        delete(SensorDataSelection::Ids(vec![]))
+            .validate()
+            .expect_err("Should error, no ids");
+        delete(SensorDataSelection::Ids((0..=DeleteSensorData::MAX_IDS).collect()))
+            .validate()
+            .expect_err("Should error, too many ids");
+        delete(SensorDataSelection::Range {
+            lowest_added_at: 0,
+            upper_added_at: 60,
+        })
+        .validate()
+        .expect("Should not error");
+        delete(SensorDataSelection::Range {
+            lowest_added_at: 60,
+            upper_added_at: 0,
+        })
+        .validate()
+        .expect_err("Should error, upper before lowest");
+    }
 }
@@ -0,0 +1,410 @@
+use std::net::IpAddr;
+
+use serde::{Deserialize, Serialize};
+use serde_valid::{Validate, validation::Error};
+use ts_rs::TS;
+
+use crate::{
+    endpoints_io::{
+        alert::ApiAlertEvent, sensor::ApiSensorStatusEvent, sensor_data::ApiSensorDataEvent,
+    },
+    types::ApiTimestamp,
+};
+
+/// Kinds of events a webhook can subscribe to
+#[derive(TS, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+pub enum WebhookEventKind {
+    SensorData,
+    AlertTriggered,
+    AlertResolved,
+    SensorOffline,
+    /// A sensor that was offline reported again
+    SensorOnline,
+    /// Sent by POST /webhook/test, whatever the webhook is subscribed to
+    Test,
+}
+
+impl WebhookEventKind {
+    pub const ALL: [WebhookEventKind; 6] = [
+        WebhookEventKind::SensorData,
+        WebhookEventKind::AlertTriggered,
+        WebhookEventKind::AlertResolved,
+        WebhookEventKind::SensorOffline,
+        WebhookEventKind::SensorOnline,
+        WebhookEventKind::Test,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            WebhookEventKind::SensorData => "sensor_data",
+            WebhookEventKind::AlertTriggered => "alert_triggered",
+            WebhookEventKind::AlertResolved => "alert_resolved",
+            WebhookEventKind::SensorOffline => "sensor_offline",
+            WebhookEventKind::SensorOnline => "sensor_online",
+            WebhookEventKind::Test => "test",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<WebhookEventKind> {
+        Self::ALL.into_iter().find(|k| k.key() == key)
+    }
+}
+
+#[derive(TS, Clone, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+#[serde(tag = "kind", content = "data")]
+pub enum WebhookEvent {
+    SensorData(ApiSensorDataEvent),
+    AlertTriggered(ApiAlertEvent),
+    AlertResolved(ApiAlertEvent),
+    SensorOffline(ApiSensorStatusEvent),
+    SensorOnline(ApiSensorStatusEvent),
+    Test,
+}
+
+impl WebhookEvent {
+    pub fn kind(&self) -> WebhookEventKind {
+        match self {
+            WebhookEvent::SensorData(_) => WebhookEventKind::SensorData,
+            WebhookEvent::AlertTriggered(_) => WebhookEventKind::AlertTriggered,
+            WebhookEvent::AlertResolved(_) => WebhookEventKind::AlertResolved,
+            WebhookEvent::SensorOffline(_) => WebhookEventKind::SensorOffline,
+            WebhookEvent::SensorOnline(_) => WebhookEventKind::SensorOnline,
+            WebhookEvent::Test => WebhookEventKind::Test,
+        }
+    }
+}
+
+/// Body POSTed to the url of a webhook. It's signed with the secret of the webhook, see
+/// WebhookSignature
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiWebhookPayload {
+    /// Same on every retry of the delivery, can be used to discard duplicates
+    pub delivery_id: i32,
+    pub created_at: ApiTimestamp,
+    pub event: WebhookEvent,
+}
+
+/// Headers sent with every ApiWebhookPayload. The signature is the hex HMAC-SHA256, keyed with the
+/// secret of the webhook as is, of "{timestamp}.{body}"
+pub struct WebhookSignature;
+
+impl WebhookSignature {
+    pub const SIGNATURE_HEADER: &str = "x-sensor-signature";
+    pub const TIMESTAMP_HEADER: &str = "x-sensor-timestamp";
+    pub const EVENT_HEADER: &str = "x-sensor-event";
+    pub const DELIVERY_HEADER: &str = "x-sensor-delivery";
+    /// Prefix of the value of SIGNATURE_HEADER
+    pub const SCHEME: &str = "sha256=";
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiWebhook {
+    pub id: i32,
+    pub url: String,
+    pub events: Vec<WebhookEventKind>,
+    pub enabled: bool,
+    pub created_at: ApiTimestamp,
+    pub updated_at: ApiTimestamp,
+}
+
+/// Response of POST /webhook, the only time the secret is sent
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiNewWebhook {
+    pub webhook: ApiWebhook,
+    /// Hex key of the signatures
+    pub secret: String,
+}
+
+#[derive(TS, Debug, Clone, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+pub struct PostWebhook {
+    /// http or https url
+    #[validate(max_length = 2048)]
+    #[validate(pattern = r"^https?://[^\s/?#]+[^\s]*$")]
+    #[validate(custom(valid_webhook_host))]
+    pub url: String,
+    #[validate(min_items = 1)]
+    #[validate(unique_items)]
+    pub events: Vec<WebhookEventKind>,
+}
+
+/// Whether webhooks may be sent to the address. The server must not be usable to reach its own
+/// network, so loopback, private, link-local (i.e.: cloud metadata), unspecified, broadcast and
+/// multicast addresses are not
+pub fn is_public_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // "This network" and the shared address space of carrier-grade NATs
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_address(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Whether the address is of a private network (i.e.: the LAN of a Home Assistant), which webhooks
+/// can only be sent to if the server allows it
+pub fn is_private_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private(),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_address(IpAddr::V4(ip)),
+            None => ip.is_unique_local(),
+        },
+    }
+}
+
+/// Host of an http(s) url, without brackets if it's an IPv6 one
+pub fn url_host(url: &str) -> Option<&str> {
+    let (_, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let host = match host_port.strip_prefix('[') {
+        Some(ipv6) => ipv6.split_once(']')?.0,
+        None => host_port.split(':').next()?,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+/// Only what can be told without resolving the host, the server checks what it resolves to too.
+/// Private addresses are left to the server, which may allow them
+fn valid_webhook_host(url: &str) -> Result<(), Error> {
+    let host = url_host(url).ok_or_else(|| Error::Custom("Url without host".into()))?;
+    let local_name =
+        host.eq_ignore_ascii_case("localhost") || host.to_ascii_lowercase().ends_with(".localhost");
+    let local_ip = host
+        .parse::<IpAddr>()
+        .is_ok_and(|ip| !is_public_address(ip) && !is_private_address(ip));
+    if local_name || local_ip {
+        return Err(Error::Custom(
+            "Webhooks can't target local addresses".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// Changes the given fields of the webhook
+#[derive(TS, Debug, Clone, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+pub struct PutWebhook {
+    pub id: i32,
+    #[validate(max_length = 2048)]
+    #[validate(pattern = r"^https?://[^\s/?#]+[^\s]*$")]
+    #[validate(custom(PutWebhook::valid_host))]
+    pub url: Option<String>,
+    #[validate(min_items = 1)]
+    #[validate(unique_items)]
+    pub events: Option<Vec<WebhookEventKind>>,
+    pub enabled: Option<bool>,
+}
+
+impl PutWebhook {
+    fn valid_host(url: &Option<String>) -> Result<(), Error> {
+        url.as_deref().map_or(Ok(()), valid_webhook_host)
+    }
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+pub struct DeleteWebhook {
+    pub id: i32,
+}
+
+/// Queues a Test event to the webhook, attempted right away by the dispatcher
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+pub struct PostWebhookTest {
+    pub id: i32,
+}
+
+#[derive(TS, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+pub enum WebhookDeliveryStatus {
+    /// Waiting for its first attempt or a retry
+    Pending,
+    Delivered,
+    /// Gave up after too many attempts
+    Failed,
+}
+
+impl WebhookDeliveryStatus {
+    pub const ALL: [WebhookDeliveryStatus; 3] = [
+        WebhookDeliveryStatus::Pending,
+        WebhookDeliveryStatus::Delivered,
+        WebhookDeliveryStatus::Failed,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            WebhookDeliveryStatus::Pending => "pending",
+            WebhookDeliveryStatus::Delivered => "delivered",
+            WebhookDeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<WebhookDeliveryStatus> {
+        Self::ALL.into_iter().find(|s| s.key() == key)
+    }
+}
+
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+// WARN: Dont accept this in any endpoint
+pub struct ApiWebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub kind: WebhookEventKind,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, None if it didn't get a response
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: ApiTimestamp,
+    /// None once delivered or failed
+    pub next_attempt_at: Option<ApiTimestamp>,
+    pub delivered_at: Option<ApiTimestamp>,
+}
+
+/// Latest deliveries of the webhook first
+#[derive(TS, Debug, Serialize, Deserialize, Validate)]
+#[ts(export, export_to = "./api/endpoints/webhook/")]
+pub struct GetWebhookDeliveries {
+    pub id: i32,
+    /// Defaults to GetWebhookDeliveries::MAX_LIMIT
+    #[validate(minimum = 1)]
+    pub limit: Option<u32>,
+}
+
+impl GetWebhookDeliveries {
+    pub const MAX_LIMIT: u32 = 200;
+}
+
+#[cfg(test)]
+mod test {
+    use serde_valid::{Validate, json::json};
+
+    use crate::endpoints_io::webhook::{
+        PostWebhook, PutWebhook, WebhookEvent, WebhookEventKind, url_host,
+    };
+
+    #[test]
+    fn test_post_webhook() {
+        let webhook = |url: &str, events: Vec<WebhookEventKind>| PostWebhook {
+            url: url.to_string(),
+            events,
+        };
+        let events = vec![WebhookEventKind::AlertTriggered];
+
+        assert!(
+            webhook(
+                "http://homeassistant.local:8123/api/webhook/x",
+                events.clone()
+            )
+            .validate()
+            .is_ok()
+        );
+        assert!(
+            webhook("https://example.com", events.clone())
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            webhook("ftp://example.com", events.clone())
+                .validate()
+                .is_err()
+        );
+        assert!(webhook("https://", events.clone()).validate().is_err());
+        for local in [
+            "http://localhost:3001/metrics",
+            "http://127.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "https://user@[::1]:8443/hook",
+            "http://0.0.0.0/hook",
+        ] {
+            assert!(
+                webhook(local, events.clone()).validate().is_err(),
+                "{local}"
+            );
+        }
+        // Whether private networks can be reached is up to the server
+        for private in [
+            "http://10.1.2.3/hook",
+            "http://[::ffff:192.168.1.1]/hook",
+            "http://[fd00::1]:8123/hook",
+        ] {
+            assert!(
+                webhook(private, events.clone()).validate().is_ok(),
+                "{private}"
+            );
+        }
+        assert!(
+            webhook("http://93.184.216.34:8080/hook", events.clone())
+                .validate()
+                .is_ok()
+        );
+        assert!(webhook("https://example.com", vec![]).validate().is_err());
+        assert!(
+            webhook("https://example.com", vec![events[0], events[0]])
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_put_webhook() {
+        let webhook = |url: Option<&str>| PutWebhook {
+            id: 1,
+            url: url.map(str::to_string),
+            events: None,
+            enabled: None,
+        };
+
+        assert!(webhook(None).validate().is_ok());
+        assert!(webhook(Some("https://example.com/hook")).validate().is_ok());
+        assert!(webhook(Some("http://192.168.0.10/hook")).validate().is_ok());
+        assert!(webhook(Some("http://127.0.0.1/hook")).validate().is_err());
+    }
+
+    #[test]
+    fn test_url_host() {
+        assert_eq!(url_host("https://example.com"), Some("example.com"));
+        assert_eq!(
+            url_host("http://a:b@example.com:80/x?y#z"),
+            Some("example.com")
+        );
+        assert_eq!(url_host("http://[fe80::1]:8080/x"), Some("fe80::1"));
+        assert_eq!(url_host("https:///x"), None);
+    }
+
+    #[test]
+    fn test_webhook_event_json() {
+        assert_eq!(json!(WebhookEvent::Test), json!({"kind": "Test"}));
+        for kind in WebhookEventKind::ALL {
+            assert_eq!(WebhookEventKind::from_key(kind.key()), Some(kind));
+        }
+    }
+}
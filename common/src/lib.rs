@@ -5,6 +5,8 @@ pub mod ble_protocol;
 #[cfg(feature = "api")]
 pub mod endpoints_io;
 
+pub mod psychrometrics;
+
 pub mod types;
 
 #[cfg(test)]
@@ -0,0 +1,65 @@
+//! Metrics derived from the temperature (Celsius degrees) and relative humidity (%) of a reading
+//! The server's derived_metrics SQL function must use these same formulas
+
+/// Magnus formula coefficients (Sonntag 1990), good to 0.35 °C in -45..60 °C
+const MAGNUS_B: f64 = 17.62;
+const MAGNUS_C: f64 = 243.12;
+
+/// Saturation vapour pressure over water in hPa
+pub fn saturation_vapour_pressure(temperature: f64) -> f64 {
+    6.112 * ((MAGNUS_B * temperature) / (MAGNUS_C + temperature)).exp()
+}
+
+/// In Celsius degrees, None if humidity is not in (0, 100]
+pub fn dew_point(temperature: f64, humidity: f64) -> Option<f64> {
+    if humidity <= 0.0 || humidity > 100.0 {
+        return None;
+    }
+
+    let gamma = (humidity / 100.0).ln() + (MAGNUS_B * temperature) / (MAGNUS_C + temperature);
+    Some(MAGNUS_C * gamma / (MAGNUS_B - gamma))
+}
+
+/// Grams of water vapour per cubic meter of air, None if humidity is not in [0, 100]
+pub fn absolute_humidity(temperature: f64, humidity: f64) -> Option<f64> {
+    if !(0.0..=100.0).contains(&humidity) {
+        return None;
+    }
+
+    let vapour_pressure = saturation_vapour_pressure(temperature) * humidity / 100.0;
+    Some(216.7 * vapour_pressure / (273.15 + temperature))
+}
+
+/// Environment Canada's humidex, how hot the air feels in Celsius degrees
+/// None if humidity is not in (0, 100]
+pub fn humidex(temperature: f64, humidity: f64) -> Option<f64> {
+    let dew_point = dew_point(temperature, humidity)?;
+    let vapour_pressure = 6.11 * (5417.7530 * (1.0 / 273.16 - 1.0 / (273.15 + dew_point))).exp();
+    Some(temperature + 0.5555 * (vapour_pressure - 10.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: Option<f64>, expected: f64) {
+        let value = value.expect("Should be Some");
+        assert!(
+            (value - expected).abs() < 0.1,
+            "{value} is not close to {expected}"
+        );
+    }
+
+    #[test]
+    fn test_derived_metrics() {
+        assert_close(dew_point(20.0, 50.0), 9.3);
+        assert_close(dew_point(30.0, 100.0), 30.0);
+        assert_close(absolute_humidity(20.0, 50.0), 8.6);
+        assert_close(absolute_humidity(20.0, 0.0), 0.0);
+        assert_close(humidex(30.0, 70.0), 41.2);
+
+        assert_eq!(dew_point(20.0, 0.0), None);
+        assert_eq!(humidex(20.0, 101.0), None);
+        assert_eq!(absolute_humidity(20.0, -1.0), None);
+    }
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use serde_valid::{Validate, validation::Error};
+use ts_rs::TS;
+
+/// Free text of an annotation, i.e.: "Window opened, 2nd floor"
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, TS, PartialEq)]
+#[ts(export, export_to = "./api/types/")]
+pub struct ApiAnnotationText(#[validate(custom(ApiAnnotationText::valid))] String);
+
+impl ApiAnnotationText {
+    pub const MIN_LEN: usize = 1;
+    pub const MAX_LEN: usize = 200;
+
+    fn valid(val: &String) -> Result<(), serde_valid::validation::Error> {
+        if !(Self::MIN_LEN..=Self::MAX_LEN).contains(&val.chars().count())
+            || val.chars().any(char::is_control)
+            || val.trim() != val
+        {
+            Err(Error::Custom("Invalid annotation text".into()))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for ApiAnnotationText {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<ApiAnnotationText> for String {
+    fn from(value: ApiAnnotationText) -> Self {
+        value.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_valid::Validate;
+
+    use crate::types::validate::api_annotation_text::ApiAnnotationText;
+
+    #[test]
+    fn test_api_annotation_text() {
+        for valid in ["New HVAC filter (F7)", "Sensor moved 2m, next to the window", "á"] {
+            ApiAnnotationText::from(valid.to_string())
+                .validate()
+                .expect("Should be fine");
+        }
+        ApiAnnotationText::from("a".repeat(ApiAnnotationText::MAX_LEN))
+            .validate()
+            .expect("Should be fine");
+
+        for invalid in ["", " Window opened", "Window\nopened"] {
+            ApiAnnotationText::from(invalid.to_string())
+                .validate()
+                .expect_err("Should error");
+        }
+        ApiAnnotationText::from("a".repeat(ApiAnnotationText::MAX_LEN + 1))
+            .validate()
+            .expect_err("Should error");
+    }
+}
@@ -1,3 +1,4 @@
+pub mod api_annotation_text;
 pub mod api_color;
 pub mod api_description;
 pub mod api_email;
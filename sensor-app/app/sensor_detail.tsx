@@ -65,8 +65,7 @@ export default function SensorDetail() {
         if (hookedLastData) {
             return objectNumberKeysToArray(hookedLastData[0]);
         } else if (globalData) {
-            const parsed = JSON.parse(JSON.parse(globalData.data));
-            return objectNumberKeysToArray(parsed);
+            return objectNumberKeysToArray(globalData.data);
         } else {
             return undefined;
         }
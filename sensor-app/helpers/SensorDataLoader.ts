@@ -49,10 +49,9 @@ export class SensorDataLoader {
         let commonNumberKeys: string[] | undefined = undefined;
 
         for (const { data, added_at } of gotData) {
-            const parsed = JSON.parse(JSON.parse(data));
-            parsedData.push([parsed, added_at]);
+            parsedData.push([data, added_at]);
 
-            const numberKeys = Object.entries(parsed)
+            const numberKeys = Object.entries(data)
                 .filter(([, v]) => typeof v === 'number')
                 .map(([k]) => k as string);
 
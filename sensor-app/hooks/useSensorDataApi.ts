@@ -1,16 +1,23 @@
 import { ApiUserSensor } from '@/bindings/api/endpoints/sensor/ApiUserSensor';
 import { GetSensorData } from '@/bindings/api/endpoints/sensor_data/GetSensorData';
 import { SensorDataLoader } from '@/helpers/SensorDataLoader';
-import { useEffect, useMemo, useState } from 'react';
+import { useEffect, useMemo, useRef, useState } from 'react';
 import useApi from './useApi';
-import { ApiSensorData } from '@/bindings/api/endpoints/sensor_data/ApiSensorData';
+import { ApiSensorDataPage } from '@/bindings/api/endpoints/sensor_data/ApiSensorDataPage';
 import { DataShape } from '@/model/ShapedData';
+import { ApiSensorData } from '@/bindings/api/endpoints/sensor_data/ApiSensorData';
 
 function fabGetSensorData(sensor: ApiUserSensor, offsetMillis: number): GetSensorData {
     return {
         device_id: sensor.device_id,
         lowest_added_at: ~~((Date.now() - offsetMillis) / 1000),
         upper_added_at: null,
+        bucket: null,
+        exclude_suspect: null,
+        limit: null,
+        // Newest first, every page is requested until there is no next_cursor
+        order: 'Desc',
+        cursor: null,
     };
 }
 
@@ -24,6 +31,12 @@ const computeParams = (apiBody: GetSensorData | undefined) => {
     if (apiBody.upper_added_at) {
         params_arr.push(['upper_added_at', '' + apiBody.upper_added_at]);
     }
+    if (apiBody.order) {
+        params_arr.push(['order', apiBody.order]);
+    }
+    if (apiBody.cursor) {
+        params_arr.push(['cursor', apiBody.cursor]);
+    }
     return params_arr;
 };
 
@@ -50,6 +63,7 @@ export default function useSensorDataApi(
                 ? {
                       ...prev,
                       lowest_added_at: ~~((Date.now() - offsetMillis) / 1000),
+                      cursor: null,
                   }
                 : fabGetSensorData(sensor, offsetMillis),
         );
@@ -62,7 +76,13 @@ export default function useSensorDataApi(
     );
     const api = useApi('/sensor_data', apiMethod, false, undefined, apiParams);
 
+    // Data of the pages received so far for the range, newest first
+    const pagesRef = useRef<ApiSensorData[]>([]);
+    // So that a page isn't added twice when only maxLabels changes
+    const lastPageRef = useRef<ApiSensorDataPage | undefined>(undefined);
+
     const reload = () => {
+        setApiBody((prev) => (prev?.cursor ? { ...prev, cursor: null } : prev));
         setApiMethod(undefined);
         setTimeout(() => {
             setApiMethod('GET');
@@ -73,9 +93,24 @@ export default function useSensorDataApi(
     useEffect(() => {
         if (!(api.returnedOk && api.response)) return;
 
+        const page = api.response as ApiSensorDataPage;
+        if (lastPageRef.current !== page) {
+            lastPageRef.current = page;
+            // The first page of the range
+            if (!apiBody?.cursor) {
+                pagesRef.current = [];
+            }
+            pagesRef.current = pagesRef.current.concat(page.data);
+        }
+        if (page.next_cursor) {
+            const cursor = page.next_cursor;
+            setApiBody((prev) => (prev ? { ...prev, cursor } : prev));
+            return;
+        }
+
         let cancelled = false;
         const load = async () => {
-            const data = api.response as ApiSensorData[];
+            const data = [...pagesRef.current].reverse();
             const dataShape: DataShape = { maxLabels, maxPoints: 1000 };
             const loader = await SensorDataLoader.load(data, dataShape);
             if (!cancelled) {
@@ -7,7 +7,7 @@ use crate::{
     server_communicator::ServerCommunicator,
     wifi_connector::{WifiClientConfig, WifiConnector},
 };
-use common::types::{validate::device_id::DeviceId, zstr20::ZStr20};
+use common::types::{ApiTimestamp, validate::device_id::DeviceId, zstr20::ZStr20};
 use common::{
     auth::{self, keys::Keys},
     endpoints_io::sensor_data::PostSensorData,
@@ -16,6 +16,7 @@ use esp_idf_svc::{
     eventloop::EspEventLoop,
     hal::{i2c::I2cDriver, prelude::*},
     nvs::EspDefaultNvsPartition,
+    sntp::{EspSntp, SyncStatus},
     wifi::Configuration,
 };
 use esp_idf_sys::{
@@ -157,6 +158,10 @@ fn main() {
 
     let mut communicator = communicator.expect("GENERATE_COMMUNICATOR_RETRIES exceeded");
 
+    // Kept alive so that the clock keeps being synced, the server tracks the skew of the device
+    // from the measurement times
+    let sntp = EspSntp::new_default().expect("Should be able to start SNTP");
+
     // reverse count of errors that can occur
     // - on each success IT recovers one point
     // - on each failure IT looses one point
@@ -184,11 +189,12 @@ fn main() {
         // --
 
         // -- Post the new data
-        let serialized_data =
-            serde_json::to_string(&measurement.data).expect("Data should be serializable");
+        // Same key and time on every retry, so that a reading stored but whose response was lost
+        // isn't stored twice
         let data = PostSensorData {
-            serialized_data,
-            created_at: None,
+            data: measurement.data.into(),
+            created_at: measured_at(&sntp),
+            idempotency_key: Some(hex::encode(get_random_buf::<16>())),
         };
 
         for _ in 0..POST_DATA_RETRIES {
@@ -206,7 +212,18 @@ fn main() {
                 }
                 Err(e) => match e {
                     server_communicator::Error::UnexpectedResponse(c) => match c {
-                        401 | 404 => handle_unauthorized(persistence),
+                        // The session may have been rotated on a post whose response was lost
+                        401 | 404 => match ServerCommunicator::generate(&mut keys, device_id.clone())
+                        {
+                            Ok(c) => {
+                                log::warn!("Session rejected on post data, regenerated it");
+                                communicator = c;
+                            }
+                            Err(server_communicator::Error::UnexpectedResponse(401 | 404)) => {
+                                handle_unauthorized(persistence)
+                            }
+                            Err(e) => log::error!("Error regenerating session: {e:?}"),
+                        },
                         _ => {
                             log::error!("Response was an error on post data: {e:?}");
                         }
@@ -223,6 +240,18 @@ fn main() {
     panic!("Errors exceeded");
 }
 
+/// Current UNIX time, None until SNTP has synced the clock as the server would reject it
+fn measured_at(sntp: &EspSntp) -> Option<ApiTimestamp> {
+    if sntp.get_sync_status() != SyncStatus::Completed {
+        return None;
+    }
+
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|since_epoch| since_epoch.as_secs() as ApiTimestamp)
+}
+
 fn handle_unauthorized(mut persistence: Persistence) -> ! {
     log::warn!("Received UNAUTHORIZED code from server, emptying persistence and rebooting");
     if !persistence
@@ -1,6 +1,7 @@
 use std::fmt::Debug;
 
 use adafruit_aht10::AdafruitAHT10;
+use common::endpoints_io::sensor_data::SensorReading;
 use esp_idf_svc::hal::{delay::FreeRtos, i2c::I2cDriver};
 use esp_idf_sys::esp_timer_get_time;
 use scd4x::Scd4x;
@@ -120,6 +121,16 @@ pub struct SensorsData {
     pub temperature: Option<f32>,
 }
 
+impl From<SensorsData> for SensorReading {
+    fn from(value: SensorsData) -> Self {
+        SensorReading {
+            co2: value.co2,
+            temperature: value.temperature,
+            humidity: value.humidity,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct SensorsMeasurement {
     pub aht10_measured: bool,
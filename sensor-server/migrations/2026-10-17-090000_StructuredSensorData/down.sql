@@ -0,0 +1,12 @@
+ALTER TABLE sensor_data
+DROP CONSTRAINT IF EXISTS sensor_data_data_is_object;
+
+-- Kept aside by up.sql as they were
+UPDATE sensor_data
+SET data = (data ->> 'legacy_raw')::jsonb
+WHERE data ? 'legacy_raw'
+  AND (SELECT COUNT(*) FROM jsonb_object_keys(data)) = 1;
+
+UPDATE sensor_data
+SET data = to_jsonb(data::text)
+WHERE jsonb_typeof(data) = 'object';
@@ -0,0 +1,24 @@
+-- sensor_data.data used to be stored as a JSON string holding the serialized reading,
+-- turn those into real JSON objects so they can be queried
+CREATE OR REPLACE FUNCTION pg_temp.try_parse_jsonb(txt TEXT)
+RETURNS JSONB AS $$
+BEGIN
+   RETURN txt::jsonb;
+EXCEPTION WHEN others THEN
+   RETURN NULL;
+END;
+$$ language 'plpgsql';
+
+UPDATE sensor_data
+SET data = pg_temp.try_parse_jsonb(data #>> '{}')
+WHERE jsonb_typeof(data) = 'string'
+  AND jsonb_typeof(pg_temp.try_parse_jsonb(data #>> '{}')) = 'object';
+
+-- Anything that could not be parsed into an object is kept as the text of the original value, a
+-- string so that it's never taken for a metric. down.sql restores it
+UPDATE sensor_data
+SET data = jsonb_build_object('legacy_raw', data::text)
+WHERE jsonb_typeof(data) <> 'object';
+
+ALTER TABLE sensor_data
+ADD CONSTRAINT sensor_data_data_is_object CHECK (jsonb_typeof(data) = 'object');
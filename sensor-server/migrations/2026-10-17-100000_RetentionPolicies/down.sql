@@ -0,0 +1,2 @@
+DROP INDEX idx_sensor_data_added_at;
+DROP TABLE retention_policies;
@@ -0,0 +1,19 @@
+-- Per user or per sensor retention of sensor_data, the server wide one is set by env
+CREATE TABLE retention_policies (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER UNIQUE REFERENCES users(id) ON DELETE CASCADE,
+    sensor_id INTEGER UNIQUE REFERENCES user_sensors(id) ON DELETE CASCADE,
+    keep_days INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT retention_policies_keep_days_positive CHECK (keep_days > 0),
+    CONSTRAINT retention_policies_one_target CHECK ((user_id IS NULL) <> (sensor_id IS NULL))
+);
+
+CREATE TRIGGER update_retention_policies_updated_at
+BEFORE UPDATE ON retention_policies
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+-- The pruner filters expired data by added_at across every sensor
+CREATE INDEX idx_sensor_data_added_at ON sensor_data (added_at);
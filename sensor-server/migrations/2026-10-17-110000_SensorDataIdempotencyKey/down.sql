@@ -0,0 +1,3 @@
+ALTER TABLE sensor_data DROP CONSTRAINT sensor_data_sensor_id_idempotency_key_uniq;
+
+ALTER TABLE sensor_data DROP COLUMN idempotency_key;
@@ -0,0 +1,5 @@
+-- Sent by sensors so that retried uploads are stored only once, NULLs never conflict
+ALTER TABLE sensor_data ADD COLUMN idempotency_key TEXT;
+
+ALTER TABLE sensor_data
+    ADD CONSTRAINT sensor_data_sensor_id_idempotency_key_uniq UNIQUE (sensor_id, idempotency_key);
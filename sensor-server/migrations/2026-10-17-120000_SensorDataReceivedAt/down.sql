@@ -0,0 +1,5 @@
+ALTER TABLE user_sensors DROP COLUMN clock_skew_secs;
+
+ALTER TABLE sensor_data DROP COLUMN reported_at;
+
+ALTER TABLE sensor_data DROP COLUMN received_at;
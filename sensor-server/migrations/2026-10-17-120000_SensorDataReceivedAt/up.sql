@@ -0,0 +1,12 @@
+-- added_at stays the measurement time, received_at is when the server got it and reported_at is
+-- the measurement time as sent by the device, if it sent any
+ALTER TABLE sensor_data ADD COLUMN received_at TIMESTAMP;
+UPDATE sensor_data SET received_at = added_at;
+ALTER TABLE sensor_data
+    ALTER COLUMN received_at SET NOT NULL,
+    ALTER COLUMN received_at SET DEFAULT NOW();
+
+ALTER TABLE sensor_data ADD COLUMN reported_at TIMESTAMP;
+
+-- Seconds the device clock was ahead of the server on its last timestamped reading
+ALTER TABLE user_sensors ADD COLUMN clock_skew_secs INTEGER;
@@ -0,0 +1,48 @@
+-- Threshold rules on a metric of a sensor or of every sensor of a place
+CREATE TABLE alert_rules (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    place_id INTEGER REFERENCES user_places(id) ON DELETE CASCADE,
+    sensor_id INTEGER REFERENCES user_sensors(id) ON DELETE CASCADE,
+    metric TEXT NOT NULL,
+    comparison TEXT NOT NULL,
+    threshold DOUBLE PRECISION NOT NULL,
+    hysteresis DOUBLE PRECISION NOT NULL DEFAULT 0,
+    for_secs INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT alert_rules_one_target CHECK ((place_id IS NULL) <> (sensor_id IS NULL)),
+    CONSTRAINT alert_rules_hysteresis_positive CHECK (hysteresis >= 0),
+    CONSTRAINT alert_rules_for_secs_positive CHECK (for_secs >= 0)
+);
+
+CREATE TRIGGER update_alert_rules_updated_at
+BEFORE UPDATE ON alert_rules
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+CREATE INDEX idx_alert_rules_place_id ON alert_rules (place_id);
+CREATE INDEX idx_alert_rules_sensor_id ON alert_rules (sensor_id);
+
+-- Sensors currently breaching a rule, since when
+CREATE TABLE alert_rule_states (
+    rule_id INTEGER NOT NULL REFERENCES alert_rules(id) ON DELETE CASCADE,
+    sensor_id INTEGER NOT NULL REFERENCES user_sensors(id) ON DELETE CASCADE,
+    breaching_since TIMESTAMP NOT NULL,
+    PRIMARY KEY (rule_id, sensor_id)
+);
+
+CREATE TABLE alert_events (
+    id SERIAL PRIMARY KEY,
+    rule_id INTEGER NOT NULL REFERENCES alert_rules(id) ON DELETE CASCADE,
+    sensor_id INTEGER NOT NULL REFERENCES user_sensors(id) ON DELETE CASCADE,
+    trigger_value DOUBLE PRECISION NOT NULL,
+    triggered_at TIMESTAMP NOT NULL,
+    resolved_at TIMESTAMP,
+    acknowledged_at TIMESTAMP
+);
+
+-- At most one open alert per rule and sensor
+CREATE UNIQUE INDEX idx_alert_events_open ON alert_events (rule_id, sensor_id)
+WHERE resolved_at IS NULL;
+CREATE INDEX idx_alert_events_sensor_triggered ON alert_events (sensor_id, triggered_at DESC);
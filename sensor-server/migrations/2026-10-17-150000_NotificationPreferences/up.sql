@@ -0,0 +1,14 @@
+-- Which emails the user gets, users without a row get all of them
+CREATE TABLE notification_preferences (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    alert_emails BOOLEAN NOT NULL DEFAULT TRUE,
+    account_emails BOOLEAN NOT NULL DEFAULT TRUE,
+    sensor_emails BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_notification_preferences_updated_at
+BEFORE UPDATE ON notification_preferences
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
@@ -0,0 +1,4 @@
+ALTER TABLE user_sensors
+    DROP COLUMN offline_since,
+    DROP COLUMN expected_interval_secs,
+    DROP COLUMN last_seen_at;
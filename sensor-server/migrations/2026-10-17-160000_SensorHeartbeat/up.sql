@@ -0,0 +1,9 @@
+-- When the server last got a reading from the sensor, and how often it is expected to report
+ALTER TABLE user_sensors ADD COLUMN last_seen_at TIMESTAMP;
+UPDATE user_sensors SET last_seen_at = (
+    SELECT MAX(received_at) FROM sensor_data WHERE sensor_data.sensor_id = user_sensors.id
+);
+ALTER TABLE user_sensors ADD COLUMN expected_interval_secs INTEGER NOT NULL DEFAULT 60;
+
+-- Set by the heartbeat checker while the sensor is considered offline
+ALTER TABLE user_sensors ADD COLUMN offline_since TIMESTAMP;
@@ -0,0 +1 @@
+DROP FUNCTION derived_metrics(JSONB);
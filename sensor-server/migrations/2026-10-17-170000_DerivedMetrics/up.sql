@@ -0,0 +1,36 @@
+-- The derived metrics of a stored SensorReading, with the same formulas as
+-- common::psychrometrics so that they can be aggregated like the measured ones
+CREATE FUNCTION derived_metrics(data JSONB) RETURNS JSONB AS $$
+DECLARE
+    t FLOAT8;
+    rh FLOAT8;
+    gamma FLOAT8;
+    dew_point FLOAT8;
+    absolute_humidity FLOAT8;
+BEGIN
+    IF jsonb_typeof(data->'temperature') IS DISTINCT FROM 'number'
+        OR jsonb_typeof(data->'humidity') IS DISTINCT FROM 'number' THEN
+        RETURN '{}';
+    END IF;
+
+    t := (data->>'temperature')::FLOAT8;
+    rh := (data->>'humidity')::FLOAT8;
+    IF rh < 0 OR rh > 100 THEN
+        RETURN '{}';
+    END IF;
+
+    absolute_humidity := 216.7 * (6.112 * exp(17.62 * t / (243.12 + t)) * rh / 100) / (273.15 + t);
+    IF rh = 0 THEN
+        RETURN jsonb_build_object('absolute_humidity', absolute_humidity);
+    END IF;
+
+    gamma := ln(rh / 100) + 17.62 * t / (243.12 + t);
+    dew_point := 243.12 * gamma / (17.62 - gamma);
+
+    RETURN jsonb_build_object(
+        'dew_point', dew_point,
+        'absolute_humidity', absolute_humidity,
+        'humidex', t + 0.5555 * (6.11 * exp(5417.7530 * (1 / 273.16 - 1 / (273.15 + dew_point))) - 10)
+    );
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
@@ -0,0 +1,2 @@
+DROP INDEX sensor_data_suspect_idx;
+ALTER TABLE sensor_data DROP COLUMN suspect_metrics;
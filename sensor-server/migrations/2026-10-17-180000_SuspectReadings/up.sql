@@ -0,0 +1,5 @@
+-- Metric keys of the reading flagged as anomalous on ingestion, empty for good readings
+ALTER TABLE sensor_data ADD COLUMN suspect_metrics TEXT[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX sensor_data_suspect_idx ON sensor_data (sensor_id, added_at)
+    WHERE suspect_metrics <> '{}';
@@ -0,0 +1,2 @@
+DROP FUNCTION calibrated_data(INTEGER, TIMESTAMP, JSONB);
+DROP TABLE sensor_calibrations;
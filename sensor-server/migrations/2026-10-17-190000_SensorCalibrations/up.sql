@@ -0,0 +1,37 @@
+-- Linear correction of a measured metric of a sensor, calibrated = raw * scale + offset_value
+-- Applies to the readings added from effective_from until the next calibration of the metric
+CREATE TABLE sensor_calibrations (
+    id SERIAL PRIMARY KEY,
+    sensor_id INTEGER NOT NULL REFERENCES user_sensors(id) ON DELETE CASCADE,
+    metric TEXT NOT NULL,
+    offset_value DOUBLE PRECISION NOT NULL DEFAULT 0,
+    scale DOUBLE PRECISION NOT NULL DEFAULT 1,
+    effective_from TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT sensor_calibrations_scale_positive CHECK (scale > 0),
+    CONSTRAINT sensor_calibrations_unique UNIQUE (sensor_id, metric, effective_from)
+);
+
+-- The stored data of a sensor with the calibrations in effect at added_at applied, with the same
+-- rules as the server so that it can be aggregated. CO2 is kept in whole, non negative ppm
+CREATE FUNCTION calibrated_data(p_sensor_id INTEGER, p_added_at TIMESTAMP, data JSONB)
+RETURNS JSONB AS $$
+    SELECT data || COALESCE(jsonb_object_agg(
+        m.key,
+        CASE WHEN m.key = 'co2'
+            THEN GREATEST(0, round((m.value #>> '{}')::FLOAT8 * c.scale + c.offset_value))
+            ELSE (m.value #>> '{}')::FLOAT8 * c.scale + c.offset_value
+        END
+    ), '{}')
+    FROM jsonb_each(data) AS m
+    CROSS JOIN LATERAL (
+        SELECT scale, offset_value
+        FROM sensor_calibrations
+        WHERE sensor_id = p_sensor_id
+          AND metric = m.key
+          AND effective_from <= p_added_at
+        ORDER BY effective_from DESC
+        LIMIT 1
+    ) c
+    WHERE jsonb_typeof(m.value) = 'number';
+$$ LANGUAGE SQL STABLE;
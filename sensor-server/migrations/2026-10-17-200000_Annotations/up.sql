@@ -0,0 +1,22 @@
+-- Notes of a user on the timeline of a place, of a sensor or of neither, at a point in time or
+-- over a range
+CREATE TABLE annotations (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    place_id INTEGER REFERENCES user_places(id) ON DELETE CASCADE,
+    sensor_id INTEGER REFERENCES user_sensors(id) ON DELETE CASCADE,
+    text TEXT NOT NULL,
+    starts_at TIMESTAMP NOT NULL,
+    ends_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    CONSTRAINT annotations_at_most_one_target CHECK (place_id IS NULL OR sensor_id IS NULL),
+    CONSTRAINT annotations_ends_after_start CHECK (ends_at IS NULL OR ends_at >= starts_at)
+);
+
+CREATE TRIGGER update_annotations_updated_at
+BEFORE UPDATE ON annotations
+FOR EACH ROW
+EXECUTE PROCEDURE update_updated_at_column();
+
+CREATE INDEX idx_annotations_user_id_starts_at ON annotations (user_id, starts_at);
@@ -0,0 +1,6 @@
+-- Token Prometheus scrapes the sensors of a user with, only its SHA-256 is stored
+CREATE TABLE scrape_tokens (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    token_hash TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
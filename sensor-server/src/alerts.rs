@@ -0,0 +1,359 @@
+use chrono::{NaiveDateTime, TimeDelta};
+use common::{
+    endpoints_io::{
+        alert::{AlertComparison, ApiAlertEvent},
+        sensor_data::{Metric, SensorReading},
+    },
+    types::{ApiTimestamp, validate::device_id::DeviceId},
+};
+use diesel::Connection;
+
+use crate::db::{
+    DbConn, Error,
+    alerts::{
+        get_breaching_since, get_open_alert_event, get_sensor_alert_rules, insert_alert_event,
+        resolve_alert_event, set_breaching_since,
+    },
+    model::{AlertEvent, AlertRule, NewAlertEvent, UserSensor},
+};
+
+/// How an AlertComparison is stored in alert_rules.comparison
+pub fn comparison_key(comparison: AlertComparison) -> &'static str {
+    match comparison {
+        AlertComparison::Above => "above",
+        AlertComparison::Below => "below",
+    }
+}
+
+pub fn comparison_from_key(key: &str) -> Option<AlertComparison> {
+    match key {
+        "above" => Some(AlertComparison::Above),
+        "below" => Some(AlertComparison::Below),
+        _ => None,
+    }
+}
+
+/// Where a value stands regarding a condition
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Level {
+    /// Past the threshold
+    Breaching,
+    /// Past the threshold minus the hysteresis, back to normal
+    Clear,
+    /// In between, keeps whatever state the alert had so that it doesn't flap
+    Hysteresis,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition {
+    Triggered,
+    Resolved,
+}
+
+/// An alert triggered or resolved by a reading
+#[derive(Debug, Clone)]
+pub struct AlertChange {
+    pub transition: Transition,
+    pub event: AlertEvent,
+    pub rule: AlertRule,
+}
+
+/// The condition of an AlertRule, parsed
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertCondition {
+    pub metric: Metric,
+    pub comparison: AlertComparison,
+    pub threshold: f64,
+    pub hysteresis: f64,
+    pub for_duration: TimeDelta,
+}
+
+impl TryFrom<&AlertRule> for AlertCondition {
+    type Error = Error;
+
+    fn try_from(rule: &AlertRule) -> Result<Self, Self::Error> {
+        let invalid = |what: &str| {
+            log::error!("Stored alert rule ({}) has an invalid {what}", rule.id);
+            Error::InternalError(format!("Invalid alert rule {what}").into())
+        };
+
+        Ok(AlertCondition {
+            metric: Metric::from_key(&rule.metric).ok_or_else(|| invalid("metric"))?,
+            comparison: comparison_from_key(&rule.comparison)
+                .ok_or_else(|| invalid("comparison"))?,
+            threshold: rule.threshold,
+            hysteresis: rule.hysteresis,
+            for_duration: TimeDelta::seconds(rule.for_secs.into()),
+        })
+    }
+}
+
+impl AlertCondition {
+    fn level(&self, value: f64) -> Level {
+        let (breaching, clear) = match self.comparison {
+            AlertComparison::Above => (
+                value > self.threshold,
+                value < self.threshold - self.hysteresis,
+            ),
+            AlertComparison::Below => (
+                value < self.threshold,
+                value > self.threshold + self.hysteresis,
+            ),
+        };
+
+        match (breaching, clear) {
+            (true, _) => Level::Breaching,
+            (false, true) => Level::Clear,
+            (false, false) => Level::Hysteresis,
+        }
+    }
+
+    /// Given since when the threshold is breached and whether the alert is open, returns the new
+    /// breaching since and how the alert changes with the value measured at
+    pub fn step(
+        &self,
+        value: f64,
+        at: NaiveDateTime,
+        breaching_since: Option<NaiveDateTime>,
+        open: bool,
+    ) -> (Option<NaiveDateTime>, Option<Transition>) {
+        match self.level(value) {
+            Level::Breaching => {
+                let since = breaching_since.unwrap_or(at);
+                let transition =
+                    (!open && at - since >= self.for_duration).then_some(Transition::Triggered);
+                (Some(since), transition)
+            }
+            Level::Clear => (None, open.then_some(Transition::Resolved)),
+            Level::Hysteresis => (breaching_since, None),
+        }
+    }
+}
+
+/// Evaluates every rule that applies to the sensor on the reading measured at, returns the alerts
+/// triggered or resolved by it. Readings must be evaluated in the order they were measured, older
+/// ones than the state of a rule are ignored by it
+pub fn evaluate_reading(
+    conn: &mut DbConn,
+    sensor: &UserSensor,
+    reading: &SensorReading,
+    at: NaiveDateTime,
+) -> Result<Vec<AlertChange>, Error> {
+    let mut changed = vec![];
+
+    for rule in get_sensor_alert_rules(conn, sensor)? {
+        let condition = AlertCondition::try_from(&rule)?;
+        let Some(value) = reading.metric(condition.metric) else {
+            continue;
+        };
+
+        let change = conn.transaction::<_, Error, _>(|conn| {
+            let breaching_since = get_breaching_since(conn, rule.id, sensor.id)?;
+            let open = get_open_alert_event(conn, rule.id, sensor.id)?;
+
+            let last_at = open.as_ref().map(|e| e.triggered_at).max(breaching_since);
+            if last_at.is_some_and(|last_at| at < last_at) {
+                log::trace!(
+                    "Reading at {at} older than the state of alert rule {}",
+                    rule.id
+                );
+                return Ok(None);
+            }
+
+            let (new_since, transition) =
+                condition.step(value, at, breaching_since, open.is_some());
+            if new_since != breaching_since {
+                set_breaching_since(conn, rule.id, sensor.id, new_since)?;
+            }
+
+            let event = match (transition, open) {
+                (Some(Transition::Triggered), _) => insert_alert_event(
+                    conn,
+                    NewAlertEvent {
+                        rule_id: rule.id,
+                        sensor_id: sensor.id,
+                        trigger_value: value,
+                        triggered_at: at,
+                    },
+                )?,
+                (Some(Transition::Resolved), Some(open)) => resolve_alert_event(conn, open.id, at)?,
+                _ => return Ok(None),
+            };
+
+            Ok(transition.map(|t| (t, event)))
+        })?;
+
+        if let Some((transition, event)) = change {
+            log::info!(
+                "Alert rule {} {transition:?} on sensor {} (value {value})",
+                rule.id,
+                sensor.device_id
+            );
+            changed.push(AlertChange {
+                transition,
+                event,
+                rule,
+            });
+        }
+    }
+
+    Ok(changed)
+}
+
+pub fn api_alert_event(
+    event: AlertEvent,
+    rule: &AlertRule,
+    device_id: &str,
+) -> Result<ApiAlertEvent, Error> {
+    let condition = AlertCondition::try_from(rule)?;
+    let timestamp = |at: NaiveDateTime| at.and_utc().timestamp() as ApiTimestamp;
+
+    Ok(ApiAlertEvent {
+        id: event.id,
+        rule_id: rule.id,
+        device_id: DeviceId::from_string(device_id).map_err(|e| {
+            log::error!("Could not construct DeviceId: {e:?}");
+            Error::InternalError("Invalid device_id".into())
+        })?,
+        metric: condition.metric,
+        comparison: condition.comparison,
+        threshold: condition.threshold,
+        trigger_value: event.trigger_value,
+        triggered_at: timestamp(event.triggered_at),
+        resolved_at: event.resolved_at.map(timestamp),
+        acknowledged_at: event.acknowledged_at.map(timestamp),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::db::{
+        alerts::insert_alert_rule,
+        establish_connection,
+        model::NewAlertRule,
+        tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+    };
+
+    use super::*;
+
+    fn co2_above(threshold: f64, hysteresis: f64, for_secs: i64) -> AlertCondition {
+        AlertCondition {
+            metric: Metric::Co2,
+            comparison: AlertComparison::Above,
+            threshold,
+            hysteresis,
+            for_duration: TimeDelta::seconds(for_secs),
+        }
+    }
+
+    #[test]
+    fn test_step() {
+        let condition = co2_above(1200.0, 100.0, 600);
+        let t0 = Utc::now().naive_utc();
+        let t = |mins| t0 + TimeDelta::minutes(mins);
+
+        // Starts breaching, not for long enough yet
+        assert_eq!(
+            condition.step(1300.0, t(0), None, false),
+            (Some(t(0)), None)
+        );
+        // In the hysteresis band it keeps breaching
+        assert_eq!(
+            condition.step(1150.0, t(5), Some(t(0)), false),
+            (Some(t(0)), None)
+        );
+        assert_eq!(
+            condition.step(1300.0, t(10), Some(t(0)), false),
+            (Some(t(0)), Some(Transition::Triggered))
+        );
+        // Already open
+        assert_eq!(
+            condition.step(1300.0, t(11), Some(t(0)), true),
+            (Some(t(0)), None)
+        );
+        // Doesn't flap around the threshold
+        assert_eq!(
+            condition.step(1150.0, t(12), Some(t(0)), true),
+            (Some(t(0)), None)
+        );
+        assert_eq!(
+            condition.step(1050.0, t(13), Some(t(0)), true),
+            (None, Some(Transition::Resolved))
+        );
+        assert_eq!(condition.step(1050.0, t(14), None, false), (None, None));
+
+        let condition = AlertCondition {
+            comparison: AlertComparison::Below,
+            ..co2_above(30.0, 5.0, 0)
+        };
+        assert_eq!(
+            condition.step(29.0, t(0), None, false),
+            (Some(t(0)), Some(Transition::Triggered))
+        );
+        assert_eq!(
+            condition.step(34.0, t(1), Some(t(0)), true),
+            (Some(t(0)), None)
+        );
+        assert_eq!(
+            condition.step(36.0, t(2), Some(t(0)), true),
+            (None, Some(Transition::Resolved))
+        );
+    }
+
+    #[test]
+    fn test_evaluate_reading() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        insert_alert_rule(
+            &mut conn,
+            NewAlertRule {
+                user_id: user.id,
+                place_id: Some(place.id),
+                sensor_id: None,
+                metric: Metric::Co2.key().to_string(),
+                comparison: comparison_key(AlertComparison::Above).to_string(),
+                threshold: 1200.0,
+                hysteresis: 100.0,
+                for_secs: 600,
+            },
+        )
+        .unwrap();
+
+        let t0 = Utc::now().naive_utc() - TimeDelta::hours(1);
+        let t = |mins| t0 + TimeDelta::minutes(mins);
+        let co2 = |co2| SensorReading {
+            co2: Some(co2),
+            ..Default::default()
+        };
+        let mut evaluate = |reading: SensorReading, at| {
+            evaluate_reading(&mut conn, &sensor, &reading, at)
+                .expect("Should not fail")
+                .into_iter()
+                .map(|change| change.transition)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(evaluate(co2(1300), t(0)), vec![]);
+        // Without the metric
+        assert_eq!(
+            evaluate(
+                SensorReading {
+                    temperature: Some(20.0),
+                    ..Default::default()
+                },
+                t(5)
+            ),
+            vec![]
+        );
+        assert_eq!(evaluate(co2(1300), t(10)), vec![Transition::Triggered]);
+        assert_eq!(evaluate(co2(1300), t(11)), vec![]);
+        // Older than the state
+        assert_eq!(evaluate(co2(400), t(1)), vec![]);
+        assert_eq!(evaluate(co2(400), t(12)), vec![Transition::Resolved]);
+    }
+}
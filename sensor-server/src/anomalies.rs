@@ -0,0 +1,211 @@
+use chrono::TimeDelta;
+use common::endpoints_io::sensor_data::{Metric, SensorReading, SortOrder};
+use serde::Deserialize;
+
+use crate::{
+    db::{
+        DbConn, Error,
+        model::SensorData,
+        sensor_data::{Identifier, SensorDataCursor, get_sensor_data, set_suspect_metrics},
+    },
+    insights::median,
+};
+
+/// Readings before the new one its changes are compared against
+const WINDOW: u32 = 30;
+/// Older readings are not in the window, so that a sensor coming back after a while starts fresh
+const WINDOW_MAX_AGE: TimeDelta = TimeDelta::hours(6);
+/// Fewer good readings in the window than this and nothing is flagged
+const MIN_WINDOW: usize = 10;
+/// Modified z-score past which a change is an outlier (Iglewicz and Hoaglin)
+const MAX_Z_SCORE: f64 = 3.5;
+/// Makes the MAD comparable to a standard deviation for normally distributed changes
+const MAD_SCALE: f64 = 0.6745;
+
+/// Lowest spread of the changes of a metric between readings, so that a flat window doesn't turn
+/// sensor noise into outliers
+fn min_mad(metric: Metric) -> f64 {
+    match metric {
+        Metric::Co2 => 20.0,
+        Metric::Temperature => 0.2,
+        Metric::Humidity => 1.0,
+        Metric::DewPoint | Metric::AbsoluteHumidity | Metric::Humidex => 0.0,
+    }
+}
+
+/// A stored reading the new one is compared against
+#[derive(Debug, Clone)]
+pub struct PastReading {
+    pub data: SensorReading,
+    pub suspect_metrics: Vec<Metric>,
+}
+
+impl PastReading {
+    fn from_sensor_data(datum: SensorData) -> Result<Self, Error> {
+        let data = SensorReading::deserialize(datum.data).map_err(|e| {
+            log::error!(
+                "Stored sensor_data ({}) is not a valid SensorReading: {e:?}",
+                datum.id
+            );
+            Error::InternalError(e.into())
+        })?;
+
+        Ok(Self {
+            data,
+            suspect_metrics: datum
+                .suspect_metrics
+                .iter()
+                .filter_map(|key| Metric::from_key(key))
+                .collect(),
+        })
+    }
+}
+
+/// Modified z-score of change among the changes of the window
+fn z_score(change: f64, changes: &[f64], min_mad: f64) -> Option<f64> {
+    let median_change = median(changes.to_vec())?;
+    let mad = median(changes.iter().map(|c| (c - median_change).abs()).collect())?;
+    Some(MAD_SCALE * (change - median_change) / mad.max(min_mad))
+}
+
+/// Measured metrics of reading that jump away from the window (oldest first) unlike the changes
+/// between its readings do. A jump is only suspect if it is an outlier both from the last good
+/// value and from the previous value, so that only the first reading of a lasting change of level
+/// is flagged. Derived metrics are added when what they are computed from is suspect
+pub fn suspect_metrics(window: &[PastReading], reading: &SensorReading) -> Vec<Metric> {
+    let mut suspect: Vec<Metric> = Metric::MEASURED
+        .into_iter()
+        .filter(|&metric| {
+            let Some(value) = reading.metric(metric) else {
+                return false;
+            };
+
+            let good: Vec<f64> = window
+                .iter()
+                .filter(|past| !past.suspect_metrics.contains(&metric))
+                .filter_map(|past| past.data.metric(metric))
+                .collect();
+            if good.len() < MIN_WINDOW {
+                return false;
+            }
+            let changes: Vec<f64> = good.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+            let is_outlier = |previous: f64| {
+                z_score(value - previous, &changes, min_mad(metric))
+                    .is_some_and(|z| z.abs() > MAX_Z_SCORE)
+            };
+            let last_good = good.last().copied();
+            let previous = window.last().and_then(|past| past.data.metric(metric));
+
+            last_good.is_some_and(is_outlier) && previous.is_none_or(is_outlier)
+        })
+        .collect();
+
+    if suspect.contains(&Metric::Temperature) || suspect.contains(&Metric::Humidity) {
+        suspect.extend([Metric::DewPoint, Metric::AbsoluteHumidity, Metric::Humidex]);
+    }
+
+    suspect
+}
+
+/// Checks the stored datum against the readings of its sensor before it, storing its suspect
+/// metrics if any. Meant for readings stored in order, as they are received
+pub fn flag_sensor_data(conn: &mut DbConn, datum: &mut SensorData) -> Result<(), Error> {
+    let (before, _) = get_sensor_data(
+        conn,
+        Identifier::SensorId(datum.sensor_id),
+        (datum.added_at - WINDOW_MAX_AGE)..datum.added_at,
+        SortOrder::Desc,
+        WINDOW,
+        Some(SensorDataCursor::from(&*datum)),
+    )?;
+    let window = before
+        .into_iter()
+        .rev()
+        .map(PastReading::from_sensor_data)
+        .collect::<Result<Vec<PastReading>, Error>>()?;
+
+    let reading = PastReading::from_sensor_data(datum.clone())?;
+    let suspect = suspect_metrics(&window, &reading.data);
+    if suspect.is_empty() {
+        return Ok(());
+    }
+
+    log::warn!(
+        "Reading {} of sensor {} looks like a glitch: {suspect:?}",
+        datum.id,
+        datum.sensor_id
+    );
+    *datum = set_suspect_metrics(
+        conn,
+        datum.id,
+        suspect.iter().map(|m| m.key().to_string()).collect(),
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(co2: impl IntoIterator<Item = u16>) -> Vec<PastReading> {
+        co2.into_iter()
+            .map(|co2| PastReading {
+                data: SensorReading {
+                    co2: Some(co2),
+                    temperature: Some(21.0),
+                    humidity: Some(40.0),
+                },
+                suspect_metrics: vec![],
+            })
+            .collect()
+    }
+
+    fn reading(co2: u16, temperature: f32) -> SensorReading {
+        SensorReading {
+            co2: Some(co2),
+            temperature: Some(temperature),
+            humidity: Some(40.0),
+        }
+    }
+
+    #[test]
+    fn test_suspect_metrics() {
+        let flat = window([450, 452, 449, 451, 450, 448, 450, 453, 451, 450]);
+        assert!(suspect_metrics(&flat, &reading(455, 21.1)).is_empty());
+        assert_eq!(
+            suspect_metrics(&flat, &reading(0, 80.0)),
+            vec![
+                Metric::Co2,
+                Metric::Temperature,
+                Metric::DewPoint,
+                Metric::AbsoluteHumidity,
+                Metric::Humidex
+            ]
+        );
+
+        // Not enough readings to tell
+        assert!(suspect_metrics(&flat[..5], &reading(0, 21.0)).is_empty());
+
+        // Steady rises are fine
+        let rising = window((0..20).map(|i| 450 + 30 * i));
+        assert!(suspect_metrics(&rising, &reading(1050, 21.0)).is_empty());
+
+        // Back from a glitch
+        let mut glitch = flat.clone();
+        glitch.push(PastReading {
+            data: reading(0, 21.0),
+            suspect_metrics: vec![Metric::Co2],
+        });
+        assert!(suspect_metrics(&glitch, &reading(450, 21.0)).is_empty());
+
+        // After a glitch, a lasting change of level is only flagged once
+        let mut shifted = flat.clone();
+        shifted.push(PastReading {
+            data: reading(900, 21.0),
+            suspect_metrics: vec![Metric::Co2],
+        });
+        assert!(suspect_metrics(&shifted, &reading(905, 21.0)).is_empty());
+    }
+}
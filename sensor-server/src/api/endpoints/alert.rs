@@ -0,0 +1,207 @@
+use axum::{extract::Query, routing::MethodRouter};
+use axum_serde_valid::Json;
+use chrono::Utc;
+use common::endpoints_io::alert::{ApiAlertEvent, GetAlerts, PutAlert};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    alerts::api_alert_event,
+    api::{Endpoint, capped_limit, route::Route},
+    auth::claims::Claims,
+    db::{
+        DbConnHolder,
+        alerts::{AlertEventFilter, acknowledge_alert_event, get_alert_events},
+        user_sensors::AuthorizedSensor,
+        users,
+    },
+};
+
+pub struct Alert {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for Alert {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for Alert {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Alert {
+    pub const API_PATH: &str = "/alert";
+    pub fn new() -> Alert {
+        let mr = MethodRouter::new()
+            .get(Self::alert_get)
+            .put(Self::alert_put);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    async fn alert_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetAlerts>,
+    ) -> Result<Json<Vec<ApiAlertEvent>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let sensor_id = payload
+            .device_id
+            .as_ref()
+            .map(|device_id| {
+                AuthorizedSensor::from_username(conn, device_id, &claims.username)
+                    .map(|sensor| sensor.get().id)
+            })
+            .transpose()?;
+
+        let filter = AlertEventFilter {
+            event_id: None,
+            sensor_id,
+            open_only: payload.open_only.unwrap_or_default(),
+            unacknowledged_only: payload.unacknowledged_only.unwrap_or_default(),
+        };
+        let limit = capped_limit(payload.limit, GetAlerts::MAX_LIMIT)?;
+
+        let events = get_alert_events(conn, user_id, filter, limit.into())?
+            .into_iter()
+            .map(|(event, rule, device_id)| api_alert_event(event, &rule, &device_id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Json(events))
+    }
+
+    /// Acknowledges the alert, it's still resolved only by the readings
+    async fn alert_put(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PutAlert>,
+    ) -> Result<Json<ApiAlertEvent>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let (event, rule, device_id) =
+            acknowledge_alert_event(conn, payload.id, user_id, Utc::now().naive_utc())?;
+        log::info!("User {} acknowledged alert {}", claims.username, event.id);
+
+        Ok(Json(api_alert_event(event, &rule, &device_id)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::Query;
+    use axum_serde_valid::Json;
+    use chrono::{TimeDelta, Utc};
+    use common::endpoints_io::{
+        alert::{AlertComparison, GetAlerts, PutAlert},
+        sensor_data::{Metric, SensorReading},
+    };
+    use hyper::StatusCode;
+
+    use crate::{
+        alerts::{comparison_key, evaluate_reading},
+        api::endpoints::alert::Alert,
+        auth::claims::Claims,
+        db::{
+            DbConn, DbConnHolder,
+            alerts::insert_alert_rule,
+            establish_connection,
+            model::{NewAlertRule, User},
+            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+        },
+    };
+
+    /// Returns a user with an open alert and the id of the alert
+    fn user_with_alert() -> (DbConn, User, i32) {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        insert_alert_rule(
+            &mut conn,
+            NewAlertRule {
+                user_id: user.id,
+                place_id: None,
+                sensor_id: Some(sensor.id),
+                metric: Metric::Humidity.key().to_string(),
+                comparison: comparison_key(AlertComparison::Below).to_string(),
+                threshold: 30.0,
+                hysteresis: 0.0,
+                for_secs: 0,
+            },
+        )
+        .unwrap();
+
+        let reading = SensorReading {
+            humidity: Some(25.0),
+            ..Default::default()
+        };
+        let at = Utc::now().naive_utc() - TimeDelta::minutes(1);
+        let events = evaluate_reading(&mut conn, &sensor, &reading, at).unwrap();
+        assert_eq!(events.len(), 1);
+
+        (conn, user, events[0].event.id)
+    }
+
+    #[tokio::test]
+    async fn test_alert_get() {
+        let (conn, user, _) = user_with_alert();
+
+        let query = GetAlerts {
+            device_id: None,
+            open_only: Some(true),
+            unacknowledged_only: None,
+            limit: None,
+        };
+        let res = Alert::alert_get(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].trigger_value, 25.0);
+        assert_eq!(res[0].resolved_at, None);
+        assert_eq!(res[0].acknowledged_at, None);
+    }
+
+    #[tokio::test]
+    async fn test_alert_put() {
+        let (conn, user, event_id) = user_with_alert();
+        let res = Alert::alert_put(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(PutAlert { id: event_id }),
+        )
+        .await
+        .expect("Should not fail");
+        assert!(res.acknowledged_at.is_some());
+
+        let (mut conn, _, event_id) = user_with_alert();
+        let (other_user, _) = create_test_user(&mut conn);
+        let res = Alert::alert_put(
+            Claims::new(other_user.username),
+            DbConnHolder(conn),
+            Json(PutAlert { id: event_id }),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,307 @@
+use axum::routing::MethodRouter;
+use axum_serde_valid::Json;
+use common::{
+    endpoints_io::alert::{
+        AlertTarget, ApiAlertRule, DeleteAlertRule, PostAlertRule, PutAlertRule,
+    },
+    types::{ApiTimestamp, validate::device_id::DeviceId},
+};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    alerts::{AlertCondition, comparison_key},
+    api::{Endpoint, route::Route},
+    auth::claims::Claims,
+    db::{
+        DbConn, DbConnHolder, Error,
+        alerts::{delete_alert_rule, get_alert_rules, insert_alert_rule, update_alert_rule},
+        model::{AlertRule, AlertRuleCondition, NewAlertRule},
+        user_places,
+        user_sensors::AuthorizedSensor,
+        users,
+    },
+};
+
+pub struct AlertRules {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for AlertRules {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for AlertRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlertRules {
+    pub const API_PATH: &str = "/alert_rule";
+    pub fn new() -> AlertRules {
+        let mr = MethodRouter::new()
+            .get(Self::alert_rule_get)
+            .post(Self::alert_rule_post)
+            .put(Self::alert_rule_put)
+            .delete(Self::alert_rule_delete);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    fn api_alert_rule(
+        rule: AlertRule,
+        place_name: Option<String>,
+        device_id: Option<String>,
+    ) -> Result<ApiAlertRule, Error> {
+        let condition = AlertCondition::try_from(&rule)?;
+
+        let target = match (place_name, device_id) {
+            (Some(place_name), None) => AlertTarget::Place(place_name.into()),
+            (None, Some(device_id)) => {
+                AlertTarget::Sensor(DeviceId::from_string(&device_id).map_err(|e| {
+                    log::error!("Could not construct DeviceId: {e:?}");
+                    Error::InternalError("Invalid device_id".into())
+                })?)
+            }
+            _ => {
+                log::error!("Alert rule ({}) without a single target", rule.id);
+                Err(Error::InternalError("Invalid alert rule target".into()))?
+            }
+        };
+
+        Ok(ApiAlertRule {
+            id: rule.id,
+            target,
+            metric: condition.metric,
+            comparison: condition.comparison,
+            threshold: condition.threshold,
+            hysteresis: condition.hysteresis,
+            for_secs: rule.for_secs as u32,
+            created_at: rule.created_at.and_utc().timestamp() as ApiTimestamp,
+            updated_at: rule.updated_at.and_utc().timestamp() as ApiTimestamp,
+        })
+    }
+
+    fn get_api_alert_rules(conn: &mut DbConn, user_id: i32) -> Result<Vec<ApiAlertRule>, Error> {
+        get_alert_rules(conn, user_id)?
+            .into_iter()
+            .map(|(rule, place_name, device_id)| Self::api_alert_rule(rule, place_name, device_id))
+            .collect()
+    }
+
+    fn get_api_alert_rule(
+        conn: &mut DbConn,
+        user_id: i32,
+        rule_id: i32,
+    ) -> Result<ApiAlertRule, Error> {
+        Self::get_api_alert_rules(conn, user_id)?
+            .into_iter()
+            .find(|rule| rule.id == rule_id)
+            .ok_or_else(|| Error::NotFound("Alert rule not found".into()))
+    }
+
+    async fn alert_rule_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+    ) -> Result<Json<Vec<ApiAlertRule>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        Ok(Json(Self::get_api_alert_rules(conn, user_id)?))
+    }
+
+    async fn alert_rule_post(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PostAlertRule>,
+    ) -> Result<Json<ApiAlertRule>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let (place_id, sensor_id) = match &payload.target {
+            AlertTarget::Place(name) => {
+                let place_id = user_places::get_user_place_id(
+                    conn,
+                    user_places::Identifier::PlaceNameAndUserId(name.as_str(), user_id),
+                )?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::NotFound("Place not found".into()))?;
+                (Some(place_id), None)
+            }
+            AlertTarget::Sensor(device_id) => {
+                let sensor = AuthorizedSensor::from_username(conn, device_id, &claims.username)?;
+                (None, Some(sensor.get().id))
+            }
+        };
+
+        let rule = insert_alert_rule(
+            conn,
+            NewAlertRule {
+                user_id,
+                place_id,
+                sensor_id,
+                metric: payload.metric.key().to_string(),
+                comparison: comparison_key(payload.comparison).to_string(),
+                threshold: payload.threshold,
+                hysteresis: payload.hysteresis.unwrap_or_default(),
+                for_secs: payload.for_secs.unwrap_or_default() as i32,
+            },
+        )?;
+        log::info!(
+            "User {} created alert rule {} on {:?}",
+            claims.username,
+            rule.id,
+            payload.target
+        );
+
+        Ok(Json(Self::get_api_alert_rule(conn, user_id, rule.id)?))
+    }
+
+    async fn alert_rule_put(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PutAlertRule>,
+    ) -> Result<Json<ApiAlertRule>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let condition = AlertRuleCondition {
+            comparison: comparison_key(payload.comparison).to_string(),
+            threshold: payload.threshold,
+            hysteresis: payload.hysteresis.unwrap_or_default(),
+            for_secs: payload.for_secs.unwrap_or_default() as i32,
+        };
+        update_alert_rule(conn, payload.id, user_id, condition)?;
+
+        Ok(Json(Self::get_api_alert_rule(conn, user_id, payload.id)?))
+    }
+
+    async fn alert_rule_delete(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<DeleteAlertRule>,
+    ) -> Result<Json<Vec<ApiAlertRule>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let rule = delete_alert_rule(conn, payload.id, user_id)?;
+        log::info!("User {} deleted alert rule {}", claims.username, rule.id);
+
+        Ok(Json(Self::get_api_alert_rules(conn, user_id)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_serde_valid::Json;
+    use common::{
+        endpoints_io::{
+            alert::{AlertComparison, AlertTarget, DeleteAlertRule, PostAlertRule},
+            sensor_data::Metric,
+        },
+        types::validate::device_id::DeviceId,
+    };
+    use hyper::StatusCode;
+
+    use crate::{
+        api::endpoints::alert_rule::AlertRules,
+        auth::claims::Claims,
+        db::{
+            DbConnHolder, establish_connection,
+            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+        },
+    };
+
+    fn co2_above(target: AlertTarget) -> PostAlertRule {
+        PostAlertRule {
+            target,
+            metric: Metric::Co2,
+            comparison: AlertComparison::Above,
+            threshold: 1200.0,
+            hysteresis: Some(100.0),
+            for_secs: Some(600),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_alert_rule_post() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+
+        let target = AlertTarget::Place(place.name.into());
+        let res = AlertRules::alert_rule_post(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(co2_above(target.clone())),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(res.target, target);
+        assert_eq!(res.metric, Metric::Co2);
+        assert_eq!(res.hysteresis, 100.0);
+        assert_eq!(res.for_secs, 600);
+    }
+
+    #[tokio::test]
+    async fn test_alert_rule_post_foreign_sensor() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other_user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &other_user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let target = AlertTarget::Sensor(DeviceId::from_string(&sensor.device_id).unwrap());
+        let res = AlertRules::alert_rule_post(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(co2_above(target)),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_alert_rule_delete_foreign_rule() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other_user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &other_user);
+        let rule = crate::db::alerts::insert_alert_rule(
+            &mut conn,
+            crate::db::model::NewAlertRule {
+                user_id: other_user.id,
+                place_id: Some(place.id),
+                sensor_id: None,
+                metric: "co2".into(),
+                comparison: "above".into(),
+                threshold: 1200.0,
+                hysteresis: 0.0,
+                for_secs: 0,
+            },
+        )
+        .unwrap();
+
+        let res = AlertRules::alert_rule_delete(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(DeleteAlertRule { id: rule.id }),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+}
@@ -0,0 +1,427 @@
+use std::ops::Range;
+
+use axum::{extract::Query, routing::MethodRouter};
+use axum_serde_valid::Json;
+use chrono::{DateTime, NaiveDateTime};
+use common::{
+    endpoints_io::annotation::{
+        AnnotationTarget, ApiAnnotation, DeleteAnnotation, GetAnnotations, PostAnnotation,
+        PutAnnotation,
+    },
+    types::{ApiTimestamp, validate::device_id::DeviceId},
+};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    api::{Endpoint, capped_limit, route::Route},
+    auth::claims::Claims,
+    db::{
+        DbConn, DbConnHolder, Error,
+        annotations::{
+            AnnotationWithTarget, Identifier, delete_annotation, get_annotation, get_annotations,
+            insert_annotation, update_annotation,
+        },
+        model::NewAnnotation,
+        user_places,
+        user_sensors::AuthorizedSensor,
+        users,
+    },
+};
+
+pub struct Annotations {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for Annotations {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for Annotations {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Annotations {
+    pub const API_PATH: &str = "/annotation";
+    pub fn new() -> Annotations {
+        let mr = MethodRouter::new()
+            .get(Self::annotation_get)
+            .post(Self::annotation_post)
+            .put(Self::annotation_put)
+            .delete(Self::annotation_delete);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    fn naive(timestamp: ApiTimestamp) -> Result<NaiveDateTime, StatusCode> {
+        DateTime::from_timestamp(timestamp as i64, 0)
+            .map(|date| date.naive_utc())
+            .ok_or_else(|| {
+                log::warn!("Invalid annotation timestamp: {timestamp}");
+                StatusCode::BAD_REQUEST
+            })
+    }
+
+    fn timestamp(at: NaiveDateTime) -> ApiTimestamp {
+        at.and_utc().timestamp() as ApiTimestamp
+    }
+
+    fn api_annotation(
+        (annotation, place_name, device_id): AnnotationWithTarget,
+    ) -> Result<ApiAnnotation, Error> {
+        let target = match (place_name, device_id) {
+            (Some(place_name), None) => Some(AnnotationTarget::Place(place_name.into())),
+            (None, Some(device_id)) => Some(AnnotationTarget::Sensor(
+                DeviceId::from_string(&device_id).map_err(|e| {
+                    log::error!("Could not construct DeviceId: {e:?}");
+                    Error::InternalError("Invalid device_id".into())
+                })?,
+            )),
+            (None, None) => None,
+            (Some(_), Some(_)) => {
+                log::error!("Annotation ({}) with two targets", annotation.id);
+                Err(Error::InternalError("Invalid annotation target".into()))?
+            }
+        };
+
+        Ok(ApiAnnotation {
+            id: annotation.id,
+            target,
+            text: annotation.text.into(),
+            starts_at: Self::timestamp(annotation.starts_at),
+            ends_at: annotation.ends_at.map(Self::timestamp),
+            created_at: Self::timestamp(annotation.created_at),
+            updated_at: Self::timestamp(annotation.updated_at),
+        })
+    }
+
+    /// Annotations overlapping the range of a range query, so that charts can overlay them
+    pub fn range_annotations(
+        conn: &mut DbConn,
+        identifier: Identifier,
+        range: Range<NaiveDateTime>,
+    ) -> Result<Vec<ApiAnnotation>, Error> {
+        get_annotations(conn, identifier, range, GetAnnotations::MAX_LIMIT)?
+            .into_iter()
+            .map(Self::api_annotation)
+            .collect()
+    }
+
+    /// The annotation with its target resolved to the ids of the user's place or sensor
+    fn new_annotation(
+        conn: &mut DbConn,
+        username: &str,
+        user_id: i32,
+        payload: PostAnnotation,
+    ) -> Result<NewAnnotation, StatusCode> {
+        let (place_id, sensor_id) = match &payload.target {
+            Some(AnnotationTarget::Place(name)) => {
+                let place_id = user_places::get_user_place_id(
+                    conn,
+                    user_places::Identifier::PlaceNameAndUserId(name.as_str(), user_id),
+                )?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::NotFound("Place not found".into()))?;
+                (Some(place_id), None)
+            }
+            Some(AnnotationTarget::Sensor(device_id)) => {
+                let sensor = AuthorizedSensor::from_username(conn, device_id, username)?;
+                (None, Some(sensor.get().id))
+            }
+            None => (None, None),
+        };
+
+        Ok(NewAnnotation {
+            user_id,
+            place_id,
+            sensor_id,
+            text: payload.text.into(),
+            starts_at: Self::naive(payload.starts_at)?,
+            ends_at: payload.ends_at.map(Self::naive).transpose()?,
+        })
+    }
+
+    fn get_api_annotation(
+        conn: &mut DbConn,
+        user_id: i32,
+        annotation_id: i32,
+    ) -> Result<ApiAnnotation, Error> {
+        Self::api_annotation(get_annotation(conn, annotation_id, user_id)?)
+    }
+
+    async fn annotation_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetAnnotations>,
+    ) -> Result<Json<Vec<ApiAnnotation>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let identifier = match (&payload.device_id, &payload.place_name) {
+            (Some(device_id), _) => {
+                let sensor =
+                    AuthorizedSensor::from_username(conn, device_id, &claims.username)?.get();
+                Identifier::Sensor {
+                    user_id,
+                    sensor_id: sensor.id,
+                    place_id: sensor.place_id,
+                }
+            }
+            (None, Some(place_name)) => {
+                let place_id = user_places::get_user_place_id(
+                    conn,
+                    user_places::Identifier::PlaceNameAndUserId(place_name.as_str(), user_id),
+                )?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::NotFound("Place not found".into()))?;
+                Identifier::Place { user_id, place_id }
+            }
+            (None, None) => Identifier::UserId(user_id),
+        };
+
+        let low = match payload.lowest_at {
+            Some(lowest_at) => Self::naive(lowest_at)?,
+            None => DateTime::UNIX_EPOCH.naive_utc(),
+        };
+        let up = match payload.upper_at {
+            Some(upper_at) => Self::naive(upper_at)?,
+            None => NaiveDateTime::MAX,
+        };
+        let limit = capped_limit(payload.limit, GetAnnotations::MAX_LIMIT)?;
+
+        let annotations = get_annotations(conn, identifier, low..up, limit)?
+            .into_iter()
+            .map(Self::api_annotation)
+            .collect::<Result<Vec<ApiAnnotation>, Error>>()?;
+
+        log::trace!("Returning {} annotations", annotations.len());
+
+        Ok(Json(annotations))
+    }
+
+    async fn annotation_post(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PostAnnotation>,
+    ) -> Result<Json<ApiAnnotation>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let new_annotation = Self::new_annotation(conn, &claims.username, user_id, payload)?;
+        let annotation = insert_annotation(conn, new_annotation)?;
+        log::info!(
+            "User {} created annotation {}",
+            claims.username,
+            annotation.id
+        );
+
+        Ok(Json(Self::get_api_annotation(
+            conn,
+            user_id,
+            annotation.id,
+        )?))
+    }
+
+    async fn annotation_put(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PutAnnotation>,
+    ) -> Result<Json<ApiAnnotation>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let annotation = Self::new_annotation(conn, &claims.username, user_id, payload.annotation)?;
+        update_annotation(conn, payload.id, annotation)?;
+
+        Ok(Json(Self::get_api_annotation(conn, user_id, payload.id)?))
+    }
+
+    async fn annotation_delete(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<DeleteAnnotation>,
+    ) -> Result<Json<ApiAnnotation>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let annotation = Self::get_api_annotation(conn, user_id, payload.id)?;
+        delete_annotation(conn, payload.id, user_id)?;
+        log::info!(
+            "User {} deleted annotation {}",
+            claims.username,
+            annotation.id
+        );
+
+        Ok(Json(annotation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::db::{
+        establish_connection,
+        tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+    };
+
+    use super::*;
+
+    fn post(
+        target: Option<AnnotationTarget>,
+        text: &str,
+        starts_at: NaiveDateTime,
+    ) -> PostAnnotation {
+        PostAnnotation {
+            target,
+            text: text.to_string().into(),
+            starts_at: Annotations::timestamp(starts_at),
+            ends_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_annotation_post() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let target = Some(AnnotationTarget::Sensor(
+            DeviceId::from_string(&sensor.device_id).unwrap(),
+        ));
+        let now = Utc::now().naive_utc();
+        let mut payload = post(target.clone(), "New HVAC filter", now);
+        payload.ends_at = Some(Annotations::timestamp(now + TimeDelta::hours(1)));
+
+        let res = Annotations::annotation_post(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(payload.clone()),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(res.target, target);
+        assert_eq!(res.text, payload.text);
+        assert_eq!(res.starts_at, payload.starts_at);
+        assert_eq!(res.ends_at, payload.ends_at);
+
+        // Sensor of someone else
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other, _) = create_test_user(&mut conn);
+        let other_place = create_test_user_place(&mut conn, &other);
+        let other_sensor = create_test_user_sensor(&mut conn, &other_place);
+        let target =
+            AnnotationTarget::Sensor(DeviceId::from_string(&other_sensor.device_id).unwrap());
+
+        let res = Annotations::annotation_post(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(post(Some(target), "Window opened", now)),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_annotation_get() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let other_place = create_test_user_place(&mut conn, &user);
+        let device_id = DeviceId::from_string(&sensor.device_id).unwrap();
+
+        let now = Utc::now().naive_utc();
+        let annotations = [
+            (
+                Some(AnnotationTarget::Sensor(device_id.clone())),
+                "Sensor moved",
+                1,
+            ),
+            (
+                Some(AnnotationTarget::Place(place.name.clone().into())),
+                "Window opened",
+                3,
+            ),
+            (None, "Heating on", 2),
+            (
+                Some(AnnotationTarget::Place(other_place.name.clone().into())),
+                "Other place",
+                1,
+            ),
+            // Out of range
+            (None, "Last week", 24 * 7),
+        ];
+        for (target, text, hours_ago) in annotations {
+            let new_annotation = Annotations::new_annotation(
+                &mut conn,
+                &user.username,
+                user.id,
+                post(target, text, now - TimeDelta::hours(hours_ago)),
+            )
+            .unwrap();
+            insert_annotation(&mut conn, new_annotation).unwrap();
+        }
+
+        let query = GetAnnotations {
+            device_id: Some(device_id),
+            place_name: None,
+            lowest_at: Some(Annotations::timestamp(now - TimeDelta::days(1))),
+            upper_at: None,
+            limit: None,
+        };
+        let res = Annotations::annotation_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+
+        let texts: Vec<&str> = res.iter().map(|a| a.text.as_str()).collect();
+        assert_eq!(texts, vec!["Window opened", "Heating on", "Sensor moved"]);
+    }
+
+    #[tokio::test]
+    async fn test_annotation_delete() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other, _) = create_test_user(&mut conn);
+
+        let now = Utc::now().naive_utc();
+        let new_annotation = Annotations::new_annotation(
+            &mut conn,
+            &other.username,
+            other.id,
+            post(None, "Mine", now),
+        )
+        .unwrap();
+        let annotation = insert_annotation(&mut conn, new_annotation).unwrap();
+
+        let res = Annotations::annotation_delete(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(DeleteAnnotation { id: annotation.id }),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+}
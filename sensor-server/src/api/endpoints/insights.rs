@@ -0,0 +1,266 @@
+use std::ops::Range;
+
+use axum::{extract::Query, routing::MethodRouter};
+use axum_serde_valid::Json;
+use chrono::{NaiveDateTime, TimeDelta};
+use common::{
+    endpoints_io::{
+        insights::{AirQualityBand, ApiInsights, ApiSensorInsights, GetInsights},
+        sensor_data::{ApiSensorData, BucketSize, SortOrder},
+    },
+    types::validate::device_id::DeviceId,
+};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    api::{
+        Endpoint,
+        endpoints::sensor_data::{RangeDelimiter, SensorData},
+        route::Route,
+    },
+    auth::claims::Claims,
+    db::{
+        self, DbConn, DbConnHolder,
+        calibrations::calibrate,
+        sensor_data::{Identifier, SensorDataCursor, get_sensor_data},
+        user_places::get_user_place,
+        user_sensors::get_user_sensor_and_place_and_last_data,
+    },
+    insights::{Co2Reading, air_quality_shares, median, occupancy, ventilation_estimates},
+};
+
+pub struct Insights {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for Insights {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for Insights {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Insights {
+    pub const API_PATH: &str = "/insights";
+    const PAGE_SIZE: u32 = 1000;
+
+    pub fn new() -> Insights {
+        let mr = MethodRouter::new().get(Self::insights_get);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    /// Every CO2 reading of the sensor in range, oldest first
+    fn co2_readings(
+        conn: &mut DbConn,
+        sensor_id: i32,
+        range: Range<NaiveDateTime>,
+    ) -> Result<Vec<Co2Reading>, db::Error> {
+        let mut readings = vec![];
+        let mut cursor: Option<SensorDataCursor> = None;
+        loop {
+            let (mut page, next_cursor) = get_sensor_data(
+                conn,
+                Identifier::SensorId(sensor_id),
+                range.clone(),
+                SortOrder::Asc,
+                Self::PAGE_SIZE,
+                cursor,
+            )?;
+            calibrate(conn, &mut page)?;
+
+            for datum in page {
+                let at = datum.added_at;
+                if let Some(co2) = ApiSensorData::try_from(datum)?.data.co2 {
+                    readings.push(Co2Reading {
+                        at,
+                        co2: co2 as f64,
+                    });
+                }
+            }
+
+            match next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => return Ok(readings),
+            }
+        }
+    }
+
+    async fn insights_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetInsights>,
+    ) -> Result<Json<ApiInsights>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id =
+            db::users::get_user(conn, db::users::Identifier::Username(&claims.username))?.id;
+
+        let place_name = payload.place_name.as_str();
+        if get_user_place(
+            conn,
+            db::user_places::Identifier::PlaceNameAndUserId(place_name, user_id),
+        )?
+        .is_empty()
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        let low = SensorData::convert_opt_timestamp_into_naive(
+            payload.lowest_added_at,
+            RangeDelimiter::Bottom,
+        )?;
+        let up = SensorData::convert_opt_timestamp_into_naive(
+            payload.upper_added_at,
+            RangeDelimiter::Top,
+        )?;
+        if up - low > TimeDelta::days(GetInsights::MAX_RANGE_DAYS) {
+            log::trace!("Insights range too long: {low} - {up}");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let sensors = get_user_sensor_and_place_and_last_data(
+            conn,
+            db::user_sensors::Identifier::PlaceNameAndUserId(place_name, user_id).into(),
+        )?;
+
+        let mut sensor_insights = vec![];
+        let mut place_readings = vec![];
+        for (_, sensor, _) in sensors {
+            let readings = Self::co2_readings(conn, sensor.id, low..up)?;
+            let Some(last) = readings.last() else {
+                continue;
+            };
+
+            let device_id = DeviceId::from_string(&sensor.device_id).map_err(|e| {
+                log::error!("Could not construct DeviceId: {e:?}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            let ventilation = ventilation_estimates(&readings);
+            sensor_insights.push(ApiSensorInsights {
+                device_id,
+                current_band: Some(AirQualityBand::from_co2(last.co2)),
+                bands: air_quality_shares(&readings),
+                air_changes_per_hour: median(
+                    ventilation.iter().map(|v| v.air_changes_per_hour).collect(),
+                ),
+                ventilation,
+            });
+            place_readings.push(readings);
+        }
+
+        let occupancy = occupancy(&place_readings, payload.bucket.unwrap_or(BucketSize::Hour));
+
+        log::trace!(
+            "Returning insights of {} sensors and {} occupancy buckets",
+            sensor_insights.len(),
+            occupancy.len()
+        );
+
+        Ok(Json(ApiInsights {
+            sensors: sensor_insights,
+            occupancy,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::endpoints_io::sensor_data::SensorReading;
+
+    use crate::db::{
+        establish_connection,
+        sensor_data::insert_sensor_data_batch,
+        tests::{
+            create_test_user, create_test_user_place, create_test_user_sensor, new_test_sensor_data,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_insights_get() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+
+        // Since the epoch
+        let query = GetInsights {
+            place_name: place.name.clone().into(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            bucket: None,
+        };
+        let res = Insights::insights_get(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        // Without CO2 readings
+        create_test_user_sensor(&mut conn, &place);
+
+        // Someone in the room for the last half hour
+        let now = chrono::Utc::now().naive_utc();
+        let new_data = (0..30)
+            .map(|i| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now - TimeDelta::minutes(30 - i as i64),
+                    SensorReading {
+                        co2: Some(500 + 20 * i),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).unwrap();
+
+        let query = GetInsights {
+            place_name: place.name.clone().into(),
+            lowest_added_at: Some((now - TimeDelta::days(1)).and_utc().timestamp() as usize),
+            upper_added_at: None,
+            bucket: Some(BucketSize::Day),
+        };
+        let res = Insights::insights_get(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(res.sensors.len(), 1);
+        let insights = &res.sensors[0];
+        assert_eq!(
+            insights.device_id,
+            DeviceId::from_string(&sensor.device_id).unwrap()
+        );
+        // Last one is 1080 ppm
+        assert_eq!(insights.current_band, Some(AirQualityBand::Moderate));
+        assert_eq!(insights.bands.iter().map(|b| b.readings).sum::<usize>(), 30);
+        assert!(insights.ventilation.is_empty());
+        assert!(!res.occupancy.is_empty());
+        assert!(res.occupancy.iter().all(|bucket| bucket.likelihood == 1.0));
+    }
+}
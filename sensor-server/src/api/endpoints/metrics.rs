@@ -0,0 +1,356 @@
+use axum::{
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use axum_serde_valid::Json;
+use common::{
+    endpoints_io::{
+        metrics::ApiScrapeToken,
+        sensor_data::{ApiSensorData, Metric},
+    },
+    types::ApiTimestamp,
+};
+use hyper::{StatusCode, header::CONTENT_TYPE};
+
+use crate::{
+    RoutePath,
+    api::{Endpoint, route::Route},
+    auth::{claims::Claims, keys::generate_secret},
+    db::{
+        DbConnHolder,
+        calibrations::calibrate,
+        model::{ScrapeToken, UserPlace, UserSensor},
+        scrape_tokens::{self, delete_scrape_token, get_scrape_token, set_scrape_token},
+        user_sensors::{self, get_user_sensor_and_place_and_last_data},
+        users,
+    },
+    prometheus::{self, Exposition, MetricType},
+};
+
+pub struct Metrics {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for Metrics {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Name and help of the gauge of each metric
+fn gauge(metric: Metric) -> (&'static str, &'static str) {
+    match metric {
+        Metric::Co2 => ("sensor_co2_ppm", "Latest CO2 reading of the sensor"),
+        Metric::Temperature => (
+            "sensor_temperature_celsius",
+            "Latest temperature reading of the sensor",
+        ),
+        Metric::Humidity => (
+            "sensor_humidity_percent",
+            "Latest relative humidity reading of the sensor",
+        ),
+        Metric::DewPoint => (
+            "sensor_dew_point_celsius",
+            "Dew point of the latest reading of the sensor",
+        ),
+        Metric::AbsoluteHumidity => (
+            "sensor_absolute_humidity_grams_per_cubic_meter",
+            "Absolute humidity of the latest reading of the sensor",
+        ),
+        Metric::Humidex => (
+            "sensor_humidex_celsius",
+            "Humidex of the latest reading of the sensor",
+        ),
+    }
+}
+
+const LAST_READING_GAUGE: &str = "sensor_last_reading_timestamp_seconds";
+
+fn labels<'a>(place: &'a UserPlace, sensor: &'a UserSensor) -> [(&'static str, &'a str); 3] {
+    [
+        ("device_id", &sensor.device_id),
+        ("sensor", &sensor.name),
+        ("place", &place.name),
+    ]
+}
+
+/// Gauges of the last readings, each labelled with the device_id, sensor name and place name
+/// Suspect metrics are left out so that glitches don't reach the dashboards
+fn exposition(sensors: &[(UserPlace, UserSensor, ApiSensorData)]) -> String {
+    let mut exposition = Exposition::new();
+
+    for metric in Metric::ALL {
+        let (name, help) = gauge(metric);
+        exposition.family(name, help, MetricType::Gauge);
+        for (place, sensor, data) in sensors {
+            let Some(value) = data.data.without(&data.suspect_metrics).metric(metric) else {
+                continue;
+            };
+            exposition.sample(name, &labels(place, sensor), value);
+        }
+    }
+
+    exposition.family(
+        LAST_READING_GAUGE,
+        "When the latest reading of the sensor was added, in seconds since the UNIX epoch",
+        MetricType::Gauge,
+    );
+    for (place, sensor, data) in sensors {
+        exposition.sample(
+            LAST_READING_GAUGE,
+            &labels(place, sensor),
+            data.added_at as f64,
+        );
+    }
+
+    exposition.into_string()
+}
+
+impl Metrics {
+    pub const API_PATH: &str = "/metrics";
+    pub const TOKEN_API_PATH: &str = "/user/scrape_token";
+
+    pub fn new() -> Metrics {
+        let mr = MethodRouter::new().get(Self::metrics_get);
+
+        let token_mr = MethodRouter::new()
+            .get(Self::scrape_token_get)
+            .put(Self::scrape_token_put)
+            .delete(Self::scrape_token_delete);
+
+        Self {
+            resources: vec![
+                Route::new(
+                    RoutePath::from_string(Self::API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    mr,
+                ),
+                Route::new(
+                    RoutePath::from_string(Self::TOKEN_API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    token_mr,
+                ),
+            ],
+        }
+    }
+
+    fn api_scrape_token(scrape_token: ScrapeToken, token: Option<String>) -> ApiScrapeToken {
+        ApiScrapeToken {
+            token,
+            created_at: scrape_token.created_at.and_utc().timestamp() as ApiTimestamp,
+        }
+    }
+
+    /// The latest readings of every sensor of the user the scrape token belongs to
+    async fn metrics_get(
+        mut conn: DbConnHolder,
+        bearer: Option<TypedHeader<Authorization<Bearer>>>,
+    ) -> Result<Response, StatusCode> {
+        let conn = &mut conn.0;
+        let Some(TypedHeader(Authorization(bearer))) = bearer else {
+            log::warn!("Metrics scraped without a token, UNAUTHORIZED");
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+        let Some(scrape_token) =
+            get_scrape_token(conn, scrape_tokens::Identifier::Token(bearer.token()))?
+        else {
+            log::warn!("Metrics scraped with an unknown token, UNAUTHORIZED");
+            return Err(StatusCode::UNAUTHORIZED);
+        };
+
+        let (sensors, mut data): (Vec<(UserPlace, UserSensor)>, Vec<_>) =
+            get_user_sensor_and_place_and_last_data(
+                conn,
+                user_sensors::ListIdentifier::UserId(scrape_token.user_id),
+            )?
+            .into_iter()
+            .filter_map(|(place, sensor, data)| Some(((place, sensor), data?)))
+            .unzip();
+        calibrate(conn, &mut data)?;
+
+        let sensors = sensors
+            .into_iter()
+            .zip(data)
+            .map(|((place, sensor), data)| Ok((place, sensor, ApiSensorData::try_from(data)?)))
+            .collect::<Result<Vec<_>, crate::db::Error>>()?;
+
+        log::trace!(
+            "Exposing the last readings of {} sensors of user {}",
+            sensors.len(),
+            scrape_token.user_id
+        );
+
+        Ok((
+            [(CONTENT_TYPE, prometheus::CONTENT_TYPE)],
+            exposition(&sensors),
+        )
+            .into_response())
+    }
+
+    async fn scrape_token_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+    ) -> Result<Json<Option<ApiScrapeToken>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let scrape_token = get_scrape_token(conn, scrape_tokens::Identifier::UserId(user_id))?;
+
+        Ok(Json(scrape_token.map(|t| Self::api_scrape_token(t, None))))
+    }
+
+    /// Creates a new token for the user, replacing the previous one if any
+    async fn scrape_token_put(
+        claims: Claims,
+        mut conn: DbConnHolder,
+    ) -> Result<Json<ApiScrapeToken>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let token = generate_secret();
+        let scrape_token = set_scrape_token(conn, user_id, &token)?;
+        log::info!("User {} got a new scrape token", claims.username);
+
+        Ok(Json(Self::api_scrape_token(scrape_token, Some(token))))
+    }
+
+    async fn scrape_token_delete(
+        claims: Claims,
+        mut conn: DbConnHolder,
+    ) -> Result<Json<ApiScrapeToken>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let scrape_token = delete_scrape_token(conn, user_id)?.ok_or(StatusCode::NOT_FOUND)?;
+        log::info!("User {} deleted their scrape token", claims.username);
+
+        Ok(Json(Self::api_scrape_token(scrape_token, None)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::to_bytes;
+    use chrono::{TimeDelta, Utc};
+    use common::endpoints_io::sensor_data::SensorReading;
+
+    use crate::db::{
+        establish_connection,
+        sensor_data::{insert_sensor_data_batch, set_suspect_metrics},
+        tests::{
+            create_test_user, create_test_user_place, create_test_user_sensor, new_test_sensor_data,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_metrics_get() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        // Without readings
+        create_test_user_sensor(&mut conn, &place);
+        let (other, _) = create_test_user(&mut conn);
+        let other_place = create_test_user_place(&mut conn, &other);
+        let other_sensor = create_test_user_sensor(&mut conn, &other_place);
+
+        let now = Utc::now().naive_utc();
+        let new_data = [
+            (&sensor, 2, 500),
+            (&sensor, 1, 600),
+            (&other_sensor, 1, 700),
+        ]
+        .into_iter()
+        .map(|(s, minutes_ago, co2)| {
+            new_test_sensor_data(
+                s.id,
+                now - TimeDelta::minutes(minutes_ago),
+                SensorReading {
+                    co2: Some(co2),
+                    temperature: Some(21.0),
+                    humidity: Some(50.0),
+                },
+            )
+        })
+        .collect();
+        let inserted = insert_sensor_data_batch(&mut conn, new_data).unwrap();
+        set_suspect_metrics(&mut conn, inserted[1].id, vec!["temperature".to_string()]).unwrap();
+
+        set_scrape_token(&mut conn, user.id, "token").unwrap();
+
+        let res = Metrics::metrics_get(
+            DbConnHolder(conn),
+            Some(TypedHeader(Authorization::bearer("token").unwrap())),
+        )
+        .await
+        .expect("Should not fail");
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            prometheus::CONTENT_TYPE
+        );
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        let labels = format!(
+            "{{device_id=\"{}\",sensor=\"{}\",place=\"{}\"}}",
+            sensor.device_id, sensor.name, place.name
+        );
+        assert!(body.contains("# TYPE sensor_co2_ppm gauge\n"));
+        assert!(body.contains(&format!("sensor_co2_ppm{labels} 600\n")));
+        assert!(body.contains(&format!("sensor_humidity_percent{labels} 50\n")));
+        // The suspect temperature and what is derived from it
+        assert!(!body.contains("sensor_temperature_celsius{"));
+        assert!(body.contains(&format!(
+            "{LAST_READING_GAUGE}{labels} {}\n",
+            inserted[1].added_at.and_utc().timestamp()
+        )));
+        // Only the sensors of the user with readings
+        assert!(!body.contains(&other_sensor.device_id));
+        assert_eq!(body.matches("sensor_co2_ppm{").count(), 1);
+
+        let conn = establish_connection(true).unwrap();
+        let res = Metrics::metrics_get(
+            DbConnHolder(conn),
+            Some(TypedHeader(Authorization::bearer("unknown").unwrap())),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::UNAUTHORIZED));
+
+        let conn = establish_connection(true).unwrap();
+        let res = Metrics::metrics_get(DbConnHolder(conn), None).await;
+        assert_eq!(res.err(), Some(StatusCode::UNAUTHORIZED));
+    }
+
+    #[tokio::test]
+    async fn test_scrape_token_put() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+
+        let res = Metrics::scrape_token_put(Claims::new(user.username), DbConnHolder(conn))
+            .await
+            .expect("Should not fail");
+        assert_eq!(res.token.as_ref().map(|t| t.len()), Some(64));
+
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+
+        let res =
+            Metrics::scrape_token_delete(Claims::new(user.username), DbConnHolder(conn)).await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+}
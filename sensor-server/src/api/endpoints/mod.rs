@@ -1,20 +1,44 @@
 use crate::api::Endpoint;
 
+pub mod alert;
+pub mod alert_rule;
+pub mod annotation;
 pub mod health;
+pub mod insights;
+pub mod metrics;
+pub mod notification;
 pub mod place;
+pub mod retention;
 pub mod sensor;
 pub mod sensor_data;
+pub mod sensor_data_export;
+pub mod sensor_data_gaps;
+pub mod sensor_data_import;
+pub mod sensor_data_stream;
 pub mod session;
 pub mod user;
+pub mod webhook;
 
 pub fn generate_endpoints() -> Vec<Box<dyn Endpoint>> {
     let mut endpoints = Vec::<Box<dyn Endpoint>>::new();
 
+    endpoints.push(Box::new(alert::Alert::new()));
+    endpoints.push(Box::new(alert_rule::AlertRules::new()));
+    endpoints.push(Box::new(annotation::Annotations::new()));
+    endpoints.push(Box::new(notification::Notifications::new()));
     endpoints.push(Box::new(place::Place::new()));
+    endpoints.push(Box::new(retention::Retention::new()));
     endpoints.push(Box::new(sensor::Sensor::new()));
     endpoints.push(Box::new(sensor_data::SensorData::new()));
+    endpoints.push(Box::new(sensor_data_export::SensorDataExport::new()));
+    endpoints.push(Box::new(sensor_data_gaps::SensorDataGaps::new()));
+    endpoints.push(Box::new(sensor_data_import::SensorDataImport::new()));
+    endpoints.push(Box::new(sensor_data_stream::SensorDataStream::new()));
     endpoints.push(Box::new(session::Session::new()));
     endpoints.push(Box::new(user::User::new()));
+    endpoints.push(Box::new(webhook::Webhooks::new()));
+    endpoints.push(Box::new(insights::Insights::new()));
+    endpoints.push(Box::new(metrics::Metrics::new()));
     endpoints.push(Box::new(health::Health::new()));
 
     endpoints
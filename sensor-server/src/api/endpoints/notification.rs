@@ -0,0 +1,146 @@
+use axum::routing::MethodRouter;
+use axum_serde_valid::Json;
+use common::endpoints_io::notification::{ApiNotificationPreferences, PutNotificationPreferences};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    api::{Endpoint, route::Route},
+    auth::claims::Claims,
+    db::{
+        DbConnHolder,
+        model::{NotificationPreferences, NotificationPreferencesChange},
+        notification_preferences::{get_notification_preferences, update_notification_preferences},
+        users,
+    },
+};
+
+pub struct Notifications {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for Notifications {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Notifications {
+    pub const API_PATH: &str = "/user/notification";
+
+    pub fn new() -> Notifications {
+        let mr = MethodRouter::new()
+            .get(Self::notification_get)
+            .put(Self::notification_put);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    fn api_preferences(preferences: NotificationPreferences) -> ApiNotificationPreferences {
+        ApiNotificationPreferences {
+            alert_emails: preferences.alert_emails,
+            account_emails: preferences.account_emails,
+            sensor_emails: preferences.sensor_emails,
+        }
+    }
+
+    async fn notification_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+    ) -> Result<Json<ApiNotificationPreferences>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let preferences = get_notification_preferences(conn, user_id)?;
+
+        Ok(Json(Self::api_preferences(preferences)))
+    }
+
+    async fn notification_put(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PutNotificationPreferences>,
+    ) -> Result<Json<ApiNotificationPreferences>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let change = NotificationPreferencesChange {
+            alert_emails: payload.alert_emails,
+            account_emails: payload.account_emails,
+            sensor_emails: payload.sensor_emails,
+        };
+        let preferences = update_notification_preferences(conn, user_id, change)?;
+        log::trace!(
+            "User {} notification preferences: {preferences:?}",
+            claims.username
+        );
+
+        Ok(Json(Self::api_preferences(preferences)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{establish_connection, tests::create_test_user};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_notification_put() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let claims = Claims::new(user.username.clone());
+
+        // All of them until changed
+        let res = Notifications::notification_get(claims.clone(), DbConnHolder(conn))
+            .await
+            .unwrap();
+        assert_eq!(
+            res.0,
+            ApiNotificationPreferences {
+                alert_emails: true,
+                account_emails: true,
+                sensor_emails: true,
+            }
+        );
+
+        // Each handler takes its own test transaction
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let claims = Claims::new(user.username.clone());
+
+        let res = Notifications::notification_put(
+            claims.clone(),
+            DbConnHolder(conn),
+            Json(PutNotificationPreferences {
+                alert_emails: Some(false),
+                ..Default::default()
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            res.0,
+            ApiNotificationPreferences {
+                alert_emails: false,
+                account_emails: true,
+                sensor_emails: true,
+            }
+        );
+    }
+}
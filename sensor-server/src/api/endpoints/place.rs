@@ -1,19 +1,34 @@
 use axum::{extract::Query, routing::MethodRouter};
 use axum_serde_valid::Json;
+use chrono::TimeDelta;
 use common::{
-    endpoints_io::place::{ApiUserPlace, DeletePlace, GetPlace, PostPlace, PutPlace},
-    types::ApiTimestamp,
+    endpoints_io::{
+        place::{
+            ApiPlaceStats, ApiPlaceStatsBucket, ApiUserPlace, DeletePlace, GetPlace, GetPlaceStats,
+            MetricExtreme, PlaceMetricStats, PostPlace, PutPlace,
+        },
+        sensor_data::Metric,
+    },
+    types::{ApiTimestamp, validate::device_id::DeviceId},
 };
 use hyper::StatusCode;
 
 use crate::{
     RoutePath,
-    api::{Endpoint, route::Route},
+    api::{
+        Endpoint,
+        endpoints::{
+            annotation::Annotations,
+            sensor_data::{RangeDelimiter, SensorData},
+        },
+        route::Route,
+    },
     auth::claims::Claims,
-    db::model::NewUserPlace,
+    db::model::{NewUserPlace, PlaceStatsBucket},
     db::{
-        self, DbConnHolder,
-        user_places::{Identifier, Update, update_user_place},
+        self, DbConnHolder, annotations,
+        sensor_data::get_place_stats,
+        user_places::{Identifier, Update, get_user_place, update_user_place},
     },
 };
 
@@ -44,19 +59,28 @@ impl Endpoint for Place {
 
 impl Place {
     pub const API_PATH: &str = "/place";
+    pub const STATS_API_PATH: &str = "/place/stats";
     pub fn new() -> Place {
         let mr = MethodRouter::new()
             .get(Self::place_get)
             .post(Self::place_post)
             .put(Self::place_put)
             .delete(Self::place_delete);
+        let stats_mr = MethodRouter::new().get(Self::place_stats_get);
 
         Self {
-            resources: vec![Route::new(
-                RoutePath::from_string(Self::API_PATH.to_string())
-                    .expect("The route should be correct"),
-                mr,
-            )],
+            resources: vec![
+                Route::new(
+                    RoutePath::from_string(Self::API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    mr,
+                ),
+                Route::new(
+                    RoutePath::from_string(Self::STATS_API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    stats_mr,
+                ),
+            ],
         }
     }
 
@@ -228,6 +252,137 @@ impl Place {
 
         Ok(Json(vec))
     }
+
+    /// Groups the per metric rows (expected ordered by bucket_start) into one ApiPlaceStatsBucket
+    /// per bucket
+    fn rows_into_api_buckets(
+        rows: Vec<PlaceStatsBucket>,
+    ) -> Result<Vec<ApiPlaceStatsBucket>, StatusCode> {
+        let mut buckets: Vec<ApiPlaceStatsBucket> = vec![];
+
+        for row in rows {
+            let Some(metric) = Metric::from_key(&row.metric) else {
+                log::warn!(
+                    "Unknown metric found aggregating place stats: {}",
+                    row.metric
+                );
+                continue;
+            };
+
+            let device_id = |device_id: &str| {
+                DeviceId::from_string(device_id).map_err(|e| {
+                    log::error!("Could not construct DeviceId: {e:?}");
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })
+            };
+            let stats = PlaceMetricStats {
+                min: MetricExtreme {
+                    value: row.min,
+                    device_id: device_id(&row.min_device_id)?,
+                    added_at: row.min_added_at.and_utc().timestamp() as ApiTimestamp,
+                },
+                max: MetricExtreme {
+                    value: row.max,
+                    device_id: device_id(&row.max_device_id)?,
+                    added_at: row.max_added_at.and_utc().timestamp() as ApiTimestamp,
+                },
+                avg: row.avg,
+                count: row.count as usize,
+            };
+
+            let bucket_start = row.bucket_start.and_utc().timestamp() as ApiTimestamp;
+            if buckets
+                .last()
+                .is_none_or(|b| b.bucket_start != bucket_start)
+            {
+                buckets.push(ApiPlaceStatsBucket {
+                    bucket_start,
+                    ..Default::default()
+                });
+            }
+
+            if let Some(bucket) = buckets.last_mut() {
+                *bucket.metric_mut(metric) = Some(stats);
+            }
+        }
+
+        Ok(buckets)
+    }
+
+    async fn place_stats_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetPlaceStats>,
+    ) -> Result<Json<ApiPlaceStats>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id =
+            db::users::get_user(conn, db::users::Identifier::Username(&claims.username))?.id;
+        let place = get_user_place(
+            conn,
+            Identifier::PlaceNameAndUserId(payload.place_name.as_str(), user_id),
+        )?
+        .into_iter()
+        .next()
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+        let low = SensorData::convert_opt_timestamp_into_naive(
+            payload.lowest_added_at,
+            RangeDelimiter::Bottom,
+        )?;
+        let up = SensorData::convert_opt_timestamp_into_naive(
+            payload.upper_added_at,
+            RangeDelimiter::Top,
+        )?;
+        if up - low > TimeDelta::days(GetPlaceStats::MAX_RANGE_DAYS)
+            || payload.bucket.is_some_and(|bucket_size| {
+                SensorData::buckets_in(&(low..up), bucket_size) > GetPlaceStats::MAX_BUCKETS as i64
+            })
+        {
+            log::trace!(
+                "Place stats range too long: {low} - {up}, {:?}",
+                payload.bucket
+            );
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let (total, buckets): (Vec<PlaceStatsBucket>, Vec<PlaceStatsBucket>) = get_place_stats(
+            conn,
+            place.id,
+            low..up,
+            payload.bucket,
+            payload.exclude_suspect.unwrap_or(false),
+        )?
+        .into_iter()
+        .partition(|row| row.total);
+
+        let total = Self::rows_into_api_buckets(total)?
+            .pop()
+            .unwrap_or_else(|| ApiPlaceStatsBucket {
+                bucket_start: low.and_utc().timestamp() as ApiTimestamp,
+                ..Default::default()
+            });
+        let buckets = Self::rows_into_api_buckets(buckets)?;
+        let annotations = Annotations::range_annotations(
+            conn,
+            annotations::Identifier::Place {
+                user_id,
+                place_id: place.id,
+            },
+            low..up,
+        )?;
+
+        log::trace!(
+            "Returning stats of place {} with {} buckets",
+            place.id,
+            buckets.len()
+        );
+
+        Ok(Json(ApiPlaceStats {
+            total,
+            buckets,
+            annotations,
+        }))
+    }
 }
 
 #[cfg(test)]
@@ -235,6 +390,14 @@ mod tests {
 
     use axum::extract::Query;
     use axum_serde_valid::Json;
+    use chrono::{TimeDelta, Timelike};
+    use common::{
+        endpoints_io::{
+            place::GetPlaceStats,
+            sensor_data::{BucketSize, SensorReading},
+        },
+        types::{ApiTimestamp, validate::device_id::DeviceId},
+    };
     use serde_valid::json::ToJsonString;
 
     use crate::{
@@ -242,7 +405,11 @@ mod tests {
         auth::claims::{Claims, get_new_id},
         db::{
             DbConnHolder, establish_connection,
-            tests::{create_test_user, create_test_user_place},
+            sensor_data::insert_sensor_data_batch,
+            tests::{
+                create_test_user, create_test_user_place, create_test_user_sensor,
+                new_test_sensor_data,
+            },
         },
     };
 
@@ -372,4 +539,126 @@ mod tests {
             place_to_delete.name.into()
         );
     }
+
+    #[tokio::test]
+    async fn test_place_stats_get() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let first = create_test_user_sensor(&mut conn, &user_place);
+        let second = create_test_user_sensor(&mut conn, &user_place);
+
+        let now = chrono::Utc::now()
+            .naive_utc()
+            .with_nanosecond(0)
+            .expect("Should be valid");
+        let first_at = now - TimeDelta::hours(2);
+        let second_at = now - TimeDelta::hours(1);
+        let new_data = [
+            (first.id, 400, 20.0, first_at),
+            (second.id, 800, 22.0, second_at),
+        ]
+        .into_iter()
+        .map(|(sensor_id, co2, temperature, added_at)| {
+            new_test_sensor_data(
+                sensor_id,
+                added_at,
+                SensorReading {
+                    co2: Some(co2),
+                    temperature: Some(temperature),
+                    humidity: None,
+                },
+            )
+        })
+        .collect();
+        insert_sensor_data_batch(&mut conn, new_data).unwrap();
+
+        let lowest_added_at = (now - TimeDelta::days(1)).and_utc().timestamp() as ApiTimestamp;
+        let query = GetPlaceStats {
+            place_name: user_place.name.clone().into(),
+            lowest_added_at: Some(lowest_added_at),
+            upper_added_at: None,
+            bucket: Some(BucketSize::Hour),
+            exclude_suspect: None,
+        };
+        let res = Place::place_stats_get(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(res.total.bucket_start, lowest_added_at);
+        assert!(res.total.humidity.is_none());
+        let co2 = res.total.co2.clone().expect("Should have co2");
+        assert_eq!(co2.count, 2);
+        assert_eq!(co2.avg, 600.0);
+        assert_eq!(co2.min.value, 400.0);
+        assert_eq!(
+            co2.min.device_id,
+            DeviceId::from_string(&first.device_id).unwrap()
+        );
+        assert_eq!(
+            co2.min.added_at,
+            first_at.and_utc().timestamp() as ApiTimestamp
+        );
+        assert_eq!(co2.max.value, 800.0);
+        assert_eq!(
+            co2.max.device_id,
+            DeviceId::from_string(&second.device_id).unwrap()
+        );
+        let temperature = res
+            .total
+            .temperature
+            .clone()
+            .expect("Should have temperature");
+        assert_eq!(temperature.avg, 21.0);
+
+        assert_eq!(res.buckets.len(), 2);
+        for bucket in &res.buckets {
+            assert_eq!(bucket.co2.as_ref().map(|co2| co2.count), Some(1));
+        }
+
+        // Not the place of the user
+        let mut conn = establish_connection(true).unwrap();
+        let (other, _) = create_test_user(&mut conn);
+        let query = GetPlaceStats {
+            place_name: user_place.name.into(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            bucket: None,
+            exclude_suspect: None,
+        };
+        let res = Place::place_stats_get(
+            Claims::new(other.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await;
+        assert_eq!(res.err(), Some(hyper::StatusCode::NOT_FOUND));
+
+        // Longer than MAX_RANGE_DAYS, or split in more than MAX_BUCKETS
+        let week_ago = (now - TimeDelta::weeks(1)).and_utc().timestamp() as ApiTimestamp;
+        for (lowest_added_at, bucket) in [(None, None), (Some(week_ago), Some(BucketSize::Minute))]
+        {
+            let mut conn = establish_connection(true).unwrap();
+            let (user, _) = create_test_user(&mut conn);
+            let user_place = create_test_user_place(&mut conn, &user);
+            let query = GetPlaceStats {
+                place_name: user_place.name.into(),
+                lowest_added_at,
+                upper_added_at: None,
+                bucket,
+                exclude_suspect: None,
+            };
+            let res = Place::place_stats_get(
+                Claims::new(user.username),
+                DbConnHolder(conn),
+                Query(query),
+            )
+            .await;
+            assert_eq!(res.err(), Some(hyper::StatusCode::BAD_REQUEST));
+        }
+    }
 }
@@ -0,0 +1,188 @@
+use axum::routing::MethodRouter;
+use axum_serde_valid::Json;
+use common::{
+    endpoints_io::retention::{
+        ApiRetentionPolicies, ApiSensorRetention, PutRetention, RetentionTarget,
+    },
+    types::validate::device_id::DeviceId,
+};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    api::{Endpoint, route::Route},
+    auth::claims::Claims,
+    db::{
+        DbConn, DbConnHolder,
+        retention_policies::{
+            Identifier, SERVER_KEEP_DAYS, effective_keep_days, get_retention_policy,
+            get_user_sensors_keep_days, set_retention_policy,
+        },
+        user_sensors::AuthorizedSensor,
+        users,
+    },
+};
+
+pub struct Retention {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for Retention {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Retention {
+    pub const API_PATH: &str = "/retention";
+    pub fn new() -> Retention {
+        let mr = MethodRouter::new()
+            .get(Self::retention_get)
+            .put(Self::retention_put);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    fn get_policies(conn: &mut DbConn, user_id: i32) -> Result<ApiRetentionPolicies, StatusCode> {
+        let server_keep_days = *SERVER_KEEP_DAYS;
+        let user_keep_days =
+            get_retention_policy(conn, Identifier::UserId(user_id))?.map(|p| p.keep_days as u32);
+
+        let sensors = get_user_sensors_keep_days(conn, user_id)?
+            .into_iter()
+            .map(|(device_id, keep_days)| {
+                let keep_days = keep_days.map(|d| d as u32);
+                Ok(ApiSensorRetention {
+                    device_id: DeviceId::from_string(&device_id).map_err(|e| {
+                        log::error!("Could not construct DeviceId: {e:?}");
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    })?,
+                    keep_days,
+                    effective_keep_days: effective_keep_days(
+                        server_keep_days,
+                        user_keep_days,
+                        keep_days,
+                    ),
+                })
+            })
+            .collect::<Result<Vec<_>, StatusCode>>()?;
+
+        Ok(ApiRetentionPolicies {
+            server_keep_days,
+            user_keep_days,
+            sensors,
+        })
+    }
+
+    async fn retention_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+    ) -> Result<Json<ApiRetentionPolicies>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        Ok(Json(Self::get_policies(conn, user_id)?))
+    }
+
+    async fn retention_put(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PutRetention>,
+    ) -> Result<Json<ApiRetentionPolicies>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let identifier = match &payload.target {
+            RetentionTarget::User => Identifier::UserId(user_id),
+            RetentionTarget::Sensor(device_id) => Identifier::SensorId(
+                AuthorizedSensor::from_username(conn, device_id, &claims.username)?
+                    .get()
+                    .id,
+            ),
+        };
+
+        set_retention_policy(conn, identifier, payload.keep_days)?;
+        log::info!(
+            "User {} set retention of {:?} to {:?} days",
+            claims.username,
+            payload.target,
+            payload.keep_days
+        );
+
+        Ok(Json(Self::get_policies(conn, user_id)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_serde_valid::Json;
+    use common::{
+        endpoints_io::retention::{PutRetention, RetentionTarget},
+        types::validate::device_id::DeviceId,
+    };
+
+    use crate::{
+        api::endpoints::retention::Retention,
+        auth::claims::Claims,
+        db::{
+            DbConnHolder, establish_connection,
+            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_retention_put() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let conn = DbConnHolder(conn);
+
+        let claims = Claims::new(user.username.clone());
+        let body = PutRetention {
+            target: RetentionTarget::User,
+            keep_days: Some(90),
+        };
+        let res = Retention::retention_put(claims, conn, Json(body))
+            .await
+            .expect("Should not fail");
+        assert_eq!(res.user_keep_days, Some(90));
+        assert_eq!(res.sensors.len(), 1);
+        assert_eq!(res.sensors[0].device_id.as_str(), sensor.device_id);
+        assert_eq!(res.sensors[0].keep_days, None);
+        assert_eq!(res.sensors[0].effective_keep_days, Some(90));
+    }
+
+    #[tokio::test]
+    async fn test_retention_put_foreign_sensor() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other_user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &other_user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let conn = DbConnHolder(conn);
+
+        let claims = Claims::new(user.username);
+        let body = PutRetention {
+            target: RetentionTarget::Sensor(DeviceId::from_string(&sensor.device_id).unwrap()),
+            keep_days: Some(1),
+        };
+        let res = Retention::retention_put(claims, conn, Json(body)).await;
+        assert_eq!(res.err(), Some(hyper::StatusCode::NOT_FOUND));
+    }
+}
@@ -1,10 +1,11 @@
 use axum::{extract::Query, routing::MethodRouter};
 use axum_serde_valid::Json;
+use chrono::{DateTime, Utc};
 use common::{
     endpoints_io::{
         sensor::{
-            ApiUserSensor, DeleteSensor, GetSensor, GetSensorEnum, GetSensorResponse, PostSensor,
-            PutSensor, SensorChange,
+            ApiCalibration, ApiUserSensor, DeleteSensor, GetSensor, GetSensorEnum,
+            GetSensorResponse, PostSensor, PutCalibration, PutSensor, SensorChange, SensorStatus,
         },
         sensor_data::ApiSensorData,
     },
@@ -21,11 +22,13 @@ use crate::{
     auth::claims::Claims,
     db::model::NewUserSensor,
     db::{
-        self, DbConnHolder, Error,
+        self, DbConn, DbConnHolder, Error,
+        calibrations::{calibrate, get_calibrations},
         user_places::get_user_place,
         user_sensors::{AuthorizedSensor, Identifier, Update, update_user_sensor},
         users,
     },
+    mail::{notify_user, templates::Notification},
 };
 
 // impl ApiUserSensor {
@@ -75,6 +78,16 @@ impl Sensor {
     ) -> Result<Json<ApiUserSensor>, StatusCode> {
         let conn = &mut conn.0;
 
+        if let SensorChange::Calibration(PutCalibration {
+            effective_from: Some(timestamp),
+            ..
+        }) = &change
+            && DateTime::from_timestamp(*timestamp as i64, 0).is_none()
+        {
+            log::warn!("Invalid calibration effective_from: {timestamp}");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
         let auth_sensor = AuthorizedSensor::from_username(conn, &device_id, &claims.username)?;
         let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
         let sensor = update_user_sensor(conn, auth_sensor, change.clone() as Update, user_id)?;
@@ -110,9 +123,22 @@ impl Sensor {
             updated_at: sensor.updated_at.and_utc().timestamp() as ApiTimestamp,
             place_name: place_name.into(),
             pub_key: sensor.pub_key.into(),
+            clock_skew_secs: sensor.clock_skew_secs,
+            last_seen_at: sensor
+                .last_seen_at
+                .map(|at| at.and_utc().timestamp() as ApiTimestamp),
+            expected_interval_secs: sensor.expected_interval_secs as u32,
+            calibrations: Self::api_calibrations(conn, sensor.id)?,
         }))
     }
 
+    fn api_calibrations(conn: &mut DbConn, sensor_id: i32) -> Result<Vec<ApiCalibration>, Error> {
+        get_calibrations(conn, &[sensor_id])?
+            .into_iter()
+            .map(ApiCalibration::try_from)
+            .collect()
+    }
+
     async fn sensor_get(
         claims: Claims,
         mut conn: DbConnHolder,
@@ -135,7 +161,8 @@ impl Sensor {
             }
         };
 
-        let vec = match db::user_sensors::get_user_sensor_and_place_and_last_data(&mut conn.0, id) {
+        let now = Utc::now().timestamp() as ApiTimestamp;
+        let vec = match db::user_sensors::get_user_sensor_and_place_and_last_data(&mut conn.0, id.into()) {
             Ok(vec) => {
                 let vec: Result<Vec<GetSensorResponse>, db::Error> = vec
                     .into_iter()
@@ -155,14 +182,24 @@ impl Sensor {
                                 .expect("Should be valid"),
                             place_name: place.name.into(),
                             pub_key: sensor.pub_key.into(),
+                            clock_skew_secs: sensor.clock_skew_secs,
+                            last_seen_at: sensor
+                                .last_seen_at
+                                .map(|at| at.and_utc().timestamp() as ApiTimestamp),
+                            expected_interval_secs: sensor.expected_interval_secs as u32,
+                            calibrations: Self::api_calibrations(&mut conn.0, sensor.id)?,
                         };
 
-                        let data = data.map(|d| ApiSensorData {
-                            data: d.data.to_string(),
-                            added_at: d.added_at.and_utc().timestamp() as ApiTimestamp,
-                        });
+                        let mut data = data;
+                        calibrate(&mut conn.0, data.as_mut_slice())?;
+                        let data = data.map(ApiSensorData::try_from).transpose()?;
 
                         Ok(GetSensorResponse {
+                            status: SensorStatus::from_last_seen(
+                                aus.last_seen_at,
+                                aus.expected_interval_secs,
+                                now,
+                            ),
                             sensor: aus,
                             last_data: data,
                         })
@@ -187,11 +224,11 @@ impl Sensor {
     ) -> Result<Json<ApiUserSensor>, StatusCode> {
         log::trace!("sensor_post: {payload:?}");
 
-        let user_id = db::users::get_user(
+        let user = db::users::get_user(
             &mut conn.0,
             db::users::Identifier::Username(&claims.username),
-        )?
-        .id;
+        )?;
+        let user_id = user.id;
 
         let color_id = db::colors::get_color_id(
             &mut conn.0,
@@ -229,6 +266,15 @@ impl Sensor {
 
         let res = db::user_sensors::insert_user_sensor(&mut conn.0, sensor)?;
 
+        let notification = Notification::SensorRegistered {
+            sensor_name: res.name.clone(),
+            device_id: res.device_id.clone(),
+            place_name: payload.place_name.as_str().to_string(),
+        };
+        if let Err(e) = notify_user(&mut conn.0, &user, &notification) {
+            log::error!("Could not notify user {user_id} of the new sensor: {e:?}");
+        }
+
         let res = ApiUserSensor {
             name: res.name.into(),
             description: res.description.map(|d| d.into()),
@@ -241,6 +287,12 @@ impl Sensor {
             })?,
             place_name: payload.place_name,
             pub_key: payload.pub_key,
+            clock_skew_secs: res.clock_skew_secs,
+            last_seen_at: res
+                .last_seen_at
+                .map(|at| at.and_utc().timestamp() as ApiTimestamp),
+            expected_interval_secs: res.expected_interval_secs as u32,
+            calibrations: vec![],
         };
 
         log::trace!("Sensor created correctly: {res:?}");
@@ -291,6 +343,13 @@ impl Sensor {
                                 .expect("Should be valid ApiId"),
                             place_name: up.name.into(),
                             pub_key: us.pub_key.into(),
+                            clock_skew_secs: us.clock_skew_secs,
+                            last_seen_at: us
+                                .last_seen_at
+                                .map(|at| at.and_utc().timestamp() as ApiTimestamp),
+                            expected_interval_secs: us.expected_interval_secs as u32,
+                            // Deleted along with the sensor
+                            calibrations: vec![],
                         };
                         Ok(aus)
                     })
@@ -324,15 +383,29 @@ mod tests {
 
     use axum::extract::Query;
     use axum_serde_valid::Json;
-    use common::types::validate::{api_pub_key::ApiPubKey, device_id::DeviceId};
+    use chrono::{TimeDelta, Utc};
+    use common::{
+        endpoints_io::{
+            sensor::{PutCalibration, PutSensor, SensorChange, SensorStatus},
+            sensor_data::{Metric, SensorReading},
+        },
+        types::validate::{api_pub_key::ApiPubKey, device_id::DeviceId},
+    };
+    use hyper::StatusCode;
 
     use crate::{
         api::endpoints::sensor::{DeleteSensor, GetSensor, GetSensorEnum, PostSensor, Sensor},
         auth::claims::{Claims, get_new_id},
         db::{
             DbConnHolder, establish_connection,
-            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+            sensor_data::insert_sensor_data,
+            tests::{
+                create_test_user, create_test_user_place, create_test_user_sensor,
+                new_test_sensor_data,
+            },
+            user_sensors::{AuthorizedSensor, set_last_seen, update_user_sensor},
         },
+        mail::tests::wait_sent_to,
     };
 
     #[tokio::test]
@@ -341,6 +414,7 @@ mod tests {
         let (user, _) = create_test_user(&mut conn);
         let user_place = create_test_user_place(&mut conn, &user);
         let user_sensor = create_test_user_sensor(&mut conn, &user_place);
+        set_last_seen(&mut conn, user_sensor.id, chrono::Utc::now().naive_utc()).unwrap();
 
         let body = GetSensorEnum::FromSensorDeviceId(
             DeviceId::from_string(&user_sensor.device_id).expect("Valid"),
@@ -366,6 +440,8 @@ mod tests {
             res_body.first().unwrap().sensor.device_id,
             DeviceId::from_string(&user_sensor.device_id).expect("ApiId valid")
         );
+        assert_eq!(res_body[0].status, SensorStatus::Online);
+        assert!(res_body[0].sensor.last_seen_at.is_some());
     }
 
     #[tokio::test]
@@ -432,6 +508,13 @@ mod tests {
         assert_eq!(res_body.description, payload.description.map(|d| d.into()));
         assert_eq!(res_body.color, payload.color.into());
         assert_eq!(res_body.device_id, payload.device_id);
+
+        let sent = wait_sent_to(&user.email, 1).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            sent[0].subject,
+            "New sensor registered: My New Awesome Sensor"
+        );
     }
 
     #[tokio::test]
@@ -470,4 +553,83 @@ mod tests {
             sensor_to_delete_device_id
         );
     }
+
+    #[tokio::test]
+    async fn test_calibration() {
+        let calibration = |effective_from| PutCalibration {
+            metric: Metric::Temperature,
+            offset: -1.5,
+            scale: None,
+            effective_from,
+        };
+
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let user_sensor = create_test_user_sensor(&mut conn, &user_place);
+        let device_id = DeviceId::from_string(&user_sensor.device_id).unwrap();
+
+        let res = Sensor::sensor_put(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Json(PutSensor {
+                device_id: device_id.clone(),
+                change: SensorChange::Calibration(calibration(Some(i64::MAX as usize))),
+            }),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let user_sensor = create_test_user_sensor(&mut conn, &user_place);
+        let device_id = DeviceId::from_string(&user_sensor.device_id).unwrap();
+
+        let now = Utc::now().naive_utc();
+        let auth_sensor =
+            AuthorizedSensor::from_username(&mut conn, &device_id, &user.username).unwrap();
+        let effective_from = (now - TimeDelta::hours(1)).and_utc().timestamp() as usize;
+        update_user_sensor(
+            &mut conn,
+            auth_sensor,
+            SensorChange::Calibration(calibration(Some(effective_from))),
+            user.id,
+        )
+        .unwrap();
+
+        let reading = SensorReading {
+            co2: Some(450),
+            temperature: Some(22.0),
+            humidity: Some(40.0),
+        };
+        insert_sensor_data(
+            &mut conn,
+            new_test_sensor_data(user_sensor.id, now, reading.clone()),
+        )
+        .unwrap();
+
+        let res = Sensor::sensor_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(GetSensor {
+                param: GetSensorEnum::FromSensorDeviceId(device_id),
+            }),
+        )
+        .await
+        .expect("Should not fail");
+
+        let calibrations = &res[0].sensor.calibrations;
+        assert_eq!(calibrations.len(), 1);
+        assert_eq!(calibrations[0].scale, 1.0);
+        assert_eq!(calibrations[0].effective_from, effective_from);
+
+        let last_data = res[0].last_data.as_ref().expect("Should have data");
+        assert_eq!(last_data.data.temperature, Some(20.5));
+        assert_eq!(last_data.data.co2, reading.co2);
+        assert_eq!(
+            last_data.derived.dew_point,
+            last_data.data.metric(Metric::DewPoint)
+        );
+    }
 }
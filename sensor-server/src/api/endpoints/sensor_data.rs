@@ -1,51 +1,252 @@
+use std::{collections::BTreeMap, ops::Range};
+
 use axum::{extract::Query, routing::MethodRouter};
 use axum_extra::extract::CookieJar;
 use axum_serde_valid::Json;
 use chrono::{NaiveDateTime, TimeDelta, Utc};
 use common::{
-    endpoints_io::sensor_data::{
-        ApiSensorData, GetSensorData, PostSensorData, PostSensorDataResponse,
+    endpoints_io::{
+        annotation::ApiAnnotation,
+        sensor_data::{
+            ApiSensorData, ApiSensorDataBucket, ApiSensorDataBuckets, ApiSensorDataComparison,
+            ApiSensorDataEvent, ApiSensorDataPage, BatchItemResult, BucketSize, ComparisonRow,
+            DeleteSensorData, DeleteSensorDataResponse, GetSensorData, GetSensorDataComparison,
+            GetSensorDataResponse, GetSuspectSensorData, Metric, MetricAggregate, PostSensorData,
+            PostSensorDataBatch, PostSensorDataBatchResponse, PostSensorDataResponse,
+            SensorDataSelection,
+        },
+        session::ApiSession,
+        webhook::WebhookEvent,
     },
     types::{ApiTimestamp, validate::device_id::DeviceId},
 };
 use hyper::StatusCode;
-use serde_valid::json::json;
+use serde_valid::{Validate, json::json};
 
 use crate::{
     RoutePath,
-    api::{Endpoint, endpoints::session::ServerApiSession, route::Route},
+    alerts::{self, Transition},
+    anomalies,
+    api::{
+        Endpoint, capped_limit,
+        endpoints::{annotation::Annotations, session::ServerApiSession},
+        route::Route,
+    },
     auth::{claims::Claims, sensor_claims::SensorClaims},
+    clock_skew::{CLOCK_SKEW_POLICY, batch_clock_skew_secs, clock_skew_secs},
     db::{
-        DbConnHolder,
-        model::NewSensorData,
-        sensor_data::{Identifier, get_sensor_data, insert_sensor_data},
-        user_sensors::AuthorizedSensor,
+        self, DbConn, DbConnHolder, annotations,
+        calibrations::calibrate,
+        model::{NewSensorData, SensorData as SensorDataModel, SensorDataBucket, UserSensor},
+        sensor_data::{
+            Identifier, Selection, SensorDataCursor, delete_sensor_data, get_sensor_data,
+            get_sensor_data_buckets, get_sensor_data_by_idempotency_key, get_suspect_sensor_data,
+            insert_sensor_data_batch, insert_sensor_data_once,
+        },
+        user_sensors::{AuthorizedSensor, set_clock_skew, set_last_seen},
+        users,
+    },
+    mail::{notify_place_owner, templates::Notification},
+    state::{
+        poisonable_identifier::PoisonableIdentifier,
+        sensor_data_hub::{self, SensorDataEvent},
+        server_metrics::{self, IngestionSource},
     },
-    state::poisonable_identifier::PoisonableIdentifier,
+    webhooks,
 };
 
 pub struct SensorData {
     resources: Vec<Route>,
 }
 
-enum RangeDelimiter {
+pub enum RangeDelimiter {
     Top,
     Bottom,
 }
 
 impl SensorData {
     pub const API_PATH: &str = "/sensor_data";
+    pub const BATCH_API_PATH: &str = "/sensor_data/batch";
+    pub const SUSPECT_API_PATH: &str = "/sensor_data/suspect";
+    pub const COMPARE_API_PATH: &str = "/sensor_data/compare";
     pub fn new() -> SensorData {
         let mr = MethodRouter::new()
             .get(Self::sensor_data_get)
-            .post(Self::sensor_data_post);
+            .post(Self::sensor_data_post)
+            .delete(Self::sensor_data_delete);
+
+        let batch_mr = MethodRouter::new().post(Self::sensor_data_batch_post);
+        let suspect_mr = MethodRouter::new().get(Self::sensor_data_suspect_get);
+        let compare_mr = MethodRouter::new().get(Self::sensor_data_compare_get);
 
         Self {
-            resources: vec![Route::new(
-                RoutePath::from_string(Self::API_PATH.to_string())
-                    .expect("The route should be correct"),
-                mr,
-            )],
+            resources: vec![
+                Route::new(
+                    RoutePath::from_string(Self::API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    mr,
+                ),
+                Route::new(
+                    RoutePath::from_string(Self::BATCH_API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    batch_mr,
+                ),
+                Route::new(
+                    RoutePath::from_string(Self::SUSPECT_API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    suspect_mr,
+                ),
+                Route::new(
+                    RoutePath::from_string(Self::COMPARE_API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    compare_mr,
+                ),
+            ],
+        }
+    }
+
+    /// Poisons the JWT used by the sensor on this request and generates a new session for it
+    fn rotate_sensor_session(
+        jar: CookieJar,
+        claims: &SensorClaims,
+    ) -> Result<(CookieJar, ApiSession), StatusCode> {
+        let jwt_id_hex = claims.jwt_id_hex(); // ID to Poison
+        let device_id = DeviceId::from_string(&claims.device_id).map_err(|e| {
+            log::error!("Could not construct DeviceID from claims.device_id: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        let claims = SensorClaims::new(device_id);
+
+        let new_session = ServerApiSession::from_sensor_claims(claims).map_err(|e| {
+            log::error!("Error generating new session from_claims: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        // Poison used JWT
+        PoisonableIdentifier::SensorJWTId(jwt_id_hex).poison()?;
+
+        Ok((jar.add(new_session.build_cookie()), new_session.into()))
+    }
+
+    /// Records the sensor as seen, pushes just stored readings to the live stream subscribers and
+    /// the webhooks, and evaluates the alert rules of the sensor on them, oldest first. Failing to
+    /// do any of it doesn't fail the request as the readings are already stored
+    fn handle_stored_readings(
+        conn: &mut DbConn,
+        sensor: &UserSensor,
+        mut readings: Vec<(NaiveDateTime, ApiSensorData)>,
+    ) {
+        let device_id = match DeviceId::from_string(&sensor.device_id) {
+            Ok(device_id) => device_id,
+            Err(e) => {
+                log::error!("Could not construct DeviceId: {e:?}");
+                return;
+            }
+        };
+        readings.sort_by_key(|(measured_at, _)| *measured_at);
+
+        let now = Utc::now().naive_utc();
+        if let Err(e) = set_last_seen(conn, sensor.id, now) {
+            log::error!(
+                "Error setting last_seen_at of sensor {}: {e:?}",
+                sensor.device_id
+            );
+        }
+
+        let mut events = vec![];
+        for (_, api_data) in &readings {
+            sensor_data_hub::publish(SensorDataEvent {
+                sensor_id: sensor.id,
+                place_id: sensor.place_id,
+                device_id: device_id.clone(),
+                api_data: api_data.clone(),
+            });
+            events.push(WebhookEvent::SensorData(ApiSensorDataEvent {
+                device_id: device_id.clone(),
+                api_data: api_data.clone(),
+            }));
+        }
+
+        for (measured_at, api_data) in &readings {
+            let data = api_data.data.without(&api_data.suspect_metrics);
+            let changes = match alerts::evaluate_reading(conn, sensor, &data, *measured_at) {
+                Ok(changes) => changes,
+                Err(e) => {
+                    log::error!(
+                        "Error evaluating alert rules of sensor {}: {e:?}",
+                        sensor.device_id
+                    );
+                    break;
+                }
+            };
+
+            for change in changes {
+                let api_event =
+                    match alerts::api_alert_event(change.event, &change.rule, &sensor.device_id) {
+                        Ok(api_event) => api_event,
+                        Err(e) => {
+                            log::error!("Could not construct ApiAlertEvent: {e:?}");
+                            continue;
+                        }
+                    };
+                let sensor_name = sensor.name.clone();
+                let notification = match change.transition {
+                    Transition::Triggered => {
+                        events.push(WebhookEvent::AlertTriggered(api_event.clone()));
+                        Notification::AlertTriggered {
+                            sensor_name,
+                            event: api_event,
+                        }
+                    }
+                    Transition::Resolved => {
+                        events.push(WebhookEvent::AlertResolved(api_event.clone()));
+                        Notification::AlertResolved {
+                            sensor_name,
+                            event: api_event,
+                        }
+                    }
+                };
+                if let Err(e) = notify_place_owner(conn, sensor.place_id, &notification) {
+                    log::error!(
+                        "Error notifying the alert of sensor {}: {e:?}",
+                        sensor.device_id
+                    );
+                }
+            }
+        }
+
+        if let Err(e) = webhooks::queue_events(conn, sensor.place_id, &events, now) {
+            log::error!(
+                "Error queueing webhooks of sensor {}: {e:?}",
+                sensor.device_id
+            );
+        }
+    }
+
+    /// The reading as stored by the first attempt, given back to a retry of it
+    fn retried_reading(
+        conn: &mut DbConn,
+        sensor: &UserSensor,
+        mut stored: SensorDataModel,
+    ) -> Result<ApiSensorData, StatusCode> {
+        log::info!(
+            "Sensor {} retried an already stored reading ({:?})",
+            sensor.device_id,
+            stored.idempotency_key
+        );
+        calibrate(conn, std::slice::from_mut(&mut stored))?;
+        Ok(ApiSensorData::try_from(stored)?)
+    }
+
+    /// Flags the reading if it's an anomaly. Only logs on failure, the reading is already stored
+    fn flag_suspect(conn: &mut DbConn, data: &mut SensorDataModel) {
+        if let Err(e) = anomalies::flag_sensor_data(conn, data) {
+            log::error!(
+                "Error checking reading {} of sensor {} for anomalies: {e:?}",
+                data.id,
+                data.sensor_id
+            );
         }
     }
 
@@ -53,7 +254,7 @@ impl SensorData {
     /// - if true, will set returned timestamp to at most the reference_utc for max
     /// - if false, will set returned timestamp to at least reference_utc for !max
     /// Also, if timestamp is None, it will return the reference_utc
-    fn convert_opt_timestamp_into_naive(
+    pub fn convert_opt_timestamp_into_naive(
         timestamp: Option<ApiTimestamp>,
         max: RangeDelimiter,
     ) -> Result<NaiveDateTime, StatusCode> {
@@ -101,18 +302,26 @@ impl SensorData {
         claims: Claims,
         mut conn: DbConnHolder,
         Query(payload): Query<GetSensorData>,
-    ) -> Result<Json<Vec<ApiSensorData>>, StatusCode> {
+    ) -> Result<Json<GetSensorDataResponse>, StatusCode> {
         let conn = &mut conn.0;
         let sensor = AuthorizedSensor::from_username(conn, &payload.device_id, &claims.username)?;
 
         log::trace!("Getting data for sensor: {sensor:?}");
 
-        let low = Self::convert_opt_timestamp_into_naive(
-            payload.lowest_added_at,
-            RangeDelimiter::Bottom,
-        )?;
         let up =
             Self::convert_opt_timestamp_into_naive(payload.upper_added_at, RangeDelimiter::Top)?;
+        let low = match payload.bucket {
+            Some(bucket_size) => Self::aggregated_range_start(
+                payload.lowest_added_at,
+                up,
+                bucket_size,
+                GetSensorData::MAX_BUCKETS,
+            )?,
+            None => Self::convert_opt_timestamp_into_naive(
+                payload.lowest_added_at,
+                RangeDelimiter::Bottom,
+            )?,
+        };
 
         let range = low..up;
 
@@ -120,20 +329,380 @@ impl SensorData {
 
         let sensor = sensor.get();
 
-        let sensor_data: Vec<ApiSensorData> =
-            get_sensor_data(conn, Identifier::SensorId(sensor.id), low..up)?
-                .into_iter()
-                .map(|d| ApiSensorData {
-                    data: d.data.to_string(),
-                    added_at: d.added_at.and_utc().timestamp() as ApiTimestamp,
+        if let Some(bucket_size) = payload.bucket {
+            if Self::buckets_in(&range, bucket_size) > GetSensorData::MAX_BUCKETS as i64 {
+                log::warn!("Range {range:?} has too many buckets of {bucket_size:?}");
+                return Err(StatusCode::BAD_REQUEST);
+            }
+
+            let rows = get_sensor_data_buckets(
+                conn,
+                Identifier::SensorId(sensor.id),
+                range.clone(),
+                bucket_size,
+                payload.exclude_suspect.unwrap_or(false),
+            )?;
+            let buckets = Self::rows_into_api_buckets(rows);
+            let annotations = Self::range_annotations(
+                conn,
+                &claims.username,
+                std::slice::from_ref(&sensor),
+                range.clone(),
+            )?;
+
+            log::trace!("Returning {} buckets of {bucket_size:?}", buckets.len());
+
+            return Ok(Json(GetSensorDataResponse::Aggregated(
+                ApiSensorDataBuckets {
+                    buckets,
+                    annotations,
+                },
+            )));
+        }
+
+        let cursor = payload
+            .cursor
+            .as_deref()
+            .map(|c| {
+                SensorDataCursor::decode(c).ok_or_else(|| {
+                    log::warn!("Invalid cursor received: {c}");
+                    StatusCode::BAD_REQUEST
                 })
-                .collect();
+            })
+            .transpose()?;
+        let limit = capped_limit(payload.limit, GetSensorData::MAX_LIMIT)?;
+        let annotations = match cursor {
+            Some(_) => vec![],
+            None => Self::range_annotations(
+                conn,
+                &claims.username,
+                std::slice::from_ref(&sensor),
+                range.clone(),
+            )?,
+        };
+
+        let (mut sensor_data, next_cursor) = get_sensor_data(
+            conn,
+            Identifier::SensorId(sensor.id),
+            range,
+            payload.order.unwrap_or_default(),
+            limit,
+            cursor,
+        )?;
+        calibrate(conn, &mut sensor_data)?;
+        let sensor_data = sensor_data
+            .into_iter()
+            .map(ApiSensorData::try_from)
+            .collect::<Result<Vec<ApiSensorData>, _>>()?;
 
         log::trace!("Returning {} datums", sensor_data.len());
 
+        Ok(Json(GetSensorDataResponse::Raw(ApiSensorDataPage {
+            data: sensor_data,
+            next_cursor: next_cursor.map(|c| c.encode()),
+            annotations,
+        })))
+    }
+
+    pub async fn sensor_data_suspect_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetSuspectSensorData>,
+    ) -> Result<Json<Vec<ApiSensorData>>, StatusCode> {
+        let conn = &mut conn.0;
+        let sensor =
+            AuthorizedSensor::from_username(conn, &payload.device_id, &claims.username)?.get();
+
+        let low = Self::convert_opt_timestamp_into_naive(
+            payload.lowest_added_at,
+            RangeDelimiter::Bottom,
+        )?;
+        let up =
+            Self::convert_opt_timestamp_into_naive(payload.upper_added_at, RangeDelimiter::Top)?;
+        let limit = capped_limit(payload.limit, GetSensorData::MAX_LIMIT)?;
+
+        let mut sensor_data =
+            get_suspect_sensor_data(conn, Identifier::SensorId(sensor.id), low..up, limit)?;
+        calibrate(conn, &mut sensor_data)?;
+        let sensor_data = sensor_data
+            .into_iter()
+            .map(ApiSensorData::try_from)
+            .collect::<Result<Vec<ApiSensorData>, _>>()?;
+
+        log::trace!(
+            "Returning {} suspect datums of sensor {}",
+            sensor_data.len(),
+            sensor.device_id
+        );
+
         Ok(Json(sensor_data))
     }
 
+    pub async fn sensor_data_delete(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<DeleteSensorData>,
+    ) -> Result<Json<DeleteSensorDataResponse>, StatusCode> {
+        let conn = &mut conn.0;
+        let sensor =
+            AuthorizedSensor::from_username(conn, &payload.device_id, &claims.username)?.get();
+        let dry_run = payload.dry_run.unwrap_or(false);
+
+        let ids: Vec<i64>;
+        let selection = match payload.selection {
+            SensorDataSelection::Range {
+                lowest_added_at,
+                upper_added_at,
+            } => Selection::Range(
+                Self::convert_opt_timestamp_into_naive(
+                    Some(lowest_added_at),
+                    RangeDelimiter::Bottom,
+                )?
+                    ..Self::convert_opt_timestamp_into_naive(
+                        Some(upper_added_at),
+                        RangeDelimiter::Top,
+                    )?,
+            ),
+            SensorDataSelection::Ids(selected) => {
+                ids = selected.into_iter().map(|id| id as i64).collect();
+                Selection::Ids(&ids)
+            }
+        };
+
+        log::trace!(
+            "Deleting data of sensor {} (dry run: {dry_run}): {selection:?}",
+            sensor.device_id
+        );
+
+        let deleted =
+            delete_sensor_data(conn, Identifier::SensorId(sensor.id), selection, dry_run)?;
+
+        log::info!(
+            "{} {} data of sensor {}",
+            if dry_run { "Would delete" } else { "Deleted" },
+            deleted.count,
+            sensor.device_id
+        );
+
+        Ok(Json(DeleteSensorDataResponse {
+            dry_run,
+            deleted: deleted.count,
+            oldest_added_at: deleted
+                .oldest
+                .map(|at| at.and_utc().timestamp() as ApiTimestamp),
+            newest_added_at: deleted
+                .newest
+                .map(|at| at.and_utc().timestamp() as ApiTimestamp),
+        }))
+    }
+
+    /// One row per bucket from the first to the last one with data of any of the columns
+    fn align_columns(
+        columns: &[BTreeMap<NaiveDateTime, MetricAggregate>],
+        bucket_size: BucketSize,
+    ) -> Vec<ComparisonRow> {
+        let first = columns.iter().filter_map(|c| c.keys().next()).min();
+        let last = columns.iter().filter_map(|c| c.keys().next_back()).max();
+        let (Some(&first), Some(&last)) = (first, last) else {
+            return vec![];
+        };
+
+        let step = Self::bucket_step(bucket_size);
+        let buckets = (last - first).num_seconds() / step.num_seconds() + 1;
+
+        (0..buckets as i32)
+            .map(|i| {
+                let bucket_start = first + step * i;
+                ComparisonRow {
+                    bucket_start: bucket_start.and_utc().timestamp() as ApiTimestamp,
+                    values: columns
+                        .iter()
+                        .map(|c| c.get(&bucket_start).cloned())
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    /// Annotations of the user overlapping range that are about any of the sensors, their places
+    /// or nothing in particular, sorted by starts_at
+    fn range_annotations(
+        conn: &mut DbConn,
+        username: &str,
+        sensors: &[UserSensor],
+        range: Range<NaiveDateTime>,
+    ) -> Result<Vec<ApiAnnotation>, db::Error> {
+        let user_id = users::get_user(conn, users::Identifier::Username(username))?.id;
+
+        let mut annotations: Vec<ApiAnnotation> = vec![];
+        for sensor in sensors {
+            let identifier = annotations::Identifier::Sensor {
+                user_id,
+                sensor_id: sensor.id,
+                place_id: sensor.place_id,
+            };
+            for annotation in Annotations::range_annotations(conn, identifier, range.clone())? {
+                if annotations.iter().all(|a| a.id != annotation.id) {
+                    annotations.push(annotation);
+                }
+            }
+        }
+        annotations.sort_by_key(|a| (a.starts_at, a.id));
+
+        Ok(annotations)
+    }
+
+    /// Buckets are aligned by date_trunc, so whole steps go from one to the next
+    pub fn bucket_step(bucket_size: BucketSize) -> TimeDelta {
+        match bucket_size {
+            BucketSize::Minute => TimeDelta::minutes(1),
+            BucketSize::Hour => TimeDelta::hours(1),
+            BucketSize::Day => TimeDelta::days(1),
+            BucketSize::Week => TimeDelta::weeks(1),
+        }
+    }
+
+    /// Most buckets of bucket_size the range can be split in, known before querying it
+    pub fn buckets_in(range: &Range<NaiveDateTime>, bucket_size: BucketSize) -> i64 {
+        (range.end - range.start).num_seconds() / Self::bucket_step(bucket_size).num_seconds() + 1
+    }
+
+    /// Start of an aggregated range ending at up. If lowest_added_at isn't given it's as far back
+    /// as max_buckets of bucket_size reach instead of the epoch, so the default range isn't
+    /// rejected for having too many buckets
+    pub fn aggregated_range_start(
+        lowest_added_at: Option<ApiTimestamp>,
+        up: NaiveDateTime,
+        bucket_size: BucketSize,
+        max_buckets: usize,
+    ) -> Result<NaiveDateTime, StatusCode> {
+        let low = Self::convert_opt_timestamp_into_naive(lowest_added_at, RangeDelimiter::Bottom)?;
+        if lowest_added_at.is_some() {
+            return Ok(low);
+        }
+        let reach = Self::bucket_step(bucket_size) * (max_buckets as i32 - 1);
+        Ok(low.max(up - reach))
+    }
+
+    pub async fn sensor_data_compare_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetSensorDataComparison>,
+    ) -> Result<Json<ApiSensorDataComparison>, StatusCode> {
+        let conn = &mut conn.0;
+
+        let device_ids = payload.device_ids().map_err(|id| {
+            log::warn!("Invalid device_id to compare: {id}");
+            StatusCode::BAD_REQUEST
+        })?;
+        if device_ids.len() > GetSensorDataComparison::MAX_SENSORS {
+            log::warn!("Too many sensors to compare: {}", device_ids.len());
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let sensors = device_ids
+            .iter()
+            .map(|id| AuthorizedSensor::from_username(conn, id, &claims.username).map(|s| s.get()))
+            .collect::<Result<Vec<UserSensor>, db::Error>>()?;
+
+        let up =
+            Self::convert_opt_timestamp_into_naive(payload.upper_added_at, RangeDelimiter::Top)?;
+        let low = Self::aggregated_range_start(
+            payload.lowest_added_at,
+            up,
+            payload.bucket,
+            GetSensorDataComparison::MAX_ROWS,
+        )?;
+        if Self::buckets_in(&(low..up), payload.bucket) > GetSensorDataComparison::MAX_ROWS as i64 {
+            log::warn!(
+                "Comparison from {low} to {up} in {:?} buckets has too many rows",
+                payload.bucket
+            );
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let metric = payload.metric.key();
+        let mut columns = vec![];
+        for sensor in &sensors {
+            let column: BTreeMap<NaiveDateTime, MetricAggregate> = get_sensor_data_buckets(
+                conn,
+                Identifier::SensorId(sensor.id),
+                low..up,
+                payload.bucket,
+                payload.exclude_suspect.unwrap_or(false),
+            )?
+            .into_iter()
+            .filter(|row| row.metric == metric)
+            .map(|row| {
+                (
+                    row.bucket_start,
+                    MetricAggregate {
+                        min: row.min,
+                        max: row.max,
+                        avg: row.avg,
+                        count: row.count as usize,
+                    },
+                )
+            })
+            .collect();
+            columns.push(column);
+        }
+
+        let rows = Self::align_columns(&columns, payload.bucket);
+        let annotations = Self::range_annotations(conn, &claims.username, &sensors, low..up)?;
+
+        log::trace!(
+            "Returning comparison of {} sensors in {} rows",
+            sensors.len(),
+            rows.len()
+        );
+
+        Ok(Json(ApiSensorDataComparison {
+            device_ids,
+            metric: payload.metric,
+            bucket: payload.bucket,
+            rows,
+            annotations,
+        }))
+    }
+
+    /// Groups the per metric rows (expected ordered by bucket_start) into one ApiSensorDataBucket
+    /// per bucket
+    fn rows_into_api_buckets(rows: Vec<SensorDataBucket>) -> Vec<ApiSensorDataBucket> {
+        let mut buckets: Vec<ApiSensorDataBucket> = vec![];
+
+        for row in rows {
+            let Some(metric) = Metric::from_key(&row.metric) else {
+                log::warn!(
+                    "Unknown metric found aggregating sensor data: {}",
+                    row.metric
+                );
+                continue;
+            };
+
+            let bucket_start = row.bucket_start.and_utc().timestamp() as ApiTimestamp;
+            if buckets
+                .last()
+                .is_none_or(|b| b.bucket_start != bucket_start)
+            {
+                buckets.push(ApiSensorDataBucket {
+                    bucket_start,
+                    ..Default::default()
+                });
+            }
+
+            if let Some(bucket) = buckets.last_mut() {
+                *bucket.metric_mut(metric) = Some(MetricAggregate {
+                    min: row.min,
+                    max: row.max,
+                    avg: row.avg,
+                    count: row.count as usize,
+                });
+            }
+        }
+
+        buckets
+    }
+
     pub async fn sensor_data_post(
         jar: CookieJar,
         claims: SensorClaims,
@@ -144,52 +713,200 @@ impl SensorData {
 
         let sensor = AuthorizedSensor::from_sensor_claims(conn, &claims)?;
 
-        log::trace!(
-            "Adding data to sensor {sensor:?}, data: {:?}",
-            payload.serialized_data
-        );
+        log::trace!("Adding data to sensor {sensor:?}, data: {:?}", payload.data);
 
-        let added_at = payload
+        let received_at = Utc::now().naive_utc();
+        let reported_at = payload
             .created_at
-            .and_then(|timestamp| chrono::DateTime::from_timestamp(timestamp as i64, 0))
-            .and_then(|date| Some(date.naive_utc()));
+            .map(|timestamp| {
+                chrono::DateTime::from_timestamp(timestamp as i64, 0)
+                    .map(|date| date.naive_utc())
+                    .ok_or_else(|| {
+                        log::warn!("Invalid created_at: {timestamp}");
+                        StatusCode::BAD_REQUEST
+                    })
+            })
+            .transpose()?;
 
         let sensor = sensor.get();
 
-        let new_data = NewSensorData {
-            sensor_id: sensor.id,
-            data: json!(payload.serialized_data),
-            added_at,
+        // A retry of an already stored reading gets the stored one back
+        let stored = match &payload.idempotency_key {
+            Some(key) => {
+                get_sensor_data_by_idempotency_key(conn, Identifier::SensorId(sensor.id), key)?
+            }
+            None => None,
         };
 
-        let data = insert_sensor_data(conn, new_data)?;
+        let api_data = match stored {
+            Some(stored) => Self::retried_reading(conn, &sensor, stored)?,
+            None => {
+                let added_at = match reported_at {
+                    Some(reported_at) => {
+                        let skew = clock_skew_secs(reported_at, received_at);
+                        set_clock_skew(conn, sensor.id, skew)?;
 
-        let api_data = ApiSensorData {
-            data: data.data.to_string(),
-            added_at: data.added_at.and_utc().timestamp() as usize,
+                        let added_at = CLOCK_SKEW_POLICY.check(reported_at, received_at).map_err(
+                            |reason| {
+                                log::warn!(
+                                    "Rejected reading for sensor {} (skew {skew}s): {reason}",
+                                    sensor.device_id
+                                );
+                                StatusCode::BAD_REQUEST
+                            },
+                        )?;
+                        Some(added_at)
+                    }
+                    None => None,
+                };
+
+                let new_data = NewSensorData {
+                    sensor_id: sensor.id,
+                    data: json!(payload.data),
+                    added_at,
+                    idempotency_key: payload.idempotency_key.clone(),
+                    reported_at,
+                };
+                match (
+                    insert_sensor_data_once(conn, new_data)?,
+                    &payload.idempotency_key,
+                ) {
+                    (Some(mut stored), _) => {
+                        Self::flag_suspect(conn, &mut stored);
+                        calibrate(conn, std::slice::from_mut(&mut stored))?;
+                        let measured_at = stored.added_at;
+                        let api_data = ApiSensorData::try_from(stored)?;
+                        server_metrics::readings_ingested(IngestionSource::Single, 1);
+                        Self::handle_stored_readings(
+                            conn,
+                            &sensor,
+                            vec![(measured_at, api_data.clone())],
+                        );
+                        api_data
+                    }
+                    // A concurrent retry stored it between the check and the insert
+                    (None, Some(key)) => {
+                        let stored = get_sensor_data_by_idempotency_key(
+                            conn,
+                            Identifier::SensorId(sensor.id),
+                            key,
+                        )?
+                        .ok_or_else(|| db::Error::NotFound("Conflicting reading is gone".into()))?;
+                        Self::retried_reading(conn, &sensor, stored)?
+                    }
+                    (None, None) => {
+                        log::error!("Reading without idempotency_key conflicted on insert");
+                        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                    }
+                }
+            }
         };
 
-        let jwt_id_hex = claims.jwt_id_hex(); // ID to Poison
-        let device_id = DeviceId::from_string(&claims.device_id).map_err(|e| {
-            log::error!("Could not construct DeviceID from claims.device_id: {e:?}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        let (jar, new_session) = Self::rotate_sensor_session(jar, &claims)?;
 
-        let claims = SensorClaims::new(device_id);
+        Ok((
+            jar,
+            Json(PostSensorDataResponse {
+                api_data,
+                new_session,
+            }),
+        ))
+    }
 
-        let new_session = ServerApiSession::from_sensor_claims(claims).map_err(|e| {
-            log::error!("Error generating new session from_claims: {e:?}");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+    /// Stores many readings at once, meant for sensors uploading the backlog they buffered while
+    /// offline. Invalid readings are rejected individually, the session is rotated only once
+    pub async fn sensor_data_batch_post(
+        jar: CookieJar,
+        claims: SensorClaims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PostSensorDataBatch>,
+    ) -> Result<(CookieJar, Json<PostSensorDataBatchResponse>), StatusCode> {
+        let conn = &mut conn.0;
 
-        // Poison used JWT
-        PoisonableIdentifier::SensorJWTId(jwt_id_hex).poison()?;
+        let sensor = AuthorizedSensor::from_sensor_claims(conn, &claims)?.get();
+
+        log::trace!(
+            "Adding batch of {} data to sensor {sensor:?}",
+            payload.readings.len()
+        );
+
+        let received_at = Utc::now().naive_utc();
+
+        let created_ats = payload.readings.iter().map(|reading| reading.created_at);
+        if let Some(skew) = batch_clock_skew_secs(created_ats, received_at) {
+            set_clock_skew(conn, sensor.id, skew)?;
+        }
+
+        // Err contains the reason for rejecting the reading
+        let checked: Vec<Result<NewSensorData, String>> = payload
+            .readings
+            .into_iter()
+            .map(|reading| {
+                reading.data.validate().map_err(|e| e.to_string())?;
+
+                let reported_at = chrono::DateTime::from_timestamp(reading.created_at as i64, 0)
+                    .ok_or_else(|| format!("Invalid created_at: {}", reading.created_at))?
+                    .naive_utc();
+                let added_at = CLOCK_SKEW_POLICY.check(reported_at, received_at)?;
+
+                Ok(NewSensorData {
+                    sensor_id: sensor.id,
+                    data: json!(reading.data),
+                    added_at: Some(added_at),
+                    idempotency_key: None,
+                    reported_at: Some(reported_at),
+                })
+            })
+            .collect();
+
+        let accepted: Vec<NewSensorData> = checked
+            .iter()
+            .filter_map(|c| c.as_ref().ok())
+            .cloned()
+            .collect();
+
+        // Returned in the same order as accepted
+        let mut inserted = insert_sensor_data_batch(conn, accepted)?;
+        // Each is compared against the ones before it
+        let mut by_added_at: Vec<&mut SensorDataModel> = inserted.iter_mut().collect();
+        by_added_at.sort_by_key(|data| (data.added_at, data.id));
+        for data in by_added_at {
+            Self::flag_suspect(conn, data);
+        }
+        calibrate(conn, &mut inserted)?;
+        let mut inserted = inserted.into_iter();
+        let mut stored = vec![];
+
+        let results = checked
+            .into_iter()
+            .map(|c| match c {
+                Ok(_) => {
+                    let data = inserted.next().ok_or_else(|| {
+                        log::error!("insert_sensor_data_batch returned less data than inserted");
+                        db::Error::InternalError("Missing inserted data".into())
+                    })?;
+                    let measured_at = data.added_at;
+                    let api_data = ApiSensorData::try_from(data)?;
+                    stored.push((measured_at, api_data.clone()));
+                    Ok(BatchItemResult::Accepted(api_data))
+                }
+                Err(reason) => {
+                    log::warn!("Rejected reading for sensor {}: {reason}", sensor.device_id);
+                    Ok(BatchItemResult::Rejected { reason })
+                }
+            })
+            .collect::<Result<Vec<BatchItemResult>, db::Error>>()?;
+
+        server_metrics::readings_ingested(IngestionSource::Batch, stored.len());
+        Self::handle_stored_readings(conn, &sensor, stored);
+
+        let (jar, new_session) = Self::rotate_sensor_session(jar, &claims)?;
 
         Ok((
-            jar.add(new_session.build_cookie()),
-            Json(PostSensorDataResponse {
-                api_data,
-                new_session: new_session.into(),
+            jar,
+            Json(PostSensorDataBatchResponse {
+                results,
+                new_session,
             }),
         ))
     }
@@ -209,15 +926,43 @@ mod test {
     use axum::extract::Query;
     use axum_extra::extract::CookieJar;
     use axum_serde_valid::Json;
-    use common::types::validate::device_id::DeviceId;
+    use chrono::{DurationRound, TimeDelta, Utc};
+    use common::{
+        endpoints_io::{
+            alert::AlertComparison,
+            sensor_data::{
+                ApiSensorData, BatchItemResult, BatchSensorReading, BucketSize, DeleteSensorData,
+                GetSensorDataComparison, GetSensorDataResponse, GetSuspectSensorData, Metric,
+                PostSensorDataBatch, SensorDataSelection, SensorReading,
+            },
+            webhook::WebhookEventKind,
+        },
+        types::validate::device_id::DeviceId,
+    };
+    use hyper::StatusCode;
+    use serde_valid::json::json;
 
     use crate::{
+        alerts::comparison_key,
         api::endpoints::sensor_data::{GetSensorData, PostSensorData, SensorData},
         auth::{claims::Claims, sensor_claims::SensorClaims},
         db::{
-            DbConnHolder, establish_connection,
-            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+            DbConn, DbConnHolder,
+            alerts::insert_alert_rule,
+            annotations::insert_annotation,
+            establish_connection,
+            model::{NewAlertRule, NewAnnotation, NewSensorData, NewWebhook},
+            sensor_data::{
+                Identifier, get_sensor_data_buckets, insert_sensor_data, insert_sensor_data_batch,
+                set_suspect_metrics,
+            },
+            tests::{
+                create_test_user, create_test_user_place, create_test_user_sensor,
+                new_test_sensor_data,
+            },
+            webhooks::{get_webhook_deliveries, insert_webhook},
         },
+        mail::tests::wait_sent_to,
     };
 
     #[tokio::test]
@@ -237,6 +982,11 @@ mod test {
             device_id,
             lowest_added_at: None,
             upper_added_at: None,
+            bucket: None,
+            exclude_suspect: None,
+            limit: None,
+            order: None,
+            cursor: None,
         };
 
         let conn = DbConnHolder(conn_uref);
@@ -245,7 +995,182 @@ mod test {
             .await
             .expect("Should not fail");
 
-        assert!(res.len() == 0);
+        match res.0 {
+            GetSensorDataResponse::Raw(page) => {
+                assert!(page.data.is_empty());
+                assert!(page.next_cursor.is_none());
+            }
+            r => panic!("Should be Raw, was: {r:?}"),
+        }
+
+        // Not enforced by Query
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+        let json = GetSensorData {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            bucket: None,
+            exclude_suspect: None,
+            limit: Some(0),
+            order: None,
+            cursor: None,
+        };
+        let res = SensorData::sensor_data_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(json),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_get_sensor_data_aggregated() {
+        let mut conn_uref = establish_connection(true).unwrap();
+        let conn = &mut conn_uref;
+
+        let (user, _) = create_test_user(conn);
+        let user_place = create_test_user_place(conn, &user);
+        let sensor = create_test_user_sensor(conn, &user_place);
+
+        let now = chrono::Utc::now().naive_utc();
+        let new_data = [400, 600]
+            .into_iter()
+            .map(|co2| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now,
+                    SensorReading {
+                        co2: Some(co2),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(conn, new_data).expect("Should not fail");
+
+        // Of the place of the sensor, the second one is out of the range
+        let [in_range, _] = [TimeDelta::hours(1), TimeDelta::days(30)].map(|ago| {
+            insert_annotation(
+                conn,
+                NewAnnotation {
+                    user_id: user.id,
+                    place_id: Some(user_place.id),
+                    sensor_id: None,
+                    text: "Window open".into(),
+                    starts_at: now - ago,
+                    ends_at: None,
+                },
+            )
+            .expect("Should not fail")
+        });
+
+        let claims = Claims::new(user.username);
+
+        let json = GetSensorData {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: Some((now - TimeDelta::days(7)).and_utc().timestamp() as usize),
+            upper_added_at: None,
+            bucket: Some(BucketSize::Day),
+            exclude_suspect: None,
+            limit: None,
+            order: None,
+            cursor: None,
+        };
+
+        let conn = DbConnHolder(conn_uref);
+
+        let res = SensorData::sensor_data_get(claims, conn, Query(json))
+            .await
+            .expect("Should not fail");
+
+        let GetSensorDataResponse::Aggregated(aggregated) = res.0 else {
+            panic!("Should be Aggregated");
+        };
+        assert_eq!(
+            aggregated
+                .annotations
+                .iter()
+                .map(|a| a.id)
+                .collect::<Vec<_>>(),
+            vec![in_range.id]
+        );
+        let buckets = aggregated.buckets;
+        assert_eq!(buckets.len(), 1);
+        assert!(buckets[0].temperature.is_none());
+        let co2 = buckets[0].co2.clone().expect("Should have co2");
+        assert_eq!(co2.count, 2);
+        assert_eq!(co2.avg, 500.0);
+
+        // Without lowest_added_at it goes back as many buckets as allowed
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+        let now = Utc::now().naive_utc();
+        for ago in [TimeDelta::minutes(5), TimeDelta::days(6000)] {
+            insert_sensor_data(
+                &mut conn,
+                new_test_sensor_data(
+                    sensor.id,
+                    now - ago,
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                ),
+            )
+            .unwrap();
+        }
+
+        let json = GetSensorData {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            bucket: Some(BucketSize::Day),
+            exclude_suspect: None,
+            limit: None,
+            order: None,
+            cursor: None,
+        };
+        let res = SensorData::sensor_data_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(json),
+        )
+        .await
+        .expect("Should not fail");
+        let GetSensorDataResponse::Aggregated(aggregated) = res.0 else {
+            panic!("Should be Aggregated");
+        };
+        assert_eq!(aggregated.buckets.len(), 1);
+
+        // Since 1970 in days is too many buckets
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+
+        let json = GetSensorData {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: Some(0),
+            upper_added_at: None,
+            bucket: Some(BucketSize::Day),
+            exclude_suspect: None,
+            limit: None,
+            order: None,
+            cursor: None,
+        };
+        let res = SensorData::sensor_data_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(json),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
     }
 
     #[tokio::test]
@@ -260,14 +1185,563 @@ mod test {
         let claims = SensorClaims::new(DeviceId::from_string(&sensor.device_id).unwrap());
 
         let json = PostSensorData {
-            serialized_data: String::default(),
+            data: SensorReading {
+                co2: Some(612),
+                temperature: Some(23.5),
+                humidity: None,
+            },
             created_at: None,
+            idempotency_key: None,
+        };
+
+        let conn = DbConnHolder(conn_uref);
+
+        let res = SensorData::sensor_data_post(CookieJar::new(), claims, conn, Json(json.clone()))
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(res.1.api_data.data, json.data);
+    }
+
+    #[tokio::test]
+    async fn test_post_sensor_data_clock_skew() {
+        let now = chrono::Utc::now().timestamp() as usize;
+
+        for (created_at, accepted) in [(now + 60, true), (1, false)] {
+            let mut conn = establish_connection(true).unwrap();
+            let (user, _) = create_test_user(&mut conn);
+            let user_place = create_test_user_place(&mut conn, &user);
+            let sensor = create_test_user_sensor(&mut conn, &user_place);
+
+            let claims = SensorClaims::new(DeviceId::from_string(&sensor.device_id).unwrap());
+            let json = PostSensorData {
+                data: SensorReading {
+                    co2: Some(612),
+                    temperature: None,
+                    humidity: None,
+                },
+                created_at: Some(created_at),
+                idempotency_key: None,
+            };
+
+            let res = SensorData::sensor_data_post(
+                CookieJar::new(),
+                claims,
+                DbConnHolder(conn),
+                Json(json),
+            )
+            .await;
+
+            if accepted {
+                let (_, res) = res.expect("Should not fail");
+                assert_eq!(res.api_data.added_at, created_at);
+            } else {
+                assert_eq!(res.err(), Some(hyper::StatusCode::BAD_REQUEST));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_post_sensor_data_retried() {
+        let mut conn_uref = establish_connection(true).unwrap();
+        let conn = &mut conn_uref;
+
+        let (user, _) = create_test_user(conn);
+        let user_place = create_test_user_place(conn, &user);
+        let sensor = create_test_user_sensor(conn, &user_place);
+
+        let claims = SensorClaims::new(DeviceId::from_string(&sensor.device_id).unwrap());
+
+        let stored_reading = SensorReading {
+            co2: Some(612),
+            temperature: None,
+            humidity: None,
+        };
+        let stored = insert_sensor_data(
+            conn,
+            NewSensorData {
+                sensor_id: sensor.id,
+                data: json!(stored_reading),
+                added_at: None,
+                idempotency_key: Some("reading-1".into()),
+                reported_at: None,
+            },
+        )
+        .unwrap();
+
+        // Same key, the stored reading is answered instead of storing a new one
+        let json = PostSensorData {
+            data: SensorReading {
+                co2: Some(700),
+                temperature: None,
+                humidity: None,
+            },
+            created_at: Some(1),
+            idempotency_key: Some("reading-1".into()),
         };
 
         let conn = DbConnHolder(conn_uref);
 
-        let _res = SensorData::sensor_data_post(CookieJar::new(), claims, conn, Json(json.clone()))
+        let (_, res) = SensorData::sensor_data_post(CookieJar::new(), claims, conn, Json(json))
             .await
             .expect("Should not fail");
+
+        assert_eq!(res.api_data.data, stored_reading);
+        assert_eq!(
+            res.api_data.added_at,
+            stored.added_at.and_utc().timestamp() as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn test_post_sensor_data_batch() {
+        let mut conn_uref = establish_connection(true).unwrap();
+        let conn = &mut conn_uref;
+
+        let (user, _) = create_test_user(conn);
+        let user_place = create_test_user_place(conn, &user);
+        let sensor = create_test_user_sensor(conn, &user_place);
+
+        let claims = SensorClaims::new(DeviceId::from_string(&sensor.device_id).unwrap());
+
+        let now = chrono::Utc::now().timestamp() as usize;
+        let valid = SensorReading {
+            co2: Some(800),
+            temperature: Some(20.0),
+            humidity: Some(40.0),
+        };
+
+        let json = PostSensorDataBatch {
+            readings: vec![
+                BatchSensorReading {
+                    data: valid.clone(),
+                    created_at: now - 120,
+                },
+                BatchSensorReading {
+                    data: SensorReading::default(),
+                    created_at: now - 60,
+                },
+                BatchSensorReading {
+                    data: valid.clone(),
+                    created_at: now,
+                },
+                // Broken clock
+                BatchSensorReading {
+                    data: valid.clone(),
+                    created_at: 1,
+                },
+            ],
+        };
+
+        let conn = DbConnHolder(conn_uref);
+
+        let (_, res) =
+            SensorData::sensor_data_batch_post(CookieJar::new(), claims, conn, Json(json))
+                .await
+                .expect("Should not fail");
+
+        assert_eq!(res.results.len(), 4);
+        match &res.results[0] {
+            BatchItemResult::Accepted(data) => {
+                assert_eq!(data.data, valid);
+                assert_eq!(data.added_at, now - 120);
+            }
+            r => panic!("Should be accepted, was: {r:?}"),
+        }
+        assert!(matches!(res.results[1], BatchItemResult::Rejected { .. }));
+        assert!(matches!(res.results[2], BatchItemResult::Accepted(_)));
+        assert!(matches!(res.results[3], BatchItemResult::Rejected { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_sensor_data_delete() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+
+        let now = Utc::now().naive_utc();
+        let new_data = (1..=3)
+            .map(|hours_ago| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now - TimeDelta::hours(hours_ago),
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).unwrap();
+
+        let payload = DeleteSensorData {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            selection: SensorDataSelection::Range {
+                lowest_added_at: (now - TimeDelta::minutes(150)).and_utc().timestamp() as usize,
+                upper_added_at: now.and_utc().timestamp() as usize,
+            },
+            dry_run: Some(true),
+        };
+        let res = SensorData::sensor_data_delete(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(payload),
+        )
+        .await
+        .expect("Should not fail");
+        assert!(res.dry_run);
+        assert_eq!(res.deleted, 2);
+        assert_eq!(
+            res.oldest_added_at,
+            Some((now - TimeDelta::hours(2)).and_utc().timestamp() as usize)
+        );
+
+        // A sensor of someone else
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other, _) = create_test_user(&mut conn);
+        let other_place = create_test_user_place(&mut conn, &other);
+        let other_sensor = create_test_user_sensor(&mut conn, &other_place);
+
+        let payload = DeleteSensorData {
+            device_id: DeviceId::from_string(&other_sensor.device_id).unwrap(),
+            selection: SensorDataSelection::Ids(vec![1]),
+            dry_run: None,
+        };
+        let res = SensorData::sensor_data_delete(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(payload),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_sensor_data_compare_get() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let bedroom = create_test_user_sensor(&mut conn, &user_place);
+        let living_room = create_test_user_sensor(&mut conn, &user_place);
+
+        let hour = Utc::now()
+            .naive_utc()
+            .duration_trunc(TimeDelta::hours(1))
+            .unwrap();
+        let readings = [(&bedroom, 5, 400), (&bedroom, 3, 600), (&bedroom, 1, 800)]
+            .into_iter()
+            .chain([(&living_room, 3, 500), (&living_room, 2, 700)])
+            .map(|(sensor, hours_ago, co2)| {
+                new_test_sensor_data(
+                    sensor.id,
+                    hour - TimeDelta::hours(hours_ago) + TimeDelta::minutes(10),
+                    SensorReading {
+                        co2: Some(co2),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, readings).unwrap();
+
+        let device_ids = [&bedroom, &living_room]
+            .map(|s| s.device_id.as_str())
+            .join(",");
+        let query = GetSensorDataComparison {
+            device_ids: device_ids.clone(),
+            metric: Metric::Co2,
+            bucket: BucketSize::Hour,
+            lowest_added_at: Some((hour - TimeDelta::days(1)).and_utc().timestamp() as usize),
+            upper_added_at: None,
+            exclude_suspect: None,
+        };
+        let res = SensorData::sensor_data_compare_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(
+            res.device_ids,
+            vec![
+                DeviceId::from_string(&bedroom.device_id).unwrap(),
+                DeviceId::from_string(&living_room.device_id).unwrap()
+            ]
+        );
+        let avgs: Vec<Vec<Option<f64>>> = res
+            .rows
+            .iter()
+            .map(|row| {
+                row.values
+                    .iter()
+                    .map(|v| v.as_ref().map(|v| v.avg))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            avgs,
+            vec![
+                vec![Some(400.0), None],
+                vec![None, None],
+                vec![Some(600.0), Some(500.0)],
+                vec![None, Some(700.0)],
+                vec![Some(800.0), None],
+            ]
+        );
+        assert_eq!(
+            res.rows[0].bucket_start,
+            (hour - TimeDelta::hours(5)).and_utc().timestamp() as usize
+        );
+
+        // A sensor of someone else
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let other_place = create_test_user_place(&mut conn, &other);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+        let other_sensor = create_test_user_sensor(&mut conn, &other_place);
+
+        let query = GetSensorDataComparison {
+            device_ids: format!("{},{}", sensor.device_id, other_sensor.device_id),
+            metric: Metric::Co2,
+            bucket: BucketSize::Hour,
+            lowest_added_at: None,
+            upper_added_at: None,
+            exclude_suspect: None,
+        };
+        let res = SensorData::sensor_data_compare_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+
+        // Rejected from the range alone, before aggregating it
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+
+        let query = GetSensorDataComparison {
+            device_ids: sensor.device_id.clone(),
+            metric: Metric::Co2,
+            bucket: BucketSize::Minute,
+            lowest_added_at: Some((hour - TimeDelta::days(7)).and_utc().timestamp() as usize),
+            upper_added_at: None,
+            exclude_suspect: None,
+        };
+        let res = SensorData::sensor_data_compare_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+
+        // Without lowest_added_at it goes back as many rows as allowed
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+        let readings = [TimeDelta::hours(1), TimeDelta::days(300)]
+            .into_iter()
+            .map(|ago| {
+                new_test_sensor_data(
+                    sensor.id,
+                    hour - ago,
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, readings).unwrap();
+
+        let query = GetSensorDataComparison {
+            device_ids: sensor.device_id.clone(),
+            metric: Metric::Co2,
+            bucket: BucketSize::Hour,
+            lowest_added_at: None,
+            upper_added_at: None,
+            exclude_suspect: None,
+        };
+        let res = SensorData::sensor_data_compare_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+        assert_eq!(res.rows.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_suspect_readings() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+        let claims = SensorClaims::new(DeviceId::from_string(&sensor.device_id).unwrap());
+
+        // A loose connection after 10 minutes
+        let now = chrono::Utc::now().timestamp() as usize;
+        let readings = (0..11)
+            .map(|i| BatchSensorReading {
+                data: SensorReading {
+                    co2: Some(if i == 10 { 0 } else { 450 + i % 3 }),
+                    ..Default::default()
+                },
+                created_at: now - 600 + i as usize * 60,
+            })
+            .collect();
+
+        let (_, res) = SensorData::sensor_data_batch_post(
+            CookieJar::new(),
+            claims,
+            DbConnHolder(conn),
+            Json(PostSensorDataBatch { readings }),
+        )
+        .await
+        .expect("Should not fail");
+
+        let suspect: Vec<Vec<Metric>> = res
+            .results
+            .iter()
+            .map(|r| match r {
+                BatchItemResult::Accepted(data) => data.suspect_metrics.clone(),
+                r => panic!("Should be accepted, was: {r:?}"),
+            })
+            .collect();
+        assert!(suspect[..10].iter().all(Vec::is_empty));
+        assert_eq!(suspect[10], vec![Metric::Co2]);
+
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let user_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &user_place);
+        let now = Utc::now().naive_utc();
+        let stored = insert_sensor_data_batch(
+            &mut conn,
+            [450, 0]
+                .into_iter()
+                .map(|co2| {
+                    new_test_sensor_data(
+                        sensor.id,
+                        now,
+                        SensorReading {
+                            co2: Some(co2),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        )
+        .unwrap();
+        set_suspect_metrics(&mut conn, stored[1].id, vec!["co2".into()]).unwrap();
+
+        let count = |conn: &mut DbConn, exclude_suspect| {
+            get_sensor_data_buckets(
+                conn,
+                Identifier::SensorId(sensor.id),
+                (now - TimeDelta::hours(1))..(now + TimeDelta::hours(1)),
+                BucketSize::Day,
+                exclude_suspect,
+            )
+            .unwrap()[0]
+                .count
+        };
+        assert_eq!(count(&mut conn, false), 2);
+        assert_eq!(count(&mut conn, true), 1);
+
+        let query = GetSuspectSensorData {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            limit: None,
+        };
+        let res = SensorData::sensor_data_suspect_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].data.co2, Some(0));
+        assert_eq!(res[0].suspect_metrics, vec![Metric::Co2]);
+    }
+
+    #[tokio::test]
+    async fn test_handle_stored_readings() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        insert_alert_rule(
+            &mut conn,
+            NewAlertRule {
+                user_id: user.id,
+                place_id: Some(place.id),
+                sensor_id: None,
+                metric: Metric::Co2.key().to_string(),
+                comparison: comparison_key(AlertComparison::Above).to_string(),
+                threshold: 1200.0,
+                hysteresis: 0.0,
+                for_secs: 0,
+            },
+        )
+        .unwrap();
+        let webhook = insert_webhook(
+            &mut conn,
+            NewWebhook {
+                user_id: user.id,
+                url: "http://127.0.0.1:1/hook".to_string(),
+                secret: "secret".to_string(),
+                events: [
+                    WebhookEventKind::SensorData,
+                    WebhookEventKind::AlertTriggered,
+                ]
+                .iter()
+                .map(|k| k.key().to_string())
+                .collect(),
+            },
+        )
+        .unwrap();
+
+        let now = Utc::now().naive_utc();
+        let api_data = ApiSensorData {
+            id: 0,
+            data: SensorReading {
+                co2: Some(1300),
+                ..Default::default()
+            },
+            derived: Default::default(),
+            suspect_metrics: vec![],
+            added_at: now.and_utc().timestamp() as usize,
+        };
+        SensorData::handle_stored_readings(&mut conn, &sensor, vec![(now, api_data)]);
+
+        let mut kinds: Vec<String> = get_webhook_deliveries(&mut conn, webhook.id, 10)
+            .unwrap()
+            .into_iter()
+            .map(|delivery| delivery.kind)
+            .collect();
+        kinds.sort();
+        assert_eq!(kinds, vec!["alert_triggered", "sensor_data"]);
+
+        let sent = wait_sent_to(&user.email, 1).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            sent[0].subject,
+            format!("Alert on {}: CO2 above 1200 ppm", sensor.name)
+        );
     }
 }
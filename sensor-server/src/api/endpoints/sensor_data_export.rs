@@ -0,0 +1,364 @@
+use std::ops::Range;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::Query,
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat};
+use common::{
+    endpoints_io::sensor_data::{
+        ApiSensorData, ExportFormat, ExportTimestampFormat, GetSensorDataExport, Metric, SortOrder,
+    },
+    types::ApiTimestamp,
+};
+use futures_util::{Stream, StreamExt, stream};
+use hyper::{
+    StatusCode,
+    header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+};
+
+use crate::{
+    RoutePath,
+    api::{
+        Endpoint,
+        endpoints::sensor_data::{RangeDelimiter, SensorData},
+        route::Route,
+    },
+    auth::claims::Claims,
+    db::{
+        DbConn, DbConnHolder,
+        calibrations::calibrate,
+        sensor_data::{Identifier, SensorDataCursor, get_sensor_data},
+        user_sensors::AuthorizedSensor,
+    },
+};
+
+pub struct SensorDataExport {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for SensorDataExport {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for SensorDataExport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How every exported datum is written
+#[derive(Debug, Clone)]
+struct ExportOptions {
+    format: ExportFormat,
+    metrics: Vec<Metric>,
+    timestamp_format: ExportTimestampFormat,
+}
+
+impl ExportOptions {
+    fn header(&self) -> Option<String> {
+        match self.format {
+            ExportFormat::Csv => {
+                let mut header = String::from("added_at");
+                for metric in &self.metrics {
+                    header.push(',');
+                    header.push_str(metric.key());
+                }
+                header.push('\n');
+                Some(header)
+            }
+            ExportFormat::Ndjson => None,
+        }
+    }
+
+    fn timestamp(&self, added_at: ApiTimestamp) -> String {
+        match self.timestamp_format {
+            ExportTimestampFormat::Unix => added_at.to_string(),
+            ExportTimestampFormat::Rfc3339 => DateTime::from_timestamp(added_at as i64, 0)
+                .map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .unwrap_or_default(),
+        }
+    }
+
+    fn row(&self, datum: &ApiSensorData) -> String {
+        let timestamp = self.timestamp(datum.added_at);
+        let values = self
+            .metrics
+            .iter()
+            .map(|metric| (metric.key(), datum.data.display_metric(*metric)));
+
+        match self.format {
+            ExportFormat::Csv => {
+                let mut row = timestamp;
+                for (_, value) in values {
+                    row.push(',');
+                    row.push_str(value.as_deref().unwrap_or_default());
+                }
+                row.push('\n');
+                row
+            }
+            ExportFormat::Ndjson => {
+                let mut row = match self.timestamp_format {
+                    ExportTimestampFormat::Unix => format!("{{\"added_at\":{timestamp}"),
+                    ExportTimestampFormat::Rfc3339 => format!("{{\"added_at\":\"{timestamp}\""),
+                };
+                for (key, value) in values {
+                    row.push_str(&format!(
+                        ",\"{key}\":{}",
+                        value.as_deref().unwrap_or("null")
+                    ));
+                }
+                row.push_str("}\n");
+                row
+            }
+        }
+    }
+}
+
+impl SensorDataExport {
+    pub const API_PATH: &str = "/sensor_data/export";
+    /// Datums read from the DB at once while streaming
+    const PAGE_SIZE: u32 = 1000;
+
+    pub fn new() -> SensorDataExport {
+        let mr = MethodRouter::new().get(Self::sensor_data_export_get);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    /// Streams every datum of the sensor in range, oldest first, reading it from the DB one page at
+    /// a time so that the whole history is never held in memory
+    fn export_stream(
+        conn: DbConn,
+        sensor_id: i32,
+        range: Range<NaiveDateTime>,
+        options: ExportOptions,
+    ) -> impl Stream<Item = Result<Bytes, std::io::Error>> {
+        let header = options.header().map(|h| Ok(Bytes::from(h)));
+
+        let pages = stream::try_unfold(Some((conn, None::<SensorDataCursor>)), move |state| {
+            let range = range.clone();
+            let options = options.clone();
+            async move {
+                let Some((mut conn, cursor)) = state else {
+                    return Ok(None);
+                };
+
+                let (conn, res) = tokio::task::spawn_blocking(move || {
+                    let res = get_sensor_data(
+                        &mut conn,
+                        Identifier::SensorId(sensor_id),
+                        range,
+                        SortOrder::Asc,
+                        Self::PAGE_SIZE,
+                        cursor,
+                    )
+                    .and_then(|(mut page, next_cursor)| {
+                        calibrate(&mut conn, &mut page)?;
+                        Ok((page, next_cursor))
+                    });
+                    (conn, res)
+                })
+                .await
+                .map_err(std::io::Error::other)?;
+
+                let (page, next_cursor) = res.map_err(|e| {
+                    log::error!("Error reading sensor data while exporting: {e:?}");
+                    std::io::Error::other(e)
+                })?;
+
+                let mut chunk = String::new();
+                for datum in page {
+                    let datum = ApiSensorData::try_from(datum).map_err(std::io::Error::other)?;
+                    chunk.push_str(&options.row(&datum));
+                }
+
+                Ok(Some((
+                    Bytes::from(chunk),
+                    next_cursor.map(|cursor| (conn, Some(cursor))),
+                )))
+            }
+        });
+
+        stream::iter(header).chain(pages)
+    }
+
+    async fn sensor_data_export_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetSensorDataExport>,
+    ) -> Result<Response, StatusCode> {
+        let sensor =
+            AuthorizedSensor::from_username(&mut conn.0, &payload.device_id, &claims.username)?
+                .get();
+
+        let metrics = payload.metrics().map_err(|key| {
+            log::warn!("Unknown column requested on export: {key}");
+            StatusCode::BAD_REQUEST
+        })?;
+        let options = ExportOptions {
+            format: payload.format.unwrap_or_default(),
+            metrics,
+            timestamp_format: payload.timestamp_format.unwrap_or_default(),
+        };
+
+        let low = SensorData::convert_opt_timestamp_into_naive(
+            payload.lowest_added_at,
+            RangeDelimiter::Bottom,
+        )?;
+        let up = SensorData::convert_opt_timestamp_into_naive(
+            payload.upper_added_at,
+            RangeDelimiter::Top,
+        )?;
+
+        log::info!(
+            "User {} exporting sensor {} as {:?}",
+            claims.username,
+            sensor.device_id,
+            options.format
+        );
+
+        let (content_type, extension) = match options.format {
+            ExportFormat::Csv => ("text/csv", "csv"),
+            ExportFormat::Ndjson => ("application/x-ndjson", "ndjson"),
+        };
+        let disposition = format!("attachment; filename=\"{}.{extension}\"", sensor.device_id);
+
+        let body = Body::from_stream(Self::export_stream(conn.0, sensor.id, low..up, options));
+
+        Ok((
+            [
+                (CONTENT_TYPE, content_type.to_string()),
+                (CONTENT_DISPOSITION, disposition),
+            ],
+            body,
+        )
+            .into_response())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::to_bytes, extract::Query};
+    use chrono::{TimeDelta, Utc};
+    use common::{
+        endpoints_io::sensor_data::{
+            ExportFormat, ExportTimestampFormat, GetSensorDataExport, SensorReading,
+        },
+        types::validate::device_id::DeviceId,
+    };
+    use hyper::StatusCode;
+    use serde_valid::json::FromJsonStr;
+
+    use crate::{
+        api::endpoints::sensor_data_export::SensorDataExport,
+        auth::claims::Claims,
+        db::{
+            DbConnHolder, establish_connection,
+            sensor_data::insert_sensor_data_batch,
+            tests::{
+                create_test_user, create_test_user_place, create_test_user_sensor,
+                new_test_sensor_data,
+            },
+        },
+    };
+
+    async fn export(
+        format: ExportFormat,
+        columns: Option<&str>,
+    ) -> Result<(String, usize), StatusCode> {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        // More than a page so that paging is exercised
+        let data_len = SensorDataExport::PAGE_SIZE as usize + 1;
+        let now = Utc::now().naive_utc() - TimeDelta::hours(1);
+        let new_data = (0..data_len)
+            .map(|i| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now + TimeDelta::seconds(i as i64),
+                    SensorReading {
+                        co2: Some(400),
+                        temperature: Some(21.5),
+                        humidity: None,
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).unwrap();
+
+        let query = GetSensorDataExport {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            format: Some(format),
+            columns: columns.map(String::from),
+            timestamp_format: Some(ExportTimestampFormat::Rfc3339),
+        };
+
+        let res = SensorDataExport::sensor_data_export_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await?;
+
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        Ok((String::from_utf8(body.to_vec()).unwrap(), data_len))
+    }
+
+    #[tokio::test]
+    async fn test_export_csv() {
+        let (body, data_len) = export(ExportFormat::Csv, Some("temperature,humidity"))
+            .await
+            .expect("Should not fail");
+
+        let mut lines = body.lines();
+        assert_eq!(lines.next(), Some("added_at,temperature,humidity"));
+        let first = lines.next().expect("Should have data");
+        assert!(first.ends_with("Z,21.5,"), "{first}");
+        assert_eq!(lines.count(), data_len - 1);
+    }
+
+    #[tokio::test]
+    async fn test_export_ndjson() {
+        let (body, data_len) = export(ExportFormat::Ndjson, None)
+            .await
+            .expect("Should not fail");
+
+        assert_eq!(body.lines().count(), data_len);
+        let first = body.lines().next().unwrap();
+        assert!(first.starts_with("{\"added_at\":\""), "{first}");
+        let reading = SensorReading::from_json_str(first).expect("Should be a valid reading");
+        assert_eq!(
+            reading,
+            SensorReading {
+                co2: Some(400),
+                temperature: Some(21.5),
+                humidity: None,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_unknown_column() {
+        let res = export(ExportFormat::Csv, Some("co2,pressure")).await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+    }
+}
@@ -0,0 +1,206 @@
+use axum::{extract::Query, routing::MethodRouter};
+use axum_serde_valid::Json;
+use chrono::{TimeDelta, Utc};
+use common::{
+    endpoints_io::sensor_data::{ApiSensorDataGaps, DataGap, GetSensorDataGaps},
+    types::ApiTimestamp,
+};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    api::{
+        Endpoint, capped_limit,
+        endpoints::sensor_data::{RangeDelimiter, SensorData},
+        route::Route,
+    },
+    auth::claims::Claims,
+    db::{
+        DbConnHolder,
+        sensor_data::{Identifier, get_sensor_data_gaps},
+        user_sensors::AuthorizedSensor,
+    },
+};
+
+pub struct SensorDataGaps {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for SensorDataGaps {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for SensorDataGaps {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SensorDataGaps {
+    pub const API_PATH: &str = "/sensor_data/gaps";
+
+    pub fn new() -> SensorDataGaps {
+        let mr = MethodRouter::new().get(Self::sensor_data_gaps_get);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    async fn sensor_data_gaps_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetSensorDataGaps>,
+    ) -> Result<Json<ApiSensorDataGaps>, StatusCode> {
+        let conn = &mut conn.0;
+        let sensor =
+            AuthorizedSensor::from_username(conn, &payload.device_id, &claims.username)?.get();
+
+        let low = SensorData::convert_opt_timestamp_into_naive(
+            payload.lowest_added_at,
+            RangeDelimiter::Bottom,
+        )?;
+        // Not yet is not a gap
+        let up = SensorData::convert_opt_timestamp_into_naive(
+            payload.upper_added_at,
+            RangeDelimiter::Top,
+        )?
+        .min(Utc::now().naive_utc());
+        if up <= low || up - low > TimeDelta::days(GetSensorDataGaps::MAX_RANGE_DAYS) {
+            log::trace!("Invalid gaps range: {low} - {up}");
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        let threshold_secs =
+            sensor.expected_interval_secs as u32 * GetSensorDataGaps::GAP_INTERVALS;
+        let limit = capped_limit(payload.limit, GetSensorDataGaps::MAX_LIMIT)?;
+
+        let gaps = get_sensor_data_gaps(
+            conn,
+            Identifier::SensorId(sensor.id),
+            low..up,
+            TimeDelta::seconds(threshold_secs as i64),
+            limit,
+        )?;
+
+        log::trace!(
+            "Returning {} of {} gaps of sensor {}",
+            gaps.gaps.len(),
+            gaps.total_gaps,
+            sensor.device_id
+        );
+
+        Ok(Json(ApiSensorDataGaps {
+            lowest_added_at: low.and_utc().timestamp() as ApiTimestamp,
+            upper_added_at: up.and_utc().timestamp() as ApiTimestamp,
+            threshold_secs,
+            uptime: gaps.uptime,
+            total_gaps: gaps.total_gaps,
+            gaps: gaps
+                .gaps
+                .into_iter()
+                .map(|gap| DataGap {
+                    start: gap.gap_start.and_utc().timestamp() as ApiTimestamp,
+                    end: gap.gap_end.and_utc().timestamp() as ApiTimestamp,
+                })
+                .collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{endpoints_io::sensor_data::SensorReading, types::validate::device_id::DeviceId};
+
+    use crate::db::{
+        establish_connection,
+        sensor_data::insert_sensor_data_batch,
+        tests::{
+            create_test_user, create_test_user_place, create_test_user_sensor, new_test_sensor_data,
+        },
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sensor_data_gaps_get() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        // Every minute for the last hour, but for a reboot 30 minutes ago
+        let now = Utc::now().naive_utc();
+        let new_data = (0..60)
+            .filter(|minutes| !(25..30).contains(minutes))
+            .map(|minutes| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now - TimeDelta::minutes(minutes),
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).unwrap();
+
+        let query = GetSensorDataGaps {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: Some((now - TimeDelta::minutes(59)).and_utc().timestamp() as usize),
+            upper_added_at: None,
+            limit: None,
+        };
+        let res = SensorDataGaps::sensor_data_gaps_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(
+            res.threshold_secs,
+            sensor.expected_interval_secs as u32 * GetSensorDataGaps::GAP_INTERVALS
+        );
+        assert_eq!(res.total_gaps, 1);
+        assert_eq!(
+            res.gaps,
+            vec![DataGap {
+                start: (now - TimeDelta::minutes(30)).and_utc().timestamp() as usize,
+                end: (now - TimeDelta::minutes(24)).and_utc().timestamp() as usize,
+            }]
+        );
+        assert!(res.uptime > 85.0 && res.uptime < 95.0);
+
+        // Since the epoch
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let query = GetSensorDataGaps {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            limit: None,
+        };
+        let res = SensorDataGaps::sensor_data_gaps_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+    }
+}
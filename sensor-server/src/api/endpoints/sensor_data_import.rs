@@ -0,0 +1,176 @@
+use axum::{extract::DefaultBodyLimit, extract::Query, routing::MethodRouter};
+use axum_serde_valid::Json;
+use chrono::Utc;
+use common::endpoints_io::sensor_data::{PostSensorDataImport, PostSensorDataImportResponse};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    api::{Endpoint, route::Route},
+    auth::claims::Claims,
+    db::{DbConnHolder, user_sensors::AuthorizedSensor},
+    import::{import_rows, parse_rows},
+    state::server_metrics::{self, IngestionSource},
+};
+
+pub struct SensorDataImport {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for SensorDataImport {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for SensorDataImport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SensorDataImport {
+    pub const API_PATH: &str = "/sensor_data/import";
+    /// Months of readings every 30 seconds fit in this
+    pub const MAX_BODY_BYTES: usize = 32 * 1024 * 1024;
+
+    pub fn new() -> SensorDataImport {
+        let mr = MethodRouter::new()
+            .post(Self::sensor_data_import_post)
+            .layer(DefaultBodyLimit::max(Self::MAX_BODY_BYTES));
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    async fn sensor_data_import_post(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<PostSensorDataImport>,
+        body: String,
+    ) -> Result<Json<PostSensorDataImportResponse>, StatusCode> {
+        let conn = &mut conn.0;
+        let sensor =
+            AuthorizedSensor::from_username(conn, &payload.device_id, &claims.username)?.get();
+
+        let format = payload.format.unwrap_or_default();
+        let rows = parse_rows(format, &body).map_err(|e| {
+            log::warn!(
+                "Invalid {format:?} import for sensor {}: {e}",
+                sensor.device_id
+            );
+            StatusCode::BAD_REQUEST
+        })?;
+
+        let summary = import_rows(conn, sensor.id, rows, Utc::now().naive_utc())?;
+        server_metrics::readings_ingested(IngestionSource::Import, summary.imported);
+
+        Ok(Json(summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::Query;
+    use axum_test::TestServer;
+    use common::{
+        endpoints_io::sensor_data::{ExportFormat, PostSensorDataImport},
+        types::validate::{api_username::ApiUsername, device_id::DeviceId},
+    };
+    use hyper::StatusCode;
+
+    use crate::{
+        api::endpoints::sensor_data_import::SensorDataImport,
+        auth::claims::Claims,
+        db::{
+            DbConnHolder, establish_connection,
+            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+        },
+        sensor_server::SensorServer,
+    };
+
+    #[tokio::test]
+    async fn test_sensor_data_import_post() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let query = PostSensorDataImport {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            format: Some(ExportFormat::Ndjson),
+        };
+        let body = r#"{"added_at":1735725600,"co2":400}
+{"added_at":1735725660,"co2":-1}"#;
+
+        let res = SensorDataImport::sensor_data_import_post(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+            body.to_string(),
+        )
+        .await
+        .expect("Should not fail");
+
+        assert_eq!(res.imported, 1);
+        assert_eq!(res.failed, 1);
+        assert_eq!(res.errors[0].line, 2);
+    }
+
+    #[tokio::test]
+    async fn test_sensor_data_import_post_big_body() {
+        let server = TestServer::new(SensorServer::for_test().into_router())
+            .expect("Should be created successfully");
+        let jwt = Claims::new(ApiUsername::random().into())
+            .encode_jwt()
+            .unwrap();
+
+        // Bigger than what log_request reads of the bodies it logs
+        let line = r#"{"added_at":1735725600,"co2":400}"#;
+        let body = format!("{line}\n").repeat(4_000_000 / line.len());
+        let res = server
+            .post(&format!(
+                "{}{}",
+                SensorServer::API_BASE,
+                SensorDataImport::API_PATH
+            ))
+            .add_query_param("device_id", DeviceId::random().as_str())
+            .authorization_bearer(jwt)
+            .text(body)
+            .await;
+
+        // The body made it to the handler, which doesn't find the sensor
+        res.assert_status(StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_sensor_data_import_post_bad_header() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let query = PostSensorDataImport {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            format: None,
+        };
+
+        let res = SensorDataImport::sensor_data_import_post(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+            "timestamp,co2\n1735725600,400".to_string(),
+        )
+        .await;
+
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+    }
+}
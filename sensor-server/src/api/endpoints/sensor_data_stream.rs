@@ -0,0 +1,232 @@
+use std::{collections::HashSet, future::ready};
+
+use axum::{
+    extract::Query,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::MethodRouter,
+};
+use common::endpoints_io::sensor_data::{ApiSensorDataEvent, GetSensorDataStream};
+use futures_util::{Stream, StreamExt, stream};
+use hyper::StatusCode;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::{
+    RoutePath,
+    api::{Endpoint, route::Route},
+    auth::claims::Claims,
+    db::{DbConnHolder, Error, user_places, user_sensors::AuthorizedSensor, users},
+    state::sensor_data_hub::{self, SensorDataEvent},
+};
+
+pub struct SensorDataStream {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for SensorDataStream {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for SensorDataStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Which events of the hub a subscription receives, resolved when subscribing so places created
+/// afterwards need a new subscription
+#[derive(Debug, Clone)]
+struct StreamFilter {
+    place_ids: HashSet<i32>,
+    sensor_id: Option<i32>,
+}
+
+impl StreamFilter {
+    fn matches(&self, event: &SensorDataEvent) -> bool {
+        self.place_ids.contains(&event.place_id)
+            && self.sensor_id.is_none_or(|id| id == event.sensor_id)
+    }
+}
+
+impl SensorDataStream {
+    pub const API_PATH: &str = "/sensor_data/stream";
+    /// Name of the SSE events carrying an ApiSensorDataEvent
+    pub const EVENT_NAME: &str = "sensor_data";
+
+    pub fn new() -> SensorDataStream {
+        let mr = MethodRouter::new().get(Self::sensor_data_stream_get);
+
+        Self {
+            resources: vec![Route::new(
+                RoutePath::from_string(Self::API_PATH.to_string())
+                    .expect("The route should be correct"),
+                mr,
+            )],
+        }
+    }
+
+    /// Events of the receiver that match the filter, a subscriber that falls behind skips the
+    /// events it missed
+    fn event_stream(
+        rx: broadcast::Receiver<SensorDataEvent>,
+        filter: StreamFilter,
+    ) -> impl Stream<Item = Result<Event, axum::Error>> {
+        stream::unfold(rx, |mut rx| async move {
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, rx)),
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Sensor data stream subscriber skipped {skipped} events")
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+        .filter(move |event| ready(filter.matches(event)))
+        .map(|event| {
+            Event::default()
+                .event(Self::EVENT_NAME)
+                .json_data(ApiSensorDataEvent {
+                    device_id: event.device_id,
+                    api_data: event.api_data,
+                })
+        })
+    }
+
+    async fn sensor_data_stream_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetSensorDataStream>,
+    ) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let place_ids = match &payload.place_name {
+            Some(name) => user_places::get_user_place_id(
+                conn,
+                user_places::Identifier::PlaceNameAndUserId(name.as_str(), user_id),
+            )?,
+            None => user_places::get_user_place_id(conn, user_places::Identifier::UserId(user_id))?,
+        };
+        if payload.place_name.is_some() && place_ids.is_empty() {
+            Err(Error::NotFound("Place not found".into()))?
+        }
+
+        let sensor_id = payload
+            .device_id
+            .as_ref()
+            .map(|device_id| {
+                AuthorizedSensor::from_username(conn, device_id, &claims.username)
+                    .map(|sensor| sensor.get().id)
+            })
+            .transpose()?;
+
+        let filter = StreamFilter {
+            place_ids: place_ids.into_iter().collect(),
+            sensor_id,
+        };
+        log::info!(
+            "User {} subscribed to sensor data: {filter:?}",
+            claims.username
+        );
+
+        let rx = sensor_data_hub::subscribe();
+        Ok(Sse::new(Self::event_stream(rx, filter)).keep_alive(KeepAlive::default()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{extract::Query, response::IntoResponse};
+    use common::{
+        endpoints_io::sensor_data::{ApiSensorData, GetSensorDataStream, SensorReading},
+        types::validate::device_id::DeviceId,
+    };
+    use futures_util::StreamExt;
+    use hyper::StatusCode;
+
+    use crate::{
+        api::endpoints::sensor_data_stream::SensorDataStream,
+        auth::claims::Claims,
+        db::{
+            DbConnHolder, establish_connection,
+            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+        },
+        state::sensor_data_hub::{SensorDataEvent, publish},
+    };
+
+    #[tokio::test]
+    async fn test_sensor_data_stream() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let other_sensor = create_test_user_sensor(&mut conn, &place);
+
+        let query = GetSensorDataStream {
+            device_id: Some(DeviceId::from_string(&sensor.device_id).unwrap()),
+            place_name: None,
+        };
+        let sse = SensorDataStream::sensor_data_stream_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await
+        .expect("Should not fail");
+        let mut body = sse.into_response().into_body().into_data_stream();
+
+        let api_data = ApiSensorData {
+            id: 0,
+            data: SensorReading {
+                co2: Some(400),
+                ..Default::default()
+            },
+            derived: Default::default(),
+            suspect_metrics: vec![],
+            added_at: 0,
+        };
+        for s in [&other_sensor, &sensor] {
+            publish(SensorDataEvent {
+                sensor_id: s.id,
+                place_id: s.place_id,
+                device_id: DeviceId::from_string(&s.device_id).unwrap(),
+                api_data: api_data.clone(),
+            });
+        }
+
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("Should receive the event")
+            .unwrap()
+            .unwrap();
+        let frame = String::from_utf8(frame.to_vec()).unwrap();
+        assert!(frame.starts_with("event: sensor_data\n"), "{frame}");
+        assert!(frame.contains(&sensor.device_id), "{frame}");
+        assert!(!frame.contains(&other_sensor.device_id), "{frame}");
+    }
+
+    #[tokio::test]
+    async fn test_sensor_data_stream_unknown_place() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+
+        let query = GetSensorDataStream {
+            device_id: None,
+            place_name: Some("not_a_place".to_string().into()),
+        };
+        let res = SensorDataStream::sensor_data_stream_get(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Query(query),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+}
@@ -24,6 +24,7 @@ use crate::{
         model::NewUser,
         users::{Identifier, Update, get_user, insert_user, update_user},
     },
+    mail::{notify_user, templates::Notification},
     state::poisonable_identifier::PoisonableIdentifier,
 };
 
@@ -194,6 +195,17 @@ impl User {
             }
         }
 
+        let notification = match &payload {
+            PutUser::Username(_) => Notification::UsernameChanged {
+                old_username: user.username.clone(),
+            },
+            PutUser::RawPassword(_) => Notification::PasswordChanged,
+            PutUser::Email(em) => Notification::EmailChanged {
+                old_email: user.email.clone(),
+                new_email: em.as_str().to_string(),
+            },
+        };
+
         let user = update_user(
             conn,
             Identifier::Username(&claims.username),
@@ -202,6 +214,10 @@ impl User {
 
         log::trace!("User updated to: {user:?}");
 
+        if let Err(e) = notify_user(conn, &user, &notification) {
+            log::error!("Could not notify user {} of the update: {e:?}", user.id);
+        }
+
         // Poison last identifier
         let identifier = match &payload {
             PutUser::Username(un) => Some(PoisonableIdentifier::Username(un.clone().into())),
@@ -382,6 +398,7 @@ mod test {
         api::endpoints::user::{PostUser, PutUser, User},
         auth::claims::Claims,
         db::{self, DbConnHolder, establish_connection, tests::create_test_user},
+        mail::tests::wait_sent_to,
     };
 
     #[tokio::test]
@@ -506,4 +523,25 @@ mod test {
 
         assert_eq!(res, StatusCode::CONFLICT);
     }
+
+    #[tokio::test]
+    async fn test_user_put_email() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+
+        let new_email = ApiEmail::random();
+        let json = PutUser::Email(new_email.clone());
+        let claims = Claims::new(user.username.clone());
+
+        let (_jar, res) = User::user_put(CookieJar::new(), DbConnHolder(conn), claims, Json(json))
+            .await
+            .expect("Should not fail");
+        assert_eq!(res.updated.email, new_email);
+
+        // The old address is told
+        let sent = wait_sent_to(&user.email, 1).await;
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].subject, "Your email was changed");
+        assert!(sent[0].body.contains(new_email.as_str()));
+    }
 }
@@ -0,0 +1,388 @@
+use axum::{extract::Query, routing::MethodRouter};
+use axum_serde_valid::Json;
+use chrono::Utc;
+use common::endpoints_io::webhook::{
+    ApiNewWebhook, ApiWebhook, ApiWebhookDelivery, DeleteWebhook, GetWebhookDeliveries,
+    PostWebhook, PostWebhookTest, PutWebhook, WebhookEventKind,
+};
+use hyper::StatusCode;
+
+use crate::{
+    RoutePath,
+    api::{Endpoint, capped_limit, route::Route},
+    auth::{claims::Claims, keys::generate_secret},
+    db::{
+        DbConn, DbConnHolder, Error,
+        model::{NewWebhook, WebhookChange},
+        users,
+        webhooks::{
+            delete_webhook, get_webhook, get_webhook_deliveries, get_webhooks, insert_webhook,
+            update_webhook,
+        },
+    },
+    webhooks::{api_webhook, api_webhook_delivery, check_url, queue_test_event},
+};
+
+pub struct Webhooks {
+    resources: Vec<Route>,
+}
+
+impl Endpoint for Webhooks {
+    fn routes(&self) -> &[Route] {
+        &self.resources
+    }
+    fn path(&self) -> &str {
+        Self::API_PATH
+    }
+}
+
+impl Default for Webhooks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Webhooks {
+    pub const API_PATH: &str = "/webhook";
+    pub const TEST_API_PATH: &str = "/webhook/test";
+    pub const DELIVERY_API_PATH: &str = "/webhook/delivery";
+
+    pub fn new() -> Webhooks {
+        let mr = MethodRouter::new()
+            .get(Self::webhook_get)
+            .post(Self::webhook_post)
+            .put(Self::webhook_put)
+            .delete(Self::webhook_delete);
+
+        let test_mr = MethodRouter::new().post(Self::webhook_test_post);
+
+        let delivery_mr = MethodRouter::new().get(Self::webhook_delivery_get);
+
+        Self {
+            resources: vec![
+                Route::new(
+                    RoutePath::from_string(Self::API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    mr,
+                ),
+                Route::new(
+                    RoutePath::from_string(Self::TEST_API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    test_mr,
+                ),
+                Route::new(
+                    RoutePath::from_string(Self::DELIVERY_API_PATH.to_string())
+                        .expect("The route should be correct"),
+                    delivery_mr,
+                ),
+            ],
+        }
+    }
+
+    fn event_keys(events: &[WebhookEventKind]) -> Vec<String> {
+        events.iter().map(|kind| kind.key().to_string()).collect()
+    }
+
+    /// BAD_REQUEST if deliveries to the url would be refused
+    async fn check_url(url: &str) -> Result<(), StatusCode> {
+        check_url(url).await.map_err(|e| {
+            log::warn!("Refused webhook url {url}: {e}");
+            StatusCode::BAD_REQUEST
+        })
+    }
+
+    fn get_api_webhooks(conn: &mut DbConn, user_id: i32) -> Result<Vec<ApiWebhook>, Error> {
+        get_webhooks(conn, user_id)?
+            .into_iter()
+            .map(api_webhook)
+            .collect()
+    }
+
+    async fn webhook_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+    ) -> Result<Json<Vec<ApiWebhook>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        Ok(Json(Self::get_api_webhooks(conn, user_id)?))
+    }
+
+    /// The secret of the signatures is only returned here
+    async fn webhook_post(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PostWebhook>,
+    ) -> Result<Json<ApiNewWebhook>, StatusCode> {
+        Self::check_url(&payload.url).await?;
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let webhook = insert_webhook(
+            conn,
+            NewWebhook {
+                user_id,
+                url: payload.url,
+                secret: generate_secret(),
+                events: Self::event_keys(&payload.events),
+            },
+        )?;
+        log::info!("User {} created webhook {}", claims.username, webhook.id);
+
+        let secret = webhook.secret.clone();
+        Ok(Json(ApiNewWebhook {
+            webhook: api_webhook(webhook)?,
+            secret,
+        }))
+    }
+
+    async fn webhook_put(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PutWebhook>,
+    ) -> Result<Json<ApiWebhook>, StatusCode> {
+        if let Some(url) = &payload.url {
+            Self::check_url(url).await?;
+        }
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let change = WebhookChange {
+            url: payload.url,
+            events: payload.events.as_deref().map(Self::event_keys),
+            enabled: payload.enabled,
+        };
+        let webhook = update_webhook(conn, payload.id, user_id, change)?;
+
+        Ok(Json(api_webhook(webhook)?))
+    }
+
+    async fn webhook_delete(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<DeleteWebhook>,
+    ) -> Result<Json<Vec<ApiWebhook>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let webhook = delete_webhook(conn, payload.id, user_id)?;
+        log::info!("User {} deleted webhook {}", claims.username, webhook.id);
+
+        Ok(Json(Self::get_api_webhooks(conn, user_id)?))
+    }
+
+    /// Queues a Test event for the dispatcher to send right away, even if the webhook is disabled.
+    /// Its result shows up in GET /webhook/delivery, if it fails it's retried like any other
+    async fn webhook_test_post(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Json(payload): Json<PostWebhookTest>,
+    ) -> Result<Json<ApiWebhookDelivery>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let webhook = get_webhook(conn, payload.id, user_id)?;
+        let delivery = queue_test_event(conn, &webhook, Utc::now().naive_utc())?;
+
+        Ok(Json(api_webhook_delivery(delivery)?))
+    }
+
+    async fn webhook_delivery_get(
+        claims: Claims,
+        mut conn: DbConnHolder,
+        Query(payload): Query<GetWebhookDeliveries>,
+    ) -> Result<Json<Vec<ApiWebhookDelivery>>, StatusCode> {
+        let conn = &mut conn.0;
+        let user_id = users::get_user(conn, users::Identifier::Username(&claims.username))?.id;
+
+        let webhook = get_webhook(conn, payload.id, user_id)?;
+        let limit = capped_limit(payload.limit, GetWebhookDeliveries::MAX_LIMIT)?;
+
+        let deliveries = get_webhook_deliveries(conn, webhook.id, limit.into())?
+            .into_iter()
+            .map(api_webhook_delivery)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Json(deliveries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::extract::Query;
+    use axum_serde_valid::Json;
+    use common::endpoints_io::webhook::{
+        DeleteWebhook, GetWebhookDeliveries, PostWebhook, PostWebhookTest, PutWebhook,
+        WebhookDeliveryStatus, WebhookEventKind,
+    };
+    use hyper::StatusCode;
+
+    use crate::{
+        api::endpoints::webhook::Webhooks,
+        auth::claims::Claims,
+        db::{
+            DbConnHolder, establish_connection,
+            model::NewWebhook,
+            tests::create_test_user,
+            webhooks::{get_webhook_deliveries, insert_webhook},
+        },
+    };
+
+    #[tokio::test]
+    async fn test_webhook_post_and_put() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+
+        let payload = PostWebhook {
+            url: "https://93.184.216.34/hook".to_string(),
+            events: vec![WebhookEventKind::AlertTriggered],
+        };
+        let res = Webhooks::webhook_post(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Json(payload),
+        )
+        .await
+        .expect("Should not fail");
+        assert_eq!(res.secret.len(), 64);
+        assert!(res.webhook.enabled);
+        assert_eq!(res.webhook.events, vec![WebhookEventKind::AlertTriggered]);
+
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let webhook = insert_webhook(
+            &mut conn,
+            NewWebhook {
+                user_id: user.id,
+                url: "https://example.com/hook".to_string(),
+                secret: "secret".to_string(),
+                events: vec![WebhookEventKind::SensorData.key().to_string()],
+            },
+        )
+        .unwrap();
+
+        let payload = PutWebhook {
+            id: webhook.id,
+            url: None,
+            events: Some(vec![
+                WebhookEventKind::AlertTriggered,
+                WebhookEventKind::AlertResolved,
+            ]),
+            enabled: Some(false),
+        };
+        let res = Webhooks::webhook_put(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Json(payload),
+        )
+        .await
+        .expect("Should not fail");
+        assert_eq!(res.url, webhook.url);
+        assert_eq!(res.events.len(), 2);
+        assert!(!res.enabled);
+
+        // Would reach the network of the server
+        let conn = establish_connection(true).unwrap();
+        let payload = PostWebhook {
+            url: "http://169.254.169.254/latest/meta-data".to_string(),
+            events: vec![WebhookEventKind::AlertTriggered],
+        };
+        let res = Webhooks::webhook_post(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Json(payload),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::BAD_REQUEST));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_delete_foreign_webhook() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other_user, _) = create_test_user(&mut conn);
+        let webhook = insert_webhook(
+            &mut conn,
+            NewWebhook {
+                user_id: other_user.id,
+                url: "https://example.com/hook".to_string(),
+                secret: "secret".to_string(),
+                events: vec![WebhookEventKind::SensorData.key().to_string()],
+            },
+        )
+        .unwrap();
+
+        let res = Webhooks::webhook_delete(
+            Claims::new(user.username),
+            DbConnHolder(conn),
+            Json(DeleteWebhook { id: webhook.id }),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_test_post() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let webhook = insert_webhook(
+            &mut conn,
+            NewWebhook {
+                user_id: user.id,
+                url: "https://example.com/hook".to_string(),
+                secret: "secret".to_string(),
+                events: vec![WebhookEventKind::AlertTriggered.key().to_string()],
+            },
+        )
+        .unwrap();
+
+        // Left to the dispatcher
+        let res = Webhooks::webhook_test_post(
+            Claims::new(user.username.clone()),
+            DbConnHolder(conn),
+            Json(PostWebhookTest { id: webhook.id }),
+        )
+        .await
+        .expect("Should not fail");
+        assert_eq!(res.kind, WebhookEventKind::Test);
+        assert_eq!(res.status, WebhookDeliveryStatus::Pending);
+        assert_eq!(res.attempts, 0);
+        assert!(res.next_attempt_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_delivery_get() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let (other_user, _) = create_test_user(&mut conn);
+        let webhook = insert_webhook(
+            &mut conn,
+            NewWebhook {
+                user_id: user.id,
+                url: "https://example.com/hook".to_string(),
+                secret: "secret".to_string(),
+                events: vec![WebhookEventKind::SensorData.key().to_string()],
+            },
+        )
+        .unwrap();
+        crate::webhooks::queue_test_event(&mut conn, &webhook, chrono::Utc::now().naive_utc())
+            .unwrap();
+        assert_eq!(
+            get_webhook_deliveries(&mut conn, webhook.id, 10)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let res = Webhooks::webhook_delivery_get(
+            Claims::new(other_user.username),
+            DbConnHolder(conn),
+            Query(GetWebhookDeliveries {
+                id: webhook.id,
+                limit: None,
+            }),
+        )
+        .await;
+        assert_eq!(res.err(), Some(StatusCode::NOT_FOUND));
+    }
+}
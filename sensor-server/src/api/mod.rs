@@ -1,9 +1,23 @@
 pub mod endpoints;
 pub mod route;
 
+use hyper::StatusCode;
+
 use crate::api::route::Route;
 
 pub trait Endpoint {
     fn routes(&self) -> &[Route];
     fn path(&self) -> &str;
 }
+
+/// Limit of a query, defaulting to and capped at max. minimum = 1 isn't enforced by Query, so 0 is
+/// rejected here
+pub fn capped_limit(limit: Option<u32>, max: u32) -> Result<u32, StatusCode> {
+    match limit {
+        Some(0) => {
+            log::trace!("Query with limit 0");
+            Err(StatusCode::BAD_REQUEST)
+        }
+        limit => Ok(limit.unwrap_or(max).min(max)),
+    }
+}
@@ -5,7 +5,13 @@ use hyper::StatusCode;
 use jsonwebtoken::{Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
 
-use crate::{auth::keys::PROCESS_KEYS, state};
+use crate::{
+    auth::keys::PROCESS_KEYS,
+    state::{
+        self,
+        server_metrics::{self, JwtKind, JwtRejection},
+    },
+};
 
 // Will be unique because JWT lifetime is tied to server process lifetime
 static ID_COUNTER: LazyLock<Mutex<u128>> = LazyLock::new(|| Mutex::new(u128::default()));
@@ -44,19 +50,23 @@ impl Claims {
     }
 
     pub fn encode_jwt(&self) -> Result<String, jsonwebtoken::errors::Error> {
-        encode(&Header::default(), &self, &PROCESS_KEYS.encoding)
+        let jwt = encode(&Header::default(), &self, &PROCESS_KEYS.encoding)?;
+        server_metrics::jwt_issued(JwtKind::User);
+        Ok(jwt)
     }
 
     pub fn from_jwt(jwt: &str) -> Result<Self, StatusCode> {
         let token_data = decode::<Claims>(jwt, &PROCESS_KEYS.decoding, &Validation::default())
             .map_err(|e| {
                 log::warn!("Unable to decode JWT: {e:?}");
+                server_metrics::jwt_rejected(JwtKind::User, JwtRejection::Invalid);
                 StatusCode::UNAUTHORIZED
             })?;
 
         // Check state poisoned status
         if state::poisonable_identifier::PoisonableIdentifier::UserJWTId(token_data.claims.jwt_id_hex()).is_poisoned()? {
             log::warn!("Tried to access with poisoned JWT: {jwt}, token_data: {token_data:?}");
+            server_metrics::jwt_rejected(JwtKind::User, JwtRejection::Poisoned);
             return Err(StatusCode::UNAUTHORIZED);
         }
         if state::poisonable_identifier::PoisonableIdentifier::Username(token_data.claims.username.clone())
@@ -65,6 +75,7 @@ impl Claims {
             log::warn!(
                 "Tried to access with poisoned username, JWT: {jwt}, token_data: {token_data:?}"
             );
+            server_metrics::jwt_rejected(JwtKind::User, JwtRejection::Poisoned);
             return Err(StatusCode::UNAUTHORIZED);
         }
 
@@ -30,3 +30,13 @@ pub static PROCESS_KEYS: LazyLock<ProcessKeys> = LazyLock::new(|| {
         .expect("OsRng should be able to generate random");
     ProcessKeys::new(&buff)
 });
+
+/// Random hex string, for the secrets handed to users such as the keys of the webhook signatures
+/// and the scrape tokens
+pub fn generate_secret() -> String {
+    let mut buff = [0u8; 32];
+    OsRng
+        .try_fill_bytes(&mut buff)
+        .expect("OsRng should be able to generate random");
+    hex::encode(buff)
+}
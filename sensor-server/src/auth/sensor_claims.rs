@@ -6,7 +6,13 @@ use hyper::StatusCode;
 use jsonwebtoken::{Header, Validation, decode, encode};
 use serde::{Deserialize, Serialize};
 
-use crate::{auth::keys::PROCESS_KEYS, state};
+use crate::{
+    auth::keys::PROCESS_KEYS,
+    state::{
+        self,
+        server_metrics::{self, JwtKind, JwtRejection},
+    },
+};
 
 static ID_COUNTER: LazyLock<Mutex<u128>> = LazyLock::new(|| Mutex::new(u128::default()));
 pub fn get_new_id() -> u128 {
@@ -48,7 +54,9 @@ impl SensorClaims {
     }
 
     pub fn encode_jwt(&self) -> Result<String, jsonwebtoken::errors::Error> {
-        encode(&Header::default(), &self, &PROCESS_KEYS.encoding)
+        let jwt = encode(&Header::default(), &self, &PROCESS_KEYS.encoding)?;
+        server_metrics::jwt_issued(JwtKind::Sensor);
+        Ok(jwt)
     }
 
     /// common logic to decode + poison-check
@@ -57,6 +65,7 @@ impl SensorClaims {
             decode::<SensorClaims>(jwt, &PROCESS_KEYS.decoding, &Validation::default()).map_err(
                 |e| {
                     log::warn!("Unable to decode JWT: {e:?}");
+                    server_metrics::jwt_rejected(JwtKind::Sensor, JwtRejection::Invalid);
                     StatusCode::UNAUTHORIZED
                 },
             )?;
@@ -68,6 +77,7 @@ impl SensorClaims {
         .is_poisoned()?
         {
             log::warn!("Tried to access with poisoned JWT: {jwt}, token_data: {token_data:?}");
+            server_metrics::jwt_rejected(JwtKind::Sensor, JwtRejection::Poisoned);
             return Err(StatusCode::UNAUTHORIZED);
         }
         if state::poisonable_identifier::PoisonableIdentifier::DeviceID(
@@ -78,6 +88,7 @@ impl SensorClaims {
             log::warn!(
                 "Tried to access with poisoned username, JWT: {jwt}, token_data: {token_data:?}"
             );
+            server_metrics::jwt_rejected(JwtKind::Sensor, JwtRejection::Poisoned);
             return Err(StatusCode::UNAUTHORIZED);
         }
 
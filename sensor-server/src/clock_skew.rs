@@ -0,0 +1,149 @@
+use std::sync::LazyLock;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use common::types::ApiTimestamp;
+
+/// Read from the env once, see ClockSkewPolicy::from_env
+pub static CLOCK_SKEW_POLICY: LazyLock<ClockSkewPolicy> = LazyLock::new(ClockSkewPolicy::from_env);
+
+/// Window around the server time in which the timestamps reported by sensors are trusted
+#[derive(Debug, Clone)]
+pub struct ClockSkewPolicy {
+    pub max_past: TimeDelta,
+    pub max_future: TimeDelta,
+    /// If true, readings dated outside the window are stored as measured when received instead of
+    /// being rejected
+    pub clamp: bool,
+}
+
+impl Default for ClockSkewPolicy {
+    fn default() -> Self {
+        Self {
+            max_past: Self::DEFAULT_MAX_PAST,
+            max_future: Self::DEFAULT_MAX_FUTURE,
+            clamp: false,
+        }
+    }
+}
+
+impl ClockSkewPolicy {
+    /// Sensors buffer readings while offline, so the past is allowed way more
+    pub const DEFAULT_MAX_PAST: TimeDelta = TimeDelta::days(7);
+    pub const DEFAULT_MAX_FUTURE: TimeDelta = TimeDelta::minutes(5);
+
+    /// Reads SENSOR_CLOCK_MAX_PAST_SECS, SENSOR_CLOCK_MAX_FUTURE_SECS and SENSOR_CLOCK_SKEW_CLAMP
+    pub fn from_env() -> Self {
+        let secs = |var: &str, default: TimeDelta| {
+            let Ok(secs) = std::env::var(var) else {
+                return default;
+            };
+            match secs.parse::<u32>() {
+                Ok(secs) => TimeDelta::seconds(secs.into()),
+                Err(e) => {
+                    log::error!("Invalid {var} ({secs}): {e}, using {default}");
+                    default
+                }
+            }
+        };
+
+        let policy = Self {
+            max_past: secs("SENSOR_CLOCK_MAX_PAST_SECS", Self::DEFAULT_MAX_PAST),
+            max_future: secs("SENSOR_CLOCK_MAX_FUTURE_SECS", Self::DEFAULT_MAX_FUTURE),
+            clamp: std::env::var("SENSOR_CLOCK_SKEW_CLAMP")
+                .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true")),
+        };
+        log::info!("Sensor clock skew policy: {policy:?}");
+        policy
+    }
+
+    /// Returns when the reading reported at reported_at and received at received_at is stored as
+    /// measured, Err contains the reason for rejecting it
+    pub fn check(
+        &self,
+        reported_at: NaiveDateTime,
+        received_at: NaiveDateTime,
+    ) -> Result<NaiveDateTime, String> {
+        let reason = if reported_at > received_at + self.max_future {
+            format!("created_at ({reported_at}) is too far in the future")
+        } else if reported_at < received_at - self.max_past {
+            format!("created_at ({reported_at}) is too far in the past")
+        } else {
+            return Ok(reported_at);
+        };
+
+        if self.clamp {
+            log::warn!("{reason}, storing it as measured at {received_at}");
+            Ok(received_at)
+        } else {
+            Err(reason)
+        }
+    }
+}
+
+/// Seconds the clock of the sensor is ahead of the server, negative if behind
+pub fn clock_skew_secs(reported_at: NaiveDateTime, received_at: NaiveDateTime) -> i32 {
+    (reported_at - received_at)
+        .num_seconds()
+        .clamp(i32::MIN.into(), i32::MAX.into()) as i32
+}
+
+/// Skew of a batch of readings, the rest were buffered for a while so only the latest one tells
+/// how off the clock is
+pub fn batch_clock_skew_secs(
+    created_ats: impl IntoIterator<Item = ApiTimestamp>,
+    received_at: NaiveDateTime,
+) -> Option<i32> {
+    let latest = created_ats.into_iter().max()?;
+    let reported_at = DateTime::from_timestamp(latest as i64, 0)?.naive_utc();
+    Some(clock_skew_secs(reported_at, received_at))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let now = Utc::now().naive_utc();
+        let policy = ClockSkewPolicy::default();
+
+        let recent = now - TimeDelta::hours(1);
+        assert_eq!(policy.check(recent, now), Ok(recent));
+        assert!(policy.check(now + TimeDelta::minutes(1), now).is_ok());
+        assert!(policy.check(now + TimeDelta::hours(1), now).is_err());
+        assert!(policy.check(DateTime::UNIX_EPOCH.naive_utc(), now).is_err());
+
+        let policy = ClockSkewPolicy {
+            clamp: true,
+            ..Default::default()
+        };
+        assert_eq!(policy.check(DateTime::UNIX_EPOCH.naive_utc(), now), Ok(now));
+        assert_eq!(policy.check(recent, now), Ok(recent));
+    }
+
+    #[test]
+    fn test_clock_skew_secs() {
+        let now = Utc::now().naive_utc();
+        assert_eq!(clock_skew_secs(now + TimeDelta::seconds(90), now), 90);
+        assert_eq!(clock_skew_secs(now - TimeDelta::seconds(90), now), -90);
+        assert_eq!(clock_skew_secs(NaiveDateTime::MIN, now), i32::MIN);
+    }
+
+    #[test]
+    fn test_batch_clock_skew_secs() {
+        let now = DateTime::from_timestamp(1_800_000_000, 0)
+            .unwrap()
+            .naive_utc();
+        assert_eq!(
+            batch_clock_skew_secs([1_799_990_000, 1_800_000_030, 1_799_999_000], now),
+            Some(30)
+        );
+        assert_eq!(
+            batch_clock_skew_secs([1, 60], now),
+            Some(60 - 1_800_000_000)
+        );
+        assert_eq!(batch_clock_skew_secs([], now), None);
+    }
+}
@@ -0,0 +1,299 @@
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::{
+    DbConn, Error,
+    model::{AlertEvent, AlertRule, AlertRuleCondition, NewAlertEvent, NewAlertRule, UserSensor},
+};
+
+pub fn insert_alert_rule(conn: &mut DbConn, new_rule: NewAlertRule) -> Result<AlertRule, Error> {
+    use crate::db::schema::alert_rules::dsl::alert_rules as alert_rules_table;
+
+    let res = diesel::insert_into(alert_rules_table)
+        .values(&new_rule)
+        .returning(AlertRule::as_returning())
+        .get_result(conn)?;
+
+    log::trace!("Alert rule inserted: {res:?}");
+    Ok(res)
+}
+
+/// A rule with the name of its place or the device_id of its sensor
+pub type AlertRuleWithTarget = (AlertRule, Option<String>, Option<String>);
+
+/// Returns every rule of the user with its target
+pub fn get_alert_rules(conn: &mut DbConn, user_id: i32) -> Result<Vec<AlertRuleWithTarget>, Error> {
+    use crate::db::schema::{
+        alert_rules::dsl as rule, alert_rules::dsl::alert_rules as alert_rules_table,
+        user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    let res = alert_rules_table
+        .left_join(user_places_table.on(rule::place_id.eq(user_place::id.nullable())))
+        .left_join(user_sensors_table.on(rule::sensor_id.eq(user_sensor::id.nullable())))
+        .filter(rule::user_id.eq(user_id))
+        .order(rule::id)
+        .select((
+            AlertRule::as_select(),
+            user_place::name.nullable(),
+            user_sensor::device_id.nullable(),
+        ))
+        .load(conn)?;
+
+    Ok(res)
+}
+
+pub fn update_alert_rule(
+    conn: &mut DbConn,
+    rule_id: i32,
+    user_id: i32,
+    condition: AlertRuleCondition,
+) -> Result<AlertRule, Error> {
+    use crate::db::schema::{
+        alert_rules::dsl as rule, alert_rules::dsl::alert_rules as alert_rules_table,
+    };
+
+    let res = diesel::update(alert_rules_table)
+        .filter(rule::id.eq(rule_id))
+        .filter(rule::user_id.eq(user_id))
+        .set(&condition)
+        .returning(AlertRule::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+pub fn delete_alert_rule(
+    conn: &mut DbConn,
+    rule_id: i32,
+    user_id: i32,
+) -> Result<AlertRule, Error> {
+    use crate::db::schema::{
+        alert_rules::dsl as rule, alert_rules::dsl::alert_rules as alert_rules_table,
+    };
+
+    let res = diesel::delete(alert_rules_table)
+        .filter(rule::id.eq(rule_id))
+        .filter(rule::user_id.eq(user_id))
+        .returning(AlertRule::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+/// Rules that apply to the sensor, set on it or on its place
+pub fn get_sensor_alert_rules(
+    conn: &mut DbConn,
+    sensor: &UserSensor,
+) -> Result<Vec<AlertRule>, Error> {
+    use crate::db::schema::{
+        alert_rules::dsl as rule, alert_rules::dsl::alert_rules as alert_rules_table,
+    };
+
+    let res = alert_rules_table
+        .filter(
+            rule::sensor_id
+                .eq(sensor.id)
+                .or(rule::place_id.eq(sensor.place_id)),
+        )
+        .order(rule::id)
+        .select(AlertRule::as_select())
+        .load(conn)?;
+
+    Ok(res)
+}
+
+/// Since when the sensor breaches the rule, None if it doesn't
+pub fn get_breaching_since(
+    conn: &mut DbConn,
+    rule_id: i32,
+    sensor_id: i32,
+) -> Result<Option<NaiveDateTime>, Error> {
+    use crate::db::schema::{
+        alert_rule_states::dsl as state, alert_rule_states::dsl::alert_rule_states as states_table,
+    };
+
+    let res = states_table
+        .filter(state::rule_id.eq(rule_id))
+        .filter(state::sensor_id.eq(sensor_id))
+        .select(state::breaching_since)
+        .first(conn)
+        .optional()?;
+
+    Ok(res)
+}
+
+pub fn set_breaching_since(
+    conn: &mut DbConn,
+    rule_id: i32,
+    sensor_id: i32,
+    breaching_since: Option<NaiveDateTime>,
+) -> Result<(), Error> {
+    use crate::db::schema::{
+        alert_rule_states::dsl as state, alert_rule_states::dsl::alert_rule_states as states_table,
+    };
+
+    match breaching_since {
+        Some(breaching_since) => {
+            diesel::insert_into(states_table)
+                .values((
+                    state::rule_id.eq(rule_id),
+                    state::sensor_id.eq(sensor_id),
+                    state::breaching_since.eq(breaching_since),
+                ))
+                .on_conflict((state::rule_id, state::sensor_id))
+                .do_update()
+                .set(state::breaching_since.eq(breaching_since))
+                .execute(conn)?;
+        }
+        None => {
+            diesel::delete(states_table)
+                .filter(state::rule_id.eq(rule_id))
+                .filter(state::sensor_id.eq(sensor_id))
+                .execute(conn)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The alert of the rule on the sensor that isn't resolved yet, there is at most one
+pub fn get_open_alert_event(
+    conn: &mut DbConn,
+    rule_id: i32,
+    sensor_id: i32,
+) -> Result<Option<AlertEvent>, Error> {
+    use crate::db::schema::{
+        alert_events::dsl as event, alert_events::dsl::alert_events as alert_events_table,
+    };
+
+    let res = alert_events_table
+        .filter(event::rule_id.eq(rule_id))
+        .filter(event::sensor_id.eq(sensor_id))
+        .filter(event::resolved_at.is_null())
+        .select(AlertEvent::as_select())
+        .first(conn)
+        .optional()?;
+
+    Ok(res)
+}
+
+pub fn insert_alert_event(
+    conn: &mut DbConn,
+    new_event: NewAlertEvent,
+) -> Result<AlertEvent, Error> {
+    use crate::db::schema::alert_events::dsl::alert_events as alert_events_table;
+
+    let res = diesel::insert_into(alert_events_table)
+        .values(&new_event)
+        .returning(AlertEvent::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+pub fn resolve_alert_event(
+    conn: &mut DbConn,
+    event_id: i32,
+    resolved_at: NaiveDateTime,
+) -> Result<AlertEvent, Error> {
+    use crate::db::schema::{
+        alert_events::dsl as event, alert_events::dsl::alert_events as alert_events_table,
+    };
+
+    let res = diesel::update(alert_events_table)
+        .filter(event::id.eq(event_id))
+        .set(event::resolved_at.eq(resolved_at))
+        .returning(AlertEvent::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AlertEventFilter {
+    pub event_id: Option<i32>,
+    pub sensor_id: Option<i32>,
+    pub open_only: bool,
+    pub unacknowledged_only: bool,
+}
+
+/// Returns the alerts of the sensors of the user, latest first, with their rule and the device_id
+/// of their sensor
+pub fn get_alert_events(
+    conn: &mut DbConn,
+    user_id: i32,
+    filter: AlertEventFilter,
+    limit: i64,
+) -> Result<Vec<(AlertEvent, AlertRule, String)>, Error> {
+    use crate::db::schema::{
+        alert_events::dsl as event, alert_events::dsl::alert_events as alert_events_table,
+        alert_rules::dsl as rule, alert_rules::dsl::alert_rules as alert_rules_table,
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    let mut query = alert_events_table
+        .inner_join(alert_rules_table.on(event::rule_id.eq(rule::id)))
+        .inner_join(user_sensors_table.on(event::sensor_id.eq(user_sensor::id)))
+        .filter(rule::user_id.eq(user_id))
+        .into_boxed();
+
+    if let Some(event_id) = filter.event_id {
+        query = query.filter(event::id.eq(event_id));
+    }
+    if let Some(sensor_id) = filter.sensor_id {
+        query = query.filter(event::sensor_id.eq(sensor_id));
+    }
+    if filter.open_only {
+        query = query.filter(event::resolved_at.is_null());
+    }
+    if filter.unacknowledged_only {
+        query = query.filter(event::acknowledged_at.is_null());
+    }
+
+    let res = query
+        .order((event::triggered_at.desc(), event::id.desc()))
+        .limit(limit)
+        .select((
+            AlertEvent::as_select(),
+            AlertRule::as_select(),
+            user_sensor::device_id,
+        ))
+        .load(conn)?;
+
+    Ok(res)
+}
+
+/// Acknowledges the alert if it belongs to the user, acknowledging it again keeps the first time
+pub fn acknowledge_alert_event(
+    conn: &mut DbConn,
+    event_id: i32,
+    user_id: i32,
+    acknowledged_at: NaiveDateTime,
+) -> Result<(AlertEvent, AlertRule, String), Error> {
+    use crate::db::schema::{
+        alert_events::dsl as event, alert_events::dsl::alert_events as alert_events_table,
+    };
+
+    let filter = AlertEventFilter {
+        event_id: Some(event_id),
+        ..Default::default()
+    };
+    let (found, rule, device_id) = get_alert_events(conn, user_id, filter, 1)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::NotFound("Alert not found".into()))?;
+
+    if found.acknowledged_at.is_some() {
+        return Ok((found, rule, device_id));
+    }
+
+    let res = diesel::update(alert_events_table)
+        .filter(event::id.eq(event_id))
+        .set(event::acknowledged_at.eq(acknowledged_at))
+        .returning(AlertEvent::as_returning())
+        .get_result(conn)?;
+
+    Ok((res, rule, device_id))
+}
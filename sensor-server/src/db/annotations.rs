@@ -0,0 +1,173 @@
+use std::ops::Range;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+
+use crate::db::{
+    DbConn, Error,
+    model::{Annotation, NewAnnotation},
+};
+
+pub fn insert_annotation(
+    conn: &mut DbConn,
+    new_annotation: NewAnnotation,
+) -> Result<Annotation, Error> {
+    use crate::db::schema::annotations::dsl::annotations as annotations_table;
+
+    let res = diesel::insert_into(annotations_table)
+        .values(&new_annotation)
+        .returning(Annotation::as_returning())
+        .get_result(conn)?;
+
+    log::trace!("Annotation inserted: {res:?}");
+    Ok(res)
+}
+
+/// Replaces every field of the annotation of annotation.user_id
+pub fn update_annotation(
+    conn: &mut DbConn,
+    annotation_id: i32,
+    annotation: NewAnnotation,
+) -> Result<Annotation, Error> {
+    use crate::db::schema::{
+        annotations::dsl as annotation, annotations::dsl::annotations as annotations_table,
+    };
+
+    let res = diesel::update(annotations_table)
+        .filter(annotation::id.eq(annotation_id))
+        .filter(annotation::user_id.eq(annotation.user_id))
+        .set(&annotation)
+        .returning(Annotation::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+pub fn delete_annotation(
+    conn: &mut DbConn,
+    annotation_id: i32,
+    user_id: i32,
+) -> Result<Annotation, Error> {
+    use crate::db::schema::{
+        annotations::dsl as annotation, annotations::dsl::annotations as annotations_table,
+    };
+
+    let res = diesel::delete(annotations_table)
+        .filter(annotation::id.eq(annotation_id))
+        .filter(annotation::user_id.eq(user_id))
+        .returning(Annotation::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+/// An annotation with the name of its place or the device_id of its sensor
+pub type AnnotationWithTarget = (Annotation, Option<String>, Option<String>);
+
+pub fn get_annotation(
+    conn: &mut DbConn,
+    annotation_id: i32,
+    user_id: i32,
+) -> Result<AnnotationWithTarget, Error> {
+    use crate::db::schema::{
+        annotations::dsl as annotation, annotations::dsl::annotations as annotations_table,
+        user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    let res = annotations_table
+        .left_join(user_places_table.on(annotation::place_id.eq(user_place::id.nullable())))
+        .left_join(user_sensors_table.on(annotation::sensor_id.eq(user_sensor::id.nullable())))
+        .filter(annotation::id.eq(annotation_id))
+        .filter(annotation::user_id.eq(user_id))
+        .select((
+            Annotation::as_select(),
+            user_place::name.nullable(),
+            user_sensor::device_id.nullable(),
+        ))
+        .first(conn)?;
+
+    Ok(res)
+}
+
+/// Which annotations of the user are returned, the ones without a target always are
+#[derive(Debug, Clone, Copy)]
+pub enum Identifier {
+    UserId(i32),
+    /// The ones of the sensor and of its place
+    Sensor {
+        user_id: i32,
+        sensor_id: i32,
+        place_id: i32,
+    },
+    /// The ones of the place and of its sensors
+    Place {
+        user_id: i32,
+        place_id: i32,
+    },
+}
+
+/// Returns at most limit annotations overlapping range, sorted by starts_at
+pub fn get_annotations(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    range: Range<NaiveDateTime>,
+    limit: u32,
+) -> Result<Vec<AnnotationWithTarget>, Error> {
+    use crate::db::schema::{
+        annotations::dsl as annotation, annotations::dsl::annotations as annotations_table,
+        user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    let without_target = annotation::place_id
+        .is_null()
+        .and(annotation::sensor_id.is_null());
+
+    let query = annotations_table
+        .left_join(user_places_table.on(annotation::place_id.eq(user_place::id.nullable())))
+        .left_join(user_sensors_table.on(annotation::sensor_id.eq(user_sensor::id.nullable())))
+        .filter(annotation::starts_at.le(range.end))
+        .filter(
+            annotation::ends_at.ge(range.start).or(annotation::ends_at
+                .is_null()
+                .and(annotation::starts_at.ge(range.start))),
+        )
+        .into_boxed();
+
+    let query = match identifier {
+        Identifier::UserId(user_id) => query.filter(annotation::user_id.eq(user_id)),
+        Identifier::Sensor {
+            user_id,
+            sensor_id,
+            place_id,
+        } => query.filter(annotation::user_id.eq(user_id)).filter(
+            annotation::sensor_id
+                .eq(sensor_id)
+                .or(annotation::place_id.eq(place_id))
+                .or(without_target),
+        ),
+        Identifier::Place { user_id, place_id } => {
+            query.filter(annotation::user_id.eq(user_id)).filter(
+                annotation::place_id
+                    .eq(place_id)
+                    .or(user_sensor::place_id.nullable().eq(place_id))
+                    .or(without_target),
+            )
+        }
+    };
+
+    let res = query
+        .order((annotation::starts_at, annotation::id))
+        .limit(limit as i64)
+        .select((
+            Annotation::as_select(),
+            user_place::name.nullable(),
+            user_sensor::device_id.nullable(),
+        ))
+        .load(conn)?;
+
+    log::trace!("DB Returned {} annotations", res.len());
+
+    Ok(res)
+}
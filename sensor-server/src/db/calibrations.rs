@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDateTime;
+use common::endpoints_io::sensor_data::{Metric, SensorReading};
+use diesel::{pg::upsert::excluded, prelude::*};
+use serde::Deserialize;
+use serde_valid::json::json;
+
+use crate::db::{
+    DbConn, Error,
+    model::{NewSensorCalibration, SensorCalibration, SensorData},
+};
+
+/// Replaces the calibration of the metric of the sensor with the same effective_from, if any
+pub fn set_calibration(
+    conn: &mut DbConn,
+    calibration: NewSensorCalibration,
+) -> Result<SensorCalibration, Error> {
+    use crate::db::schema::{
+        sensor_calibrations::dsl as sensor_calibration,
+        sensor_calibrations::dsl::sensor_calibrations as sensor_calibrations_table,
+    };
+
+    let res = diesel::insert_into(sensor_calibrations_table)
+        .values(&calibration)
+        .on_conflict((
+            sensor_calibration::sensor_id,
+            sensor_calibration::metric,
+            sensor_calibration::effective_from,
+        ))
+        .do_update()
+        .set((
+            sensor_calibration::offset_value.eq(excluded(sensor_calibration::offset_value)),
+            sensor_calibration::scale.eq(excluded(sensor_calibration::scale)),
+        ))
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+/// Every calibration of the sensors, sorted by sensor, metric and effective_from
+pub fn get_calibrations(
+    conn: &mut DbConn,
+    sensor_ids: &[i32],
+) -> Result<Vec<SensorCalibration>, Error> {
+    use crate::db::schema::{
+        sensor_calibrations::dsl as sensor_calibration,
+        sensor_calibrations::dsl::sensor_calibrations as sensor_calibrations_table,
+    };
+
+    let res = sensor_calibrations_table
+        .filter(sensor_calibration::sensor_id.eq_any(sensor_ids))
+        .order((
+            sensor_calibration::sensor_id,
+            sensor_calibration::metric,
+            sensor_calibration::effective_from,
+        ))
+        .select(SensorCalibration::as_select())
+        .load(conn)?;
+
+    Ok(res)
+}
+
+/// The reading with, for each of its metrics, the last calibration effective at added_at applied
+/// The calibrated_data SQL function must apply them the same way
+pub fn calibrate_reading(
+    reading: &mut SensorReading,
+    added_at: NaiveDateTime,
+    calibrations: &[SensorCalibration],
+) {
+    for metric in Metric::MEASURED {
+        let effective = calibrations
+            .iter()
+            .filter(|c| c.metric == metric.key() && c.effective_from <= added_at)
+            .max_by_key(|c| c.effective_from);
+
+        if let Some(c) = effective {
+            reading.map_metric(metric, |raw| raw * c.scale + c.offset_value);
+        }
+    }
+}
+
+/// Applies the calibrations of their sensors to the stored data, meant to be called right before
+/// returning it so that what is stored stays raw
+pub fn calibrate(conn: &mut DbConn, data: &mut [SensorData]) -> Result<(), Error> {
+    let mut sensor_ids: Vec<i32> = data.iter().map(|d| d.sensor_id).collect();
+    sensor_ids.sort_unstable();
+    sensor_ids.dedup();
+    if sensor_ids.is_empty() {
+        return Ok(());
+    }
+
+    let mut by_sensor: HashMap<i32, Vec<SensorCalibration>> = HashMap::new();
+    for calibration in get_calibrations(conn, &sensor_ids)? {
+        by_sensor
+            .entry(calibration.sensor_id)
+            .or_default()
+            .push(calibration);
+    }
+
+    for datum in data {
+        let Some(calibrations) = by_sensor.get(&datum.sensor_id) else {
+            continue;
+        };
+
+        let mut reading = SensorReading::deserialize(&datum.data).map_err(|e| {
+            log::error!(
+                "Stored sensor_data ({}) is not a valid SensorReading: {e:?}",
+                datum.id
+            );
+            Error::InternalError(e.into())
+        })?;
+        calibrate_reading(&mut reading, datum.added_at, calibrations);
+        datum.data = json!(reading);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use diesel::sql_types::{Integer, Jsonb, Timestamp};
+
+    use crate::db::{
+        establish_connection,
+        sensor_data::insert_sensor_data_batch,
+        tests::{
+            create_test_user, create_test_user_place, create_test_user_sensor, new_test_sensor_data,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_calibrate() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let now = Utc::now().naive_utc();
+        let recalibrated_at = now - TimeDelta::hours(1);
+        for (metric, offset_value, scale, effective_from) in [
+            (Metric::Temperature, -1.5, 1.0, now - TimeDelta::hours(2)),
+            (Metric::Temperature, -1.0, 1.1, recalibrated_at),
+            (Metric::Co2, 12.5, 1.0, now - TimeDelta::hours(2)),
+        ] {
+            set_calibration(
+                &mut conn,
+                NewSensorCalibration {
+                    sensor_id: sensor.id,
+                    metric: metric.key().to_string(),
+                    offset_value,
+                    scale,
+                    effective_from,
+                },
+            )
+            .unwrap();
+        }
+        // Replaces the one with the same effective_from
+        let replaced = set_calibration(
+            &mut conn,
+            NewSensorCalibration {
+                sensor_id: sensor.id,
+                metric: Metric::Temperature.key().to_string(),
+                offset_value: -2.0,
+                scale: 1.0,
+                effective_from: recalibrated_at,
+            },
+        )
+        .unwrap();
+        assert_eq!(replaced.offset_value, -2.0);
+        assert_eq!(get_calibrations(&mut conn, &[sensor.id]).unwrap().len(), 3);
+
+        let reading = SensorReading {
+            co2: Some(450),
+            temperature: Some(22.0),
+            humidity: Some(40.0),
+        };
+        let mut data = insert_sensor_data_batch(
+            &mut conn,
+            [3, 2, 1, 0]
+                .into_iter()
+                .map(|hours| {
+                    new_test_sensor_data(sensor.id, now - TimeDelta::hours(hours), reading.clone())
+                })
+                .collect(),
+        )
+        .unwrap();
+        let raw = data.clone();
+        calibrate(&mut conn, &mut data).unwrap();
+
+        let calibrated: Vec<SensorReading> = data
+            .iter()
+            .map(|d| SensorReading::deserialize(&d.data).unwrap())
+            .collect();
+        // Before any calibration
+        assert_eq!(calibrated[0], reading);
+        // 462.5 rounds half to even
+        assert_eq!(calibrated[1].co2, Some(462));
+        assert_eq!(calibrated[1].temperature, Some(20.5));
+        assert_eq!(calibrated[2].temperature, Some(20.0));
+        assert_eq!(calibrated[3].temperature, Some(20.0));
+        assert_eq!(calibrated[3].humidity, Some(40.0));
+
+        // Same as the calibrated_data SQL function
+        for (raw, calibrated) in raw.iter().zip(data) {
+            let sql: serde_valid::json::Value = diesel::select(
+                diesel::dsl::sql::<Jsonb>("calibrated_data(")
+                    .bind::<Integer, _>(raw.sensor_id)
+                    .sql(", ")
+                    .bind::<Timestamp, _>(raw.added_at)
+                    .sql(", ")
+                    .bind::<Jsonb, _>(raw.data.clone())
+                    .sql(")"),
+            )
+            .get_result(&mut conn)
+            .expect("Should not fail");
+            assert_eq!(
+                SensorReading::deserialize(&sql).unwrap(),
+                SensorReading::deserialize(&calibrated.data).unwrap()
+            );
+        }
+    }
+}
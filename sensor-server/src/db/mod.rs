@@ -1,10 +1,17 @@
+pub mod alerts;
+pub mod annotations;
+pub mod calibrations;
 pub mod colors;
 pub mod model;
+pub mod notification_preferences;
+pub mod retention_policies;
 pub mod schema;
+pub mod scrape_tokens;
 pub mod sensor_data;
 pub mod user_places;
 pub mod user_sensors;
 pub mod users;
+pub mod webhooks;
 
 use dotenv::dotenv;
 use hyper::StatusCode;
@@ -104,6 +111,11 @@ impl From<diesel::ConnectionError> for Error {
     }
 }
 
+/// State of DB_POOL and the connections it can open, for the server metrics
+pub fn pool_state() -> (original_r2d2::State, u32) {
+    (DB_POOL.state(), DB_POOL.max_size())
+}
+
 pub fn establish_connection(test: bool) -> Result<DbConn, Error> {
     dotenv().expect(".env should be available and readable");
     let database_url = std::env::var("DATABASE_URL").expect("DATABASE_URL must be set");
@@ -129,18 +141,23 @@ pub mod tests {
 
     use std::ops::Range;
 
+    use chrono::NaiveDateTime;
     use common::{
         auth::keys::Keys,
+        endpoints_io::sensor_data::SensorReading,
         types::validate::{
             api_raw_password::ApiRawPassword, api_username::ApiUsername, device_id::DeviceId,
         },
     };
     use diesel::{Insertable, RunQueryDsl};
     use rand::{Rng, distr::Alphabetic};
+    use serde_valid::json::json;
 
     use crate::{
         db::DbConn,
-        db::model::{NewUser, NewUserPlace, NewUserSensor, User, UserPlace, UserSensor},
+        db::model::{
+            NewSensorData, NewUser, NewUserPlace, NewUserSensor, User, UserPlace, UserSensor,
+        },
     };
 
     pub fn random_string(range: Range<usize>) -> String {
@@ -229,4 +246,19 @@ pub mod tests {
 
         res.first().expect("Should exist").clone()
     }
+
+    /// A reading of the sensor measured at added_at, to be inserted with insert_sensor_data(_batch)
+    pub fn new_test_sensor_data(
+        sensor_id: i32,
+        added_at: NaiveDateTime,
+        reading: SensorReading,
+    ) -> NewSensorData {
+        NewSensorData {
+            sensor_id,
+            data: json!(reading),
+            added_at: Some(added_at),
+            idempotency_key: None,
+            reported_at: None,
+        }
+    }
 }
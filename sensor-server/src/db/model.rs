@@ -1,12 +1,113 @@
 use std::array::TryFromSliceError;
 
 use chrono::NaiveDateTime;
+use common::{
+    endpoints_io::{
+        sensor::ApiCalibration,
+        sensor_data::{ApiSensorData, DerivedMetrics, Metric, SensorReading},
+    },
+    types::ApiTimestamp,
+};
 use diesel::prelude::*;
 use ed25519_dalek::{SignatureError, VerifyingKey};
 use hex::FromHexError;
+use serde::Deserialize;
+
+use crate::db::Error;
 
 pub type HexValue = String;
 
+/// Threshold on a metric of a sensor or of every sensor of a place, never both
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::alert_rules)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AlertRule {
+    pub id: i32,
+    pub user_id: i32,
+    pub place_id: Option<i32>,
+    pub sensor_id: Option<i32>,
+    pub metric: String,     // Metric::key
+    pub comparison: String, // AlertComparison, see alerts::comparison_key
+    pub threshold: f64,
+    pub hysteresis: f64,
+    pub for_secs: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::alert_rules)]
+pub struct NewAlertRule {
+    pub user_id: i32,
+    pub place_id: Option<i32>,
+    pub sensor_id: Option<i32>,
+    pub metric: String,
+    pub comparison: String,
+    pub threshold: f64,
+    pub hysteresis: f64,
+    pub for_secs: i32,
+}
+
+/// Condition of a rule, what can be changed once created
+#[derive(AsChangeset, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::alert_rules)]
+pub struct AlertRuleCondition {
+    pub comparison: String,
+    pub threshold: f64,
+    pub hysteresis: f64,
+    pub for_secs: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::alert_events)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct AlertEvent {
+    pub id: i32,
+    pub rule_id: i32,
+    pub sensor_id: i32,
+    pub trigger_value: f64,
+    pub triggered_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+    pub acknowledged_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::alert_events)]
+pub struct NewAlertEvent {
+    pub rule_id: i32,
+    pub sensor_id: i32,
+    pub trigger_value: f64,
+    pub triggered_at: NaiveDateTime,
+}
+
+/// Note on the timeline of a place, of a sensor or of neither, never both
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::annotations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Annotation {
+    pub id: i32,
+    pub user_id: i32,
+    pub place_id: Option<i32>,
+    pub sensor_id: Option<i32>,
+    pub text: String,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: Option<NaiveDateTime>, // None for a point in time
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::annotations)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewAnnotation {
+    pub user_id: i32,
+    pub place_id: Option<i32>,
+    pub sensor_id: Option<i32>,
+    pub text: String,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: Option<NaiveDateTime>,
+}
+
 #[derive(Queryable, Selectable, Clone)]
 #[diesel(table_name = crate::db::schema::colors)]
 #[diesel(check_for_backend(diesel::pg::Pg))]
@@ -24,6 +125,90 @@ pub struct SensorData {
     pub sensor_id: i32,
     pub data: serde_valid::json::Value,
     pub added_at: NaiveDateTime,
+    pub idempotency_key: Option<String>,
+    pub received_at: NaiveDateTime,
+    pub reported_at: Option<NaiveDateTime>,
+    pub suspect_metrics: Vec<String>, // Metric::key
+}
+
+impl TryFrom<SensorData> for ApiSensorData {
+    type Error = Error;
+
+    fn try_from(value: SensorData) -> Result<Self, Self::Error> {
+        let id = value.id;
+        let data = SensorReading::deserialize(value.data).map_err(|e| {
+            log::error!("Stored sensor_data ({id}) is not a valid SensorReading: {e:?}");
+            Error::InternalError(e.into())
+        })?;
+
+        Ok(ApiSensorData {
+            id: id as usize,
+            derived: DerivedMetrics::from_reading(&data),
+            data,
+            suspect_metrics: value
+                .suspect_metrics
+                .iter()
+                .filter_map(|key| Metric::from_key(key))
+                .collect(),
+            added_at: value.added_at.and_utc().timestamp() as ApiTimestamp,
+        })
+    }
+}
+
+/// Aggregate of one metric over the sensor_data rows that fall in a time bucket
+#[derive(QueryableByName, Clone, Debug)]
+pub struct SensorDataBucket {
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub bucket_start: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub metric: String, // Key of the metric in SensorData::data
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub min: f64,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub max: f64,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub avg: f64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
+
+/// Aggregate of one metric over the readings of every sensor of a place that fall in a time bucket,
+/// with the sensor that read each extreme
+#[derive(QueryableByName, Clone, Debug)]
+pub struct PlaceStatsBucket {
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub bucket_start: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub metric: String, // Key of the metric in SensorData::data
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub min: f64,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub min_device_id: String,
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub min_added_at: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub max: f64,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub max_device_id: String,
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub max_added_at: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::Double)]
+    pub avg: f64,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+    /// Over the whole range instead of a bucket
+    #[diesel(sql_type = diesel::sql_types::Bool)]
+    pub total: bool,
+}
+
+/// Silence between two consecutive readings of a sensor, or between a reading and an end of the
+/// queried range
+#[derive(QueryableByName, Clone, Debug, PartialEq)]
+pub struct SensorDataGap {
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub gap_start: NaiveDateTime,
+    #[diesel(sql_type = diesel::sql_types::Timestamp)]
+    pub gap_end: NaiveDateTime,
 }
 
 #[derive(Insertable, Clone, Debug)]
@@ -32,6 +217,113 @@ pub struct NewSensorData {
     pub sensor_id: i32,
     pub data: serde_valid::json::Value,
     pub added_at: Option<NaiveDateTime>, // UNIX timestamp in seconds
+    pub idempotency_key: Option<String>,
+    pub reported_at: Option<NaiveDateTime>, // As sent by the sensor
+}
+
+/// Linear correction of a measured metric of a sensor from effective_from on
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::sensor_calibrations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct SensorCalibration {
+    pub id: i32,
+    pub sensor_id: i32,
+    pub metric: String, // Metric::key
+    pub offset_value: f64,
+    pub scale: f64,
+    pub effective_from: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, AsChangeset, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::sensor_calibrations)]
+pub struct NewSensorCalibration {
+    pub sensor_id: i32,
+    pub metric: String,
+    pub offset_value: f64,
+    pub scale: f64,
+    pub effective_from: NaiveDateTime,
+}
+
+impl TryFrom<SensorCalibration> for ApiCalibration {
+    type Error = Error;
+
+    fn try_from(value: SensorCalibration) -> Result<Self, Self::Error> {
+        let metric = Metric::from_key(&value.metric).ok_or_else(|| {
+            log::error!(
+                "Stored calibration ({}) has an unknown metric: {}",
+                value.id,
+                value.metric
+            );
+            Error::InternalError("Unknown calibration metric".into())
+        })?;
+
+        Ok(ApiCalibration {
+            metric,
+            offset: value.offset_value,
+            scale: value.scale,
+            effective_from: value.effective_from.and_utc().timestamp() as ApiTimestamp,
+        })
+    }
+}
+
+/// Retention of the sensor data of either a user or a sensor, never both
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::retention_policies)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct RetentionPolicy {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub sensor_id: Option<i32>,
+    pub keep_days: i32,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::retention_policies)]
+pub struct NewRetentionPolicy {
+    pub user_id: Option<i32>,
+    pub sensor_id: Option<i32>,
+    pub keep_days: i32,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::db::schema::notification_preferences)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct NotificationPreferences {
+    pub alert_emails: bool,
+    pub account_emails: bool,
+    pub sensor_emails: bool,
+}
+
+impl Default for NotificationPreferences {
+    /// The ones of users that never changed them
+    fn default() -> Self {
+        Self {
+            alert_emails: true,
+            account_emails: true,
+            sensor_emails: true,
+        }
+    }
+}
+
+/// None fields are left as they are
+#[derive(AsChangeset, Clone, Debug, Default)]
+#[diesel(table_name = crate::db::schema::notification_preferences)]
+pub struct NotificationPreferencesChange {
+    pub alert_emails: Option<bool>,
+    pub account_emails: Option<bool>,
+    pub sensor_emails: Option<bool>,
+}
+
+#[derive(Queryable, Selectable, Insertable, Debug, Clone, PartialEq)]
+#[diesel(table_name = crate::db::schema::scrape_tokens)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct ScrapeToken {
+    pub user_id: i32,
+    pub token_hash: String, // SHA-256 of the token, HEX encoded
+    pub created_at: NaiveDateTime,
 }
 
 #[derive(Queryable, Selectable, Debug, Clone, AsChangeset)]
@@ -70,6 +362,11 @@ pub struct UserSensor {
     pub color_id: i32,
     pub created_at: NaiveDateTime,
     pub updated_at: NaiveDateTime,
+    pub clock_skew_secs: Option<i32>,
+    pub last_seen_at: Option<NaiveDateTime>,
+    pub expected_interval_secs: i32,
+    /// Set while the heartbeat checker considers the sensor offline
+    pub offline_since: Option<NaiveDateTime>,
 }
 
 #[derive(Insertable, Clone, Debug)]
@@ -141,3 +438,74 @@ pub struct NewUser {
     pub hashed_password: String,
     pub email: String,
 }
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::webhooks)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct Webhook {
+    pub id: i32,
+    pub user_id: i32,
+    pub url: String,
+    pub secret: String,      // Hex
+    pub events: Vec<String>, // WebhookEventKind::key
+    pub enabled: bool,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::webhooks)]
+pub struct NewWebhook {
+    pub user_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<String>,
+}
+
+/// None fields are left as they are
+#[derive(AsChangeset, Clone, Debug, Default)]
+#[diesel(table_name = crate::db::schema::webhooks)]
+pub struct WebhookChange {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Queryable, Selectable, Debug, Clone)]
+#[diesel(table_name = crate::db::schema::webhook_deliveries)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub kind: String, // WebhookEventKind::key
+    pub event: serde_valid::json::Value,
+    pub status: String, // WebhookDeliveryStatus::key
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub delivered_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::webhook_deliveries)]
+pub struct NewWebhookDelivery {
+    pub webhook_id: i32,
+    pub kind: String,
+    pub event: serde_valid::json::Value,
+    pub next_attempt_at: NaiveDateTime,
+}
+
+/// Outcome of an attempt to deliver
+#[derive(AsChangeset, Clone, Debug)]
+#[diesel(table_name = crate::db::schema::webhook_deliveries)]
+#[diesel(treat_none_as_null = true)]
+pub struct WebhookDeliveryAttempt {
+    pub status: String,
+    pub attempts: i32,
+    pub last_status_code: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: Option<NaiveDateTime>,
+    pub delivered_at: Option<NaiveDateTime>,
+}
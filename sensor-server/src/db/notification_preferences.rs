@@ -0,0 +1,64 @@
+use diesel::prelude::*;
+
+use crate::db::{
+    DbConn, Error,
+    model::{NotificationPreferences, NotificationPreferencesChange},
+};
+
+/// NotificationPreferences::default if the user never changed them
+pub fn get_notification_preferences(
+    conn: &mut DbConn,
+    user_id: i32,
+) -> Result<NotificationPreferences, Error> {
+    use crate::db::schema::{
+        notification_preferences::dsl as preferences,
+        notification_preferences::dsl::notification_preferences as preferences_table,
+    };
+
+    let res = preferences_table
+        .filter(preferences::user_id.eq(user_id))
+        .select(NotificationPreferences::as_select())
+        .first(conn)
+        .optional()?;
+
+    Ok(res.unwrap_or_default())
+}
+
+pub fn update_notification_preferences(
+    conn: &mut DbConn,
+    user_id: i32,
+    change: NotificationPreferencesChange,
+) -> Result<NotificationPreferences, Error> {
+    use crate::db::schema::{
+        notification_preferences::dsl as preferences,
+        notification_preferences::dsl::notification_preferences as preferences_table,
+    };
+
+    // An empty changeset is an error for diesel
+    if change.alert_emails.is_none()
+        && change.account_emails.is_none()
+        && change.sensor_emails.is_none()
+    {
+        return get_notification_preferences(conn, user_id);
+    }
+
+    // The row is created the first time, None fields taking the defaults
+    let res = diesel::insert_into(preferences_table)
+        .values((
+            preferences::user_id.eq(user_id),
+            change.alert_emails.map(|v| preferences::alert_emails.eq(v)),
+            change
+                .account_emails
+                .map(|v| preferences::account_emails.eq(v)),
+            change
+                .sensor_emails
+                .map(|v| preferences::sensor_emails.eq(v)),
+        ))
+        .on_conflict(preferences::user_id)
+        .do_update()
+        .set(&change)
+        .returning(NotificationPreferences::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
@@ -0,0 +1,297 @@
+use std::sync::LazyLock;
+
+use chrono::NaiveDateTime;
+use diesel::{
+    prelude::*,
+    sql_query,
+    sql_types::{BigInt, Integer, Nullable, Timestamp},
+};
+
+use crate::db::{
+    DbConn, Error,
+    model::{NewRetentionPolicy, RetentionPolicy},
+};
+
+/// Server wide retention, read from the SENSOR_DATA_KEEP_DAYS env var, None keeps data forever
+pub static SERVER_KEEP_DAYS: LazyLock<Option<u32>> = LazyLock::new(|| {
+    let keep_days = std::env::var("SENSOR_DATA_KEEP_DAYS").ok()?;
+    match keep_days.parse::<u32>() {
+        Ok(0) | Err(_) => {
+            log::error!("Invalid SENSOR_DATA_KEEP_DAYS ({keep_days}), data will be kept forever");
+            None
+        }
+        Ok(days) => Some(days),
+    }
+});
+
+/// The most specific policy applies, but never beyond the server one
+pub fn effective_keep_days(
+    server_keep_days: Option<u32>,
+    user_keep_days: Option<u32>,
+    sensor_keep_days: Option<u32>,
+) -> Option<u32> {
+    match (server_keep_days, sensor_keep_days.or(user_keep_days)) {
+        (Some(server), Some(specific)) => Some(server.min(specific)),
+        (server, specific) => server.or(specific),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Identifier {
+    UserId(i32),
+    SensorId(i32),
+}
+
+pub fn get_retention_policy(
+    conn: &mut DbConn,
+    identifier: Identifier,
+) -> Result<Option<RetentionPolicy>, Error> {
+    use crate::db::schema::{
+        retention_policies::dsl as policy,
+        retention_policies::dsl::retention_policies as retention_policies_table,
+    };
+
+    let query = retention_policies_table.into_boxed();
+    let query = match identifier {
+        Identifier::UserId(id) => query.filter(policy::user_id.eq(id)),
+        Identifier::SensorId(id) => query.filter(policy::sensor_id.eq(id)),
+    };
+
+    Ok(query
+        .select(RetentionPolicy::as_select())
+        .first(conn)
+        .optional()?)
+}
+
+/// Sets the policy of the identified user or sensor, None removes it
+pub fn set_retention_policy(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    keep_days: Option<u32>,
+) -> Result<Option<RetentionPolicy>, Error> {
+    use crate::db::schema::{
+        retention_policies::dsl as policy,
+        retention_policies::dsl::retention_policies as retention_policies_table,
+    };
+
+    let Some(keep_days) = keep_days else {
+        let deleted = match identifier {
+            Identifier::UserId(id) => {
+                diesel::delete(retention_policies_table.filter(policy::user_id.eq(id)))
+                    .execute(conn)?
+            }
+            Identifier::SensorId(id) => {
+                diesel::delete(retention_policies_table.filter(policy::sensor_id.eq(id)))
+                    .execute(conn)?
+            }
+        };
+        log::trace!("Deleted {deleted} retention policies for {identifier:?}");
+        return Ok(None);
+    };
+
+    let keep_days = i32::try_from(keep_days).map_err(|e| Error::InternalError(e.into()))?;
+    let (user_id, sensor_id) = match identifier {
+        Identifier::UserId(id) => (Some(id), None),
+        Identifier::SensorId(id) => (None, Some(id)),
+    };
+    let new_policy = NewRetentionPolicy {
+        user_id,
+        sensor_id,
+        keep_days,
+    };
+
+    let insert = diesel::insert_into(retention_policies_table).values(&new_policy);
+    let res = match identifier {
+        Identifier::UserId(_) => insert
+            .on_conflict(policy::user_id)
+            .do_update()
+            .set(policy::keep_days.eq(keep_days))
+            .returning(RetentionPolicy::as_returning())
+            .get_result(conn)?,
+        Identifier::SensorId(_) => insert
+            .on_conflict(policy::sensor_id)
+            .do_update()
+            .set(policy::keep_days.eq(keep_days))
+            .returning(RetentionPolicy::as_returning())
+            .get_result(conn)?,
+    };
+
+    log::trace!("Retention policy set: {res:?}");
+    Ok(Some(res))
+}
+
+/// Returns (device_id, keep_days) of every sensor of the user, keep_days is the one set on the
+/// sensor itself
+pub fn get_user_sensors_keep_days(
+    conn: &mut DbConn,
+    user_id: i32,
+) -> Result<Vec<(String, Option<i32>)>, Error> {
+    use crate::db::schema::{
+        retention_policies::dsl as policy,
+        retention_policies::dsl::retention_policies as retention_policies_table,
+        user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    let res = user_sensors_table
+        .inner_join(user_places_table)
+        .left_join(retention_policies_table.on(policy::sensor_id.eq(user_sensor::id.nullable())))
+        .filter(user_place::user_id.eq(user_id))
+        .order(user_sensor::id)
+        .select((user_sensor::device_id, policy::keep_days.nullable()))
+        .load(conn)?;
+
+    Ok(res)
+}
+
+/// Data added before `now - effective keep_days` of its sensor, mirrors effective_keep_days
+/// $1: now, $2: server keep_days
+const EXPIRED_SENSOR_DATA: &str = "
+    FROM sensor_data d
+    JOIN user_sensors s ON s.id = d.sensor_id
+    JOIN user_places p ON p.id = s.place_id
+    LEFT JOIN retention_policies sp ON sp.sensor_id = s.id
+    LEFT JOIN retention_policies up ON up.user_id = p.user_id
+    WHERE d.added_at < $1 - make_interval(days => LEAST(COALESCE(sp.keep_days, up.keep_days), $2))";
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+pub fn count_expired_sensor_data(
+    conn: &mut DbConn,
+    now: NaiveDateTime,
+    server_keep_days: Option<u32>,
+) -> Result<usize, Error> {
+    let server_keep_days = server_keep_days.map(|d| d as i32);
+
+    let res: Count = sql_query(format!("SELECT COUNT(*) AS count {EXPIRED_SENSOR_DATA}"))
+        .bind::<Timestamp, _>(now)
+        .bind::<Nullable<Integer>, _>(server_keep_days)
+        .get_result(conn)?;
+
+    Ok(res.count as usize)
+}
+
+/// Deletes at most batch_size expired sensor data, returns how many were deleted
+pub fn delete_expired_sensor_data_batch(
+    conn: &mut DbConn,
+    now: NaiveDateTime,
+    server_keep_days: Option<u32>,
+    batch_size: i64,
+) -> Result<usize, Error> {
+    let server_keep_days = server_keep_days.map(|d| d as i32);
+
+    let deleted = sql_query(format!(
+        "DELETE FROM sensor_data WHERE id IN (SELECT d.id {EXPIRED_SENSOR_DATA} LIMIT $3)"
+    ))
+    .bind::<Timestamp, _>(now)
+    .bind::<Nullable<Integer>, _>(server_keep_days)
+    .bind::<BigInt, _>(batch_size)
+    .execute(conn)?;
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use common::endpoints_io::sensor_data::SensorReading;
+
+    use crate::db::{
+        establish_connection,
+        sensor_data::insert_sensor_data_batch,
+        tests::{
+            create_test_user, create_test_user_place, create_test_user_sensor, new_test_sensor_data,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_effective_keep_days() {
+        assert_eq!(effective_keep_days(None, None, None), None);
+        assert_eq!(effective_keep_days(Some(90), None, None), Some(90));
+        assert_eq!(effective_keep_days(None, Some(30), None), Some(30));
+        assert_eq!(effective_keep_days(None, Some(30), Some(60)), Some(60));
+        assert_eq!(effective_keep_days(Some(90), Some(30), Some(365)), Some(90));
+        assert_eq!(effective_keep_days(Some(90), Some(365), Some(7)), Some(7));
+    }
+
+    #[test]
+    fn test_set_retention_policy() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let id = Identifier::UserId(user.id);
+        assert!(get_retention_policy(&mut conn, id).unwrap().is_none());
+        set_retention_policy(&mut conn, id, Some(90)).unwrap();
+        set_retention_policy(&mut conn, id, Some(30)).unwrap();
+        assert_eq!(
+            get_retention_policy(&mut conn, id)
+                .unwrap()
+                .unwrap()
+                .keep_days,
+            30
+        );
+
+        set_retention_policy(&mut conn, Identifier::SensorId(sensor.id), Some(7)).unwrap();
+        let sensors = get_user_sensors_keep_days(&mut conn, user.id).unwrap();
+        assert_eq!(sensors, vec![(sensor.device_id.clone(), Some(7))]);
+
+        set_retention_policy(&mut conn, Identifier::SensorId(sensor.id), None).unwrap();
+        let sensors = get_user_sensors_keep_days(&mut conn, user.id).unwrap();
+        assert_eq!(sensors, vec![(sensor.device_id, None)]);
+        assert!(get_retention_policy(&mut conn, id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_expired_sensor_data() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let other_sensor = create_test_user_sensor(&mut conn, &place);
+
+        let now = Utc::now().naive_utc();
+        let new_data = [(&sensor, 1), (&sensor, 10), (&other_sensor, 10)]
+            .into_iter()
+            .map(|(s, days)| {
+                new_test_sensor_data(
+                    s.id,
+                    now - TimeDelta::days(days),
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).unwrap();
+
+        // No policies
+        assert_eq!(count_expired_sensor_data(&mut conn, now, None).unwrap(), 0);
+
+        set_retention_policy(&mut conn, Identifier::UserId(user.id), Some(5)).unwrap();
+        set_retention_policy(&mut conn, Identifier::SensorId(other_sensor.id), Some(30)).unwrap();
+        assert_eq!(count_expired_sensor_data(&mut conn, now, None).unwrap(), 1);
+        // Server one caps the sensor one
+        assert_eq!(
+            count_expired_sensor_data(&mut conn, now, Some(7)).unwrap(),
+            2
+        );
+
+        assert_eq!(
+            delete_expired_sensor_data_batch(&mut conn, now, Some(7), 1).unwrap(),
+            1
+        );
+        assert_eq!(
+            count_expired_sensor_data(&mut conn, now, Some(7)).unwrap(),
+            1
+        );
+    }
+}
@@ -1,5 +1,55 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    alert_events (id) {
+        id -> Int4,
+        rule_id -> Int4,
+        sensor_id -> Int4,
+        trigger_value -> Float8,
+        triggered_at -> Timestamp,
+        resolved_at -> Nullable<Timestamp>,
+        acknowledged_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    alert_rule_states (rule_id, sensor_id) {
+        rule_id -> Int4,
+        sensor_id -> Int4,
+        breaching_since -> Timestamp,
+    }
+}
+
+diesel::table! {
+    alert_rules (id) {
+        id -> Int4,
+        user_id -> Int4,
+        place_id -> Nullable<Int4>,
+        sensor_id -> Nullable<Int4>,
+        metric -> Text,
+        comparison -> Text,
+        threshold -> Float8,
+        hysteresis -> Float8,
+        for_secs -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    annotations (id) {
+        id -> Int4,
+        user_id -> Int4,
+        place_id -> Nullable<Int4>,
+        sensor_id -> Nullable<Int4>,
+        text -> Text,
+        starts_at -> Timestamp,
+        ends_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     colors (id) {
         id -> Int4,
@@ -8,12 +58,58 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    notification_preferences (user_id) {
+        user_id -> Int4,
+        alert_emails -> Bool,
+        account_emails -> Bool,
+        sensor_emails -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    retention_policies (id) {
+        id -> Int4,
+        user_id -> Nullable<Int4>,
+        sensor_id -> Nullable<Int4>,
+        keep_days -> Int4,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     sensor_data (id) {
         id -> Int8,
         sensor_id -> Int4,
         data -> Jsonb,
         added_at -> Timestamp,
+        idempotency_key -> Nullable<Text>,
+        received_at -> Timestamp,
+        reported_at -> Nullable<Timestamp>,
+        suspect_metrics -> Array<Text>,
+    }
+}
+
+diesel::table! {
+    scrape_tokens (user_id) {
+        user_id -> Int4,
+        token_hash -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    sensor_calibrations (id) {
+        id -> Int4,
+        sensor_id -> Int4,
+        metric -> Text,
+        offset_value -> Float8,
+        scale -> Float8,
+        effective_from -> Timestamp,
+        created_at -> Timestamp,
     }
 }
 
@@ -40,6 +136,10 @@ diesel::table! {
         color_id -> Int4,
         created_at -> Timestamp,
         updated_at -> Timestamp,
+        clock_skew_secs -> Nullable<Int4>,
+        last_seen_at -> Nullable<Timestamp>,
+        expected_interval_secs -> Int4,
+        offline_since -> Nullable<Timestamp>,
     }
 }
 
@@ -55,16 +155,72 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    webhook_deliveries (id) {
+        id -> Int4,
+        webhook_id -> Int4,
+        kind -> Text,
+        event -> Jsonb,
+        status -> Text,
+        attempts -> Int4,
+        last_status_code -> Nullable<Int4>,
+        last_error -> Nullable<Text>,
+        created_at -> Timestamp,
+        next_attempt_at -> Nullable<Timestamp>,
+        delivered_at -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    webhooks (id) {
+        id -> Int4,
+        user_id -> Int4,
+        url -> Text,
+        secret -> Text,
+        events -> Array<Text>,
+        enabled -> Bool,
+        created_at -> Timestamp,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::joinable!(alert_events -> alert_rules (rule_id));
+diesel::joinable!(alert_events -> user_sensors (sensor_id));
+diesel::joinable!(alert_rule_states -> alert_rules (rule_id));
+diesel::joinable!(alert_rule_states -> user_sensors (sensor_id));
+diesel::joinable!(alert_rules -> user_places (place_id));
+diesel::joinable!(alert_rules -> user_sensors (sensor_id));
+diesel::joinable!(alert_rules -> users (user_id));
+diesel::joinable!(annotations -> user_places (place_id));
+diesel::joinable!(annotations -> user_sensors (sensor_id));
+diesel::joinable!(annotations -> users (user_id));
+diesel::joinable!(notification_preferences -> users (user_id));
+diesel::joinable!(retention_policies -> user_sensors (sensor_id));
+diesel::joinable!(retention_policies -> users (user_id));
+diesel::joinable!(scrape_tokens -> users (user_id));
+diesel::joinable!(sensor_calibrations -> user_sensors (sensor_id));
 diesel::joinable!(sensor_data -> user_sensors (sensor_id));
 diesel::joinable!(user_places -> colors (color_id));
 diesel::joinable!(user_places -> users (user_id));
 diesel::joinable!(user_sensors -> colors (color_id));
 diesel::joinable!(user_sensors -> user_places (place_id));
+diesel::joinable!(webhook_deliveries -> webhooks (webhook_id));
+diesel::joinable!(webhooks -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    alert_events,
+    alert_rule_states,
+    alert_rules,
+    annotations,
     colors,
+    notification_preferences,
+    retention_policies,
+    scrape_tokens,
+    sensor_calibrations,
     sensor_data,
     user_places,
     user_sensors,
     users,
+    webhook_deliveries,
+    webhooks,
 );
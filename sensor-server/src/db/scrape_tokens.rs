@@ -0,0 +1,127 @@
+use chrono::Utc;
+use diesel::{prelude::*, upsert::excluded};
+use sha2::{Digest, Sha256};
+
+use crate::db::{DbConn, Error, model::ScrapeToken};
+
+pub enum Identifier<'a> {
+    UserId(i32),
+    /// As sent by the scraper, not its hash
+    Token(&'a str),
+}
+
+/// What is stored of a token, tokens are random so a fast hash is enough
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Replaces the token of the user if any, the previous one stops working
+pub fn set_scrape_token(
+    conn: &mut DbConn,
+    user_id: i32,
+    token: &str,
+) -> Result<ScrapeToken, Error> {
+    use crate::db::schema::{
+        scrape_tokens::dsl as scrape_token,
+        scrape_tokens::dsl::scrape_tokens as scrape_tokens_table,
+    };
+
+    let new_token = ScrapeToken {
+        user_id,
+        token_hash: hash_token(token),
+        created_at: Utc::now().naive_utc(),
+    };
+
+    let res = diesel::insert_into(scrape_tokens_table)
+        .values(&new_token)
+        .on_conflict(scrape_token::user_id)
+        .do_update()
+        .set((
+            scrape_token::token_hash.eq(excluded(scrape_token::token_hash)),
+            scrape_token::created_at.eq(excluded(scrape_token::created_at)),
+        ))
+        .returning(ScrapeToken::as_returning())
+        .get_result(conn)?;
+
+    log::trace!("Scrape token of user {user_id} set");
+
+    Ok(res)
+}
+
+pub fn get_scrape_token(
+    conn: &mut DbConn,
+    identifier: Identifier,
+) -> Result<Option<ScrapeToken>, Error> {
+    use crate::db::schema::{
+        scrape_tokens::dsl as scrape_token,
+        scrape_tokens::dsl::scrape_tokens as scrape_tokens_table,
+    };
+
+    let query = scrape_tokens_table.into_boxed();
+    let query = match identifier {
+        Identifier::UserId(user_id) => query.filter(scrape_token::user_id.eq(user_id)),
+        Identifier::Token(token) => query.filter(scrape_token::token_hash.eq(hash_token(token))),
+    };
+
+    Ok(query
+        .select(ScrapeToken::as_select())
+        .first(conn)
+        .optional()?)
+}
+
+/// Returns the deleted token, None if the user had none
+pub fn delete_scrape_token(conn: &mut DbConn, user_id: i32) -> Result<Option<ScrapeToken>, Error> {
+    use crate::db::schema::{
+        scrape_tokens::dsl as scrape_token,
+        scrape_tokens::dsl::scrape_tokens as scrape_tokens_table,
+    };
+
+    Ok(
+        diesel::delete(scrape_tokens_table.filter(scrape_token::user_id.eq(user_id)))
+            .returning(ScrapeToken::as_returning())
+            .get_result(conn)
+            .optional()?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{establish_connection, tests::create_test_user};
+
+    use super::*;
+
+    #[test]
+    fn test_scrape_tokens() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+
+        assert_eq!(
+            get_scrape_token(&mut conn, Identifier::UserId(user.id)).unwrap(),
+            None
+        );
+
+        let first = set_scrape_token(&mut conn, user.id, "first").unwrap();
+        assert_eq!(first.token_hash, hash_token("first"));
+        assert_eq!(
+            get_scrape_token(&mut conn, Identifier::Token("first")).unwrap(),
+            Some(first)
+        );
+
+        // Rotated
+        let second = set_scrape_token(&mut conn, user.id, "second").unwrap();
+        assert_eq!(
+            get_scrape_token(&mut conn, Identifier::Token("first")).unwrap(),
+            None
+        );
+        assert_eq!(
+            get_scrape_token(&mut conn, Identifier::UserId(user.id)).unwrap(),
+            Some(second.clone())
+        );
+
+        assert_eq!(
+            delete_scrape_token(&mut conn, user.id).unwrap(),
+            Some(second)
+        );
+        assert_eq!(delete_scrape_token(&mut conn, user.id).unwrap(), None);
+    }
+}
@@ -1,11 +1,16 @@
 use std::ops::Range;
 
-use chrono::NaiveDateTime;
-use diesel::prelude::*;
+use chrono::{DateTime, NaiveDateTime, TimeDelta};
+use common::endpoints_io::sensor_data::{BucketSize, SortOrder};
+use diesel::{
+    prelude::*,
+    sql_query,
+    sql_types::{BigInt, Bool, Double, Integer, Nullable, Text, Timestamp},
+};
 
 use crate::{
     db::{DbConn, Error},
-    db::model::{NewSensorData, SensorData},
+    db::model::{NewSensorData, PlaceStatsBucket, SensorData, SensorDataBucket, SensorDataGap},
 };
 
 pub fn insert_sensor_data(conn: &mut DbConn, new_data: NewSensorData) -> Result<SensorData, Error> {
@@ -23,29 +28,962 @@ pub fn insert_sensor_data(conn: &mut DbConn, new_data: NewSensorData) -> Result<
     Ok(data)
 }
 
+/// Inserts the data unless the sensor already stored one with the same idempotency_key, None
+/// then. Done in a single statement so that concurrent retries can't both get past the check
+pub fn insert_sensor_data_once(
+    conn: &mut DbConn,
+    new_data: NewSensorData,
+) -> Result<Option<SensorData>, Error> {
+    use crate::db::schema::sensor_data::dsl::{
+        self as sensor_data, sensor_data as sensor_data_table,
+    };
+
+    let data = diesel::insert_into(sensor_data_table)
+        .values(&new_data)
+        .on_conflict((sensor_data::sensor_id, sensor_data::idempotency_key))
+        .do_nothing()
+        .get_result::<SensorData>(conn)
+        .optional()?;
+
+    log::trace!("Data added: {data:?}");
+    Ok(data)
+}
+
+/// Inserts the data whose idempotency_key wasn't stored yet for its sensor, returns how many were
+/// inserted
+pub fn insert_sensor_data_batch_once(
+    conn: &mut DbConn,
+    new_data: Vec<NewSensorData>,
+) -> Result<usize, Error> {
+    use crate::db::schema::sensor_data::dsl::{
+        self as sensor_data, sensor_data as sensor_data_table,
+    };
+
+    if new_data.is_empty() {
+        return Ok(0);
+    }
+
+    let inserted = diesel::insert_into(sensor_data_table)
+        .values(&new_data)
+        .on_conflict((sensor_data::sensor_id, sensor_data::idempotency_key))
+        .do_nothing()
+        .execute(conn)?;
+
+    log::trace!("Batch of {inserted} data added, {} already stored", new_data.len() - inserted);
+    Ok(inserted)
+}
+
+/// Inserts all the data in a single transaction, either every datum is stored or none is
+/// Returned in the same order as new_data, RETURNING of a multi row insert doesn't guarantee it so
+/// they are inserted one by one
+pub fn insert_sensor_data_batch(
+    conn: &mut DbConn,
+    new_data: Vec<NewSensorData>,
+) -> Result<Vec<SensorData>, Error> {
+    use crate::db::schema::sensor_data::dsl::sensor_data as sensor_data_table;
+
+    if new_data.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let data = conn.transaction::<Vec<SensorData>, Error, _>(|conn| {
+        new_data
+            .iter()
+            .map(|datum| {
+                diesel::insert_into(sensor_data_table)
+                    .values(datum)
+                    .get_result(conn)
+                    .map_err(Error::from)
+            })
+            .collect()
+    })?;
+
+    log::trace!("Batch of {} data added", data.len());
+    Ok(data)
+}
+
 pub enum Identifier {
     SensorId(i32),
 }
 
+/// Position of the last datum of a page, (added_at, id) is unique so it can be used to resume
+/// the listing right after it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorDataCursor {
+    pub added_at: NaiveDateTime,
+    pub id: i64,
+}
+
+impl SensorDataCursor {
+    pub fn encode(&self) -> String {
+        hex::encode(format!(
+            "{}:{}",
+            self.added_at.and_utc().timestamp_micros(),
+            self.id
+        ))
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let decoded = String::from_utf8(hex::decode(cursor).ok()?).ok()?;
+        let (micros, id) = decoded.split_once(':')?;
+        let added_at = DateTime::from_timestamp_micros(micros.parse().ok()?)?.naive_utc();
+        Some(Self {
+            added_at,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+impl From<&SensorData> for SensorDataCursor {
+    fn from(value: &SensorData) -> Self {
+        Self {
+            added_at: value.added_at,
+            id: value.id,
+        }
+    }
+}
+
+/// Returns at most limit datums in range sorted by order, starting right after the cursor if any
+/// The returned cursor is Some only if there is more data after the returned page
 pub fn get_sensor_data(
     conn: &mut DbConn,
     identifier: Identifier,
     range: Range<NaiveDateTime>,
-) -> Result<Vec<SensorData>, Error> {
+    order: SortOrder,
+    limit: u32,
+    cursor: Option<SensorDataCursor>,
+) -> Result<(Vec<SensorData>, Option<SensorDataCursor>), Error> {
     match identifier {
         Identifier::SensorId(device_id) => {
             use crate::db::schema::{
                 sensor_data::dsl as sensor_data, sensor_data::dsl::sensor_data as sensor_data_table,
             };
 
-            let res = sensor_data_table
+            let mut query = sensor_data_table
                 .filter(sensor_data::sensor_id.eq(device_id))
                 .filter(sensor_data::added_at.between(range.start, range.end))
-                .load(conn)?;
+                .into_boxed();
+
+            query = match (order, cursor) {
+                (SortOrder::Asc, Some(c)) => query.filter(
+                    sensor_data::added_at
+                        .gt(c.added_at)
+                        .or(sensor_data::added_at
+                            .eq(c.added_at)
+                            .and(sensor_data::id.gt(c.id))),
+                ),
+                (SortOrder::Desc, Some(c)) => query.filter(
+                    sensor_data::added_at
+                        .lt(c.added_at)
+                        .or(sensor_data::added_at
+                            .eq(c.added_at)
+                            .and(sensor_data::id.lt(c.id))),
+                ),
+                (_, None) => query,
+            };
+
+            query = match order {
+                SortOrder::Asc => query.order((sensor_data::added_at.asc(), sensor_data::id.asc())),
+                SortOrder::Desc => {
+                    query.order((sensor_data::added_at.desc(), sensor_data::id.desc()))
+                }
+            };
+
+            // One more to know if there is a next page
+            let mut res: Vec<SensorData> = query.limit(limit as i64 + 1).load(conn)?;
+
+            let next_cursor = if res.len() > limit as usize {
+                res.truncate(limit as usize);
+                res.last().map(SensorDataCursor::from)
+            } else {
+                None
+            };
 
             log::trace!("DB Returned {} items", res.len());
 
+            Ok((res, next_cursor))
+        }
+    }
+}
+
+pub fn get_sensor_data_by_idempotency_key(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    idempotency_key: &str,
+) -> Result<Option<SensorData>, Error> {
+    match identifier {
+        Identifier::SensorId(sensor_id) => {
+            use crate::db::schema::{
+                sensor_data::dsl as sensor_data, sensor_data::dsl::sensor_data as sensor_data_table,
+            };
+
+            let res = sensor_data_table
+                .filter(sensor_data::sensor_id.eq(sensor_id))
+                .filter(sensor_data::idempotency_key.eq(idempotency_key))
+                .first(conn)
+                .optional()?;
+
+            Ok(res)
+        }
+    }
+}
+
+/// Returns which of the added_at are already stored for the sensor
+pub fn get_stored_added_at(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    added_at: &[NaiveDateTime],
+) -> Result<Vec<NaiveDateTime>, Error> {
+    match identifier {
+        Identifier::SensorId(sensor_id) => {
+            use crate::db::schema::{
+                sensor_data::dsl as sensor_data, sensor_data::dsl::sensor_data as sensor_data_table,
+            };
+
+            let res = sensor_data_table
+                .filter(sensor_data::sensor_id.eq(sensor_id))
+                .filter(sensor_data::added_at.eq_any(added_at))
+                .select(sensor_data::added_at)
+                .load(conn)?;
+
+            Ok(res)
+        }
+    }
+}
+
+/// Aggregates the numeric metrics of the calibrated sensor data in range into buckets of
+/// bucket_size
+/// Returns one row per bucket and metric, ordered by bucket, buckets without data are skipped
+pub fn get_sensor_data_buckets(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    range: Range<NaiveDateTime>,
+    bucket_size: BucketSize,
+    exclude_suspect: bool,
+) -> Result<Vec<SensorDataBucket>, Error> {
+    let field = match bucket_size {
+        BucketSize::Minute => "minute",
+        BucketSize::Hour => "hour",
+        BucketSize::Day => "day",
+        BucketSize::Week => "week",
+    };
+
+    match identifier {
+        Identifier::SensorId(sensor_id) => {
+            let res: Vec<SensorDataBucket> = sql_query(
+                "SELECT date_trunc($1, d.added_at) AS bucket_start,
+                        m.key AS metric,
+                        MIN((m.value #>> '{}')::float8) AS min,
+                        MAX((m.value #>> '{}')::float8) AS max,
+                        AVG((m.value #>> '{}')::float8) AS avg,
+                        COUNT(*) AS count
+                 FROM sensor_data d
+                 CROSS JOIN LATERAL calibrated_data(d.sensor_id, d.added_at, d.data) AS c(data)
+                 CROSS JOIN LATERAL jsonb_each(c.data || derived_metrics(c.data)) AS m
+                 WHERE d.sensor_id = $2
+                   AND d.added_at BETWEEN $3 AND $4
+                   AND jsonb_typeof(m.value) = 'number'
+                   AND NOT ($5 AND m.key = ANY(d.suspect_metrics))
+                 GROUP BY bucket_start, m.key
+                 ORDER BY bucket_start, m.key",
+            )
+            .bind::<Text, _>(field)
+            .bind::<Integer, _>(sensor_id)
+            .bind::<Timestamp, _>(range.start)
+            .bind::<Timestamp, _>(range.end)
+            .bind::<Bool, _>(exclude_suspect)
+            .load(conn)?;
+
+            log::trace!("DB Returned {} bucket rows", res.len());
+
+            Ok(res)
+        }
+    }
+}
+
+pub fn set_suspect_metrics(
+    conn: &mut DbConn,
+    id: i64,
+    metrics: Vec<String>,
+) -> Result<SensorData, Error> {
+    use crate::db::schema::{
+        sensor_data::dsl as sensor_data, sensor_data::dsl::sensor_data as sensor_data_table,
+    };
+
+    let res = diesel::update(sensor_data_table.filter(sensor_data::id.eq(id)))
+        .set(sensor_data::suspect_metrics.eq(metrics))
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+/// Data of a sensor to delete
+#[derive(Debug, Clone)]
+pub enum Selection<'a> {
+    Range(Range<NaiveDateTime>),
+    /// Ids of data of other sensors are ignored
+    Ids(&'a [i64]),
+}
+
+/// How many data a selection had and when the oldest and newest of them were added
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeletedSensorData {
+    pub count: usize,
+    pub oldest: Option<NaiveDateTime>,
+    pub newest: Option<NaiveDateTime>,
+}
+
+/// Deletes the selected data in a single transaction, if dry_run only returns what would be
+/// deleted
+pub fn delete_sensor_data(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    selection: Selection,
+    dry_run: bool,
+) -> Result<DeletedSensorData, Error> {
+    match identifier {
+        Identifier::SensorId(sensor_id) => {
+            use crate::db::schema::{
+                sensor_data::dsl as sensor_data, sensor_data::dsl::sensor_data as sensor_data_table,
+            };
+
+            conn.transaction::<DeletedSensorData, Error, _>(|conn| {
+                let query = sensor_data_table
+                    .filter(sensor_data::sensor_id.eq(sensor_id))
+                    .into_boxed();
+                let query = match &selection {
+                    Selection::Range(range) => {
+                        query.filter(sensor_data::added_at.between(range.start, range.end))
+                    }
+                    Selection::Ids(ids) => query.filter(sensor_data::id.eq_any(*ids)),
+                };
+                let (count, oldest, newest): (i64, Option<NaiveDateTime>, Option<NaiveDateTime>) =
+                    query
+                        .select((
+                            diesel::dsl::count_star(),
+                            diesel::dsl::min(sensor_data::added_at),
+                            diesel::dsl::max(sensor_data::added_at),
+                        ))
+                        .get_result(conn)?;
+
+                let mut res = DeletedSensorData {
+                    count: count as usize,
+                    oldest,
+                    newest,
+                };
+                if dry_run {
+                    return Ok(res);
+                }
+
+                let query = diesel::delete(sensor_data_table)
+                    .filter(sensor_data::sensor_id.eq(sensor_id))
+                    .into_boxed();
+                let query = match &selection {
+                    Selection::Range(range) => {
+                        query.filter(sensor_data::added_at.between(range.start, range.end))
+                    }
+                    Selection::Ids(ids) => query.filter(sensor_data::id.eq_any(*ids)),
+                };
+                res.count = query.execute(conn)?;
+
+                log::trace!("Deleted {} data of sensor {sensor_id}", res.count);
+
+                Ok(res)
+            })
+        }
+    }
+}
+
+/// Returns at most limit datums in range with suspect metrics, newest first
+pub fn get_suspect_sensor_data(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    range: Range<NaiveDateTime>,
+    limit: u32,
+) -> Result<Vec<SensorData>, Error> {
+    match identifier {
+        Identifier::SensorId(sensor_id) => {
+            use crate::db::schema::{
+                sensor_data::dsl as sensor_data, sensor_data::dsl::sensor_data as sensor_data_table,
+            };
+
+            let res: Vec<SensorData> = sensor_data_table
+                .filter(sensor_data::sensor_id.eq(sensor_id))
+                .filter(sensor_data::added_at.between(range.start, range.end))
+                .filter(sensor_data::suspect_metrics.ne(Vec::<String>::new()))
+                .order((sensor_data::added_at.desc(), sensor_data::id.desc()))
+                .limit(limit as i64)
+                .load(conn)?;
+
+            log::trace!("DB Returned {} suspect items", res.len());
+
             Ok(res)
         }
     }
 }
+
+/// Silences longer than a threshold between consecutive data of a sensor in range, the ends of
+/// the range count as data so that silences at them are gaps too
+/// $1: sensor_id, $2: range start, $3: range end, $4: threshold in seconds
+const SENSOR_DATA_GAPS: &str = "
+    WITH readings AS (
+        SELECT $2 AS added_at
+        UNION ALL
+        SELECT added_at FROM sensor_data WHERE sensor_id = $1 AND added_at BETWEEN $2 AND $3
+        UNION ALL
+        SELECT $3
+    ), steps AS (
+        SELECT LAG(added_at) OVER (ORDER BY added_at) AS gap_start, added_at AS gap_end
+        FROM readings
+    ), gaps AS (
+        SELECT gap_start, gap_end
+        FROM steps
+        WHERE gap_end - gap_start > make_interval(secs => $4)
+    )";
+
+#[derive(QueryableByName)]
+struct GapsSummary {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+    #[diesel(sql_type = Double)]
+    uptime: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SensorDataGaps {
+    /// Oldest first, at most the limit
+    pub gaps: Vec<SensorDataGap>,
+    pub total_gaps: usize,
+    /// Percentage of the range not in any gap
+    pub uptime: f64,
+}
+
+/// Returns the gaps longer than threshold in the data of the sensor in range, range must not be
+/// empty
+pub fn get_sensor_data_gaps(
+    conn: &mut DbConn,
+    identifier: Identifier,
+    range: Range<NaiveDateTime>,
+    threshold: TimeDelta,
+    limit: u32,
+) -> Result<SensorDataGaps, Error> {
+    match identifier {
+        Identifier::SensorId(sensor_id) => {
+            let threshold = threshold.num_milliseconds() as f64 / 1000.0;
+
+            let summary: GapsSummary = sql_query(format!(
+                "{SENSOR_DATA_GAPS}
+                 SELECT COUNT(*) AS count,
+                        (100 * (1 - COALESCE(SUM(EXTRACT(EPOCH FROM gap_end - gap_start)), 0)
+                                    / EXTRACT(EPOCH FROM $3 - $2)))::float8 AS uptime
+                 FROM gaps"
+            ))
+            .bind::<Integer, _>(sensor_id)
+            .bind::<Timestamp, _>(range.start)
+            .bind::<Timestamp, _>(range.end)
+            .bind::<Double, _>(threshold)
+            .get_result(conn)?;
+
+            let gaps: Vec<SensorDataGap> = sql_query(format!(
+                "{SENSOR_DATA_GAPS} SELECT gap_start, gap_end FROM gaps ORDER BY gap_start LIMIT $5"
+            ))
+            .bind::<Integer, _>(sensor_id)
+            .bind::<Timestamp, _>(range.start)
+            .bind::<Timestamp, _>(range.end)
+            .bind::<Double, _>(threshold)
+            .bind::<BigInt, _>(limit as i64)
+            .load(conn)?;
+
+            log::trace!(
+                "DB Returned {} of {} gaps, {}% uptime",
+                gaps.len(),
+                summary.count,
+                summary.uptime
+            );
+
+            Ok(SensorDataGaps {
+                gaps,
+                total_gaps: summary.count as usize,
+                uptime: summary.uptime,
+            })
+        }
+    }
+}
+
+/// Per metric stats of the data of every sensor of the place, ties on the extremes go to the
+/// earliest reading
+/// The totals over the whole range start at range.start, they are aggregated in the same pass as
+/// the buckets of bucket_size, if any
+pub fn get_place_stats(
+    conn: &mut DbConn,
+    place_id: i32,
+    range: Range<NaiveDateTime>,
+    bucket_size: Option<BucketSize>,
+    exclude_suspect: bool,
+) -> Result<Vec<PlaceStatsBucket>, Error> {
+    let field = bucket_size.map(|bucket_size| match bucket_size {
+        BucketSize::Minute => "minute",
+        BucketSize::Hour => "hour",
+        BucketSize::Day => "day",
+        BucketSize::Week => "week",
+    });
+
+    // date_trunc(NULL, ..) is NULL, so without buckets the bucketed set only repeats the totals
+    let res: Vec<PlaceStatsBucket> = sql_query(
+        "SELECT COALESCE(date_trunc($1, v.added_at), $3) AS bucket_start,
+                v.metric,
+                MIN(v.value) AS min,
+                (array_agg(v.device_id ORDER BY v.value, v.added_at))[1] AS min_device_id,
+                (array_agg(v.added_at ORDER BY v.value, v.added_at))[1] AS min_added_at,
+                MAX(v.value) AS max,
+                (array_agg(v.device_id ORDER BY v.value DESC, v.added_at))[1] AS max_device_id,
+                (array_agg(v.added_at ORDER BY v.value DESC, v.added_at))[1] AS max_added_at,
+                AVG(v.value) AS avg,
+                COUNT(*) AS count,
+                GROUPING(date_trunc($1, v.added_at)) = 1 AS total
+         FROM (
+             SELECT d.added_at, s.device_id, m.key AS metric, (m.value #>> '{}')::float8 AS value
+             FROM sensor_data d
+             JOIN user_sensors s ON s.id = d.sensor_id
+             CROSS JOIN LATERAL calibrated_data(d.sensor_id, d.added_at, d.data) AS c(data)
+             CROSS JOIN LATERAL jsonb_each(c.data || derived_metrics(c.data)) AS m
+             WHERE s.place_id = $2
+               AND d.added_at BETWEEN $3 AND $4
+               AND jsonb_typeof(m.value) = 'number'
+               AND NOT ($5 AND m.key = ANY(d.suspect_metrics))
+         ) v
+         GROUP BY GROUPING SETS ((v.metric), (date_trunc($1, v.added_at), v.metric))
+         HAVING $1 IS NOT NULL OR GROUPING(date_trunc($1, v.added_at)) = 1
+         ORDER BY total DESC, 1, v.metric",
+    )
+    .bind::<Nullable<Text>, _>(field)
+    .bind::<Integer, _>(place_id)
+    .bind::<Timestamp, _>(range.start)
+    .bind::<Timestamp, _>(range.end)
+    .bind::<Bool, _>(exclude_suspect)
+    .load(conn)?;
+
+    log::trace!("DB Returned {} place stats rows", res.len());
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+    use common::endpoints_io::sensor_data::{Metric, SensorReading};
+    use serde_valid::json::json;
+
+    use crate::db::{
+        establish_connection,
+        tests::{
+            create_test_user, create_test_user_place, create_test_user_sensor, new_test_sensor_data,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_insert_sensor_data_once() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let new_data = |co2: u16, idempotency_key: Option<&str>| NewSensorData {
+            sensor_id: sensor.id,
+            data: json!(SensorReading {
+                co2: Some(co2),
+                ..Default::default()
+            }),
+            added_at: None,
+            idempotency_key: idempotency_key.map(String::from),
+            reported_at: None,
+        };
+
+        let first = insert_sensor_data_once(&mut conn, new_data(400, Some("reading-1")))
+            .expect("Should not fail")
+            .expect("Should be inserted");
+        // The retry is left out instead of failing on the unique constraint
+        let retry = insert_sensor_data_once(&mut conn, new_data(500, Some("reading-1")))
+            .expect("Should not fail");
+        assert!(retry.is_none());
+
+        let stored = get_sensor_data_by_idempotency_key(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            "reading-1",
+        )
+        .expect("Should not fail");
+        assert_eq!(stored.map(|data| data.id), Some(first.id));
+
+        // Readings without a key never conflict
+        for _ in 0..2 {
+            let inserted =
+                insert_sensor_data_once(&mut conn, new_data(400, None)).expect("Should not fail");
+            assert!(inserted.is_some());
+        }
+
+        let batch = vec![
+            new_data(400, Some("reading-1")),
+            new_data(400, Some("reading-2")),
+            new_data(400, None),
+        ];
+        let inserted = insert_sensor_data_batch_once(&mut conn, batch).expect("Should not fail");
+        assert_eq!(inserted, 2);
+    }
+
+    #[test]
+    fn test_insert_sensor_data_batch() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let now = Utc::now().naive_utc();
+        let new_data: Vec<NewSensorData> = (1..=3)
+            .map(|minutes| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now - TimeDelta::minutes(minutes),
+                    SensorReading {
+                        co2: Some(400 + minutes as u16),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+
+        let inserted =
+            insert_sensor_data_batch(&mut conn, new_data.clone()).expect("Should not fail");
+        assert_eq!(inserted.len(), new_data.len());
+        // In the same order, each has a different co2
+        for (inserted, new) in inserted.iter().zip(&new_data) {
+            assert_eq!(inserted.data, new.data);
+        }
+
+        let (stored, next_cursor) = get_sensor_data(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            (now - TimeDelta::hours(1))..now,
+            SortOrder::Asc,
+            100,
+            None,
+        )
+        .expect("Should not fail");
+        assert_eq!(stored.len(), new_data.len());
+        assert!(next_cursor.is_none());
+
+        let inserted = insert_sensor_data_batch(&mut conn, vec![]).expect("Should not fail");
+        assert!(inserted.is_empty());
+    }
+
+    #[test]
+    fn test_get_sensor_data_pages() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let now = Utc::now().naive_utc();
+        // Two datums share added_at so the id tiebreak is exercised
+        let new_data: Vec<NewSensorData> = [5, 4, 4, 3, 2]
+            .into_iter()
+            .map(|minutes| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now - TimeDelta::minutes(minutes),
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        let inserted = insert_sensor_data_batch(&mut conn, new_data).expect("Should not fail");
+
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let mut expected: Vec<i64> = inserted.iter().map(|d| d.id).collect();
+            if order == SortOrder::Desc {
+                expected.reverse();
+            }
+
+            let mut got = vec![];
+            let mut cursor = None;
+            loop {
+                let (page, next_cursor) = get_sensor_data(
+                    &mut conn,
+                    Identifier::SensorId(sensor.id),
+                    (now - TimeDelta::hours(1))..now,
+                    order,
+                    2,
+                    cursor,
+                )
+                .expect("Should not fail");
+                assert!(page.len() <= 2);
+                got.extend(page.iter().map(|d| d.id));
+                match next_cursor {
+                    Some(c) => cursor = Some(c),
+                    None => break,
+                }
+            }
+
+            assert_eq!(got, expected);
+        }
+    }
+
+    #[test]
+    fn test_delete_sensor_data() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let other_sensor = create_test_user_sensor(&mut conn, &place);
+
+        let hour = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S")
+            .expect("Valid date");
+        let new_data: Vec<NewSensorData> = [(&sensor, 0), (&sensor, 10), (&sensor, 20)]
+            .into_iter()
+            .chain([(&other_sensor, 10)])
+            .map(|(s, minutes)| {
+                new_test_sensor_data(
+                    s.id,
+                    hour + TimeDelta::minutes(minutes),
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        let inserted = insert_sensor_data_batch(&mut conn, new_data).expect("Should not fail");
+
+        let range = hour..(hour + TimeDelta::minutes(15));
+        let expected = DeletedSensorData {
+            count: 2,
+            oldest: Some(hour),
+            newest: Some(hour + TimeDelta::minutes(10)),
+        };
+        let dry_run = delete_sensor_data(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            Selection::Range(range.clone()),
+            true,
+        )
+        .expect("Should not fail");
+        assert_eq!(dry_run, expected);
+
+        let deleted = delete_sensor_data(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            Selection::Range(range.clone()),
+            false,
+        )
+        .expect("Should not fail");
+        assert_eq!(deleted, expected);
+
+        // The other sensor keeps its data and ids of other sensors are ignored
+        let ids: Vec<i64> = inserted.iter().map(|d| d.id).collect();
+        let deleted = delete_sensor_data(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            Selection::Ids(&ids),
+            false,
+        )
+        .expect("Should not fail");
+        assert_eq!(deleted.count, 1);
+        assert_eq!(deleted.newest, Some(hour + TimeDelta::minutes(20)));
+
+        let (left, _) = get_sensor_data(
+            &mut conn,
+            Identifier::SensorId(other_sensor.id),
+            range,
+            SortOrder::Asc,
+            100,
+            None,
+        )
+        .expect("Should not fail");
+        assert_eq!(left.len(), 1);
+    }
+
+    #[test]
+    fn test_get_sensor_data_gaps() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let hour = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S")
+            .expect("Valid date");
+        let new_data: Vec<NewSensorData> = [0, 1, 2, 10, 11]
+            .into_iter()
+            .map(|minutes| {
+                new_test_sensor_data(
+                    sensor.id,
+                    hour + TimeDelta::minutes(minutes),
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).expect("Should not fail");
+
+        // Silent for 5 minutes before the first one, 8 in between and 9 after the last one
+        let range = (hour - TimeDelta::minutes(5))..(hour + TimeDelta::minutes(20));
+        let gaps = get_sensor_data_gaps(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            range.clone(),
+            TimeDelta::minutes(2),
+            2,
+        )
+        .expect("Should not fail");
+        assert_eq!(gaps.total_gaps, 3);
+        assert!((gaps.uptime - 100.0 * 3.0 / 25.0).abs() < 1e-9);
+        assert_eq!(
+            gaps.gaps,
+            vec![
+                SensorDataGap {
+                    gap_start: range.start,
+                    gap_end: hour,
+                },
+                SensorDataGap {
+                    gap_start: hour + TimeDelta::minutes(2),
+                    gap_end: hour + TimeDelta::minutes(10),
+                },
+            ]
+        );
+
+        // Nothing in range
+        let range = (hour + TimeDelta::hours(1))..(hour + TimeDelta::hours(2));
+        let gaps = get_sensor_data_gaps(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            range.clone(),
+            TimeDelta::minutes(2),
+            10,
+        )
+        .expect("Should not fail");
+        assert_eq!(gaps.total_gaps, 1);
+        assert_eq!(gaps.uptime, 0.0);
+        assert_eq!(
+            gaps.gaps,
+            vec![SensorDataGap {
+                gap_start: range.start,
+                gap_end: range.end,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_sensor_data_cursor_encoding() {
+        // Postgres timestamps have microsecond precision, as does the cursor
+        let cursor = SensorDataCursor {
+            added_at: DateTime::from_timestamp_micros(1_760_000_000_123_456)
+                .unwrap()
+                .naive_utc(),
+            id: 1234,
+        };
+        assert_eq!(SensorDataCursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(SensorDataCursor::decode("not a cursor"), None);
+        assert_eq!(SensorDataCursor::decode(&hex::encode("12:")), None);
+    }
+
+    #[test]
+    fn test_get_sensor_data_buckets() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let hour = NaiveDateTime::parse_from_str("2025-01-01 10:00:00", "%Y-%m-%d %H:%M:%S")
+            .expect("Valid date");
+
+        let readings = [
+            (0, Some(400), Some(20.0)),
+            (10, Some(600), None),
+            (20, Some(800), Some(22.0)),
+            (70, Some(1000), Some(25.0)),
+        ];
+
+        let new_data = readings
+            .into_iter()
+            .map(|(minutes, co2, temperature)| {
+                new_test_sensor_data(
+                    sensor.id,
+                    hour + TimeDelta::minutes(minutes),
+                    SensorReading {
+                        co2,
+                        temperature,
+                        humidity: None,
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).expect("Should not fail");
+
+        let buckets = get_sensor_data_buckets(
+            &mut conn,
+            Identifier::SensorId(sensor.id),
+            hour..(hour + TimeDelta::hours(3)),
+            BucketSize::Hour,
+            false,
+        )
+        .expect("Should not fail");
+
+        // 2 hours with data, each with co2 and temperature
+        assert_eq!(buckets.len(), 4);
+
+        let first_co2 = &buckets[0];
+        assert_eq!(first_co2.bucket_start, hour);
+        assert_eq!(first_co2.metric, "co2");
+        assert_eq!(first_co2.count, 3);
+        assert_eq!(first_co2.min, 400.0);
+        assert_eq!(first_co2.max, 800.0);
+        assert_eq!(first_co2.avg, 600.0);
+
+        let first_temperature = &buckets[1];
+        assert_eq!(first_temperature.metric, "temperature");
+        assert_eq!(first_temperature.count, 2);
+        assert_eq!(first_temperature.avg, 21.0);
+
+        assert_eq!(buckets[2].bucket_start, hour + TimeDelta::hours(1));
+        assert_eq!(buckets[2].count, 1);
+    }
+
+    #[test]
+    fn test_derived_metrics_sql() {
+        let mut conn = establish_connection(true).unwrap();
+
+        for (temperature, humidity) in [
+            (21.5f32, 45.0f32),
+            (-10.0, 90.0),
+            (35.0, 100.0),
+            (20.0, 0.0),
+        ] {
+            let reading = SensorReading {
+                co2: Some(400),
+                temperature: Some(temperature),
+                humidity: Some(humidity),
+            };
+            let derived: serde_valid::json::Value = diesel::select(
+                diesel::dsl::sql::<diesel::sql_types::Jsonb>("derived_metrics(")
+                    .bind::<diesel::sql_types::Jsonb, _>(json!(reading))
+                    .sql(")"),
+            )
+            .get_result(&mut conn)
+            .expect("Should not fail");
+
+            // Same formulas as common::psychrometrics
+            for metric in Metric::ALL.into_iter().filter(Metric::is_derived) {
+                let sql = derived.get(metric.key()).and_then(|v| v.as_f64());
+                match (sql, reading.metric(metric)) {
+                    (Some(sql), Some(rust)) => assert!((sql - rust).abs() < 1e-9, "{metric:?}"),
+                    (sql, rust) => assert_eq!(sql, rust, "{metric:?}"),
+                }
+            }
+        }
+    }
+}
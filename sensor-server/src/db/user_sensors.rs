@@ -1,5 +1,6 @@
-use std::array::TryFromSliceError;
+use std::{array::TryFromSliceError, collections::HashMap};
 
+use chrono::{DateTime, NaiveDateTime, Utc};
 use common::{endpoints_io::sensor::SensorChange, types::validate::device_id::DeviceId};
 use diesel::prelude::*;
 use ed25519_dalek::{Signature, VerifyingKey};
@@ -7,8 +8,8 @@ use ed25519_dalek::{Signature, VerifyingKey};
 use crate::{
     auth::sensor_claims::SensorClaims,
     db::{
-        self, DbConn, Error, colors,
-        model::{NewUserSensor, SensorData, UserPlace, UserSensor},
+        self, DbConn, Error, calibrations, colors,
+        model::{NewSensorCalibration, NewUserSensor, SensorData, UserPlace, UserSensor},
         user_places, users,
     },
 };
@@ -123,68 +124,68 @@ pub enum Identifier<'a> {
     SensorDeviceId(AuthorizedSensor),
 }
 
+/// Apart from Identifier so that every sensor of a user can be listed but never deleted at once
+pub enum ListIdentifier<'a> {
+    Sensors(Identifier<'a>),
+    UserId(i32),
+}
+
+impl<'a> From<Identifier<'a>> for ListIdentifier<'a> {
+    fn from(identifier: Identifier<'a>) -> Self {
+        ListIdentifier::Sensors(identifier)
+    }
+}
+
+/// Sensors with their place and last datum, two queries however many sensors there are
 pub fn get_user_sensor_and_place_and_last_data(
     conn: &mut DbConn,
-    identifier: Identifier,
+    identifier: ListIdentifier,
 ) -> Result<Vec<(UserPlace, UserSensor, Option<SensorData>)>, Error> {
-    let res = match identifier {
-        Identifier::SensorDeviceId(auth_sensor) => {
-            use crate::db::schema::user_places::dsl::user_places as user_places_table;
-            use crate::db::schema::{
-                user_sensors::dsl as user_sensor,
-                user_sensors::dsl::user_sensors as user_sensors_table,
-            };
-
-            let sensor = auth_sensor.get();
-
-            let res: Vec<(UserPlace, UserSensor)> = user_sensors_table
-                .filter(user_sensor::device_id.eq(sensor.device_id))
-                .inner_join(user_places_table)
-                .select((
-                    db::model::UserPlace::as_select(),
-                    db::model::UserSensor::as_select(),
-                ))
-                .load(conn)?;
-
-            res
-        }
-        Identifier::PlaceNameAndUserId(name, user_id) => {
-            use crate::db::schema::user_sensors::dsl::user_sensors as user_sensors_table;
-            use crate::db::schema::{
-                user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
-            };
-
-            let res = user_places_table
-                .filter(user_place::name.eq(name))
-                .filter(user_place::user_id.eq(user_id))
-                .inner_join(user_sensors_table)
-                .select((
-                    db::model::UserPlace::as_select(),
-                    db::model::UserSensor::as_select(),
-                ))
-                .load(conn)?;
-
-            res
-        }
-    };
-
     use crate::db::schema::{
         sensor_data::dsl as sensor_datum, sensor_data::dsl::sensor_data as sensor_data_table,
+        user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
     };
 
-    let mut resp = vec![];
-    for (place, sensor) in res {
-        let data = sensor_data_table
-            .filter(sensor_datum::sensor_id.eq(sensor.id))
-            .order(sensor_datum::added_at.desc())
-            .limit(1)
-            .load::<SensorData>(conn)?
-            .into_iter()
-            .next();
+    let query = user_sensors_table
+        .inner_join(user_places_table)
+        .select((
+            db::model::UserPlace::as_select(),
+            db::model::UserSensor::as_select(),
+        ))
+        .into_boxed();
+    let query = match identifier {
+        ListIdentifier::Sensors(Identifier::SensorDeviceId(auth_sensor)) => {
+            query.filter(user_sensor::device_id.eq(auth_sensor.get().device_id))
+        }
+        ListIdentifier::Sensors(Identifier::PlaceNameAndUserId(name, user_id)) => query
+            .filter(user_place::name.eq(name))
+            .filter(user_place::user_id.eq(user_id)),
+        ListIdentifier::UserId(user_id) => query.filter(user_place::user_id.eq(user_id)),
+    };
+    let res: Vec<(UserPlace, UserSensor)> = query.order(user_sensor::id).load(conn)?;
+
+    let sensor_ids: Vec<i32> = res.iter().map(|(_, sensor)| sensor.id).collect();
+    let mut last_data: HashMap<i32, SensorData> = sensor_data_table
+        .filter(sensor_datum::sensor_id.eq_any(&sensor_ids))
+        .distinct_on(sensor_datum::sensor_id)
+        .order((
+            sensor_datum::sensor_id,
+            sensor_datum::added_at.desc(),
+            sensor_datum::id.desc(),
+        ))
+        .load::<SensorData>(conn)?
+        .into_iter()
+        .map(|datum| (datum.sensor_id, datum))
+        .collect();
 
-        resp.push((place, sensor, data));
-    }
-    Ok(resp)
+    Ok(res
+        .into_iter()
+        .map(|(place, sensor)| {
+            let data = last_data.remove(&sensor.id);
+            (place, sensor, data)
+        })
+        .collect())
 }
 
 pub type Update = SensorChange;
@@ -219,11 +220,40 @@ pub fn update_user_sensor(
         SensorChange::Color(api_color) => {
             sensor.color_id = colors::get_color_id(conn, colors::Identifier::Hex(api_color.into()))?
         }
+        SensorChange::ExpectedInterval(secs) => sensor.expected_interval_secs = secs as i32,
+        SensorChange::Calibration(calibration) => {
+            let effective_from = match calibration.effective_from {
+                Some(timestamp) => DateTime::from_timestamp(timestamp as i64, 0)
+                    .ok_or_else(|| {
+                        Error::InternalError(format!("Invalid effective_from: {timestamp}").into())
+                    })?
+                    .naive_utc(),
+                None => Utc::now().naive_utc(),
+            };
+            calibrations::set_calibration(
+                conn,
+                NewSensorCalibration {
+                    sensor_id: sensor.id,
+                    metric: calibration.metric.key().to_string(),
+                    offset_value: calibration.offset,
+                    scale: calibration.scale.unwrap_or(1.0),
+                    effective_from,
+                },
+            )?;
+        }
     }
 
+    // None isn't updated, the skew is only set by set_clock_skew and the heartbeat by
+    // set_last_seen and set_offline_since
+    let changes = UserSensor {
+        clock_skew_secs: None,
+        last_seen_at: None,
+        offline_since: None,
+        ..sensor.clone()
+    };
     let rows = diesel::update(user_sensors_table)
         .filter(user_sensor::id.eq(sensor.id))
-        .set(&sensor)
+        .set(&changes)
         .execute(conn)?;
 
     if rows == 0 {
@@ -235,6 +265,88 @@ pub fn update_user_sensor(
     Ok(sensor)
 }
 
+/// Stores how many seconds the clock of the sensor is ahead of the server
+pub fn set_clock_skew(
+    conn: &mut DbConn,
+    sensor_id: i32,
+    clock_skew_secs: i32,
+) -> Result<(), Error> {
+    use crate::db::schema::{
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    diesel::update(user_sensors_table)
+        .filter(user_sensor::id.eq(sensor_id))
+        .set(user_sensor::clock_skew_secs.eq(clock_skew_secs))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Moves last_seen_at of the sensor forward to seen_at
+pub fn set_last_seen(
+    conn: &mut DbConn,
+    sensor_id: i32,
+    seen_at: NaiveDateTime,
+) -> Result<(), Error> {
+    use crate::db::schema::{
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    diesel::update(user_sensors_table)
+        .filter(user_sensor::id.eq(sensor_id))
+        .filter(
+            user_sensor::last_seen_at
+                .is_null()
+                .or(user_sensor::last_seen_at.lt(seen_at)),
+        )
+        .set(user_sensor::last_seen_at.eq(seen_at))
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// Sensors that reported at least once, the ones the heartbeat checker looks at
+pub fn get_seen_user_sensors(conn: &mut DbConn) -> Result<Vec<UserSensor>, Error> {
+    use crate::db::schema::{
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    let res = user_sensors_table
+        .filter(user_sensor::last_seen_at.is_not_null())
+        .order(user_sensor::id)
+        .select(UserSensor::as_select())
+        .load(conn)?;
+
+    Ok(res)
+}
+
+/// Marks the sensor offline since then, or back online with None
+/// Returns whether it changed, so that a transition is only handled once
+pub fn set_offline_since(
+    conn: &mut DbConn,
+    sensor_id: i32,
+    offline_since: Option<NaiveDateTime>,
+) -> Result<bool, Error> {
+    use crate::db::schema::{
+        user_sensors::dsl as user_sensor, user_sensors::dsl::user_sensors as user_sensors_table,
+    };
+
+    let query = diesel::update(user_sensors_table).filter(user_sensor::id.eq(sensor_id));
+    let rows = match offline_since {
+        Some(_) => query
+            .filter(user_sensor::offline_since.is_null())
+            .set(user_sensor::offline_since.eq(offline_since))
+            .execute(conn)?,
+        None => query
+            .filter(user_sensor::offline_since.is_not_null())
+            .set(user_sensor::offline_since.eq(offline_since))
+            .execute(conn)?,
+    };
+
+    Ok(rows > 0)
+}
+
 pub fn delete_user_sensor(
     conn: &mut DbConn,
     identifier: Identifier,
@@ -301,13 +413,18 @@ pub fn delete_user_sensor(
 #[cfg(test)]
 mod tests {
 
-    use common::auth::keys::Keys;
+    use chrono::TimeDelta;
+    use common::{auth::keys::Keys, endpoints_io::sensor_data::SensorReading};
 
     use crate::{
         db::model::NewUserSensor,
         db::{
             establish_connection,
-            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+            sensor_data::insert_sensor_data_batch,
+            tests::{
+                create_test_user, create_test_user_place, create_test_user_sensor,
+                new_test_sensor_data,
+            },
         },
     };
 
@@ -339,6 +456,30 @@ mod tests {
         assert_eq!(i_up.place_id, new_us.place_id);
     }
 
+    #[test]
+    fn test_set_clock_skew() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let device_id = DeviceId::from_string(&sensor.device_id).unwrap();
+        assert_eq!(sensor.clock_skew_secs, None);
+
+        set_clock_skew(&mut conn, sensor.id, -90).expect("Should not fail");
+
+        // Not overwritten by other updates
+        let auth_sensor = AuthorizedSensor::from_username(&mut conn, &device_id, &user.username)
+            .expect("Should be authorized");
+        let change = SensorChange::Name("renamed".to_string().into());
+        update_user_sensor(&mut conn, auth_sensor, change, user.id).expect("Should not fail");
+
+        let sensor = AuthorizedSensor::from_username(&mut conn, &device_id, &user.username)
+            .unwrap()
+            .get();
+        assert_eq!(sensor.name, "renamed");
+        assert_eq!(sensor.clock_skew_secs, Some(-90));
+    }
+
     #[test]
     fn test_get_sensor() {
         let mut conn = establish_connection(true).unwrap();
@@ -348,7 +489,7 @@ mod tests {
 
         let _p1 = get_user_sensor_and_place_and_last_data(
             &mut conn,
-            Identifier::PlaceNameAndUserId(&place.name, user.id),
+            Identifier::PlaceNameAndUserId(&place.name, user.id).into(),
         )
         .expect("No error");
 
@@ -359,6 +500,49 @@ mod tests {
         // .expect("No errror");
     }
 
+    #[test]
+    fn test_get_user_sensors_last_data() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let other_place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let silent_sensor = create_test_user_sensor(&mut conn, &other_place);
+
+        let now = Utc::now().naive_utc();
+        let inserted = insert_sensor_data_batch(
+            &mut conn,
+            (1..=3)
+                .map(|minutes_ago| {
+                    new_test_sensor_data(
+                        sensor.id,
+                        now - TimeDelta::minutes(minutes_ago),
+                        SensorReading {
+                            co2: Some(400),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        )
+        .unwrap();
+
+        let res =
+            get_user_sensor_and_place_and_last_data(&mut conn, ListIdentifier::UserId(user.id))
+                .expect("No error");
+        let res: Vec<(i32, i32, Option<i64>)> = res
+            .into_iter()
+            .map(|(place, sensor, data)| (place.id, sensor.id, data.map(|d| d.id)))
+            .collect();
+        assert_eq!(
+            res,
+            vec![
+                (place.id, sensor.id, Some(inserted[0].id)),
+                (other_place.id, silent_sensor.id, None),
+            ]
+        );
+    }
+
     #[test]
     fn test_delete_sensor() {
         let mut conn = establish_connection(true).unwrap();
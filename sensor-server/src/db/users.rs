@@ -10,6 +10,8 @@ pub enum Identifier<'a> {
     Id(i32),
     Username(&'a str),
     Email(&'a str),
+    /// The owner of the place
+    PlaceId(i32),
 }
 
 pub fn insert_user(conn: &mut DbConn, new_user: NewUser) -> Result<User, Error> {
@@ -39,6 +41,17 @@ pub fn get_user(conn: &mut DbConn, identifier: Identifier) -> Result<User, Error
             .filter(user::email.eq(email))
             .select(User::as_select())
             .first::<User>(conn)?,
+        Identifier::PlaceId(place_id) => {
+            use crate::db::schema::{
+                user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
+            };
+
+            users_table
+                .inner_join(user_places_table)
+                .filter(user_place::id.eq(place_id))
+                .select(User::as_select())
+                .first::<User>(conn)?
+        }
     };
     Ok(r)
 }
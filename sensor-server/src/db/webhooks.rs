@@ -0,0 +1,217 @@
+use chrono::NaiveDateTime;
+use common::endpoints_io::webhook::WebhookDeliveryStatus;
+use diesel::prelude::*;
+
+use crate::db::{
+    DbConn, Error,
+    model::{
+        NewWebhook, NewWebhookDelivery, Webhook, WebhookChange, WebhookDelivery,
+        WebhookDeliveryAttempt,
+    },
+};
+
+pub fn insert_webhook(conn: &mut DbConn, new_webhook: NewWebhook) -> Result<Webhook, Error> {
+    use crate::db::schema::webhooks::dsl::webhooks as webhooks_table;
+
+    let res = diesel::insert_into(webhooks_table)
+        .values(&new_webhook)
+        .returning(Webhook::as_returning())
+        .get_result(conn)?;
+
+    log::trace!("Webhook inserted: {}", res.id);
+    Ok(res)
+}
+
+pub fn get_webhooks(conn: &mut DbConn, user_id: i32) -> Result<Vec<Webhook>, Error> {
+    use crate::db::schema::{webhooks::dsl as webhook, webhooks::dsl::webhooks as webhooks_table};
+
+    let res = webhooks_table
+        .filter(webhook::user_id.eq(user_id))
+        .order(webhook::id)
+        .select(Webhook::as_select())
+        .load(conn)?;
+
+    Ok(res)
+}
+
+pub fn get_webhook(conn: &mut DbConn, webhook_id: i32, user_id: i32) -> Result<Webhook, Error> {
+    use crate::db::schema::{webhooks::dsl as webhook, webhooks::dsl::webhooks as webhooks_table};
+
+    let res = webhooks_table
+        .filter(webhook::id.eq(webhook_id))
+        .filter(webhook::user_id.eq(user_id))
+        .select(Webhook::as_select())
+        .first(conn)?;
+
+    Ok(res)
+}
+
+/// Enabled webhooks of the owner of the place
+pub fn get_place_webhooks(conn: &mut DbConn, place_id: i32) -> Result<Vec<Webhook>, Error> {
+    use crate::db::schema::{
+        user_places::dsl as user_place, user_places::dsl::user_places as user_places_table,
+        webhooks::dsl as webhook, webhooks::dsl::webhooks as webhooks_table,
+    };
+
+    let res = webhooks_table
+        .inner_join(user_places_table.on(user_place::user_id.eq(webhook::user_id)))
+        .filter(user_place::id.eq(place_id))
+        .filter(webhook::enabled.eq(true))
+        .order(webhook::id)
+        .select(Webhook::as_select())
+        .load(conn)?;
+
+    Ok(res)
+}
+
+pub fn update_webhook(
+    conn: &mut DbConn,
+    webhook_id: i32,
+    user_id: i32,
+    change: WebhookChange,
+) -> Result<Webhook, Error> {
+    use crate::db::schema::{webhooks::dsl as webhook, webhooks::dsl::webhooks as webhooks_table};
+
+    // An empty changeset is an error for diesel
+    if change.url.is_none() && change.events.is_none() && change.enabled.is_none() {
+        return get_webhook(conn, webhook_id, user_id);
+    }
+
+    let res = diesel::update(webhooks_table)
+        .filter(webhook::id.eq(webhook_id))
+        .filter(webhook::user_id.eq(user_id))
+        .set(&change)
+        .returning(Webhook::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+pub fn delete_webhook(conn: &mut DbConn, webhook_id: i32, user_id: i32) -> Result<Webhook, Error> {
+    use crate::db::schema::{webhooks::dsl as webhook, webhooks::dsl::webhooks as webhooks_table};
+
+    let res = diesel::delete(webhooks_table)
+        .filter(webhook::id.eq(webhook_id))
+        .filter(webhook::user_id.eq(user_id))
+        .returning(Webhook::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+pub fn insert_webhook_deliveries(
+    conn: &mut DbConn,
+    new_deliveries: Vec<NewWebhookDelivery>,
+) -> Result<Vec<WebhookDelivery>, Error> {
+    use crate::db::schema::webhook_deliveries::dsl::webhook_deliveries as deliveries_table;
+
+    let res = diesel::insert_into(deliveries_table)
+        .values(&new_deliveries)
+        .returning(WebhookDelivery::as_returning())
+        .get_results(conn)?;
+
+    Ok(res)
+}
+
+/// Pending deliveries due at now, with their webhook. They are postponed to lease_until so that
+/// they aren't claimed again while being attempted
+pub fn claim_due_webhook_deliveries(
+    conn: &mut DbConn,
+    now: NaiveDateTime,
+    lease_until: NaiveDateTime,
+    limit: i64,
+) -> Result<Vec<(WebhookDelivery, Webhook)>, Error> {
+    use crate::db::schema::{
+        webhook_deliveries::dsl as delivery,
+        webhook_deliveries::dsl::webhook_deliveries as deliveries_table,
+        webhooks::dsl::webhooks as webhooks_table,
+    };
+
+    conn.transaction(|conn| {
+        let ids: Vec<i32> = deliveries_table
+            .filter(delivery::status.eq(WebhookDeliveryStatus::Pending.key()))
+            .filter(delivery::next_attempt_at.le(now))
+            .order(delivery::next_attempt_at)
+            .limit(limit)
+            .select(delivery::id)
+            .for_update()
+            .skip_locked()
+            .load(conn)?;
+
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        diesel::update(deliveries_table)
+            .filter(delivery::id.eq_any(&ids))
+            .set(delivery::next_attempt_at.eq(lease_until))
+            .execute(conn)?;
+
+        let res = deliveries_table
+            .inner_join(webhooks_table)
+            .filter(delivery::id.eq_any(&ids))
+            .order(delivery::id)
+            .select((WebhookDelivery::as_select(), Webhook::as_select()))
+            .load(conn)?;
+
+        Ok(res)
+    })
+}
+
+pub fn record_webhook_delivery_attempt(
+    conn: &mut DbConn,
+    delivery_id: i32,
+    attempt: WebhookDeliveryAttempt,
+) -> Result<WebhookDelivery, Error> {
+    use crate::db::schema::{
+        webhook_deliveries::dsl as delivery,
+        webhook_deliveries::dsl::webhook_deliveries as deliveries_table,
+    };
+
+    let res = diesel::update(deliveries_table)
+        .filter(delivery::id.eq(delivery_id))
+        .set(&attempt)
+        .returning(WebhookDelivery::as_returning())
+        .get_result(conn)?;
+
+    Ok(res)
+}
+
+/// Latest deliveries of the webhook first
+pub fn get_webhook_deliveries(
+    conn: &mut DbConn,
+    webhook_id: i32,
+    limit: i64,
+) -> Result<Vec<WebhookDelivery>, Error> {
+    use crate::db::schema::{
+        webhook_deliveries::dsl as delivery,
+        webhook_deliveries::dsl::webhook_deliveries as deliveries_table,
+    };
+
+    let res = deliveries_table
+        .filter(delivery::webhook_id.eq(webhook_id))
+        .order((delivery::created_at.desc(), delivery::id.desc()))
+        .limit(limit)
+        .select(WebhookDelivery::as_select())
+        .load(conn)?;
+
+    Ok(res)
+}
+
+/// Deletes the deliveries created before that are not pending anymore, returns how many
+pub fn delete_finished_webhook_deliveries(
+    conn: &mut DbConn,
+    before: NaiveDateTime,
+) -> Result<usize, Error> {
+    use crate::db::schema::{
+        webhook_deliveries::dsl as delivery,
+        webhook_deliveries::dsl::webhook_deliveries as deliveries_table,
+    };
+
+    let res = diesel::delete(deliveries_table)
+        .filter(delivery::status.ne(WebhookDeliveryStatus::Pending.key()))
+        .filter(delivery::created_at.lt(before))
+        .execute(conn)?;
+
+    Ok(res)
+}
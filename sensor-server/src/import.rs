@@ -0,0 +1,363 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use common::{
+    endpoints_io::sensor_data::{
+        ExportFormat, ImportRowError, Metric, PostSensorDataImportResponse, SensorReading,
+    },
+    types::validate::device_id::DeviceId,
+};
+use diesel::Connection;
+use serde::Deserialize;
+use serde_valid::{Validate, json::FromJsonStr, json::json};
+
+use crate::db::{
+    DbConn, Error, establish_connection,
+    model::NewSensorData,
+    sensor_data::{Identifier, get_stored_added_at, insert_sensor_data_batch_once},
+    user_sensors::AuthorizedSensor,
+};
+
+/// Rows inserted per transaction
+pub const IMPORT_CHUNK_SIZE: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub line: usize,
+    pub added_at: NaiveDateTime,
+    pub data: SensorReading,
+}
+
+/// Timestamp of an imported row, in any ExportTimestampFormat
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RowTimestamp {
+    Unix(i64),
+    Text(String),
+}
+
+impl RowTimestamp {
+    fn parse(self) -> Result<NaiveDateTime, String> {
+        match self {
+            RowTimestamp::Unix(secs) => DateTime::from_timestamp(secs, 0)
+                .map(|dt| dt.naive_utc())
+                .ok_or_else(|| format!("Invalid timestamp: {secs}")),
+            RowTimestamp::Text(text) => match text.parse::<i64>() {
+                Ok(secs) => RowTimestamp::Unix(secs).parse(),
+                Err(_) => DateTime::parse_from_rfc3339(&text)
+                    .map(|dt| dt.naive_utc())
+                    .map_err(|e| format!("Invalid timestamp ({text}): {e}")),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+struct NdjsonRow {
+    added_at: RowTimestamp,
+    #[serde(flatten)]
+    #[validate]
+    data: SensorReading,
+}
+
+fn parse_csv_metric(
+    reading: &mut SensorReading,
+    metric: Metric,
+    value: &str,
+) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+
+    let invalid = |e: &dyn std::fmt::Display| format!("Invalid {} ({value}): {e}", metric.key());
+    match metric {
+        Metric::Co2 => reading.co2 = Some(value.parse().map_err(|e| invalid(&e))?),
+        Metric::Temperature => reading.temperature = Some(value.parse().map_err(|e| invalid(&e))?),
+        Metric::Humidity => reading.humidity = Some(value.parse().map_err(|e| invalid(&e))?),
+        // Computed from the measured ones, ignored so that exports including them can be imported
+        Metric::DewPoint | Metric::AbsoluteHumidity | Metric::Humidex => (),
+    }
+    Ok(())
+}
+
+fn parse_csv(input: &str) -> Result<Vec<Result<ImportRow, ImportRowError>>, String> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()));
+
+    let (_, header) = lines.next().ok_or("Missing CSV header")?;
+    let mut columns = header.split(',').map(str::trim);
+    if columns.next() != Some("added_at") {
+        return Err("The first CSV column must be added_at".into());
+    }
+    let metrics = columns
+        .map(|key| Metric::from_key(key).ok_or_else(|| format!("Unknown CSV column: {key}")))
+        .collect::<Result<Vec<Metric>, String>>()?;
+
+    let rows = lines
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line, text)| {
+            let row_error = |reason: String| ImportRowError { line, reason };
+
+            let mut cells = text.split(',').map(str::trim);
+            let added_at = RowTimestamp::Text(cells.next().unwrap_or_default().to_string())
+                .parse()
+                .map_err(row_error)?;
+
+            let values: Vec<&str> = cells.collect();
+            if values.len() != metrics.len() {
+                return Err(row_error(format!(
+                    "Expected {} values, found {}",
+                    metrics.len(),
+                    values.len()
+                )));
+            }
+
+            let mut data = SensorReading::default();
+            for (metric, value) in metrics.iter().zip(values) {
+                parse_csv_metric(&mut data, *metric, value).map_err(row_error)?;
+            }
+            data.validate()
+                .map_err(|e| row_error(format!("Invalid reading: {e}")))?;
+
+            Ok(ImportRow {
+                line,
+                added_at,
+                data,
+            })
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+fn parse_ndjson(input: &str) -> Vec<Result<ImportRow, ImportRowError>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line, text)| {
+            let row_error = |reason: String| ImportRowError { line, reason };
+
+            let row = NdjsonRow::from_json_str(text)
+                .map_err(|e| row_error(format!("Invalid row: {e}")))?;
+
+            Ok(ImportRow {
+                line,
+                added_at: row.added_at.parse().map_err(row_error)?,
+                data: row.data,
+            })
+        })
+        .collect()
+}
+
+/// Parses every row of the input, on error the whole input is invalid (i.e.: bad CSV header)
+pub fn parse_rows(
+    format: ExportFormat,
+    input: &str,
+) -> Result<Vec<Result<ImportRow, ImportRowError>>, String> {
+    match format {
+        ExportFormat::Csv => parse_csv(input),
+        ExportFormat::Ndjson => Ok(parse_ndjson(input)),
+    }
+}
+
+/// Key of an imported row, so that the database skips the rows of an import that were already
+/// imported, even by a concurrent one
+fn import_idempotency_key(added_at: NaiveDateTime) -> String {
+    format!("import:{}", added_at.and_utc().timestamp_micros())
+}
+
+/// Stores the valid rows for the sensor IMPORT_CHUNK_SIZE at a time, each chunk in a single
+/// transaction. Rows dated after now or already stored are not imported
+pub fn import_rows(
+    conn: &mut DbConn,
+    sensor_id: i32,
+    rows: Vec<Result<ImportRow, ImportRowError>>,
+    now: NaiveDateTime,
+) -> Result<PostSensorDataImportResponse, Error> {
+    let mut summary = PostSensorDataImportResponse::default();
+    let fail = |summary: &mut PostSensorDataImportResponse, error: ImportRowError| {
+        summary.failed += 1;
+        if summary.errors.len() < PostSensorDataImportResponse::MAX_ERRORS {
+            summary.errors.push(error);
+        }
+    };
+
+    let mut seen = HashSet::new();
+    let mut valid = vec![];
+    for row in rows {
+        match row {
+            Err(e) => fail(&mut summary, e),
+            Ok(row) if row.added_at > now + TimeDelta::minutes(1) => fail(
+                &mut summary,
+                ImportRowError {
+                    line: row.line,
+                    reason: "Timestamp is in the future".into(),
+                },
+            ),
+            Ok(row) if !seen.insert(row.added_at) => summary.skipped += 1,
+            Ok(row) => valid.push(row),
+        }
+    }
+
+    for chunk in valid.chunks(IMPORT_CHUNK_SIZE) {
+        let (imported, skipped) = conn.transaction::<_, Error, _>(|conn| {
+            // Readings the sensor sent itself have no import key to conflict on
+            let added_at: Vec<NaiveDateTime> = chunk.iter().map(|r| r.added_at).collect();
+            let stored: HashSet<NaiveDateTime> =
+                get_stored_added_at(conn, Identifier::SensorId(sensor_id), &added_at)?
+                    .into_iter()
+                    .collect();
+
+            let new_data: Vec<NewSensorData> = chunk
+                .iter()
+                .filter(|r| !stored.contains(&r.added_at))
+                .map(|r| NewSensorData {
+                    sensor_id,
+                    data: json!(r.data),
+                    added_at: Some(r.added_at),
+                    idempotency_key: Some(import_idempotency_key(r.added_at)),
+                    reported_at: None,
+                })
+                .collect();
+
+            let imported = insert_sensor_data_batch_once(conn, new_data)?;
+            Ok((imported, chunk.len() - imported))
+        })?;
+
+        summary.imported += imported;
+        summary.skipped += skipped;
+    }
+
+    log::info!(
+        "Imported sensor data for sensor {sensor_id}: {} imported, {} skipped, {} failed",
+        summary.imported,
+        summary.skipped,
+        summary.failed
+    );
+
+    Ok(summary)
+}
+
+pub const CLI_USAGE: &str = "sensor-server import <username> <device_id> <csv|ndjson> <file>";
+
+/// Admin entry point, imports the file into a sensor of the user, args are the ones after "import"
+pub fn run_cli(args: &[String]) -> Result<PostSensorDataImportResponse, String> {
+    let [username, device_id, format, path] = args else {
+        return Err(format!("Usage: {CLI_USAGE}"));
+    };
+
+    let device_id =
+        DeviceId::from_string(device_id).map_err(|e| format!("Invalid device_id: {e:?}"))?;
+    let format = match format.as_str() {
+        "csv" => ExportFormat::Csv,
+        "ndjson" => ExportFormat::Ndjson,
+        f => return Err(format!("Unknown format {f}, usage: {CLI_USAGE}")),
+    };
+    let input = std::fs::read_to_string(path).map_err(|e| format!("Could not read {path}: {e}"))?;
+
+    let mut conn = establish_connection(false).map_err(|e| e.to_string())?;
+    let sensor = AuthorizedSensor::from_username(&mut conn, &device_id, username)
+        .map_err(|e| format!("Sensor not found for user {username}: {e}"))?
+        .get();
+
+    let rows = parse_rows(format, &input)?;
+    import_rows(&mut conn, sensor.id, rows, Utc::now().naive_utc()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::tests::{create_test_user, create_test_user_place, create_test_user_sensor};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let input = "added_at,temperature,co2
+2025-01-01T10:00:00Z,21.5,400
+1735725600,,410
+
+1735725600,21.5
+1735725600,21.5,40001
+yesterday,21.5,400";
+
+        let rows = parse_rows(ExportFormat::Csv, input).expect("Header should be valid");
+        // The empty line is not a row
+        assert_eq!(rows.len(), 5);
+        assert_eq!(
+            rows[0],
+            Ok(ImportRow {
+                line: 2,
+                added_at: DateTime::from_timestamp(1735725600, 0).unwrap().naive_utc(),
+                data: SensorReading {
+                    co2: Some(400),
+                    temperature: Some(21.5),
+                    humidity: None,
+                },
+            })
+        );
+        assert!(rows[1].is_ok());
+        assert_eq!(rows[2].as_ref().unwrap_err().line, 5);
+        assert!(rows[3].is_err());
+        assert!(rows[4].is_err());
+
+        assert!(parse_rows(ExportFormat::Csv, "added_at,pressure\n").is_err());
+        assert!(parse_rows(ExportFormat::Csv, "co2,added_at\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_ndjson() {
+        let input = r#"{"added_at":1735725600,"co2":400,"humidity":null}
+{"added_at":"2025-01-01T10:00:00Z","temperature":21.5}
+{"added_at":1735725600}
+not json"#;
+
+        let rows = parse_rows(ExportFormat::Ndjson, input).expect("Should not fail");
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0].as_ref().unwrap().data.co2, Some(400));
+        assert_eq!(
+            rows[1].as_ref().unwrap().added_at,
+            rows[0].as_ref().unwrap().added_at
+        );
+        assert!(rows[2].is_err(), "No metrics");
+        assert!(rows[3].is_err());
+    }
+
+    #[test]
+    fn test_import_rows() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let now = Utc::now().naive_utc();
+        let input = format!(
+            "added_at,co2
+1735725600,400
+1735725601,401
+1735725601,401
+1735725602,40001
+{}",
+            (now + TimeDelta::days(1)).and_utc().timestamp()
+        );
+
+        let rows = parse_rows(ExportFormat::Csv, &input).unwrap();
+        let summary = import_rows(&mut conn, sensor.id, rows, now).expect("Should not fail");
+        assert_eq!(summary.imported, 2);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.failed, 2);
+        assert_eq!(
+            summary.errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+            vec![5, 6]
+        );
+
+        // Importing again only skips
+        let rows = parse_rows(ExportFormat::Csv, &input).unwrap();
+        let summary = import_rows(&mut conn, sensor.id, rows, now).expect("Should not fail");
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.skipped, 3);
+    }
+}
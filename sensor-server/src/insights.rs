@@ -0,0 +1,285 @@
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, DurationRound, NaiveDateTime, TimeDelta};
+use common::{
+    endpoints_io::{
+        insights::{AirQualityBand, AirQualityShare, OccupancyBucket, VentilationEstimate},
+        sensor_data::BucketSize,
+    },
+    types::ApiTimestamp,
+};
+
+/// Assumed CO2 ppm outdoors, what the air indoors decays towards when ventilated
+pub const OUTDOOR_CO2: f64 = 420.0;
+/// Readings further apart than this are not treated as continuous
+const MAX_GAP: TimeDelta = TimeDelta::minutes(10);
+/// How far above outdoors a peak must be for its decay to be used
+const MIN_PEAK_EXCESS: f64 = 200.0;
+/// Decays end once this close to outdoors, where sensor noise dominates the excess
+const MIN_DECAY_EXCESS: f64 = 50.0;
+/// Shortest decay used to estimate ventilation
+const MIN_DECAY: TimeDelta = TimeDelta::minutes(15);
+/// A decay ends when CO2 rises more than this over its lowest reading
+const NOISE_CO2: f64 = 20.0;
+/// CO2 rising at least this fast means someone is breathing in the room
+const OCCUPIED_RISE_PER_MIN: f64 = 1.0;
+/// Without decaying faster than OCCUPIED_RISE_PER_MIN, CO2 this far above outdoors means the room
+/// is occupied and ventilated enough to keep it steady
+const OCCUPIED_EXCESS: f64 = 400.0;
+
+/// A CO2 reading of a sensor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Co2Reading {
+    pub at: NaiveDateTime,
+    pub co2: f64,
+}
+
+fn timestamp(at: NaiveDateTime) -> ApiTimestamp {
+    at.and_utc().timestamp() as ApiTimestamp
+}
+
+fn minutes(delta: TimeDelta) -> f64 {
+    delta.num_milliseconds() as f64 / 60_000.0
+}
+
+/// Start of the bucket at falls in, like date_trunc does
+pub fn bucket_start(at: NaiveDateTime, bucket_size: BucketSize) -> NaiveDateTime {
+    let truncated = match bucket_size {
+        BucketSize::Minute => at.duration_trunc(TimeDelta::minutes(1)),
+        BucketSize::Hour => at.duration_trunc(TimeDelta::hours(1)),
+        BucketSize::Day => at.duration_trunc(TimeDelta::days(1)),
+        // Weeks start on monday
+        BucketSize::Week => at
+            .duration_trunc(TimeDelta::days(1))
+            .map(|day| day - TimeDelta::days(day.weekday().num_days_from_monday() as i64)),
+    };
+
+    truncated.unwrap_or(at)
+}
+
+/// How many readings fall in each band, one per AirQualityBand in order
+pub fn air_quality_shares(readings: &[Co2Reading]) -> Vec<AirQualityShare> {
+    AirQualityBand::ALL
+        .into_iter()
+        .map(|band| AirQualityShare {
+            band,
+            readings: readings
+                .iter()
+                .filter(|r| AirQualityBand::from_co2(r.co2) == band)
+                .count(),
+        })
+        .collect()
+}
+
+/// Air changes per hour of a decay, from the least squares slope of ln(CO2 - outdoors) over time
+/// (C(t) - C_out = (C_0 - C_out) * e^(-ACH * t))
+fn air_changes_per_hour(decay: &[Co2Reading]) -> Option<f64> {
+    let start = decay.first()?.at;
+    let points: Vec<(f64, f64)> = decay
+        .iter()
+        .map(|r| (minutes(r.at - start) / 60.0, (r.co2 - OUTDOOR_CO2).ln()))
+        .collect();
+
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if variance == 0.0 {
+        return None;
+    }
+
+    let ach = -covariance / variance;
+    (ach > 0.0).then_some(ach)
+}
+
+/// Ventilation of every decay after a peak, readings must be sorted by at
+/// A decay lasts while CO2 keeps going down without gaps, until it gets close to outdoors
+pub fn ventilation_estimates(readings: &[Co2Reading]) -> Vec<VentilationEstimate> {
+    let mut estimates = vec![];
+
+    let mut i = 0;
+    while i + 1 < readings.len() {
+        let peak = readings[i];
+        let is_peak = peak.co2 - OUTDOOR_CO2 >= MIN_PEAK_EXCESS
+            && readings[i + 1].co2 < peak.co2
+            && (i == 0 || readings[i - 1].co2 <= peak.co2);
+        if !is_peak {
+            i += 1;
+            continue;
+        }
+
+        let mut end = i;
+        let mut lowest = peak.co2;
+        while let Some(next) = readings.get(end + 1) {
+            if next.at - readings[end].at > MAX_GAP
+                || next.co2 > lowest + NOISE_CO2
+                || next.co2 - OUTDOOR_CO2 < MIN_DECAY_EXCESS
+            {
+                break;
+            }
+            lowest = lowest.min(next.co2);
+            end += 1;
+        }
+
+        let decay = &readings[i..=end];
+        if decay.len() >= 3
+            && readings[end].at - peak.at >= MIN_DECAY
+            && let Some(ach) = air_changes_per_hour(decay)
+        {
+            estimates.push(VentilationEstimate {
+                peak_at: timestamp(peak.at),
+                peak_co2: peak.co2,
+                end_at: timestamp(readings[end].at),
+                air_changes_per_hour: ach,
+            });
+        }
+
+        i = end.max(i + 1);
+    }
+
+    estimates
+}
+
+pub fn median(mut values: Vec<f64>) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    values.sort_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some((values[middle - 1] + values[middle]) / 2.0)
+    } else {
+        Some(values[middle])
+    }
+}
+
+/// Share of the time between consecutive readings, of any of the sensors, in which CO2 looked
+/// like someone was in the place. Each sensor's readings must be sorted by at
+pub fn occupancy(sensors: &[Vec<Co2Reading>], bucket_size: BucketSize) -> Vec<OccupancyBucket> {
+    // bucket_start -> (occupied, total) steps
+    let mut buckets: BTreeMap<NaiveDateTime, (usize, usize)> = BTreeMap::new();
+
+    for readings in sensors {
+        for step in readings.windows(2) {
+            let (from, to) = (step[0], step[1]);
+            let elapsed = minutes(to.at - from.at);
+            if to.at - from.at > MAX_GAP || elapsed <= 0.0 {
+                continue;
+            }
+
+            let rise_per_min = (to.co2 - from.co2) / elapsed;
+            let occupied = rise_per_min >= OCCUPIED_RISE_PER_MIN
+                || (to.co2 - OUTDOOR_CO2 >= OCCUPIED_EXCESS
+                    && rise_per_min > -OCCUPIED_RISE_PER_MIN);
+
+            let bucket = buckets
+                .entry(bucket_start(from.at, bucket_size))
+                .or_default();
+            bucket.0 += occupied as usize;
+            bucket.1 += 1;
+        }
+    }
+
+    buckets
+        .into_iter()
+        .map(|(start, (occupied, total))| OccupancyBucket {
+            bucket_start: timestamp(start),
+            likelihood: occupied as f64 / total as f64,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn start() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 10, 14)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap()
+    }
+
+    /// One reading per minute
+    fn series(co2: impl IntoIterator<Item = f64>) -> Vec<Co2Reading> {
+        co2.into_iter()
+            .enumerate()
+            .map(|(i, co2)| Co2Reading {
+                at: start() + TimeDelta::minutes(i as i64),
+                co2,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bucket_start() {
+        let at = start() + TimeDelta::minutes(75) + TimeDelta::seconds(30);
+        assert_eq!(
+            bucket_start(at, BucketSize::Minute),
+            start() + TimeDelta::minutes(75)
+        );
+        assert_eq!(
+            bucket_start(at, BucketSize::Hour),
+            start() + TimeDelta::hours(1)
+        );
+        // 2026-10-14 is a wednesday
+        assert_eq!(
+            bucket_start(at, BucketSize::Week),
+            NaiveDate::from_ymd_opt(2026, 10, 12)
+                .unwrap()
+                .and_hms_opt(0, 0, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_ventilation_estimates() {
+        // Rises for 20 minutes, then decays at 2 air changes per hour for 40
+        let rise = (0..20).map(|i| 600.0 + 40.0 * i as f64);
+        let peak = 600.0 + 40.0 * 20.0;
+        let decay =
+            (0..40).map(|i| OUTDOOR_CO2 + (peak - OUTDOOR_CO2) * (-2.0 * i as f64 / 60.0).exp());
+        let readings = series(rise.chain(decay));
+
+        let estimates = ventilation_estimates(&readings);
+        assert_eq!(estimates.len(), 1);
+        assert_eq!(estimates[0].peak_co2, peak);
+        assert_eq!(
+            estimates[0].peak_at,
+            timestamp(start() + TimeDelta::minutes(20))
+        );
+        assert!((estimates[0].air_changes_per_hour - 2.0).abs() < 1e-6);
+
+        // Too close to outdoors
+        assert!(ventilation_estimates(&series((0..30).map(|i| 500.0 - i as f64))).is_empty());
+    }
+
+    #[test]
+    fn test_occupancy() {
+        // Someone comes in after half an hour
+        let readings = series((0..120).map(|i| match i {
+            0..30 => 450.0,
+            i => 450.0 + 10.0 * (i - 30) as f64,
+        }));
+
+        let occupancy = occupancy(&[readings], BucketSize::Hour);
+        assert_eq!(occupancy.len(), 2);
+        assert_eq!(occupancy[0].bucket_start, timestamp(start()));
+        assert!((occupancy[0].likelihood - 30.0 / 60.0).abs() < 0.05);
+        assert_eq!(occupancy[1].likelihood, 1.0);
+    }
+
+    #[test]
+    fn test_air_quality_shares() {
+        let shares = air_quality_shares(&series([420.0, 900.0, 950.0, 2000.0]));
+        let readings: Vec<usize> = shares.iter().map(|s| s.readings).collect();
+        assert_eq!(readings, vec![1, 2, 0, 1]);
+    }
+}
@@ -1,14 +1,25 @@
 use axum::routing::MethodRouter;
 
+pub mod alerts;
+pub mod anomalies;
 pub mod api;
 pub mod auth;
+pub mod clock_skew;
 pub mod db;
+pub mod import;
+pub mod insights;
+pub mod mail;
 pub mod middleware;
+pub mod prometheus;
 pub mod state;
+pub mod tasks;
+pub mod webhooks;
 
 pub mod sensor_server;
 
 pub const PORT: u16 = 3000;
+/// Of the admin listener, only bound to localhost unless ADMIN_ADDR says otherwise
+pub const ADMIN_PORT: u16 = 3001;
 
 /// RoutePath struct
 /// Represents a route in the SensorServer, just contains the path from the main 2025-08-13 17:57
@@ -0,0 +1,125 @@
+use crate::{
+    db::{
+        DbConn, Error,
+        model::User,
+        notification_preferences::get_notification_preferences,
+        users::{Identifier, get_user},
+    },
+    mail::{
+        mailer::{Email, send_later},
+        templates::{Notification, Topic},
+    },
+};
+
+pub mod mailer;
+pub mod templates;
+
+/// The email of the notification for the user, None if they turned its topic off
+pub fn notification_email(
+    conn: &mut DbConn,
+    user: &User,
+    notification: &Notification,
+) -> Result<Option<Email>, Error> {
+    let preferences = get_notification_preferences(conn, user.id)?;
+    let wanted = match notification.topic() {
+        Topic::Alerts => preferences.alert_emails,
+        Topic::Account => preferences.account_emails,
+        Topic::Sensors => preferences.sensor_emails,
+    };
+
+    if !wanted {
+        log::trace!(
+            "User {} turned {:?} emails off, not sending: {notification:?}",
+            user.id,
+            notification.topic()
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(notification.render(&user.username, &user.email)))
+}
+
+/// Emails the notification to the user in the background, returns whether it was sent
+pub fn notify_user(
+    conn: &mut DbConn,
+    user: &User,
+    notification: &Notification,
+) -> Result<bool, Error> {
+    let Some(email) = notification_email(conn, user, notification)? else {
+        return Ok(false);
+    };
+
+    send_later(email);
+    Ok(true)
+}
+
+pub fn notify_place_owner(
+    conn: &mut DbConn,
+    place_id: i32,
+    notification: &Notification,
+) -> Result<bool, Error> {
+    let user = get_user(conn, Identifier::PlaceId(place_id))?;
+    notify_user(conn, &user, notification)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Duration;
+
+    use crate::{
+        db::{
+            establish_connection,
+            model::NotificationPreferencesChange,
+            notification_preferences::update_notification_preferences,
+            tests::{create_test_user, create_test_user_place},
+        },
+        mail::mailer::TEST_MAILER,
+    };
+
+    use super::*;
+
+    /// The emails sent to the address, waiting a bit for the ones sent in the background
+    pub async fn wait_sent_to(to: &str, count: usize) -> Vec<Email> {
+        for _ in 0..50 {
+            let sent = TEST_MAILER.sent_to(to);
+            if sent.len() >= count {
+                return sent;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        TEST_MAILER.sent_to(to)
+    }
+
+    #[tokio::test]
+    async fn test_notify_place_owner() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+
+        let notification = Notification::SensorRegistered {
+            sensor_name: "Bedroom".into(),
+            device_id: "0".repeat(40),
+            place_name: place.name.clone(),
+        };
+        assert!(notify_place_owner(&mut conn, place.id, &notification).unwrap());
+
+        let sent = wait_sent_to(&user.email, 1).await;
+        assert_eq!(sent, vec![notification.render(&user.username, &user.email)]);
+
+        update_notification_preferences(
+            &mut conn,
+            user.id,
+            NotificationPreferencesChange {
+                sensor_emails: Some(false),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(!notify_place_owner(&mut conn, place.id, &notification).unwrap());
+        assert!(
+            notification_email(&mut conn, &user, &Notification::PasswordChanged)
+                .unwrap()
+                .is_some()
+        );
+    }
+}
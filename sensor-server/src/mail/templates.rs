@@ -0,0 +1,244 @@
+use chrono::DateTime;
+use common::{
+    endpoints_io::{
+        alert::{AlertComparison, ApiAlertEvent},
+        sensor_data::Metric,
+    },
+    types::ApiTimestamp,
+};
+
+use crate::mail::mailer::Email;
+
+/// What a notification is about, each topic can be turned off in the notification preferences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    Alerts,
+    Account,
+    Sensors,
+}
+
+#[derive(Debug, Clone)]
+pub enum Notification {
+    AlertTriggered {
+        sensor_name: String,
+        event: ApiAlertEvent,
+    },
+    AlertResolved {
+        sensor_name: String,
+        event: ApiAlertEvent,
+    },
+    PasswordChanged,
+    UsernameChanged {
+        old_username: String,
+    },
+    /// Sent to the old address, so that the owner notices if it wasn't them
+    EmailChanged {
+        old_email: String,
+        new_email: String,
+    },
+    SensorRegistered {
+        sensor_name: String,
+        device_id: String,
+        place_name: String,
+    },
+    SensorOffline {
+        sensor_name: String,
+        device_id: String,
+        last_seen_at: ApiTimestamp,
+    },
+    SensorOnline {
+        sensor_name: String,
+        device_id: String,
+        offline_since: ApiTimestamp,
+    },
+}
+
+fn metric_name(metric: Metric) -> (&'static str, &'static str) {
+    match metric {
+        Metric::Co2 => ("CO2", "ppm"),
+        Metric::Temperature => ("Temperature", "°C"),
+        Metric::Humidity => ("Humidity", "%"),
+        Metric::DewPoint => ("Dew point", "°C"),
+        Metric::AbsoluteHumidity => ("Absolute humidity", "g/m³"),
+        Metric::Humidex => ("Humidex", "°C"),
+    }
+}
+
+fn format_timestamp(timestamp: ApiTimestamp) -> String {
+    DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+fn condition(event: &ApiAlertEvent) -> String {
+    let (name, unit) = metric_name(event.metric);
+    let comparison = match event.comparison {
+        AlertComparison::Above => "above",
+        AlertComparison::Below => "below",
+    };
+    format!("{name} {comparison} {} {unit}", event.threshold)
+}
+
+impl Notification {
+    pub fn topic(&self) -> Topic {
+        match self {
+            Notification::AlertTriggered { .. } | Notification::AlertResolved { .. } => {
+                Topic::Alerts
+            }
+            Notification::PasswordChanged
+            | Notification::UsernameChanged { .. }
+            | Notification::EmailChanged { .. } => Topic::Account,
+            Notification::SensorRegistered { .. }
+            | Notification::SensorOffline { .. }
+            | Notification::SensorOnline { .. } => Topic::Sensors,
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        match self {
+            Notification::AlertTriggered { sensor_name, event } => {
+                format!("Alert on {sensor_name}: {}", condition(event))
+            }
+            Notification::AlertResolved { sensor_name, event } => {
+                format!("Resolved on {sensor_name}: {}", condition(event))
+            }
+            Notification::PasswordChanged => "Your password was changed".into(),
+            Notification::UsernameChanged { .. } => "Your username was changed".into(),
+            Notification::EmailChanged { .. } => "Your email was changed".into(),
+            Notification::SensorRegistered { sensor_name, .. } => {
+                format!("New sensor registered: {sensor_name}")
+            }
+            Notification::SensorOffline { sensor_name, .. } => {
+                format!("{sensor_name} is offline")
+            }
+            Notification::SensorOnline { sensor_name, .. } => {
+                format!("{sensor_name} is back online")
+            }
+        }
+    }
+
+    fn text(&self) -> String {
+        match self {
+            Notification::AlertTriggered { sensor_name, event } => {
+                let (_, unit) = metric_name(event.metric);
+                format!(
+                    "The alert \"{}\" was triggered on {sensor_name} ({}) at {}, with a reading of {} {unit}.",
+                    condition(event),
+                    event.device_id.to_string(),
+                    format_timestamp(event.triggered_at),
+                    event.trigger_value,
+                )
+            }
+            Notification::AlertResolved { sensor_name, event } => format!(
+                "The alert \"{}\" on {sensor_name} ({}), triggered at {}, was resolved at {}.",
+                condition(event),
+                event.device_id.to_string(),
+                format_timestamp(event.triggered_at),
+                event
+                    .resolved_at
+                    .map(format_timestamp)
+                    .unwrap_or_else(|| "an unknown time".into()),
+            ),
+            Notification::PasswordChanged => "The password of your account was changed.".into(),
+            Notification::UsernameChanged { old_username } => {
+                format!("Your username was changed from {old_username}.")
+            }
+            Notification::EmailChanged { new_email, .. } => format!(
+                "The email of your account was changed to {new_email}, this address won't get any more emails."
+            ),
+            Notification::SensorRegistered {
+                sensor_name,
+                device_id,
+                place_name,
+            } => format!(
+                "The sensor {sensor_name} ({device_id}) was registered to your place {place_name}."
+            ),
+            Notification::SensorOffline {
+                sensor_name,
+                device_id,
+                last_seen_at,
+            } => format!(
+                "The sensor {sensor_name} ({device_id}) stopped reporting, it was last seen at {}.",
+                format_timestamp(*last_seen_at)
+            ),
+            Notification::SensorOnline {
+                sensor_name,
+                device_id,
+                offline_since,
+            } => format!(
+                "The sensor {sensor_name} ({device_id}) is reporting again, it was offline since {}.",
+                format_timestamp(*offline_since)
+            ),
+        }
+    }
+
+    /// Address the notification goes to, given the current one of the user
+    pub fn recipient<'a>(&'a self, email: &'a str) -> &'a str {
+        match self {
+            Notification::EmailChanged { old_email, .. } => old_email,
+            _ => email,
+        }
+    }
+
+    pub fn render(&self, username: &str, email: &str) -> Email {
+        let mut body = format!("Hi {username},\n\n{}\n\n", self.text());
+        if self.topic() == Topic::Account {
+            body += "If it wasn't you, change your password and contact us.\n\n";
+        }
+        body += "You can choose which emails you get in your notification preferences.\n";
+
+        Email {
+            to: self.recipient(email).to_string(),
+            subject: self.subject(),
+            body,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::types::validate::device_id::DeviceId;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let event = ApiAlertEvent {
+            id: 1,
+            rule_id: 1,
+            device_id: DeviceId::random(),
+            metric: Metric::Co2,
+            comparison: AlertComparison::Above,
+            threshold: 1200.0,
+            trigger_value: 1350.0,
+            triggered_at: 1_760_000_000,
+            resolved_at: None,
+            acknowledged_at: None,
+        };
+        let email = Notification::AlertTriggered {
+            sensor_name: "Bedroom".into(),
+            event,
+        }
+        .render("someone", "someone@email.com");
+
+        assert_eq!(email.to, "someone@email.com");
+        assert_eq!(email.subject, "Alert on Bedroom: CO2 above 1200 ppm");
+        assert!(email.body.starts_with("Hi someone,"));
+        assert!(
+            email
+                .body
+                .contains("at 2025-10-09 08:53 UTC, with a reading of 1350 ppm")
+        );
+        assert!(!email.body.contains("If it wasn't you"));
+
+        let email = Notification::EmailChanged {
+            old_email: "old@email.com".into(),
+            new_email: "new@email.com".into(),
+        }
+        .render("someone", "new@email.com");
+
+        assert_eq!(email.to, "old@email.com");
+        assert!(email.body.contains("changed to new@email.com"));
+        assert!(email.body.contains("If it wasn't you"));
+    }
+}
@@ -5,7 +5,8 @@ use std::{
 
 use axum_server::tls_rustls::RustlsConfig;
 use dotenv::dotenv;
-use sensor_server::{PORT, sensor_server::SensorServer};
+use sensor_server::{ADMIN_PORT, PORT, import, sensor_server::SensorServer};
+use serde_valid::json::ToJsonString;
 
 #[cfg(not(feature = "production"))]
 const CERTS_DIR: &str = "self_signed_certs";
@@ -20,6 +21,21 @@ async fn main() {
         .parse_default_env()
         .init();
 
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|cmd| cmd == "import") {
+        match import::run_cli(&args[2..]) {
+            Ok(summary) => println!(
+                "{}",
+                summary.to_json_string().expect("Summary should serialize")
+            ),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     let sensor_server = SensorServer::new();
 
     let config = RustlsConfig::from_pem_file(
@@ -31,6 +47,20 @@ async fn main() {
 
     let router = sensor_server.into_router();
 
+    let admin_addr = std::env::var("ADMIN_ADDR")
+        .ok()
+        .map(|addr| addr.parse().expect("ADMIN_ADDR should be a socket address"))
+        .unwrap_or(SocketAddr::from((Ipv4Addr::LOCALHOST, ADMIN_PORT)));
+    log::info!("Serving the admin listener on {admin_addr}");
+    tokio::spawn(async move {
+        if let Err(e) = axum_server::bind(admin_addr)
+            .serve(SensorServer::admin_router().into_make_service())
+            .await
+        {
+            log::error!("Admin listener stopped: {e:?}");
+        }
+    });
+
     let addr = SocketAddr::from((Ipv4Addr::UNSPECIFIED, PORT));
     axum_server::bind_rustls(addr, config)
         .serve(router.into_make_service())
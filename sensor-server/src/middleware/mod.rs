@@ -1,15 +1,32 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::Instant};
 
 use axum::{
     body::{self, Body},
-    extract::Request,
+    extract::{Request, State},
     middleware::Next,
     response::Response,
 };
-use hyper::StatusCode;
+use hyper::{StatusCode, header::CONTENT_LENGTH, http::request::Parts};
+
+use crate::{api::endpoints::sensor_data_import::SensorDataImport, state::server_metrics};
 
 pub mod extractor;
 
+/// Bodies are read whole to be logged, only up to this
+const LOG_BODY_MAX_BYTES: usize = 3_000_000;
+
+/// Imports can be way bigger than LOG_BODY_MAX_BYTES, so their bodies go through as they are, the
+/// route enforces its own limit (see SensorDataImport::MAX_BODY_BYTES). Same for any body declared
+/// bigger than that
+fn skip_body_log(parts: &Parts) -> bool {
+    let too_big = parts
+        .headers
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse::<usize>().ok())
+        .is_some_and(|len| len > LOG_BODY_MAX_BYTES);
+    too_big || parts.uri.path().ends_with(SensorDataImport::API_PATH)
+}
+
 pub async fn log_request(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
     let ip = req
         .extensions()
@@ -28,12 +45,17 @@ pub async fn log_request(req: Request<Body>, next: Next) -> Result<Response, Sta
     // Split and read the body
     // WARN: This operation is heavy and unsafe (max body = 3MB), remove for prod
     let (parts, body) = req.into_parts();
-    let bytes = body::to_bytes(body, 3_000_000).await.map_err(|e| {
-        log::error!("Error extracting body for printing: {e:?}");
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    log::debug!("body: {}", String::from_utf8_lossy(bytes.iter().as_slice()));
-    let body = Body::from(bytes);
+    let body = if skip_body_log(&parts) {
+        log::debug!("body: <not logged>");
+        body
+    } else {
+        let bytes = body::to_bytes(body, LOG_BODY_MAX_BYTES).await.map_err(|e| {
+            log::error!("Error extracting body for printing: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        log::debug!("body: {}", String::from_utf8_lossy(bytes.iter().as_slice()));
+        Body::from(bytes)
+    };
     let req = Request::from_parts(parts, body);
 
     // Run real handler -- Reconstruct req
@@ -46,3 +68,19 @@ pub async fn log_request(req: Request<Body>, next: Next) -> Result<Response, Sta
 
     Ok(res)
 }
+
+/// Counts the request and how long it took under route, the path it was registered with, so that
+/// the ids in the URIs don't end up in the labels
+pub async fn track_request(
+    State(route): State<String>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let method = req.method().clone();
+    let start = Instant::now();
+
+    let res = next.run(req).await;
+
+    server_metrics::observe_request(&route, &method, res.status(), start.elapsed());
+    res
+}
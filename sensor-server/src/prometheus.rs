@@ -0,0 +1,109 @@
+//! Writer of the Prometheus text exposition format (0.0.4)
+//! https://prometheus.io/docs/instrumenting/exposition_formats/
+
+use std::fmt::Write;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+    Histogram,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+/// Backslash, double quote and line feed are the only characters escaped in label values
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Help texts only escape backslashes and line feeds
+fn escape_help(help: &str) -> String {
+    help.replace('\\', r"\\").replace('\n', r"\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Text of a scrape, every sample must be written right after the family it belongs to
+#[derive(Debug, Default)]
+pub struct Exposition {
+    text: String,
+}
+
+impl Exposition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn family(&mut self, name: &str, help: &str, metric_type: MetricType) {
+        let _ = writeln!(self.text, "# HELP {name} {}", escape_help(help));
+        let _ = writeln!(self.text, "# TYPE {name} {}", metric_type.as_str());
+    }
+
+    /// name is the family one, with the _bucket, _sum or _count suffix for histograms
+    pub fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(label, value)| format!("{label}=\"{}\"", escape_label_value(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", format_value(value));
+    }
+
+    pub fn into_string(self) -> String {
+        self.text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposition() {
+        let mut exposition = Exposition::new();
+        exposition.family("sensor_co2_ppm", "Latest CO2\nreading", MetricType::Gauge);
+        exposition.sample(
+            "sensor_co2_ppm",
+            &[("device_id", "ab"), ("sensor", "Say \"hi\"\\")],
+            412.0,
+        );
+        exposition.sample("sensor_co2_ppm", &[], f64::INFINITY);
+        exposition.sample("sensor_co2_ppm", &[], 21.5);
+
+        assert_eq!(
+            exposition.into_string(),
+            "# HELP sensor_co2_ppm Latest CO2\\nreading\n\
+             # TYPE sensor_co2_ppm gauge\n\
+             sensor_co2_ppm{device_id=\"ab\",sensor=\"Say \\\"hi\\\"\\\\\"} 412\n\
+             sensor_co2_ppm +Inf\n\
+             sensor_co2_ppm 21.5\n"
+        );
+    }
+}
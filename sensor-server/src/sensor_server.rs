@@ -1,4 +1,9 @@
-use axum::{Router, http::HeaderValue, routing::MethodRouter};
+use axum::{
+    Router,
+    http::HeaderValue,
+    response::IntoResponse,
+    routing::{MethodRouter, get},
+};
 use dotenv::dotenv;
 use hyper::{
     Method,
@@ -10,6 +15,12 @@ use crate::{
     api::{Endpoint, endpoints::generate_endpoints},
     auth::keys::PROCESS_KEYS,
     db::establish_connection,
+    prometheus,
+    state::server_metrics,
+    tasks::{
+        heartbeat_checker::HeartbeatChecker, retention_pruner::RetentionPruner,
+        webhook_dispatcher::WebhookDispatcher,
+    },
 };
 
 pub type ServerMethodRouter = MethodRouter;
@@ -20,6 +31,7 @@ pub struct SensorServer {
 
 impl SensorServer {
     pub const API_BASE: &str = "/api/v0";
+    pub const ADMIN_METRICS_PATH: &str = "/metrics";
     pub fn new() -> Self {
         // Load LazyStatics
         let _ = *PROCESS_KEYS;
@@ -34,6 +46,10 @@ impl SensorServer {
         establish_connection(false).expect("Connection should be available");
         log::info!("Loaded DB_POOL");
 
+        RetentionPruner::from_env().spawn();
+        WebhookDispatcher::from_env().spawn();
+        HeartbeatChecker::from_env().spawn();
+
         let endpoints = generate_endpoints();
 
         Self { endpoints }
@@ -73,6 +89,10 @@ impl SensorServer {
         let mut router = Router::new();
 
         for (path, route) in self.routes() {
+            let route = route.layer(axum::middleware::from_fn_with_state(
+                path.clone(),
+                crate::middleware::track_request,
+            ));
             router = router.route(&path, route);
         }
 
@@ -87,6 +107,20 @@ impl SensorServer {
             .layer(axum::middleware::from_fn(crate::middleware::log_request))
             .layer(cors_layer)
     }
+
+    /// Served apart from the API, on ADMIN_ADDR, as it exposes the state of the whole server
+    pub fn admin_router() -> Router {
+        Router::new().route(
+            Self::ADMIN_METRICS_PATH,
+            get(|| async {
+                (
+                    [(CONTENT_TYPE, prometheus::CONTENT_TYPE)],
+                    server_metrics::exposition(),
+                )
+                    .into_response()
+            }),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -97,7 +131,10 @@ mod tests {
         endpoints_io::{
             place::{ApiUserPlace, GetPlace, PostPlace},
             sensor::{ApiUserSensor, GetSensor, GetSensorEnum, GetSensorResponse, PostSensor},
-            sensor_data::{ApiSensorData, GetSensorData, PostSensorData, PostSensorDataResponse},
+            sensor_data::{
+                ApiSensorData, ApiSensorDataBuckets, ApiSensorDataPage, BucketSize, GetSensorData,
+                PostSensorData, PostSensorDataResponse, SensorReading, SortOrder,
+            },
             session::{ApiSession, PostSession, SensorLogin, UserLogin},
             user::{ApiUser, GetUser, NotUniqueUser, PostUser},
         },
@@ -396,17 +433,16 @@ mod tests {
             endpoints::sensor_data::SensorData::API_PATH
         );
 
-        let serialized_data = "
-        {
-            co2: 312
-            temperature: 12
-            humidity: 32
-        }
-        ";
+        let reading = SensorReading {
+            co2: Some(312),
+            temperature: Some(12.0),
+            humidity: Some(32.0),
+        };
 
         let body = PostSensorData {
-            serialized_data: serialized_data.to_string(),
+            data: reading.clone(),
             created_at: None,
+            idempotency_key: None,
         };
 
         let res = server.post(&path).json(&body).await;
@@ -426,6 +462,11 @@ mod tests {
             device_id: sensor_device_id.clone(),
             lowest_added_at: Some(resp1.api_data.added_at - 1),
             upper_added_at: Some(resp2.api_data.added_at + 1),
+            bucket: None,
+            exclude_suspect: None,
+            limit: None,
+            order: None,
+            cursor: None,
         };
 
         server.clear_headers();
@@ -437,22 +478,63 @@ mod tests {
         let res = server.get(&path).add_query_params(query).await;
         server.clear_query_params();
 
-        let data: Vec<ApiSensorData> = res.json();
+        let page: ApiSensorDataPage = res.json();
+        assert_eq!(page.data.len(), 2);
+        assert!(page.next_cursor.is_none());
+        assert!(page.data.iter().all(|d| d.data == reading));
+
+        // Get those added datas without specifying range, one per page, newest first
+        let mut query = GetSensorData {
+            device_id: sensor_device_id.clone(),
+            lowest_added_at: None,
+            upper_added_at: None,
+            bucket: None,
+            exclude_suspect: None,
+            limit: Some(1),
+            order: Some(SortOrder::Desc),
+            cursor: None,
+        };
+
+        let mut data: Vec<ApiSensorData> = vec![];
+        loop {
+            server.clear_query_params();
+            let res = server.get(&path).add_query_params(&query).await;
+            server.clear_query_params();
+
+            let page: ApiSensorDataPage = res.json();
+            assert!(page.data.len() <= 1);
+            data.extend(page.data);
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
         assert_eq!(data.len(), 2);
+        assert!(data[0].added_at >= data[1].added_at);
 
-        // Get those added datas without specifying range
+        // Get those added datas aggregated, a bounded range as since 1970 is too many buckets
         let query = GetSensorData {
             device_id: sensor_device_id.clone(),
-            lowest_added_at: None,
+            lowest_added_at: Some(resp1.api_data.added_at - 3600),
             upper_added_at: None,
+            bucket: Some(BucketSize::Hour),
+            exclude_suspect: None,
+            limit: None,
+            order: None,
+            cursor: None,
         };
 
         server.clear_query_params();
         let res = server.get(&path).add_query_params(query).await;
         server.clear_query_params();
 
-        let data: Vec<ApiSensorData> = res.json();
-        assert_eq!(data.len(), 2);
+        let buckets: ApiSensorDataBuckets = res.json();
+        let co2_count: usize = buckets
+            .buckets
+            .iter()
+            .filter_map(|b| b.co2.as_ref().map(|co2| co2.count))
+            .sum();
+        assert_eq!(co2_count, 2);
 
         // Login with invalid password
         let path = format!(
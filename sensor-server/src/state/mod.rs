@@ -1 +1,3 @@
 pub mod poisonable_identifier;
+pub mod sensor_data_hub;
+pub mod server_metrics;
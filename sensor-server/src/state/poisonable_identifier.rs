@@ -116,6 +116,22 @@ impl PoisonableIdentifier {
     pub fn is_poisoned(&self) -> Result<bool, Error> {
         Self::key_is_poisoned(self.associated_map(), &self.as_key())
     }
+
+    /// Keys held by each map, expired ones are never removed so they are included
+    pub fn map_sizes() -> Result<[(&'static str, usize); 5], Error> {
+        let size = |map: &PoisoningMap| {
+            map.lock()
+                .map(|map| map.len())
+                .map_err(|e| Error::LockError(e.to_string().into()))
+        };
+        Ok([
+            ("user_jwt", size(&POISONED_USER_JWTS)?),
+            ("sensor_jwt", size(&POISONED_SENSOR_JWTS)?),
+            ("username", size(&POISONED_USERNAMES)?),
+            ("email", size(&POISONED_EMAILS)?),
+            ("device_id", size(&POISONED_DEVICES)?),
+        ])
+    }
 }
 
 #[cfg(test)]
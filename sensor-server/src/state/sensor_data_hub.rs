@@ -0,0 +1,72 @@
+use std::sync::LazyLock;
+
+use common::{endpoints_io::sensor_data::ApiSensorData, types::validate::device_id::DeviceId};
+use tokio::sync::broadcast;
+
+/// Events a subscriber can fall behind before it starts missing them
+const HUB_CAPACITY: usize = 1024;
+
+static SENSOR_DATA_HUB: LazyLock<broadcast::Sender<SensorDataEvent>> =
+    LazyLock::new(|| broadcast::channel(HUB_CAPACITY).0);
+
+/// A reading just stored, with what subscribers filter on
+#[derive(Debug, Clone)]
+pub struct SensorDataEvent {
+    pub sensor_id: i32,
+    pub place_id: i32,
+    pub device_id: DeviceId,
+    pub api_data: ApiSensorData,
+}
+
+/// Pushes the event to every current subscriber, it's lost if there are none
+pub fn publish(event: SensorDataEvent) {
+    match SENSOR_DATA_HUB.send(event) {
+        Ok(subscribers) => log::trace!("Sensor data event sent to {subscribers} subscribers"),
+        Err(_) => log::trace!("Sensor data event without subscribers"),
+    }
+}
+
+/// Receives every event published from now on
+pub fn subscribe() -> broadcast::Receiver<SensorDataEvent> {
+    SENSOR_DATA_HUB.subscribe()
+}
+
+#[cfg(test)]
+mod tests {
+    use common::endpoints_io::sensor_data::SensorReading;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish() {
+        let event = SensorDataEvent {
+            sensor_id: -1,
+            place_id: -1,
+            device_id: DeviceId::random(),
+            api_data: ApiSensorData {
+                id: 0,
+                data: SensorReading {
+                    co2: Some(400),
+                    ..Default::default()
+                },
+                derived: Default::default(),
+                suspect_metrics: vec![],
+                added_at: 0,
+            },
+        };
+        // Lost
+        publish(event.clone());
+
+        let mut rx = subscribe();
+        publish(event.clone());
+
+        // Other tests may be publishing too
+        loop {
+            let received = rx.recv().await.expect("Should receive");
+            if received.device_id == event.device_id {
+                assert_eq!(received.api_data.data, event.api_data.data);
+                break;
+            }
+        }
+    }
+}
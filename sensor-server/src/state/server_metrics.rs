@@ -0,0 +1,360 @@
+//! Metrics of the server itself, exposed in the Prometheus format on the admin listener
+
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, Mutex, PoisonError},
+    time::Duration,
+};
+
+use hyper::{Method, StatusCode};
+
+use crate::{
+    db,
+    prometheus::{Exposition, MetricType},
+    state::poisonable_identifier::PoisonableIdentifier,
+};
+
+/// Upper bounds of the request latency buckets, in seconds
+pub const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+const REQUESTS: &str = "sensor_server_http_requests_total";
+const REQUEST_DURATION: &str = "sensor_server_http_request_duration_seconds";
+const DB_POOL_CONNECTIONS: &str = "sensor_server_db_pool_connections";
+const DB_POOL_MAX_CONNECTIONS: &str = "sensor_server_db_pool_max_connections";
+const POISONED_IDENTIFIERS: &str = "sensor_server_poisoned_identifiers";
+const JWTS_ISSUED: &str = "sensor_server_jwts_issued_total";
+const JWTS_REJECTED: &str = "sensor_server_jwts_rejected_total";
+const READINGS_INGESTED: &str = "sensor_server_readings_ingested_total";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JwtKind {
+    User,
+    Sensor,
+}
+
+impl JwtKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JwtKind::User => "user",
+            JwtKind::Sensor => "sensor",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JwtRejection {
+    /// Malformed, badly signed or expired
+    Invalid,
+    /// Its id, username or device id was poisoned
+    Poisoned,
+}
+
+impl JwtRejection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JwtRejection::Invalid => "invalid",
+            JwtRejection::Poisoned => "poisoned",
+        }
+    }
+}
+
+/// How the readings reached the server
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IngestionSource {
+    Single,
+    Batch,
+    Import,
+}
+
+impl IngestionSource {
+    fn as_str(&self) -> &'static str {
+        match self {
+            IngestionSource::Single => "single",
+            IngestionSource::Batch => "batch",
+            IngestionSource::Import => "import",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Histogram {
+    /// Observations of each bucket alone, they are accumulated when exposed
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if let Some(i) = LATENCY_BUCKETS.iter().position(|le| value <= *le) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += value;
+    }
+}
+
+#[derive(Debug, Default)]
+struct RouteStats {
+    /// Responses by status code
+    responses: BTreeMap<u16, u64>,
+    latency: Histogram,
+}
+
+type Counters = BTreeMap<(&'static str, Vec<(&'static str, &'static str)>), u64>;
+
+/// By route, as registered by its Endpoint, and method
+static ROUTE_STATS: LazyLock<Mutex<BTreeMap<(String, &'static str), RouteStats>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+/// By family name and labels
+static COUNTERS: LazyLock<Mutex<Counters>> = LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// Anything outside of the standard methods shares a label, so that clients can't grow the
+/// number of series
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::PATCH => "PATCH",
+        Method::HEAD => "HEAD",
+        Method::OPTIONS => "OPTIONS",
+        _ => "other",
+    }
+}
+
+// A panic while holding these locks can't leave the counts inconsistent, so they are kept going
+fn increment(name: &'static str, labels: Vec<(&'static str, &'static str)>, by: u64) {
+    *COUNTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry((name, labels))
+        .or_default() += by;
+}
+
+pub fn observe_request(route: &str, method: &Method, status: StatusCode, elapsed: Duration) {
+    let mut route_stats = ROUTE_STATS.lock().unwrap_or_else(PoisonError::into_inner);
+    let stats = route_stats
+        .entry((route.to_string(), method_label(method)))
+        .or_default();
+    *stats.responses.entry(status.as_u16()).or_default() += 1;
+    stats.latency.observe(elapsed.as_secs_f64());
+}
+
+pub fn jwt_issued(kind: JwtKind) {
+    increment(JWTS_ISSUED, vec![("kind", kind.as_str())], 1);
+}
+
+pub fn jwt_rejected(kind: JwtKind, reason: JwtRejection) {
+    increment(
+        JWTS_REJECTED,
+        vec![("kind", kind.as_str()), ("reason", reason.as_str())],
+        1,
+    );
+}
+
+pub fn readings_ingested(source: IngestionSource, count: usize) {
+    increment(
+        READINGS_INGESTED,
+        vec![("source", source.as_str())],
+        count as u64,
+    );
+}
+
+fn expose_counters(exposition: &mut Exposition, name: &'static str, help: &str) {
+    exposition.family(name, help, MetricType::Counter);
+    let counters = COUNTERS.lock().unwrap_or_else(PoisonError::into_inner);
+    for ((_, labels), value) in counters.iter().filter(|((n, _), _)| *n == name) {
+        exposition.sample(name, labels, *value as f64);
+    }
+}
+
+fn expose_requests(exposition: &mut Exposition) {
+    let route_stats = ROUTE_STATS.lock().unwrap_or_else(PoisonError::into_inner);
+
+    exposition.family(
+        REQUESTS,
+        "Requests handled, by route, method and response status",
+        MetricType::Counter,
+    );
+    for ((route, method), stats) in route_stats.iter() {
+        for (status, count) in &stats.responses {
+            exposition.sample(
+                REQUESTS,
+                &[
+                    ("route", route),
+                    ("method", method),
+                    ("status", &status.to_string()),
+                ],
+                *count as f64,
+            );
+        }
+    }
+
+    exposition.family(
+        REQUEST_DURATION,
+        "Time taken to handle the requests, by route and method",
+        MetricType::Histogram,
+    );
+    let bucket = format!("{REQUEST_DURATION}_bucket");
+    let sum = format!("{REQUEST_DURATION}_sum");
+    let count = format!("{REQUEST_DURATION}_count");
+    for ((route, method), stats) in route_stats.iter() {
+        let labels = [("route", route.as_str()), ("method", *method)];
+        let mut cumulative = 0;
+        for (le, observations) in LATENCY_BUCKETS.iter().zip(stats.latency.buckets) {
+            cumulative += observations;
+            exposition.sample(
+                &bucket,
+                &[labels[0], labels[1], ("le", &le.to_string())],
+                cumulative as f64,
+            );
+        }
+        exposition.sample(
+            &bucket,
+            &[labels[0], labels[1], ("le", "+Inf")],
+            stats.latency.count as f64,
+        );
+        exposition.sample(&sum, &labels, stats.latency.sum);
+        exposition.sample(&count, &labels, stats.latency.count as f64);
+    }
+}
+
+fn expose_db_pool(exposition: &mut Exposition) {
+    let (state, max_size) = db::pool_state();
+
+    exposition.family(
+        DB_POOL_CONNECTIONS,
+        "Connections of the database pool, by whether they are in use",
+        MetricType::Gauge,
+    );
+    exposition.sample(
+        DB_POOL_CONNECTIONS,
+        &[("state", "idle")],
+        state.idle_connections as f64,
+    );
+    exposition.sample(
+        DB_POOL_CONNECTIONS,
+        &[("state", "in_use")],
+        (state.connections - state.idle_connections) as f64,
+    );
+
+    exposition.family(
+        DB_POOL_MAX_CONNECTIONS,
+        "Connections the database pool can open",
+        MetricType::Gauge,
+    );
+    exposition.sample(DB_POOL_MAX_CONNECTIONS, &[], max_size as f64);
+}
+
+fn expose_poisoned_identifiers(exposition: &mut Exposition) {
+    exposition.family(
+        POISONED_IDENTIFIERS,
+        "Identifiers held by each poisoning map, expired ones included",
+        MetricType::Gauge,
+    );
+    match PoisonableIdentifier::map_sizes() {
+        Ok(sizes) => {
+            for (map, size) in sizes {
+                exposition.sample(POISONED_IDENTIFIERS, &[("map", map)], size as f64);
+            }
+        }
+        Err(e) => log::error!("Could not read the poisoning map sizes: {e}"),
+    }
+}
+
+/// Text of a scrape of the admin listener
+pub fn exposition() -> String {
+    let mut exposition = Exposition::new();
+
+    expose_requests(&mut exposition);
+    expose_db_pool(&mut exposition);
+    expose_poisoned_identifiers(&mut exposition);
+    expose_counters(&mut exposition, JWTS_ISSUED, "JWTs issued, by kind");
+    expose_counters(
+        &mut exposition,
+        JWTS_REJECTED,
+        "JWTs rejected, by kind and reason",
+    );
+    expose_counters(
+        &mut exposition,
+        READINGS_INGESTED,
+        "Sensor readings stored, by how they were sent",
+    );
+
+    exposition.into_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::establish_connection;
+
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::default();
+        histogram.observe(0.001);
+        histogram.observe(0.3);
+        histogram.observe(60.0);
+
+        assert_eq!(histogram.count, 3);
+        assert!((histogram.sum - 60.301).abs() < 1e-9);
+        assert_eq!(histogram.buckets[0], 1);
+        assert_eq!(histogram.buckets[5..].to_vec(), [0, 1, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_exposition() {
+        // Loads the environment of DB_POOL
+        establish_connection(true).unwrap();
+
+        // Counters are shared by the whole process, the route keeps other tests out
+        let route = "/api/v0/test_exposition";
+        observe_request(
+            route,
+            &Method::GET,
+            StatusCode::OK,
+            Duration::from_millis(30),
+        );
+        observe_request(
+            route,
+            &Method::GET,
+            StatusCode::NOT_FOUND,
+            Duration::from_millis(3),
+        );
+        observe_request(
+            route,
+            &Method::from_bytes(b"BREW").unwrap(),
+            StatusCode::METHOD_NOT_ALLOWED,
+            Duration::from_millis(1),
+        );
+        readings_ingested(IngestionSource::Import, 3);
+
+        let body = exposition();
+
+        assert!(body.contains(&format!(
+            "{REQUESTS}{{route=\"{route}\",method=\"GET\",status=\"200\"}} 1\n"
+        )));
+        assert!(body.contains(&format!(
+            "{REQUESTS}{{route=\"{route}\",method=\"other\",status=\"405\"}} 1\n"
+        )));
+        assert!(body.contains(&format!(
+            "{REQUEST_DURATION}_bucket{{route=\"{route}\",method=\"GET\",le=\"0.025\"}} 1\n"
+        )));
+        assert!(body.contains(&format!(
+            "{REQUEST_DURATION}_bucket{{route=\"{route}\",method=\"GET\",le=\"0.05\"}} 2\n"
+        )));
+        assert!(body.contains(&format!(
+            "{REQUEST_DURATION}_count{{route=\"{route}\",method=\"GET\"}} 2\n"
+        )));
+        assert!(body.contains(&format!("# TYPE {REQUEST_DURATION} histogram\n")));
+        assert!(body.contains(&format!("{DB_POOL_CONNECTIONS}{{state=\"idle\"}} ")));
+        assert!(body.contains(&format!("{POISONED_IDENTIFIERS}{{map=\"user_jwt\"}} ")));
+        assert!(body.contains(&format!("{READINGS_INGESTED}{{source=\"import\"}} ")));
+        assert!(body.contains(&format!("# TYPE {JWTS_REJECTED} counter\n")));
+    }
+}
@@ -0,0 +1,246 @@
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use common::{
+    endpoints_io::{
+        sensor::{ApiSensorStatusEvent, SensorStatus},
+        webhook::WebhookEvent,
+    },
+    types::{ApiTimestamp, validate::device_id::DeviceId},
+};
+use tokio::task::JoinHandle;
+
+use crate::{
+    db::{
+        DbConn, Error, establish_connection,
+        model::UserSensor,
+        user_sensors::{get_seen_user_sensors, set_offline_since},
+    },
+    mail::{notify_place_owner, templates::Notification},
+    webhooks,
+};
+
+fn timestamp(at: NaiveDateTime) -> ApiTimestamp {
+    at.and_utc().timestamp() as ApiTimestamp
+}
+
+/// Periodically looks for sensors that stopped reporting or came back, and tells their owner
+#[derive(Debug, Clone)]
+pub struct HeartbeatChecker {
+    pub interval: Duration,
+}
+
+impl HeartbeatChecker {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Reads HEARTBEAT_CHECKER_INTERVAL_SECS
+    pub fn from_env() -> Self {
+        let interval = std::env::var("HEARTBEAT_CHECKER_INTERVAL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Self::DEFAULT_INTERVAL);
+
+        Self { interval }
+    }
+
+    /// Marks the sensors offline or back online as of now, and queues their events
+    /// Returns how many sensors changed
+    pub fn check(&self, conn: &mut DbConn, now: NaiveDateTime) -> Result<usize, Error> {
+        let mut changed = 0;
+        for sensor in get_seen_user_sensors(conn)? {
+            let status = SensorStatus::from_last_seen(
+                sensor.last_seen_at.map(timestamp),
+                sensor.expected_interval_secs as u32,
+                timestamp(now),
+            );
+
+            let (notification, event): (_, fn(ApiSensorStatusEvent) -> WebhookEvent) =
+                match (status, sensor.offline_since) {
+                    (SensorStatus::Offline, None) => {
+                        if !set_offline_since(conn, sensor.id, Some(now))? {
+                            continue;
+                        }
+                        log::info!("Sensor {} went offline", sensor.device_id);
+                        let notification = Notification::SensorOffline {
+                            sensor_name: sensor.name.clone(),
+                            device_id: sensor.device_id.clone(),
+                            last_seen_at: sensor.last_seen_at.map(timestamp).unwrap_or_default(),
+                        };
+                        (notification, WebhookEvent::SensorOffline)
+                    }
+                    (SensorStatus::Online, Some(offline_since)) => {
+                        if !set_offline_since(conn, sensor.id, None)? {
+                            continue;
+                        }
+                        log::info!("Sensor {} is back online", sensor.device_id);
+                        let notification = Notification::SensorOnline {
+                            sensor_name: sensor.name.clone(),
+                            device_id: sensor.device_id.clone(),
+                            offline_since: timestamp(offline_since),
+                        };
+                        (notification, WebhookEvent::SensorOnline)
+                    }
+                    _ => continue,
+                };
+            changed += 1;
+
+            Self::emit(conn, &sensor, notification, event, now);
+        }
+
+        Ok(changed)
+    }
+
+    /// Queues the events and the email of the transition. Failing to queue them doesn't undo the
+    /// transition, it would be emitted again on every check
+    fn emit(
+        conn: &mut DbConn,
+        sensor: &UserSensor,
+        notification: Notification,
+        event: fn(ApiSensorStatusEvent) -> WebhookEvent,
+        now: NaiveDateTime,
+    ) {
+        let device_id = match DeviceId::from_string(&sensor.device_id) {
+            Ok(device_id) => device_id,
+            Err(e) => {
+                log::error!("Could not construct DeviceId: {e:?}");
+                return;
+            }
+        };
+        let api_event = ApiSensorStatusEvent {
+            device_id,
+            last_seen_at: sensor.last_seen_at.map(timestamp).unwrap_or_default(),
+            expected_interval_secs: sensor.expected_interval_secs as u32,
+        };
+
+        if let Err(e) = webhooks::queue_events(conn, sensor.place_id, &[event(api_event)], now) {
+            log::error!(
+                "Error queueing webhooks of sensor {}: {e:?}",
+                sensor.device_id
+            );
+        }
+        if let Err(e) = notify_place_owner(conn, sensor.place_id, &notification) {
+            log::error!(
+                "Error notifying the status of sensor {}: {e:?}",
+                sensor.device_id
+            );
+        }
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        log::info!("Starting heartbeat checker: {self:?}");
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+
+                let checker = self.clone();
+                let res = tokio::task::spawn_blocking(move || {
+                    let mut conn = establish_connection(false)?;
+                    checker.check(&mut conn, Utc::now().naive_utc())
+                })
+                .await;
+
+                match res {
+                    Ok(Ok(_)) => (),
+                    Ok(Err(e)) => log::error!("Heartbeat checker failed: {e:?}"),
+                    Err(e) => log::error!("Heartbeat checker task panicked: {e:?}"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use common::endpoints_io::webhook::WebhookEventKind;
+
+    use crate::{
+        db::{
+            model::NewWebhook,
+            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+            user_sensors::set_last_seen,
+            webhooks::{get_webhook_deliveries, insert_webhook},
+        },
+        mail::tests::wait_sent_to,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+        let webhook = insert_webhook(
+            &mut conn,
+            NewWebhook {
+                user_id: user.id,
+                url: "http://127.0.0.1:1/hook".to_string(),
+                secret: "secret".to_string(),
+                events: [
+                    WebhookEventKind::SensorOffline,
+                    WebhookEventKind::SensorOnline,
+                ]
+                .iter()
+                .map(|k| k.key().to_string())
+                .collect(),
+            },
+        )
+        .unwrap();
+        let offline_since = |conn: &mut DbConn| {
+            get_seen_user_sensors(conn)
+                .unwrap()
+                .into_iter()
+                .find(|s| s.id == sensor.id)
+                .unwrap()
+                .offline_since
+        };
+
+        let checker = HeartbeatChecker::from_env();
+        let now = Utc::now().naive_utc();
+        let timeout = TimeDelta::seconds(
+            sensor.expected_interval_secs as i64 * SensorStatus::MISSED_REPORTS as i64,
+        );
+
+        set_last_seen(&mut conn, sensor.id, now - timeout).unwrap();
+        checker.check(&mut conn, now).unwrap();
+        assert_eq!(offline_since(&mut conn), None);
+
+        let later = now + TimeDelta::seconds(1);
+        checker.check(&mut conn, later).unwrap();
+        assert!(offline_since(&mut conn).is_some());
+        // Only once
+        checker.check(&mut conn, later).unwrap();
+
+        set_last_seen(&mut conn, sensor.id, later).unwrap();
+        checker.check(&mut conn, later).unwrap();
+        assert_eq!(offline_since(&mut conn), None);
+
+        let mut kinds: Vec<String> = get_webhook_deliveries(&mut conn, webhook.id, 10)
+            .unwrap()
+            .into_iter()
+            .map(|delivery| delivery.kind)
+            .collect();
+        kinds.sort();
+        assert_eq!(kinds, vec!["sensor_offline", "sensor_online"]);
+
+        // Sent in the background, in any order
+        let mut subjects: Vec<String> = wait_sent_to(&user.email, 2)
+            .await
+            .into_iter()
+            .map(|email| email.subject)
+            .collect();
+        subjects.sort();
+        assert_eq!(
+            subjects,
+            vec![
+                format!("{} is back online", sensor.name),
+                format!("{} is offline", sensor.name)
+            ]
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod heartbeat_checker;
+pub mod retention_pruner;
+pub mod webhook_dispatcher;
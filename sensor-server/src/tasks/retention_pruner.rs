@@ -0,0 +1,169 @@
+use std::time::Duration;
+
+use chrono::{NaiveDateTime, Utc};
+use tokio::task::JoinHandle;
+
+use crate::db::{
+    DbConn, Error, establish_connection,
+    retention_policies::{
+        SERVER_KEEP_DAYS, count_expired_sensor_data, delete_expired_sensor_data_batch,
+    },
+};
+
+/// Periodically deletes the sensor data that outlived its retention policy
+#[derive(Debug, Clone)]
+pub struct RetentionPruner {
+    pub server_keep_days: Option<u32>,
+    pub batch_size: i64,
+    pub interval: Duration,
+    /// If true, only logs how much data would be pruned
+    pub dry_run: bool,
+}
+
+impl RetentionPruner {
+    pub const DEFAULT_BATCH_SIZE: i64 = 1000;
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    /// Reads RETENTION_PRUNER_DRY_RUN and RETENTION_PRUNER_INTERVAL_SECS, the server retention is
+    /// SERVER_KEEP_DAYS
+    pub fn from_env() -> Self {
+        let dry_run = std::env::var("RETENTION_PRUNER_DRY_RUN")
+            .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"));
+        let interval = std::env::var("RETENTION_PRUNER_INTERVAL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Self::DEFAULT_INTERVAL);
+
+        Self {
+            server_keep_days: *SERVER_KEEP_DAYS,
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            interval,
+            dry_run,
+        }
+    }
+
+    /// Prunes every datum expired at now, batch_size at a time so that each DELETE is short
+    /// Returns how many data were (or would be, if dry_run) deleted
+    pub fn prune(&self, conn: &mut DbConn, now: NaiveDateTime) -> Result<usize, Error> {
+        if self.dry_run {
+            let count = count_expired_sensor_data(conn, now, self.server_keep_days)?;
+            log::info!("[dry run] Retention pruner would delete {count} sensor data");
+            return Ok(count);
+        }
+
+        let mut total = 0;
+        loop {
+            let deleted = delete_expired_sensor_data_batch(
+                conn,
+                now,
+                self.server_keep_days,
+                self.batch_size,
+            )?;
+            total += deleted;
+            log::trace!("Retention pruner deleted a batch of {deleted} sensor data");
+
+            if (deleted as i64) < self.batch_size {
+                break;
+            }
+        }
+
+        log::info!("Retention pruner deleted {total} sensor data");
+        Ok(total)
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        log::info!("Starting retention pruner: {self:?}");
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.interval);
+            loop {
+                interval.tick().await;
+
+                let pruner = self.clone();
+                let res = tokio::task::spawn_blocking(move || {
+                    let mut conn = establish_connection(false)?;
+                    pruner.prune(&mut conn, Utc::now().naive_utc())
+                })
+                .await;
+
+                match res {
+                    Ok(Ok(_)) => (),
+                    Ok(Err(e)) => log::error!("Retention pruner failed: {e:?}"),
+                    Err(e) => log::error!("Retention pruner task panicked: {e:?}"),
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use common::endpoints_io::sensor_data::SensorReading;
+
+    use crate::db::{
+        retention_policies::{Identifier, set_retention_policy},
+        sensor_data::{self, get_sensor_data, insert_sensor_data_batch},
+        tests::{
+            create_test_user, create_test_user_place, create_test_user_sensor, new_test_sensor_data,
+        },
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_prune() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let now = Utc::now().naive_utc();
+        let new_data = (0..5)
+            .map(|days| {
+                new_test_sensor_data(
+                    sensor.id,
+                    now - TimeDelta::days(days * 10),
+                    SensorReading {
+                        co2: Some(400),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect();
+        insert_sensor_data_batch(&mut conn, new_data).unwrap();
+        set_retention_policy(&mut conn, Identifier::SensorId(sensor.id), Some(15)).unwrap();
+
+        let stored = |conn: &mut DbConn| {
+            get_sensor_data(
+                conn,
+                sensor_data::Identifier::SensorId(sensor.id),
+                (now - TimeDelta::days(365))..now,
+                Default::default(),
+                100,
+                None,
+            )
+            .unwrap()
+            .0
+            .len()
+        };
+
+        let mut pruner = RetentionPruner {
+            server_keep_days: None,
+            batch_size: 2,
+            interval: RetentionPruner::DEFAULT_INTERVAL,
+            dry_run: true,
+        };
+
+        assert_eq!(pruner.prune(&mut conn, now).unwrap(), 3);
+        assert_eq!(stored(&mut conn), 5);
+
+        // More than a batch to delete
+        pruner.dry_run = false;
+        assert_eq!(pruner.prune(&mut conn, now).unwrap(), 3);
+        assert_eq!(stored(&mut conn), 2);
+
+        assert_eq!(pruner.prune(&mut conn, now).unwrap(), 0);
+    }
+}
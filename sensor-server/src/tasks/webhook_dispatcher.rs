@@ -0,0 +1,257 @@
+use std::{sync::LazyLock, time::Duration};
+
+use chrono::{NaiveDateTime, TimeDelta, Utc};
+use futures_util::future::join_all;
+use tokio::{sync::Notify, task::JoinHandle};
+
+use crate::{
+    db::{
+        DbConn, Error, establish_connection,
+        webhooks::{claim_due_webhook_deliveries, delete_finished_webhook_deliveries},
+    },
+    webhooks::{ATTEMPT_LEASE, RetryPolicy, record_attempt, send},
+};
+
+static WAKE: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Makes the dispatcher attempt the due deliveries now instead of at its next interval
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+/// Attempts the queued webhook deliveries when they are due, and prunes the delivery log
+#[derive(Debug, Clone)]
+pub struct WebhookDispatcher {
+    /// At most how long a due delivery waits if the dispatcher isn't woken up
+    pub interval: Duration,
+    /// Deliveries attempted at once
+    pub batch_size: i64,
+    pub retry_policy: RetryPolicy,
+    /// Delivered and failed deliveries older than this are deleted
+    pub keep_log: TimeDelta,
+}
+
+impl WebhookDispatcher {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+    pub const DEFAULT_BATCH_SIZE: i64 = 50;
+    pub const DEFAULT_KEEP_LOG: TimeDelta = TimeDelta::days(7);
+    const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    /// Reads WEBHOOK_DISPATCHER_INTERVAL_SECS and WEBHOOK_LOG_KEEP_DAYS
+    pub fn from_env() -> Self {
+        let interval = std::env::var("WEBHOOK_DISPATCHER_INTERVAL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Self::DEFAULT_INTERVAL);
+        let keep_log = std::env::var("WEBHOOK_LOG_KEEP_DAYS")
+            .ok()
+            .and_then(|days| days.parse().ok())
+            .map(TimeDelta::days)
+            .unwrap_or(Self::DEFAULT_KEEP_LOG);
+
+        Self {
+            interval,
+            batch_size: Self::DEFAULT_BATCH_SIZE,
+            retry_policy: RetryPolicy::default(),
+            keep_log,
+        }
+    }
+
+    /// Attempts every delivery due at now, batch_size at a time
+    /// Returns how many were attempted
+    pub async fn dispatch(&self, conn: &mut DbConn, now: NaiveDateTime) -> Result<usize, Error> {
+        let mut total = 0;
+        loop {
+            let claimed =
+                claim_due_webhook_deliveries(conn, now, now + ATTEMPT_LEASE, self.batch_size)?;
+            let attempted = claimed.len();
+
+            let results = join_all(
+                claimed
+                    .iter()
+                    .map(|(delivery, webhook)| send(webhook, delivery, now)),
+            )
+            .await;
+            for ((delivery, _), result) in claimed.iter().zip(results) {
+                record_attempt(conn, delivery, &result, &self.retry_policy, now)?;
+            }
+
+            total += attempted;
+            if (attempted as i64) < self.batch_size {
+                break;
+            }
+        }
+
+        if total > 0 {
+            log::trace!("Webhook dispatcher attempted {total} deliveries");
+        }
+        Ok(total)
+    }
+
+    pub fn spawn(self) -> JoinHandle<()> {
+        log::info!("Starting webhook dispatcher: {self:?}");
+
+        tokio::spawn(async move {
+            let mut last_pruned: Option<tokio::time::Instant> = None;
+            loop {
+                // Either woken up or the interval passed
+                let _ = tokio::time::timeout(self.interval, WAKE.notified()).await;
+
+                let mut conn = match establish_connection(false) {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        log::error!("Webhook dispatcher could not connect: {e:?}");
+                        continue;
+                    }
+                };
+                let now = Utc::now().naive_utc();
+
+                if let Err(e) = self.dispatch(&mut conn, now).await {
+                    log::error!("Webhook dispatcher failed: {e:?}");
+                }
+
+                if last_pruned.is_none_or(|at| at.elapsed() >= Self::PRUNE_INTERVAL) {
+                    last_pruned = Some(tokio::time::Instant::now());
+                    match delete_finished_webhook_deliveries(&mut conn, now - self.keep_log) {
+                        Ok(deleted) => log::info!("Deleted {deleted} old webhook deliveries"),
+                        Err(e) => log::error!("Could not prune webhook deliveries: {e:?}"),
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::SubsecRound;
+    use common::{
+        endpoints_io::{
+            sensor_data::{ApiSensorData, ApiSensorDataEvent, SensorReading},
+            webhook::{WebhookDeliveryStatus, WebhookEvent, WebhookEventKind},
+        },
+        types::validate::device_id::DeviceId,
+    };
+    use hyper::StatusCode;
+
+    use crate::{
+        auth::keys::generate_secret,
+        db::{
+            model::NewWebhook,
+            tests::{create_test_user, create_test_user_place, create_test_user_sensor},
+            webhooks::{get_webhook_deliveries, insert_webhook},
+        },
+        webhooks::{queue_events, tests::stand_in},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let mut conn = establish_connection(true).unwrap();
+        let (user, _) = create_test_user(&mut conn);
+        let place = create_test_user_place(&mut conn, &user);
+        let sensor = create_test_user_sensor(&mut conn, &place);
+
+        let (ok_url, ok_received) = stand_in(StatusCode::OK).await;
+        let (failing_url, failing_received) = stand_in(StatusCode::INTERNAL_SERVER_ERROR).await;
+        let mut new_webhook = |url: String, events: &[WebhookEventKind]| {
+            insert_webhook(
+                &mut conn,
+                NewWebhook {
+                    user_id: user.id,
+                    url,
+                    secret: generate_secret(),
+                    events: events.iter().map(|k| k.key().to_string()).collect(),
+                },
+            )
+            .unwrap()
+        };
+        let ok = new_webhook(ok_url, &[WebhookEventKind::SensorData]);
+        let failing = new_webhook(
+            failing_url,
+            &[
+                WebhookEventKind::SensorData,
+                WebhookEventKind::AlertTriggered,
+            ],
+        );
+        // Not subscribed to the event
+        let other = new_webhook(
+            "http://127.0.0.1:1/hook".to_string(),
+            &[WebhookEventKind::AlertResolved],
+        );
+
+        let event = WebhookEvent::SensorData(ApiSensorDataEvent {
+            device_id: DeviceId::from_string(&sensor.device_id).unwrap(),
+            api_data: ApiSensorData {
+                id: 0,
+                data: SensorReading {
+                    co2: Some(400),
+                    ..Default::default()
+                },
+                derived: Default::default(),
+                suspect_metrics: vec![],
+                added_at: 0,
+            },
+        });
+        // Stored with microseconds
+        let now = Utc::now().naive_utc().trunc_subsecs(0);
+        assert_eq!(queue_events(&mut conn, place.id, &[event], now).unwrap(), 2);
+
+        let dispatcher = WebhookDispatcher {
+            batch_size: 1,
+            ..WebhookDispatcher::from_env()
+        };
+        assert_eq!(dispatcher.dispatch(&mut conn, now).await.unwrap(), 2);
+        assert_eq!(ok_received.lock().unwrap().len(), 1);
+        assert_eq!(failing_received.lock().unwrap().len(), 1);
+
+        let delivery = |conn: &mut DbConn, webhook_id| {
+            get_webhook_deliveries(conn, webhook_id, 10)
+                .unwrap()
+                .into_iter()
+                .next()
+        };
+        let delivered = delivery(&mut conn, ok.id).unwrap();
+        assert_eq!(delivered.status, WebhookDeliveryStatus::Delivered.key());
+        assert_eq!(delivered.last_status_code, Some(200));
+        assert_eq!(delivered.delivered_at, Some(now));
+        assert!(delivery(&mut conn, other.id).is_none());
+
+        // Retried with backoff
+        let pending = delivery(&mut conn, failing.id).unwrap();
+        assert_eq!(pending.status, WebhookDeliveryStatus::Pending.key());
+        assert_eq!(pending.attempts, 1);
+        assert_eq!(pending.last_status_code, Some(500));
+        let retry_at = pending.next_attempt_at.unwrap();
+        assert_eq!(
+            Some(retry_at),
+            dispatcher.retry_policy.next_attempt_at(1, now)
+        );
+
+        assert_eq!(dispatcher.dispatch(&mut conn, now).await.unwrap(), 0);
+        assert_eq!(dispatcher.dispatch(&mut conn, retry_at).await.unwrap(), 1);
+        assert_eq!(failing_received.lock().unwrap().len(), 2);
+        assert_eq!(delivery(&mut conn, failing.id).unwrap().attempts, 2);
+
+        // Failed once out of attempts
+        let dispatcher = WebhookDispatcher {
+            retry_policy: RetryPolicy {
+                max_attempts: 3,
+                ..RetryPolicy::default()
+            },
+            ..dispatcher
+        };
+        let later = retry_at + TimeDelta::days(1);
+        assert_eq!(dispatcher.dispatch(&mut conn, later).await.unwrap(), 1);
+        let failed = delivery(&mut conn, failing.id).unwrap();
+        assert_eq!(failed.status, WebhookDeliveryStatus::Failed.key());
+        assert_eq!(failed.next_attempt_at, None);
+
+        assert_eq!(
+            delete_finished_webhook_deliveries(&mut conn, now + TimeDelta::minutes(1)).unwrap(),
+            2
+        );
+    }
+}
@@ -0,0 +1,667 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, LazyLock},
+    time::Duration,
+};
+
+use chrono::{NaiveDateTime, TimeDelta};
+use common::{
+    endpoints_io::webhook::{
+        ApiWebhook, ApiWebhookDelivery, ApiWebhookPayload, WebhookDeliveryStatus, WebhookEvent,
+        WebhookEventKind, WebhookSignature, is_private_address, is_public_address,
+    },
+    types::ApiTimestamp,
+};
+use hmac::{Hmac, Mac};
+use http_body_util::Full;
+use hyper::{
+    Request, Uri,
+    body::Bytes,
+    header::{CONTENT_TYPE, HOST, USER_AGENT},
+};
+use hyper_util::rt::TokioIo;
+use rustls::{ClientConfig, RootCertStore, pki_types::ServerName};
+use serde::Deserialize;
+use serde_valid::json::{ToJsonString, json};
+use sha2::Sha256;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsConnector;
+
+use crate::{
+    db::{
+        DbConn, Error,
+        model::{NewWebhookDelivery, Webhook, WebhookDelivery, WebhookDeliveryAttempt},
+        webhooks::{
+            get_place_webhooks, insert_webhook_deliveries, record_webhook_delivery_attempt,
+        },
+    },
+    tasks::webhook_dispatcher,
+};
+
+/// How long an attempt can take before giving up on it
+pub const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Deliveries being attempted are postponed this much so that they aren't attempted twice
+pub const ATTEMPT_LEASE: TimeDelta = TimeDelta::minutes(5);
+
+/// Read from the env once, see AddressPolicy::from_env
+static ADDRESS_POLICY: LazyLock<AddressPolicy> = LazyLock::new(AddressPolicy::from_env);
+
+const USER_AGENT_VALUE: &str = concat!("sensor-server/", env!("CARGO_PKG_VERSION"));
+
+/// CAs trusted for https urls, read once from the system store like the ones of the mail transport
+/// (SSL_CERT_FILE or SSL_CERT_DIR override it). None if there are none, then https deliveries fail
+static TLS_CONFIG: LazyLock<Option<Arc<ClientConfig>>> = LazyLock::new(|| {
+    let native = rustls_native_certs::load_native_certs();
+    for e in native.errors {
+        log::warn!("Could not load some CA certificates for webhooks: {e}");
+    }
+
+    let mut roots = RootCertStore::empty();
+    let (added, ignored) = roots.add_parsable_certificates(native.certs);
+    if ignored > 0 {
+        log::warn!("Skipped {ignored} invalid CA certificates for webhooks");
+    }
+    if added == 0 {
+        log::error!("No CA certificates found, https webhooks will fail");
+        return None;
+    }
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Some(Arc::new(config))
+});
+
+/// HMAC-SHA256 (RFC 2104) of the concatenation of message
+fn hmac_sha256(key: &[u8], message: &[&[u8]]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC should take keys of any length");
+    for part in message {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Value of WebhookSignature::SIGNATURE_HEADER for the body sent at timestamp, the key is the
+/// secret string as is
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let timestamp = timestamp.to_string();
+    let mac = hmac_sha256(secret.as_bytes(), &[timestamp.as_bytes(), b".", body]);
+    format!("{}{}", WebhookSignature::SCHEME, hex::encode(mac))
+}
+
+/// Queues the events to the enabled webhooks of the owner of the place that are subscribed to them,
+/// due at now. Returns how many deliveries were queued
+pub fn queue_events(
+    conn: &mut DbConn,
+    place_id: i32,
+    events: &[WebhookEvent],
+    now: NaiveDateTime,
+) -> Result<usize, Error> {
+    if events.is_empty() {
+        return Ok(0);
+    }
+
+    let new_deliveries: Vec<NewWebhookDelivery> = get_place_webhooks(conn, place_id)?
+        .iter()
+        .flat_map(|webhook| {
+            events
+                .iter()
+                .filter(|event| webhook.events.iter().any(|k| k == event.kind().key()))
+                .map(|event| NewWebhookDelivery {
+                    webhook_id: webhook.id,
+                    kind: event.kind().key().to_string(),
+                    event: json!(event),
+                    next_attempt_at: now,
+                })
+        })
+        .collect();
+
+    if new_deliveries.is_empty() {
+        return Ok(0);
+    }
+
+    let queued = insert_webhook_deliveries(conn, new_deliveries)?.len();
+    log::trace!("Queued {queued} webhook deliveries for place {place_id}");
+    webhook_dispatcher::wake();
+    Ok(queued)
+}
+
+/// Queues a Test event to the webhook, due at now, and wakes the dispatcher up to attempt it
+pub fn queue_test_event(
+    conn: &mut DbConn,
+    webhook: &Webhook,
+    now: NaiveDateTime,
+) -> Result<WebhookDelivery, Error> {
+    let event = WebhookEvent::Test;
+    let new_delivery = NewWebhookDelivery {
+        webhook_id: webhook.id,
+        kind: event.kind().key().to_string(),
+        event: json!(event),
+        next_attempt_at: now,
+    };
+
+    let delivery = insert_webhook_deliveries(conn, vec![new_delivery])?
+        .into_iter()
+        .next()
+        .ok_or_else(|| Error::InternalError("Missing inserted delivery".into()))?;
+    webhook_dispatcher::wake();
+    Ok(delivery)
+}
+
+/// Delays between the failed attempts of a delivery, doubling each time
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub first_delay: TimeDelta,
+    pub max_delay: TimeDelta,
+    /// Attempts before the delivery is failed
+    pub max_attempts: i32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            first_delay: TimeDelta::seconds(30),
+            max_delay: TimeDelta::hours(6),
+            max_attempts: 10,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// When to retry after the attempts failed at now, None to give up
+    pub fn next_attempt_at(&self, attempts: i32, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+
+        let factor = 2i32.saturating_pow(attempts.saturating_sub(1).max(0) as u32);
+        let delay = self
+            .first_delay
+            .checked_mul(factor)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        Some(now + delay)
+    }
+}
+
+/// HTTP status of the response to an attempt, Err if there was none
+pub type AttemptResult = Result<u16, String>;
+
+/// POSTs the delivery to the url of the webhook, signed at now
+pub async fn send(
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+    now: NaiveDateTime,
+) -> AttemptResult {
+    let event = WebhookEvent::deserialize(&delivery.event)
+        .map_err(|e| format!("Stored event is not valid: {e}"))?;
+    let payload = ApiWebhookPayload {
+        delivery_id: delivery.id,
+        created_at: delivery.created_at.and_utc().timestamp() as ApiTimestamp,
+        event,
+    };
+    let body = payload.to_json_string().map_err(|e| e.to_string())?;
+
+    let timestamp = now.and_utc().timestamp();
+    let headers = [
+        (
+            WebhookSignature::SIGNATURE_HEADER,
+            sign(&webhook.secret, timestamp, body.as_bytes()),
+        ),
+        (WebhookSignature::TIMESTAMP_HEADER, timestamp.to_string()),
+        (WebhookSignature::EVENT_HEADER, delivery.kind.clone()),
+        (WebhookSignature::DELIVERY_HEADER, delivery.id.to_string()),
+    ];
+
+    tokio::time::timeout(ATTEMPT_TIMEOUT, post(&webhook.url, headers, body))
+        .await
+        .map_err(|_| format!("Timed out after {ATTEMPT_TIMEOUT:?}"))?
+}
+
+/// Where a webhook url points to
+struct Target {
+    uri: Uri,
+    https: bool,
+    /// Without the brackets of IPv6 ones
+    host: String,
+    port: u16,
+}
+
+impl Target {
+    fn parse(url: &str) -> Result<Self, String> {
+        let uri: Uri = url.parse().map_err(|e| format!("Invalid url: {e}"))?;
+        let https = match uri.scheme_str() {
+            Some("http") => false,
+            Some("https") => true,
+            _ => return Err("Unsupported url scheme".into()),
+        };
+        let authority = uri.authority().ok_or("Url without host")?;
+        let host = authority
+            .host()
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = authority.port_u16().unwrap_or(if https { 443 } else { 80 });
+
+        Ok(Self {
+            uri,
+            https,
+            host,
+            port,
+        })
+    }
+
+    /// Resolves the host once, refusing it if any of its addresses isn't allowed by the policy.
+    /// The connection is made to the returned address instead of the host so that a DNS answer
+    /// changing in between can't get around the check
+    async fn resolve(&self, policy: &AddressPolicy) -> Result<SocketAddr, String> {
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await
+            .map_err(|e| format!("Could not resolve {}: {e}", self.host))?
+            .collect();
+        if let Some(addr) = addrs.iter().find(|addr| !policy.allows(addr.ip())) {
+            return Err(format!(
+                "{} resolves to a local address: {}",
+                self.host,
+                addr.ip()
+            ));
+        }
+        addrs
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("{} has no address", self.host))
+    }
+}
+
+/// Which addresses webhooks can be sent to. Public ones always, private networks only if the
+/// server allows them (i.e.: a Home Assistant in the LAN). Loopback, link-local (i.e.: cloud
+/// metadata) and the like are always refused
+#[derive(Debug, Clone, Default)]
+pub struct AddressPolicy {
+    pub allow_private: bool,
+    /// The stand-ins of the tests listen on localhost
+    allow_loopback: bool,
+}
+
+impl AddressPolicy {
+    /// Reads WEBHOOK_ALLOW_PRIVATE
+    pub fn from_env() -> Self {
+        let policy = Self {
+            allow_private: std::env::var("WEBHOOK_ALLOW_PRIVATE")
+                .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true")),
+            allow_loopback: cfg!(test),
+        };
+        log::info!("Webhook address policy: {policy:?}");
+        policy
+    }
+
+    pub fn allows(&self, ip: IpAddr) -> bool {
+        is_public_address(ip)
+            || (self.allow_private && is_private_address(ip))
+            || (self.allow_loopback && ip.is_loopback())
+    }
+}
+
+/// Err if deliveries to the url would be refused, checked when webhooks are created or changed.
+/// It's checked again on every attempt, as what the host resolves to can change
+pub async fn check_url(url: &str) -> Result<(), String> {
+    Target::parse(url)?
+        .resolve(&ADDRESS_POLICY)
+        .await
+        .map(|_| ())
+}
+
+async fn post(url: &str, headers: [(&'static str, String); 4], body: String) -> AttemptResult {
+    let target = Target::parse(url)?;
+    let addr = target.resolve(&ADDRESS_POLICY).await?;
+    let authority = target.uri.authority().ok_or("Url without host")?;
+    let path = target.uri.path_and_query().map_or("/", |p| p.as_str());
+
+    let mut request = Request::post(path)
+        .header(HOST, authority.as_str())
+        .header(CONTENT_TYPE, "application/json")
+        .header(USER_AGENT, USER_AGENT_VALUE);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let request = request
+        .body(Full::new(Bytes::from(body)))
+        .map_err(|e| e.to_string())?;
+
+    let stream = TcpStream::connect(addr)
+        .await
+        .map_err(|e| format!("Could not connect: {e}"))?;
+
+    if !target.https {
+        return send_request(TokioIo::new(stream), request).await;
+    }
+
+    let config = TLS_CONFIG
+        .clone()
+        .ok_or("No CA certificates to verify https urls")?;
+    let server_name =
+        ServerName::try_from(target.host).map_err(|e| format!("Invalid host: {e}"))?;
+    let stream = TlsConnector::from(config)
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| format!("TLS error: {e}"))?;
+    send_request(TokioIo::new(stream), request).await
+}
+
+async fn send_request<T>(io: T, request: Request<Full<Bytes>>) -> AttemptResult
+where
+    T: hyper::rt::Read + hyper::rt::Write + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(io)
+        .await
+        .map_err(|e| format!("HTTP error: {e}"))?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::trace!("Webhook connection closed: {e}");
+        }
+    });
+
+    let response = sender
+        .send_request(request)
+        .await
+        .map_err(|e| format!("HTTP error: {e}"))?;
+    Ok(response.status().as_u16())
+}
+
+/// Records the result of the attempt of the delivery made at now, any 2xx is delivered
+pub fn record_attempt(
+    conn: &mut DbConn,
+    delivery: &WebhookDelivery,
+    result: &AttemptResult,
+    policy: &RetryPolicy,
+    now: NaiveDateTime,
+) -> Result<WebhookDelivery, Error> {
+    let attempts = delivery.attempts + 1;
+    let (last_status_code, last_error) = match result {
+        Ok(status) if (200..300).contains(status) => (Some(*status as i32), None),
+        Ok(status) => (
+            Some(*status as i32),
+            Some(format!("Unexpected status {status}")),
+        ),
+        Err(e) => (None, Some(e.clone())),
+    };
+
+    let delivered = last_error.is_none();
+    let next_attempt_at = match delivered {
+        true => None,
+        false => policy.next_attempt_at(attempts, now),
+    };
+    let status = match (delivered, next_attempt_at) {
+        (true, _) => WebhookDeliveryStatus::Delivered,
+        (false, Some(_)) => WebhookDeliveryStatus::Pending,
+        (false, None) => WebhookDeliveryStatus::Failed,
+    };
+
+    match status {
+        WebhookDeliveryStatus::Delivered => {
+            log::trace!("Webhook delivery {} delivered", delivery.id)
+        }
+        _ => log::warn!(
+            "Webhook delivery {} failed attempt {attempts} ({status:?}): {last_error:?}",
+            delivery.id
+        ),
+    }
+
+    let attempt = WebhookDeliveryAttempt {
+        status: status.key().to_string(),
+        attempts,
+        last_status_code,
+        last_error,
+        next_attempt_at,
+        delivered_at: delivered.then_some(now),
+    };
+    record_webhook_delivery_attempt(conn, delivery.id, attempt)
+}
+
+pub fn api_webhook(webhook: Webhook) -> Result<ApiWebhook, Error> {
+    let events = webhook
+        .events
+        .iter()
+        .map(|key| {
+            WebhookEventKind::from_key(key).ok_or_else(|| {
+                log::error!(
+                    "Stored webhook ({}) has an invalid event: {key}",
+                    webhook.id
+                );
+                Error::InternalError("Invalid webhook event".into())
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(ApiWebhook {
+        id: webhook.id,
+        url: webhook.url,
+        events,
+        enabled: webhook.enabled,
+        created_at: webhook.created_at.and_utc().timestamp() as ApiTimestamp,
+        updated_at: webhook.updated_at.and_utc().timestamp() as ApiTimestamp,
+    })
+}
+
+pub fn api_webhook_delivery(delivery: WebhookDelivery) -> Result<ApiWebhookDelivery, Error> {
+    let invalid = |what: &str| {
+        log::error!(
+            "Stored webhook delivery ({}) has an invalid {what}",
+            delivery.id
+        );
+        Error::InternalError(format!("Invalid webhook delivery {what}").into())
+    };
+    let timestamp = |at: NaiveDateTime| at.and_utc().timestamp() as ApiTimestamp;
+
+    Ok(ApiWebhookDelivery {
+        id: delivery.id,
+        webhook_id: delivery.webhook_id,
+        kind: WebhookEventKind::from_key(&delivery.kind).ok_or_else(|| invalid("kind"))?,
+        status: WebhookDeliveryStatus::from_key(&delivery.status)
+            .ok_or_else(|| invalid("status"))?,
+        attempts: delivery.attempts as u32,
+        last_status_code: delivery.last_status_code.map(|code| code as u16),
+        last_error: delivery.last_error.clone(),
+        created_at: timestamp(delivery.created_at),
+        next_attempt_at: delivery.next_attempt_at.map(timestamp),
+        delivered_at: delivery.delivered_at.map(timestamp),
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        sync::Mutex,
+    };
+
+    use axum::{Router, http::HeaderMap, routing::post};
+    use chrono::Utc;
+    use hyper::StatusCode;
+    use serde_valid::json::FromJsonStr;
+
+    use super::*;
+    use crate::auth::keys::generate_secret;
+
+    /// Request received by a stand_in
+    #[derive(Debug, Clone)]
+    pub struct Received {
+        pub headers: HeaderMap,
+        pub body: String,
+    }
+
+    /// Local HTTP server answering every POST with status, returns its url and what it received
+    pub async fn stand_in(status: StatusCode) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let received = Arc::new(Mutex::new(vec![]));
+        let router = Router::new().route(
+            "/hook",
+            post({
+                let received = received.clone();
+                move |headers: HeaderMap, body: String| async move {
+                    received.lock().unwrap().push(Received { headers, body });
+                    status
+                }
+            }),
+        );
+
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        (format!("http://{addr}/hook"), received)
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(
+                b"Jefe",
+                &[b"what do ya want ", b"for nothing?"]
+            )),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        // Test case 6, key longer than a block
+        assert_eq!(
+            hex::encode(hmac_sha256(
+                &[0xaa; 131],
+                &[b"Test Using Larger Than Block-Size Key - Hash Key First"]
+            )),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+        assert!(sign("secret", 1, b"{}").starts_with(WebhookSignature::SCHEME));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        let policy = RetryPolicy {
+            first_delay: TimeDelta::seconds(30),
+            max_delay: TimeDelta::minutes(5),
+            max_attempts: 5,
+        };
+        let now = Utc::now().naive_utc();
+
+        assert_eq!(
+            policy.next_attempt_at(1, now),
+            Some(now + TimeDelta::seconds(30))
+        );
+        assert_eq!(
+            policy.next_attempt_at(2, now),
+            Some(now + TimeDelta::seconds(60))
+        );
+        assert_eq!(
+            policy.next_attempt_at(4, now),
+            Some(now + TimeDelta::minutes(4))
+        );
+        assert_eq!(policy.next_attempt_at(5, now), None);
+
+        let policy = RetryPolicy {
+            max_attempts: i32::MAX,
+            ..policy
+        };
+        assert_eq!(
+            policy.next_attempt_at(40, now),
+            Some(now + TimeDelta::minutes(5))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send() {
+        let (url, received) = stand_in(StatusCode::NO_CONTENT).await;
+        let now = Utc::now().naive_utc();
+        let webhook = Webhook {
+            id: 1,
+            user_id: 1,
+            url,
+            secret: generate_secret(),
+            events: vec![],
+            enabled: true,
+            created_at: now,
+            updated_at: now,
+        };
+        let delivery = WebhookDelivery {
+            id: 7,
+            webhook_id: webhook.id,
+            kind: WebhookEventKind::Test.key().to_string(),
+            event: json!(WebhookEvent::Test),
+            status: WebhookDeliveryStatus::Pending.key().to_string(),
+            attempts: 0,
+            last_status_code: None,
+            last_error: None,
+            created_at: now,
+            next_attempt_at: Some(now),
+            delivered_at: None,
+        };
+
+        assert_eq!(send(&webhook, &delivery, now).await, Ok(204));
+
+        let received = received.lock().unwrap()[0].clone();
+        let header = |name: &str| received.headers[name].to_str().unwrap().to_string();
+        let timestamp: i64 = header(WebhookSignature::TIMESTAMP_HEADER).parse().unwrap();
+        assert_eq!(
+            header(WebhookSignature::SIGNATURE_HEADER),
+            sign(&webhook.secret, timestamp, received.body.as_bytes())
+        );
+        assert_eq!(header(WebhookSignature::EVENT_HEADER), "test");
+        assert_eq!(header(WebhookSignature::DELIVERY_HEADER), "7");
+
+        let payload = ApiWebhookPayload::from_json_str(&received.body).unwrap();
+        assert_eq!(payload.delivery_id, 7);
+        assert!(matches!(payload.event, WebhookEvent::Test));
+
+        // Nothing listening
+        let webhook = Webhook {
+            url: "http://127.0.0.1:1/hook".to_string(),
+            ..webhook
+        };
+        assert!(send(&webhook, &delivery, now).await.is_err());
+
+        // Never dialed
+        for url in ["http://10.0.0.1/hook", "http://[fe80::1]/hook"] {
+            let webhook = Webhook {
+                url: url.to_string(),
+                ..webhook.clone()
+            };
+            let err = send(&webhook, &delivery, now).await.unwrap_err();
+            assert!(err.contains("local address"), "{err}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_url() {
+        assert!(check_url("http://127.0.0.1:1/hook").await.is_ok());
+        assert!(check_url("https://93.184.216.34/hook").await.is_ok());
+        assert!(check_url("http://169.254.169.254/latest").await.is_err());
+        assert!(check_url("http://192.168.1.10:8123/hook").await.is_err());
+        assert!(check_url("ftp://93.184.216.34/hook").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_private() {
+        let refusing = AddressPolicy::default();
+        let allowing = AddressPolicy {
+            allow_private: true,
+            ..Default::default()
+        };
+
+        for url in [
+            "http://192.168.1.10:8123/hook",
+            "http://[fd00::10]:8123/hook",
+        ] {
+            let target = Target::parse(url).unwrap();
+            assert!(target.resolve(&refusing).await.is_err(), "{url}");
+            assert!(target.resolve(&allowing).await.is_ok(), "{url}");
+        }
+        for url in [
+            "http://127.0.0.1:8123/hook",
+            "http://169.254.169.254/latest",
+            "http://0.0.0.0/hook",
+        ] {
+            let target = Target::parse(url).unwrap();
+            assert!(target.resolve(&allowing).await.is_err(), "{url}");
+        }
+        let target = Target::parse("https://93.184.216.34/hook").unwrap();
+        assert!(target.resolve(&refusing).await.is_ok());
+    }
+}